@@ -0,0 +1,176 @@
+//! Matches the rules of a large stylesheet against every element of a large page, with the
+//! linear scan naglfar used before `SelectorMap` and with a `SelectorMap`, and prints the time
+//! each takes per pass. It runs on stable Rust:
+//!
+//! $ cargo bench --bench selector_matching
+
+extern crate naglfar;
+
+use naglfar::css::{self, Selector, SimpleSelector, Specificity, Stylesheet};
+use naglfar::dom::{ElementData, Node, NodeType};
+use naglfar::element_state::ElementState;
+use naglfar::html;
+use naglfar::selector_map::{Ancestors, SelectorMap};
+
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// A page of 200 articles, each with a header and paragraphs of classed spans and links.
+fn large_page() -> Node {
+    let mut src = "<html><body><div id=\"content\">".to_string();
+    for i in 0..200 {
+        src += &format!("<div class=\"article a{}\"><h2 class=\"title\">Title</h2>", i % 10);
+        for j in 0..5 {
+            src += &format!(
+                "<p class=\"text p{}\">Some <span class=\"em\">text</span> with \
+                 <a class=\"link\" id=\"l{}-{}\">a link</a>.</p>",
+                j, i, j
+            );
+        }
+        src += "<ul class=\"tags\"><li>one</li><li>two</li></ul></div>";
+    }
+    src += "</div></body></html>";
+    html::parse(src, PathBuf::from("bench.html"))
+}
+
+/// 400 rules in the style of a site's stylesheet, most of which don't apply to the page.
+fn large_stylesheet() -> Stylesheet {
+    let mut src = "body { color: black } p { margin: 0 } a { color: blue }".to_string();
+    for i in 0..100 {
+        src += &format!(".widget{} .item {{ color: red }}\n", i);
+        src += &format!("#sidebar{} li {{ color: red }}\n", i);
+        src += &format!("div.a{} p.p{} span {{ color: red }}\n", i % 10, i % 5);
+        src += &format!("nav > ul li.menu{} a {{ color: red }}\n", i);
+    }
+    css::parse(src)
+}
+
+/// The selector matching naglfar did before `SelectorMap`: every rule is tried on every
+/// element, and the ancestors are a vector cloned at every level of the tree.
+mod linear {
+    use super::*;
+
+    pub fn count_matches(
+        node: &Node,
+        stylesheet: &Stylesheet,
+        appeared: &Vec<SimpleSelector>,
+    ) -> usize {
+        let mut matched = 0;
+        if let NodeType::Element(ref elem) = node.data {
+            matched += matching_rules(elem, stylesheet, appeared).len();
+            let mut appeared = appeared.clone();
+            appeared.push(SimpleSelector {
+                tag_name: Some(elem.tag_name.clone()),
+                id: elem.id().cloned(),
+                class: elem.classes().iter().map(|x| x.to_string()).collect(),
+                structural: false,
+                pseudo_classes: vec![],
+            });
+            for child in &node.children {
+                matched += count_matches(child, stylesheet, &appeared);
+            }
+        }
+        matched
+    }
+
+    fn matching_rules<'a>(
+        elem: &ElementData,
+        stylesheet: &'a Stylesheet,
+        appeared: &Vec<SimpleSelector>,
+    ) -> Vec<(Specificity, &'a css::Rule)> {
+        stylesheet
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                rule.selectors
+                    .iter()
+                    .find(|selector| matches(elem, selector, appeared))
+                    .map(|selector| (selector.specificity(), rule))
+            })
+            .collect()
+    }
+
+    fn matches(elem: &ElementData, selector: &Selector, appeared: &Vec<SimpleSelector>) -> bool {
+        match *selector {
+            Selector::Simple(ref simple) => matches_simple_selector(elem, simple),
+            // Sibling combinators were matched like descendant ones.
+            Selector::Descendant(ref a, ref b)
+            | Selector::Adjacent(ref a, ref b)
+            | Selector::Sibling(ref a, ref b) => {
+                appeared.iter().any(|e| matches_appeared(e, a)) && matches(elem, b, appeared)
+            }
+            Selector::Child(ref a, ref b) => match appeared.last() {
+                Some(last) => matches_appeared(last, a) && matches(elem, b, appeared),
+                None => false,
+            },
+        }
+    }
+
+    fn matches_appeared(e: &SimpleSelector, simple: &SimpleSelector) -> bool {
+        !((simple.tag_name.is_some() && e.tag_name != simple.tag_name)
+            || (simple.id.is_some() && e.id != simple.id)
+            || (!simple.class.iter().all(|class| e.class.contains(class))))
+    }
+
+    fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
+        if selector.tag_name.iter().any(|name| elem.tag_name != *name)
+            || selector.id.iter().any(|id| elem.id() != Some(id))
+        {
+            return false;
+        }
+        let elem_classes = elem.classes();
+        selector
+            .class
+            .iter()
+            .all(|class| elem_classes.contains(&**class))
+    }
+}
+
+fn count_matches_with_map(node: &Node, map: &SelectorMap, ancestors: &mut Ancestors) -> usize {
+    let mut matched = 0;
+    if let NodeType::Element(ref elem) = node.data {
+        matched += map.matching_rules(elem, ElementState::default(), ancestors).len();
+        ancestors.push(elem, ElementState::default());
+        for child in &node.children {
+            matched += count_matches_with_map(child, map, ancestors);
+        }
+        ancestors.pop();
+    }
+    matched
+}
+
+/// The average time `f` takes, over enough runs to take about a second.
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs < 3 || start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
+
+fn main() {
+    let page = large_page();
+    let stylesheet = Rc::new(large_stylesheet());
+    let map = SelectorMap::new(stylesheet.clone());
+
+    assert_eq!(
+        count_matches_with_map(&page, &map, &mut Ancestors::new()),
+        linear::count_matches(&page, &stylesheet, &vec![])
+    );
+
+    let linear = time(|| {
+        linear::count_matches(&page, &stylesheet, &vec![]);
+    });
+    let with_map = time(|| {
+        count_matches_with_map(&page, &map, &mut Ancestors::new());
+    });
+    println!("linear scan:  {:8.3} ms per pass", millis(linear));
+    println!("selector map: {:8.3} ms per pass", millis(with_map));
+}
@@ -1,6 +1,6 @@
-use css::{Unit, Value};
 use float::Floats;
 use layout::{Dimensions, LayoutBox};
+use margin::CollapsibleMargins;
 
 use std::cmp::max;
 
@@ -11,7 +11,7 @@ impl LayoutBox {
     pub fn layout_block(
         &mut self,
         floats: &mut Floats,
-        last_margin_bottom: Au,
+        margins: Option<CollapsibleMargins>,
         containing_block: Dimensions,
         _saved_block: Dimensions,
         viewport: Dimensions,
@@ -20,253 +20,247 @@ impl LayoutBox {
 
         self.floats = floats.clone();
 
-        let margin = self.property.margin();
-        let padding = self.property.padding();
-        let border = self.property.border_width();
-
         // Child width can depend on parent width, so we need to calculate this box's width before
         // laying out its children.
-        self.calculate_block_width(containing_block, &margin, &padding, &border);
+        self.calculate_block_width(containing_block);
 
-        self.calculate_block_position(
-            last_margin_bottom,
-            containing_block,
-            margin,
-            padding,
-            border,
-        );
+        let child_margins = self.calculate_block_position(margins, containing_block);
 
         if self.floats.is_present() {
             self.floats.translate(self.dimensions.offset());
         }
 
-        self.layout_block_children(viewport);
+        let trailing_margins = self.layout_block_children(child_margins, viewport);
+        self.collapse_bottom_margin(margins, trailing_margins, containing_block.content.width);
 
         self.assign_replaced_width_if_necessary();
         self.assign_replaced_height_if_necessary();
 
         // Parent height can depend on child height, so `calculate_height` must be called after the
         // children are laid out.
-        self.calculate_block_height();
+        self.calculate_block_height(containing_block);
     }
 
     /// Calculate the width of a block-level non-replaced element in normal flow.
     /// Sets the horizontal margin/padding/border dimensions, and the `width`.
     /// ref. http://www.w3.org/TR/CSS2/visudet.html#blockwidth
-    pub fn calculate_block_width(
-        &mut self,
-        containing_block: Dimensions,
-        margin: &(Value, Value, Value, Value),
-        padding: &(Value, Value, Value, Value),
-        border: &(Value, Value, Value, Value),
-    ) {
-        let cb_width = containing_block.content.width.to_f64_px();
-
-        // `width` has initial value `auto`.
-        let auto = Value::Keyword("auto".to_string());
-        let mut width = if let Some(x) = self.property.value("width") {
-            x[0].clone()
-        } else {
-            auto.clone()
-        };
-
-        let mut margin_left = margin.3.clone();
-        let mut margin_right = margin.1.clone();
-
-        let border_left = &border.3;
-        let border_right = &border.1;
-
-        let padding_left = &padding.3;
-        let padding_right = &padding.1;
-
-        let total = sum([
-            &margin_left,
-            &margin_right,
-            border_left,
-            border_right,
-            padding_left,
-            padding_right,
-            &width,
-        ].iter()
-            .map(|v| v.maybe_percent_to_px(cb_width).unwrap_or(0.0)));
+    pub fn calculate_block_width(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
+        let width = self.property.specified_width(cb_width);
+        self.solve_block_width(containing_block, width);
+
+        // The width is solved again with the limit it violates as the specified width.
+        let used_width = self.dimensions.content.width;
+        let clamped_width = self.property.clamp_width(used_width, Some(cb_width));
+        if clamped_width != used_width {
+            self.solve_block_width(containing_block, Some(clamped_width));
+        }
+    }
+
+    /// Solve the horizontal constraint for the content `width`, None for `auto`.
+    fn solve_block_width(&mut self, containing_block: Dimensions, mut width: Option<Au>) {
+        let cb_width = containing_block.content.width;
+        let style = &self.property;
+
+        let mut margin_left = style.margin.left.to_au(cb_width);
+        let mut margin_right = style.margin.right.to_au(cb_width);
+
+        let border_left = style.border_width.left;
+        let border_right = style.border_width.right;
+
+        let padding_left = style.padding.left.to_au(cb_width);
+        let padding_right = style.padding.right.to_au(cb_width);
+
+        let total = margin_left.unwrap_or(Au(0)) + margin_right.unwrap_or(Au(0)) + border_left
+            + border_right + padding_left + padding_right + width.unwrap_or(Au(0));
 
         // If width is not auto and the total is wider than the container, treat auto margins as 0.
-        if width != auto && total > containing_block.content.width.to_f64_px() {
-            if margin_left == auto {
-                margin_left = Value::Length(0.0, Unit::Px);
+        if width.is_some() && total > cb_width {
+            if margin_left.is_none() {
+                margin_left = Some(Au(0));
             }
-            if margin_right == auto {
-                margin_right = Value::Length(0.0, Unit::Px);
+            if margin_right.is_none() {
+                margin_right = Some(Au(0));
             }
         }
 
         // Adjust used values so that the above sum equals `containing_block.width`.
         // Each arm of the `match` should increase the total width by exactly `underflow`,
-        // and afterward all values should be absolute lengths in px.
-        let underflow = containing_block.content.width - Au::from_f64_px(total);
+        // and afterward all values should be absolute lengths.
+        let underflow = cb_width - total;
 
-        match (width == auto, margin_left == auto, margin_right == auto) {
+        match (width.is_none(), margin_left.is_none(), margin_right.is_none()) {
             // If the values are overconstrained, calculate margin_right.
             (false, false, false) => {
-                margin_right = Value::Length(
-                    margin_right.maybe_percent_to_px(cb_width).unwrap() + underflow.to_f64_px(),
-                    Unit::Px,
-                );
+                margin_right = Some(margin_right.unwrap() + underflow);
             }
 
             // If exactly one size is auto, its used value follows from the equality.
             (false, false, true) => {
-                margin_right = Value::Length(underflow.to_f64_px(), Unit::Px);
+                margin_right = Some(underflow);
             }
             (false, true, false) => {
-                margin_left = Value::Length(underflow.to_f64_px(), Unit::Px);
+                margin_left = Some(underflow);
             }
 
             // If width is set to auto, any other auto values become 0.
             (true, _, _) => {
-                if margin_left == auto {
-                    margin_left = Value::Length(0.0, Unit::Px);
-                }
-                if margin_right == auto {
-                    margin_right = Value::Length(0.0, Unit::Px);
-                }
+                margin_left = Some(margin_left.unwrap_or(Au(0)));
+                margin_right = Some(margin_right.unwrap_or(Au(0)));
 
-                if underflow.to_f64_px() >= 0.0 {
+                if underflow >= Au(0) {
                     // Expand width to fill the underflow.
-                    width = Value::Length(underflow.to_f64_px(), Unit::Px);
+                    width = Some(underflow);
                 } else {
                     // Width can't be negative. Adjust the right margin instead.
-                    width = Value::Length(0.0, Unit::Px);
-                    margin_right = Value::Length(
-                        margin_right.maybe_percent_to_px(cb_width).unwrap() + underflow.to_f64_px(),
-                        Unit::Px,
-                    );
+                    width = Some(Au(0));
+                    margin_right = Some(margin_right.unwrap() + underflow);
                 }
             }
 
             // If margin-left and margin-right are both auto, their used values are equal.
             (false, true, true) => {
-                margin_left = Value::Length(underflow.to_f64_px() / 2.0, Unit::Px);
-                margin_right = Value::Length(underflow.to_f64_px() / 2.0, Unit::Px);
+                margin_left = Some(underflow / 2);
+                margin_right = Some(underflow / 2);
             }
         }
 
         let d = &mut self.dimensions;
-        if let Some(width) = width.maybe_percent_to_px(cb_width) {
-            d.content.width = Au::from_f64_px(width)
-        }
+        d.content.width = width.unwrap();
 
-        if let Some(padding_left) = padding_left.maybe_percent_to_px(cb_width) {
-            d.padding.left = Au::from_f64_px(padding_left)
-        }
-        if let Some(padding_right) = padding_right.maybe_percent_to_px(cb_width) {
-            d.padding.right = Au::from_f64_px(padding_right)
-        }
+        d.padding.left = padding_left;
+        d.padding.right = padding_right;
 
-        if let Some(border_left) = border_left.maybe_percent_to_px(cb_width) {
-            d.border.left = Au::from_f64_px(border_left)
-        }
-        if let Some(border_right) = border_right.maybe_percent_to_px(cb_width) {
-            d.border.right = Au::from_f64_px(border_right)
-        }
+        d.border.left = border_left;
+        d.border.right = border_right;
 
-        if let Some(margin_left) = margin_left.maybe_percent_to_px(cb_width) {
-            d.margin.left = Au::from_f64_px(margin_left)
-        }
-        if let Some(margin_right) = margin_right.maybe_percent_to_px(cb_width) {
-            d.margin.right = Au::from_f64_px(margin_right)
-        }
+        d.margin.left = margin_left.unwrap();
+        d.margin.right = margin_right.unwrap();
     }
 
     /// Finish calculating the block's edge sizes, and position it within its containing block.
     /// http://www.w3.org/TR/CSS2/visudet.html#normal-block
-    /// Sets the vertical margin/padding/border dimensions, and the `x`, `y` values.
+    /// Sets the vertical margin/padding/border dimensions, and the `x`, `y` values. The top
+    /// margin collapses with `margins`, those adjoining it before the box. Returns the margins
+    /// the top margin of the first child collapses with.
     pub fn calculate_block_position(
         &mut self,
-        last_margin_bottom: Au,
+        margins: Option<CollapsibleMargins>,
         containing_block: Dimensions,
-        margin: (Value, Value, Value, Value),
-        padding: (Value, Value, Value, Value),
-        border: (Value, Value, Value, Value),
-    ) {
-        let cb_width = containing_block.content.width.to_f64_px();
-        let d = &mut self.dimensions;
+    ) -> CollapsibleMargins {
+        let cb_width = containing_block.content.width;
 
-        // margin, border, and padding have initial value 0.
-        let zero = Value::Length(0.0, Unit::Px);
+        // `auto` vertical margins are 0.
+        self.dimensions.margin.top = self.property.margin.top.to_au(cb_width).unwrap_or(Au(0));
+        self.dimensions.margin.bottom =
+            self.property.margin.bottom.to_au(cb_width).unwrap_or(Au(0));
 
-        d.margin.top = Au::from_f64_px(margin.0.maybe_percent_to_px(cb_width).unwrap_or(0f64));
-        d.margin.bottom = Au::from_f64_px(margin.2.maybe_percent_to_px(cb_width).unwrap_or(0f64));
-
-        // Margin collapse
-        // TODO: Is this implementation correct?
-        if last_margin_bottom >= d.margin.top {
-            d.margin.top = Au(0);
-        } else {
-            d.margin.top = d.margin.top - last_margin_bottom;
-        }
+        let child_margins = self.collapse_top_margin(margins, cb_width);
 
-        d.border.top = Au::from_f64_px(border.0.maybe_percent_to_px(cb_width).unwrap());
-        d.border.bottom = Au::from_f64_px(border.2.maybe_percent_to_px(cb_width).unwrap());
+        let style = &self.property;
+        let d = &mut self.dimensions;
 
-        d.padding.top = Au::from_f64_px(padding.0.maybe_percent_to_px(cb_width).unwrap());
-        d.padding.bottom = Au::from_f64_px(padding.2.maybe_percent_to_px(cb_width).unwrap());
+        d.border.top = style.border_width.top;
+        d.border.bottom = style.border_width.bottom;
 
-        self.z_index = self.property.lookup("z-index", "z-index", &vec![zero])[0]
-            .clone()
-            .to_num() as i32;
+        d.padding.top = style.padding.top.to_au(cb_width);
+        d.padding.bottom = style.padding.bottom.to_au(cb_width);
 
         d.content.x = d.margin.left + d.border.left + d.padding.left;
 
         // Position the box below all the previous boxes in the container.
         d.content.y = containing_block.content.height + d.margin.top + d.border.top + d.padding.top;
+
+        child_margins
     }
 
-    /// Lay out the block's children within its content area.
-    /// Sets `self.dimensions.height` to the total content height.
-    pub fn layout_block_children(&mut self, viewport: Dimensions) {
+    /// Lay out the block's children within its content area, the first of whose top margin
+    /// collapses with `margins`, which are above it.
+    /// Sets `self.dimensions.height` to the total content height. Returns the margins adjoining
+    /// the bottom margin of the last child, and how much of them the height includes.
+    pub fn layout_block_children(
+        &mut self,
+        margins: CollapsibleMargins,
+        viewport: Dimensions,
+    ) -> (CollapsibleMargins, Au) {
         let d = &mut self.dimensions;
-        let mut last_margin_bottom = Au(0);
         let mut floats = &mut self.floats;
+        // The margins adjoining the top margin of the next in-flow child, which the height
+        // includes only partly while they collapse with the top margin of this box.
+        let mut margins = margins;
+        let mut included = Au(0);
 
         d.content.height = Au(0);
 
         // TODO: Consider a better way to position children.
         for child in &mut self.children {
-            if let Some(clear) = child.property.clear() {
-                let clearance = floats.clearance(clear);
-                d.content.height += clearance;
+            // Absolutely positioned boxes are laid out with their containing block, from their
+            // static position, where they would be in normal flow.
+            if child.is_absolutely_positioned() {
+                child.dimensions = Default::default();
+                child.dimensions.content.y = d.content.height;
+                continue;
+            }
+
+            let mut containing_block = *d;
+            if let Some(clear) = child.property.clear {
+                let floats_bottom = floats.clearance(clear);
+                if child.in_normal_flow() {
+                    // Clearance puts the border box below the floats, and keeps the top margin
+                    // from collapsing with the margins before it.
+                    let top_margins = child.top_margins(d.content.width);
+                    let border_top = d.content.height + margins.adjoin_all(top_margins).collapse()
+                        - margins.collapse();
+                    if border_top < floats_bottom {
+                        d.content.height = floats_bottom - top_margins.collapse();
+                        margins = CollapsibleMargins::default();
+                        included = Au(0);
+                        containing_block = *d;
+                    }
+                } else {
+                    containing_block.content.height = max(d.content.height, floats_bottom);
+                }
             }
 
             if floats.is_present() {
                 floats.ceiling = max(floats.ceiling, d.content.height);
             }
 
-            child.layout(&mut floats, last_margin_bottom, *d, *d, viewport);
+            child.layout(
+                &mut floats,
+                Some(margins),
+                containing_block,
+                containing_block,
+                viewport,
+            );
 
             if child.in_normal_flow() {
-                last_margin_bottom = child.dimensions.margin.bottom;
+                let width = d.content.width;
+                if child.collapses_through(width) {
+                    margins = margins
+                        .adjoin_all(child.top_margins(width))
+                        .adjoin_all(child.bottom_margins(width));
+                    included += child.dimensions.margin_box().height;
+                } else {
+                    margins = child.bottom_margins(width);
+                    included = margins.collapse();
+                }
                 // Increment the height so each child is laid out below the previous one.
                 d.content.height += child.dimensions.margin_box().height;
             }
         }
+        (margins, included)
     }
 
     /// Height of a block-level non-replaced element in normal flow with overflow visible.
-    pub fn calculate_block_height(&mut self) {
+    pub fn calculate_block_height(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
         // If the height is set to an explicit length, use that exact length.
         // Otherwise, just keep the value set by `layout_block_children`.
-        if let Some(val) = self.get_style_node().value("height") {
-            if let Value::Length(h, Unit::Px) = val[0] {
-                self.dimensions.content.height = Au::from_f64_px(h);
-            }
+        if let Some(height) = self.property.specified_height(cb_width, None) {
+            self.dimensions.content.height = height;
         }
+        let height = self.dimensions.content.height;
+        self.dimensions.content.height = self.property.clamp_height(height, cb_width, None);
     }
 }
-
-fn sum<I>(iter: I) -> f64
-where
-    I: Iterator<Item = f64>,
-{
-    iter.fold(0., |a, b| a + b)
-}
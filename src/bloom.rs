@@ -0,0 +1,84 @@
+//! A counting Bloom filter, which tells quickly that a set of strings doesn't contain one.
+
+const KEY_SIZE: usize = 12;
+const ARRAY_SIZE: usize = 1 << KEY_SIZE;
+const KEY_MASK: u32 = (1 << KEY_SIZE) - 1;
+
+/// Each hash sets two counters, taken from its low and high bits. False positives are possible
+/// but false negatives are not: a counter that reaches 255 is never decremented again, so
+/// removing a hash can't make the filter forget another one.
+#[derive(Clone)]
+pub struct BloomFilter {
+    counters: Box<[u8]>,
+}
+
+impl BloomFilter {
+    pub fn new() -> BloomFilter {
+        BloomFilter {
+            counters: vec![0; ARRAY_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn insert_hash(&mut self, hash: u32) {
+        for &i in &[hash1(hash), hash2(hash)] {
+            let counter = &mut self.counters[i];
+            if *counter != 0xff {
+                *counter += 1;
+            }
+        }
+    }
+
+    /// Removes a hash previously inserted.
+    pub fn remove_hash(&mut self, hash: u32) {
+        for &i in &[hash1(hash), hash2(hash)] {
+            let counter = &mut self.counters[i];
+            if *counter != 0xff {
+                *counter -= 1;
+            }
+        }
+    }
+
+    pub fn might_contain_hash(&self, hash: u32) -> bool {
+        self.counters[hash1(hash)] != 0 && self.counters[hash2(hash)] != 0
+    }
+}
+
+fn hash1(hash: u32) -> usize {
+    (hash & KEY_MASK) as usize
+}
+
+fn hash2(hash: u32) -> usize {
+    ((hash >> KEY_SIZE) & KEY_MASK) as usize
+}
+
+/// The FNV-1a hash of `s`, seeded with `kind` so that equal strings of different kinds (e.g. a
+/// tag name and a class) hash differently.
+pub fn hash_str(kind: u8, s: &str) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for &byte in [kind].iter().chain(s.as_bytes()) {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+#[test]
+fn test_bloom_filter() {
+    let mut filter = BloomFilter::new();
+    let div = hash_str(0, "div");
+    let nav = hash_str(1, "nav");
+    assert!(!filter.might_contain_hash(div));
+
+    filter.insert_hash(div);
+    filter.insert_hash(div);
+    filter.insert_hash(nav);
+    assert!(filter.might_contain_hash(div));
+    assert!(filter.might_contain_hash(nav));
+    assert!(!filter.might_contain_hash(hash_str(0, "nav")));
+
+    filter.remove_hash(div);
+    assert!(filter.might_contain_hash(div));
+    filter.remove_hash(div);
+    assert!(!filter.might_contain_hash(div));
+    assert!(filter.might_contain_hash(nav));
+}
@@ -1,10 +1,16 @@
 use std::{fmt, collections::HashSet};
 
 use html::remove_comments;
+use property::{self, Token};
+use font_face::FontFace;
+use style::DEFAULT_FONT_SIZE;
+
+use reqwest::Url;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
+    pub font_faces: Vec<FontFace>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,6 +24,10 @@ pub enum Selector {
     Simple(SimpleSelector),
     Descendant(SimpleSelector, Box<Selector>),
     Child(SimpleSelector, Box<Selector>),
+    /// `a + b`
+    Adjacent(SimpleSelector, Box<Selector>),
+    /// `a ~ b`
+    Sibling(SimpleSelector, Box<Selector>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,12 +35,30 @@ pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
     pub class: HashSet<String>,
+    /// Whether the selector has a structural pseudo-class, like `:nth-child()` or `:empty`, or
+    /// follows a sibling combinator, so that matching it depends on more than the element and
+    /// its ancestors. Structural pseudo-classes aren't supported yet and are otherwise ignored.
+    pub structural: bool,
+    pub pseudo_classes: Vec<PseudoClass>,
+}
+
+/// The pseudo-classes that are matched. Others are ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PseudoClass {
+    Hover,
+    Active,
+    Focus,
+    FocusWithin,
+    Link,
+    Visited,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Declaration {
     pub name: String,
     pub values: Vec<Value>,
+    /// Whether it's marked `!important`, which makes it override the normal declarations.
+    pub important: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,14 +67,50 @@ pub enum Value {
     Length(f64, Unit),
     Num(f64),
     Color(Color),
+    Str(String),
+    /// An absolute URL if the stylesheet's own URL was known, otherwise as written.
+    Url(String),
+    /// `repeat()`, `minmax()` or `fit-content()`, with its comma-separated arguments.
+    Function(String, Vec<Vec<Value>>),
+    /// The bracketed line names in a grid track list, like `[header-start main]`.
+    LineNames(Vec<String>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Unit {
+    // Absolute lengths
     Px,
     Pt,
+    Pc,
+    In,
+    Cm,
+    Mm,
+    Q,
+    // Percentages are resolved during layout.
     Percent,
+    // Font-relative lengths
     Em,
+    Rem,
+    Ex,
+    Ch,
+    // Viewport-percentage lengths
+    Vw,
+    Vh,
+    Vmin,
+    Vmax,
+    // Flexible lengths, a fraction of the leftover space in a grid container
+    Fr,
+}
+
+/// What relative lengths are resolved against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthContext {
+    /// The computed font size of the element, in px.
+    pub font_size: f64,
+    /// The computed font size of the root element, in px.
+    pub root_font_size: f64,
+    pub viewport_width: f64,
+    pub viewport_height: f64,
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -89,41 +153,276 @@ color!(AQUA, 0x00, 0xff, 0xff);
 
 impl Copy for Color {}
 
+/// Looks up a CSS named color (case-insensitively). `transparent` is transparent black.
+/// ref. https://www.w3.org/TR/css-color-4/#named-colors
+pub fn named_color(name: &str) -> Option<Color> {
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r: r, g: g, b: b, a: 0xff }
+    }
+
+    Some(match name.to_lowercase().as_str() {
+        "transparent" => Color { r: 0, g: 0, b: 0, a: 0 },
+        "aliceblue" => rgb(0xf0, 0xf8, 0xff),
+        "antiquewhite" => rgb(0xfa, 0xeb, 0xd7),
+        "aqua" => AQUA,
+        "aquamarine" => rgb(0x7f, 0xff, 0xd4),
+        "azure" => rgb(0xf0, 0xff, 0xff),
+        "beige" => rgb(0xf5, 0xf5, 0xdc),
+        "bisque" => rgb(0xff, 0xe4, 0xc4),
+        "black" => BLACK,
+        "blanchedalmond" => rgb(0xff, 0xeb, 0xcd),
+        "blue" => BLUE,
+        "blueviolet" => rgb(0x8a, 0x2b, 0xe2),
+        "brown" => rgb(0xa5, 0x2a, 0x2a),
+        "burlywood" => rgb(0xde, 0xb8, 0x87),
+        "cadetblue" => rgb(0x5f, 0x9e, 0xa0),
+        "chartreuse" => rgb(0x7f, 0xff, 0x00),
+        "chocolate" => rgb(0xd2, 0x69, 0x1e),
+        "coral" => rgb(0xff, 0x7f, 0x50),
+        "cornflowerblue" => rgb(0x64, 0x95, 0xed),
+        "cornsilk" => rgb(0xff, 0xf8, 0xdc),
+        "crimson" => rgb(0xdc, 0x14, 0x3c),
+        "cyan" => rgb(0x00, 0xff, 0xff),
+        "darkblue" => rgb(0x00, 0x00, 0x8b),
+        "darkcyan" => rgb(0x00, 0x8b, 0x8b),
+        "darkgoldenrod" => rgb(0xb8, 0x86, 0x0b),
+        "darkgray" => rgb(0xa9, 0xa9, 0xa9),
+        "darkgreen" => rgb(0x00, 0x64, 0x00),
+        "darkgrey" => rgb(0xa9, 0xa9, 0xa9),
+        "darkkhaki" => rgb(0xbd, 0xb7, 0x6b),
+        "darkmagenta" => rgb(0x8b, 0x00, 0x8b),
+        "darkolivegreen" => rgb(0x55, 0x6b, 0x2f),
+        "darkorange" => rgb(0xff, 0x8c, 0x00),
+        "darkorchid" => rgb(0x99, 0x32, 0xcc),
+        "darkred" => rgb(0x8b, 0x00, 0x00),
+        "darksalmon" => rgb(0xe9, 0x96, 0x7a),
+        "darkseagreen" => rgb(0x8f, 0xbc, 0x8f),
+        "darkslateblue" => rgb(0x48, 0x3d, 0x8b),
+        "darkslategray" => rgb(0x2f, 0x4f, 0x4f),
+        "darkslategrey" => rgb(0x2f, 0x4f, 0x4f),
+        "darkturquoise" => rgb(0x00, 0xce, 0xd1),
+        "darkviolet" => rgb(0x94, 0x00, 0xd3),
+        "deeppink" => rgb(0xff, 0x14, 0x93),
+        "deepskyblue" => rgb(0x00, 0xbf, 0xff),
+        "dimgray" => rgb(0x69, 0x69, 0x69),
+        "dimgrey" => rgb(0x69, 0x69, 0x69),
+        "dodgerblue" => rgb(0x1e, 0x90, 0xff),
+        "firebrick" => rgb(0xb2, 0x22, 0x22),
+        "floralwhite" => rgb(0xff, 0xfa, 0xf0),
+        "forestgreen" => rgb(0x22, 0x8b, 0x22),
+        "fuchsia" => FUCHSIA,
+        "gainsboro" => rgb(0xdc, 0xdc, 0xdc),
+        "ghostwhite" => rgb(0xf8, 0xf8, 0xff),
+        "gold" => rgb(0xff, 0xd7, 0x00),
+        "goldenrod" => rgb(0xda, 0xa5, 0x20),
+        "gray" => GRAY,
+        "green" => GREEN,
+        "greenyellow" => rgb(0xad, 0xff, 0x2f),
+        "grey" => rgb(0x80, 0x80, 0x80),
+        "honeydew" => rgb(0xf0, 0xff, 0xf0),
+        "hotpink" => rgb(0xff, 0x69, 0xb4),
+        "indianred" => rgb(0xcd, 0x5c, 0x5c),
+        "indigo" => rgb(0x4b, 0x00, 0x82),
+        "ivory" => rgb(0xff, 0xff, 0xf0),
+        "khaki" => rgb(0xf0, 0xe6, 0x8c),
+        "lavender" => rgb(0xe6, 0xe6, 0xfa),
+        "lavenderblush" => rgb(0xff, 0xf0, 0xf5),
+        "lawngreen" => rgb(0x7c, 0xfc, 0x00),
+        "lemonchiffon" => rgb(0xff, 0xfa, 0xcd),
+        "lightblue" => rgb(0xad, 0xd8, 0xe6),
+        "lightcoral" => rgb(0xf0, 0x80, 0x80),
+        "lightcyan" => rgb(0xe0, 0xff, 0xff),
+        "lightgoldenrodyellow" => rgb(0xfa, 0xfa, 0xd2),
+        "lightgray" => rgb(0xd3, 0xd3, 0xd3),
+        "lightgreen" => rgb(0x90, 0xee, 0x90),
+        "lightgrey" => rgb(0xd3, 0xd3, 0xd3),
+        "lightpink" => rgb(0xff, 0xb6, 0xc1),
+        "lightsalmon" => rgb(0xff, 0xa0, 0x7a),
+        "lightseagreen" => rgb(0x20, 0xb2, 0xaa),
+        "lightskyblue" => rgb(0x87, 0xce, 0xfa),
+        "lightslategray" => rgb(0x77, 0x88, 0x99),
+        "lightslategrey" => rgb(0x77, 0x88, 0x99),
+        "lightsteelblue" => rgb(0xb0, 0xc4, 0xde),
+        "lightyellow" => rgb(0xff, 0xff, 0xe0),
+        "lime" => LIME,
+        "limegreen" => rgb(0x32, 0xcd, 0x32),
+        "linen" => rgb(0xfa, 0xf0, 0xe6),
+        "magenta" => rgb(0xff, 0x00, 0xff),
+        "maroon" => MAROON,
+        "mediumaquamarine" => rgb(0x66, 0xcd, 0xaa),
+        "mediumblue" => rgb(0x00, 0x00, 0xcd),
+        "mediumorchid" => rgb(0xba, 0x55, 0xd3),
+        "mediumpurple" => rgb(0x93, 0x70, 0xdb),
+        "mediumseagreen" => rgb(0x3c, 0xb3, 0x71),
+        "mediumslateblue" => rgb(0x7b, 0x68, 0xee),
+        "mediumspringgreen" => rgb(0x00, 0xfa, 0x9a),
+        "mediumturquoise" => rgb(0x48, 0xd1, 0xcc),
+        "mediumvioletred" => rgb(0xc7, 0x15, 0x85),
+        "midnightblue" => rgb(0x19, 0x19, 0x70),
+        "mintcream" => rgb(0xf5, 0xff, 0xfa),
+        "mistyrose" => rgb(0xff, 0xe4, 0xe1),
+        "moccasin" => rgb(0xff, 0xe4, 0xb5),
+        "navajowhite" => rgb(0xff, 0xde, 0xad),
+        "navy" => NAVY,
+        "oldlace" => rgb(0xfd, 0xf5, 0xe6),
+        "olive" => OLIVE,
+        "olivedrab" => rgb(0x6b, 0x8e, 0x23),
+        "orange" => rgb(0xff, 0xa5, 0x00),
+        "orangered" => rgb(0xff, 0x45, 0x00),
+        "orchid" => rgb(0xda, 0x70, 0xd6),
+        "palegoldenrod" => rgb(0xee, 0xe8, 0xaa),
+        "palegreen" => rgb(0x98, 0xfb, 0x98),
+        "paleturquoise" => rgb(0xaf, 0xee, 0xee),
+        "palevioletred" => rgb(0xdb, 0x70, 0x93),
+        "papayawhip" => rgb(0xff, 0xef, 0xd5),
+        "peachpuff" => rgb(0xff, 0xda, 0xb9),
+        "peru" => rgb(0xcd, 0x85, 0x3f),
+        "pink" => rgb(0xff, 0xc0, 0xcb),
+        "plum" => rgb(0xdd, 0xa0, 0xdd),
+        "powderblue" => rgb(0xb0, 0xe0, 0xe6),
+        "purple" => PURPLE,
+        "rebeccapurple" => rgb(0x66, 0x33, 0x99),
+        "red" => RED,
+        "rosybrown" => rgb(0xbc, 0x8f, 0x8f),
+        "royalblue" => rgb(0x41, 0x69, 0xe1),
+        "saddlebrown" => rgb(0x8b, 0x45, 0x13),
+        "salmon" => rgb(0xfa, 0x80, 0x72),
+        "sandybrown" => rgb(0xf4, 0xa4, 0x60),
+        "seagreen" => rgb(0x2e, 0x8b, 0x57),
+        "seashell" => rgb(0xff, 0xf5, 0xee),
+        "sienna" => rgb(0xa0, 0x52, 0x2d),
+        "silver" => SILVER,
+        "skyblue" => rgb(0x87, 0xce, 0xeb),
+        "slateblue" => rgb(0x6a, 0x5a, 0xcd),
+        "slategray" => rgb(0x70, 0x80, 0x90),
+        "slategrey" => rgb(0x70, 0x80, 0x90),
+        "snow" => rgb(0xff, 0xfa, 0xfa),
+        "springgreen" => rgb(0x00, 0xff, 0x7f),
+        "steelblue" => rgb(0x46, 0x82, 0xb4),
+        "tan" => rgb(0xd2, 0xb4, 0x8c),
+        "teal" => TEAL,
+        "thistle" => rgb(0xd8, 0xbf, 0xd8),
+        "tomato" => rgb(0xff, 0x63, 0x47),
+        "turquoise" => rgb(0x40, 0xe0, 0xd0),
+        "violet" => rgb(0xee, 0x82, 0xee),
+        "wheat" => rgb(0xf5, 0xde, 0xb3),
+        "white" => WHITE,
+        "whitesmoke" => rgb(0xf5, 0xf5, 0xf5),
+        "yellow" => YELLOW,
+        "yellowgreen" => rgb(0x9a, 0xcd, 0x32),
+        _ => return None,
+    })
+}
+
+impl Unit {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Unit::Px => "px",
+            Unit::Pt => "pt",
+            Unit::Pc => "pc",
+            Unit::In => "in",
+            Unit::Cm => "cm",
+            Unit::Mm => "mm",
+            Unit::Q => "q",
+            Unit::Percent => "%",
+            Unit::Em => "em",
+            Unit::Rem => "rem",
+            Unit::Ex => "ex",
+            Unit::Ch => "ch",
+            Unit::Vw => "vw",
+            Unit::Vh => "vh",
+            Unit::Vmin => "vmin",
+            Unit::Vmax => "vmax",
+            Unit::Fr => "fr",
+        }
+    }
+
+    pub fn is_viewport_relative(&self) -> bool {
+        match *self {
+            Unit::Vw | Unit::Vh | Unit::Vmin | Unit::Vmax => true,
+            _ => false,
+        }
+    }
+
+    /// Returns how many px one of this unit is, or None for percentages and `fr`.
+    /// ref. https://www.w3.org/TR/css-values-3/#lengths
+    pub fn px_per_unit(&self, ctx: &LengthContext) -> Option<f64> {
+        match *self {
+            Unit::Px => Some(1.0),
+            Unit::Pt => Some(pt2px(1.0)),
+            Unit::Pc => Some(pt2px(12.0)),
+            Unit::In => Some(DPI),
+            Unit::Cm => Some(DPI / 2.54),
+            Unit::Mm => Some(DPI / 25.4),
+            Unit::Q => Some(DPI / 101.6),
+            Unit::Percent | Unit::Fr => None,
+            Unit::Em => Some(ctx.font_size),
+            Unit::Rem => Some(ctx.root_font_size),
+            // We don't have the font metrics here, so use the fallback of 0.5em that CSS allows.
+            Unit::Ex | Unit::Ch => Some(ctx.font_size * 0.5),
+            Unit::Vw => Some(ctx.viewport_width / 100.0),
+            Unit::Vh => Some(ctx.viewport_height / 100.0),
+            Unit::Vmin => Some(ctx.viewport_width.min(ctx.viewport_height) / 100.0),
+            Unit::Vmax => Some(ctx.viewport_width.max(ctx.viewport_height) / 100.0),
+        }
+    }
+}
+
+impl Default for LengthContext {
+    fn default() -> LengthContext {
+        LengthContext {
+            font_size: DEFAULT_FONT_SIZE,
+            root_font_size: DEFAULT_FONT_SIZE,
+            viewport_width: 0.0,
+            viewport_height: 0.0,
+        }
+    }
+}
+
 impl Value {
+    /// Relative lengths are normally resolved when styles are computed (see
+    /// `ComputedStyle::compute`). Those that remain are resolved against the initial font size.
     pub fn to_px(&self) -> Option<f64> {
+        self.to_px_in(&LengthContext::default())
+    }
+
+    pub fn to_px_in(&self, ctx: &LengthContext) -> Option<f64> {
         match *self {
-            Value::Length(f, Unit::Px) | Value::Num(f) => Some(f),
-            Value::Length(f, Unit::Pt) => Some(pt2px(f)),
-            Value::Length(_, Unit::Em) => {
-                println!("The unit 'em' is currently unsupported. Treated as '16px'");
-                Some(16.0)
-            }
+            Value::Num(f) => Some(f),
+            Value::Length(f, unit) => unit.px_per_unit(ctx).map(|px| f * px),
             _ => None,
         }
     }
 
     pub fn maybe_percent_to_px(&self, len: f64) -> Option<f64> {
         match *self {
-            Value::Length(f, Unit::Px) | Value::Num(f) => Some(f),
-            Value::Length(f, Unit::Pt) => Some(pt2px(f)),
             Value::Length(f, Unit::Percent) => Some(len * (f / 100.0)),
-            Value::Length(_, Unit::Em) => {
-                println!("The unit 'em' is currently unsupported. Treated as '16px'");
-                Some(16.0)
-            }
-            _ => None,
+            _ => self.to_px(),
         }
     }
 
     pub fn to_pt(&self) -> Option<f64> {
         match *self {
             Value::Length(f, Unit::Pt) | Value::Num(f) => Some(f),
-            Value::Length(f, Unit::Px) => Some(px2pt(f)),
-            Value::Length(_, Unit::Em) => {
-                println!("The unit 'em' is currently unsupported. Treated as '12pt'");
-                Some(12.0)
-            }
-            _ => None,
+            _ => self.to_px().map(px2pt),
+        }
+    }
+
+    /// Converts a length in any unit but `%` into px.
+    pub fn resolve_length(&self, ctx: &LengthContext) -> Value {
+        match *self {
+            Value::Length(_, Unit::Px)
+            | Value::Length(_, Unit::Percent)
+            | Value::Length(_, Unit::Fr) => self.clone(),
+            Value::Length(_, _) => Value::Length(self.to_px_in(ctx).unwrap(), Unit::Px),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn is_viewport_relative(&self) -> bool {
+        match *self {
+            Value::Length(_, unit) => unit.is_viewport_relative(),
+            _ => false,
         }
     }
 
@@ -134,32 +433,23 @@ impl Value {
         }
     }
 
+    /// Returns the color this value represents. `currentColor` can't be resolved here, so it
+    /// yields `None`; use `to_color_or_current` where the element's `color` is known.
     pub fn to_color(&self) -> Option<Color> {
         match *self {
             Value::Color(color) => Some(color),
-            Value::Keyword(ref color_name) => match color_name.as_str() {
-                "black" => Some(BLACK),
-                "silver" => Some(SILVER),
-                "gray" => Some(GRAY),
-                "white" => Some(WHITE),
-                "red" => Some(RED),
-                "maroon" => Some(MAROON),
-                "purple" => Some(PURPLE),
-                "fuchsia" => Some(FUCHSIA),
-                "green" => Some(GREEN),
-                "lime" => Some(LIME),
-                "olive" => Some(OLIVE),
-                "yellow" => Some(YELLOW),
-                "navy" => Some(NAVY),
-                "blue" => Some(BLUE),
-                "teal" => Some(TEAL),
-                "aqua" => Some(AQUA),
-                _ => None,
-            },
+            Value::Keyword(ref color_name) => named_color(color_name.as_str()),
             _ => None,
         }
     }
 
+    pub fn to_color_or_current(&self, current_color: Color) -> Option<Color> {
+        match *self {
+            Value::Keyword(ref k) if k == "currentcolor" => Some(current_color),
+            _ => self.to_color(),
+        }
+    }
+
     pub fn to_text_decoration(&self) -> Option<TextDecoration> {
         match *self {
             Value::Keyword(ref name) => match name.to_lowercase().as_str() {
@@ -186,6 +476,106 @@ pub fn pt2px(f: f64) -> f64 {
     f * ((1.0 / 72.0) * DPI)
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColorComponent {
+    Number(f64),
+    Percent(f64),
+}
+
+impl ColorComponent {
+    /// Returns the component scaled so that `100%` equals `max`.
+    fn scaled(&self, max: f64) -> f64 {
+        match *self {
+            ColorComponent::Number(n) => n,
+            ColorComponent::Percent(p) => max * p / 100.0,
+        }
+    }
+
+    /// Returns the component as a fraction in 0..1. Numbers are treated as percentages, as
+    /// `hsl()` and `hwb()` accept both.
+    fn fraction(&self) -> f64 {
+        let f = match *self {
+            ColorComponent::Number(n) | ColorComponent::Percent(n) => n / 100.0,
+        };
+        f.max(0.0).min(1.0)
+    }
+}
+
+fn clamp_to_u8(f: f64) -> u8 {
+    f.max(0.0).min(255.0).round() as u8
+}
+
+fn color_from_function(
+    name: &str,
+    components: &Vec<ColorComponent>,
+    alpha: Option<ColorComponent>,
+) -> Option<Color> {
+    let a = match alpha {
+        // Truncation (rather than rounding) is kept for compatibility with the old rgba() parser.
+        Some(alpha) => (alpha.scaled(1.0).max(0.0).min(1.0) * 255.0) as u8,
+        None => 0xff,
+    };
+    let (r, g, b) = match name {
+        "rgb" | "rgba" => (
+            clamp_to_u8(components[0].scaled(255.0)),
+            clamp_to_u8(components[1].scaled(255.0)),
+            clamp_to_u8(components[2].scaled(255.0)),
+        ),
+        "hsl" | "hsla" => {
+            let hue = match components[0] {
+                ColorComponent::Number(h) => h,
+                ColorComponent::Percent(_) => return None,
+            };
+            hsl_to_rgb(hue, components[1].fraction(), components[2].fraction())
+        }
+        "hwb" => {
+            let hue = match components[0] {
+                ColorComponent::Number(h) => h,
+                ColorComponent::Percent(_) => return None,
+            };
+            hwb_to_rgb(hue, components[1].fraction(), components[2].fraction())
+        }
+        _ => return None,
+    };
+    Some(Color {
+        r: r,
+        g: g,
+        b: b,
+        a: a,
+    })
+}
+
+// ref. https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+fn hsl_to_rgb_f64(hue: f64, saturation: f64, lightness: f64) -> (f64, f64, f64) {
+    let hue = ((hue % 360.0) + 360.0) % 360.0;
+    let f = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).min(1.0).max(-1.0)
+    };
+    (f(0.0), f(8.0), f(4.0))
+}
+
+pub fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (u8, u8, u8) {
+    let (r, g, b) = hsl_to_rgb_f64(hue, saturation, lightness);
+    (
+        clamp_to_u8(r * 255.0),
+        clamp_to_u8(g * 255.0),
+        clamp_to_u8(b * 255.0),
+    )
+}
+
+// ref. https://www.w3.org/TR/css-color-4/#hwb-to-rgb
+pub fn hwb_to_rgb(hue: f64, whiteness: f64, blackness: f64) -> (u8, u8, u8) {
+    if whiteness + blackness >= 1.0 {
+        let gray = clamp_to_u8(whiteness / (whiteness + blackness) * 255.0);
+        return (gray, gray, gray);
+    }
+    let (r, g, b) = hsl_to_rgb_f64(hue, 1.0, 0.5);
+    let f = |c: f64| clamp_to_u8((c * (1.0 - whiteness - blackness) + whiteness) * 255.0);
+    (f(r), f(g), f(b))
+}
+
 pub type Specificity = (usize, usize, usize);
 
 impl Selector {
@@ -193,19 +583,17 @@ impl Selector {
     pub fn specificity(&self) -> Specificity {
         fn specificity_simple(simple: &SimpleSelector) -> Specificity {
             let a = simple.id.iter().count();
-            let b = simple.class.len();
+            let b = simple.class.len() + simple.pseudo_classes.len();
             let c = simple.tag_name.iter().count();
             (a, b, c)
         }
 
         match *self {
             Selector::Simple(ref simple) => specificity_simple(simple),
-            Selector::Descendant(ref a, ref b) => {
-                let (a1, b1, c1) = specificity_simple(a);
-                let (a2, b2, c2) = (*b).specificity();
-                (a1 + a2, b1 + b2, c1 + c2)
-            }
-            Selector::Child(ref a, ref b) => {
+            Selector::Descendant(ref a, ref b)
+            | Selector::Child(ref a, ref b)
+            | Selector::Adjacent(ref a, ref b)
+            | Selector::Sibling(ref a, ref b) => {
                 let (a1, b1, c1) = specificity_simple(a);
                 let (a2, b2, c2) = (*b).specificity();
                 (a1 + a2, b1 + b2, c1 + c2)
@@ -215,9 +603,14 @@ impl Selector {
 }
 
 pub fn parse(source: String) -> Stylesheet {
-    Stylesheet {
-        rules: Parser::new(source).parse_rules(),
-    }
+    Parser::new(source).parse_stylesheet()
+}
+
+/// Parse a stylesheet fetched from `base_url`. Relative `url()`s are resolved against it.
+pub fn parse_with_base_url(source: String, base_url: Url) -> Stylesheet {
+    let mut parser = Parser::new(source);
+    parser.base_url = Some(base_url);
+    parser.parse_stylesheet()
 }
 
 pub fn parse_attr_style(source: String) -> Vec<Declaration> {
@@ -229,7 +622,7 @@ pub fn parse_attr_style(source: String) -> Vec<Declaration> {
             break;
         }
         match parser.parse_declaration() {
-            Ok(ok) => decls.push(ok),
+            Ok(mut ok) => decls.append(&mut ok),
             Err(_) => {}
         }
     }
@@ -245,7 +638,52 @@ pub fn parse_value(source: String) -> Value {
 
 fn valid_ident_char_cheating(c: char) -> bool {
     // TODO: other char codes?
-    c.is_alphanumeric() || c == '-' || c == '_' || c == ':' || c == '.' || c == '/' || c == '%'
+    c.is_alphanumeric() || c == '-' || c == '_' || c == ':' || c == '.' || c == '%'
+}
+
+impl PseudoClass {
+    fn from_name(name: &str) -> Option<PseudoClass> {
+        match name {
+            "hover" => Some(PseudoClass::Hover),
+            "active" => Some(PseudoClass::Active),
+            "focus" => Some(PseudoClass::Focus),
+            "focus-within" => Some(PseudoClass::FocusWithin),
+            "link" => Some(PseudoClass::Link),
+            "visited" => Some(PseudoClass::Visited),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PseudoClass::Hover => "hover",
+            PseudoClass::Active => "active",
+            PseudoClass::Focus => "focus",
+            PseudoClass::FocusWithin => "focus-within",
+            PseudoClass::Link => "link",
+            PseudoClass::Visited => "visited",
+        }
+    }
+}
+
+fn leftmost_simple_selector(selector: &mut Selector) -> &mut SimpleSelector {
+    match *selector {
+        Selector::Simple(ref mut simple)
+        | Selector::Descendant(ref mut simple, _)
+        | Selector::Child(ref mut simple, _)
+        | Selector::Adjacent(ref mut simple, _)
+        | Selector::Sibling(ref mut simple, _) => simple,
+    }
+}
+
+/// Whether matching the pseudo-class `name` depends on the siblings or children of the element.
+fn is_structural_pseudo_class(name: &str) -> bool {
+    match name {
+        "empty" | "first-child" | "last-child" | "only-child" | "nth-child"
+        | "nth-last-child" | "first-of-type" | "last-of-type" | "only-of-type"
+        | "nth-of-type" | "nth-last-of-type" | "has" => true,
+        _ => false,
+    }
 }
 
 fn valid_ident_char(c: char) -> bool {
@@ -276,6 +714,8 @@ fn valid_hex_char(c: char) -> bool {
 struct Parser {
     pos: usize,
     input: String,
+    base_url: Option<Url>,
+    font_faces: Vec<FontFace>,
 }
 
 impl Parser {
@@ -283,6 +723,16 @@ impl Parser {
         Parser {
             pos: 0,
             input: remove_comments(input.as_bytes(), "/*", "*/"),
+            base_url: None,
+            font_faces: vec![],
+        }
+    }
+
+    fn parse_stylesheet(mut self) -> Stylesheet {
+        let rules = self.parse_rules();
+        Stylesheet {
+            rules: rules,
+            font_faces: self.font_faces,
         }
     }
 
@@ -318,19 +768,21 @@ impl Parser {
                 if ident == "charset" || ident == "import" {
                     self.consume_while(|c| c != ';').unwrap();
                     assert_eq!(self.consume_char().unwrap(), ';');
-                } else if ident == "font-face" || ident == "-ms-viewport" {
-                    //println!("We are at a @font-face query: {}",self.pos);
-                    self.consume_braces_pair();
-                    /* self.consume_while(|c| c != '{').unwrap();
-                    assert_eq!(self.consume_char().unwrap(), '{');
-                    loop {
-                        self.consume_whitespace().unwrap();
-                        if self.next_char().unwrap() == '}' {
-                            self.consume_char().unwrap();
-                            break;
+                } else if ident == "font-face" {
+                    let start = self.pos;
+                    match self.parse_font_face_descriptors() {
+                        Ok(descriptors) => {
+                            if let Some(font_face) = FontFace::from_descriptors(descriptors) {
+                                self.font_faces.push(font_face);
+                            }
                         }
-                        self.parse_rule().unwrap();
-                    } */
+                        Err(()) => {
+                            self.pos = start;
+                            self.consume_braces_pair();
+                        }
+                    }
+                } else if ident == "-ms-viewport" {
+                    self.consume_braces_pair();
                 } else {
                     //println!("We are at a @media query: {}",self.pos);
                     self.consume_braces_pair();
@@ -356,6 +808,31 @@ impl Parser {
         rules
     }
 
+    /// Parses the block of a `@font-face` rule. Descriptors aren't properties, so they are
+    /// returned as written.
+    fn parse_font_face_descriptors(&mut self) -> Result<Vec<(String, Vec<Token>)>, ()> {
+        self.consume_whitespace()?;
+        if self.consume_char()? != '{' {
+            return Err(());
+        }
+        let mut descriptors = vec![];
+        loop {
+            self.consume_whitespace()?;
+            if self.next_char()? == '}' {
+                self.consume_char()?;
+                break;
+            }
+            let name = self.parse_identifier()?;
+            self.consume_whitespace()?;
+            if !self.skip_char_if_any(':')? {
+                return Err(());
+            }
+            let (values, _) = self.parse_values()?;
+            descriptors.push((name, values));
+        }
+        Ok(descriptors)
+    }
+
     fn parse_rule(&mut self) -> Result<Rule, ()> {
         Ok(Rule {
             selectors: self.parse_selectors()?,
@@ -403,12 +880,16 @@ impl Parser {
                 let s2 = self.parse_selector()?;
                 return Ok(Selector::Child(s1, Box::new(s2)));
             }
-            '+' => {
-                assert_eq!(self.consume_char()?, '+');
+            c @ '+' | c @ '~' => {
+                self.consume_char()?;
                 self.consume_whitespace()?;
-                let s2 = self.parse_selector()?;
-                // TODO: Adjacent sibling selector is treated as Descendant selector for now.
-                return Ok(Selector::Descendant(s1, Box::new(s2)));
+                let mut s2 = self.parse_selector()?;
+                leftmost_simple_selector(&mut s2).structural = true;
+                return Ok(if c == '+' {
+                    Selector::Adjacent(s1, Box::new(s2))
+                } else {
+                    Selector::Sibling(s1, Box::new(s2))
+                });
             }
             _ => {}
         }
@@ -421,6 +902,8 @@ impl Parser {
             tag_name: None,
             id: None,
             class: HashSet::new(),
+            structural: false,
+            pseudo_classes: vec![],
         };
         while !self.eof() {
             match self.next_char()? {
@@ -437,7 +920,9 @@ impl Parser {
                     self.consume_char()?;
                 }
                 ':' => {
-                    self.parse_pseudo_class_or_element()?;
+                    let name = self.parse_pseudo_class_or_element()?;
+                    selector.structural |= is_structural_pseudo_class(name.as_str());
+                    selector.pseudo_classes.extend(PseudoClass::from_name(name.as_str()));
                 }
                 '[' => {
                     unsupported_feature = self.parse_attribute().is_err();
@@ -456,17 +941,18 @@ impl Parser {
     }
 
     // TODO: Implement correctly
-    fn parse_pseudo_class_or_element(&mut self) -> Result<(), ()> {
+    /// Returns the name of the pseudo-class or pseudo-element.
+    fn parse_pseudo_class_or_element(&mut self) -> Result<String, ()> {
         assert_eq!(self.skip_char_if_any(':')?, true); // pseudo-class
         self.skip_char_if_any(':')?; //pseudo-element
         self.consume_whitespace()?;
-        self.parse_identifier()?;
-        self.consume_whitespace()?;
+        let name = self.parse_identifier()?;
+        // Whitespace after the name is a descendant combinator.
         if self.skip_char_if_any('(')? {
             self.consume_while(|c| c != ')')?;
             assert_eq!(self.consume_char()?, ')');
         }
-        Ok(())
+        Ok(name)
     }
 
     // TODO: Implement correctly
@@ -489,31 +975,36 @@ impl Parser {
                 break;
             }
             //println!("About to parse declaration: {}",self.pos);
-            declarations.push(self.parse_declaration()?);
+            declarations.append(&mut self.parse_declaration()?);
             //println!("Parsed declaration: {}",self.pos);
         }
         Ok(declarations)
     }
 
-    fn parse_declaration(&mut self) -> Result<Declaration, ()> {
+    /// Parses a declaration and expands it into longhands. Returns no declarations if the value
+    /// is invalid for the property.
+    fn parse_declaration(&mut self) -> Result<Vec<Declaration>, ()> {
         //background:no-repeat center/100% url("assets/logo_homepage.normal.v108.svg"),linear-gradient(transparent, transparent)
         let property_name = self.parse_identifier()?;
         self.consume_whitespace()?;
         assert_eq!(self.consume_char()?, ':');
         self.consume_whitespace()?;
-        let values = self.parse_values()?;
+        let (values, important) = self.parse_values()?;
         self.consume_whitespace()?;
 
-        Ok(Declaration {
-            name: property_name,
-            values: values,
-        })
+        let mut declarations = property::expand(property_name.as_str(), values).unwrap_or(vec![]);
+        for declaration in &mut declarations {
+            declaration.important = important;
+        }
+        Ok(declarations)
     }
 
     // Methods for parsing values:
 
-    fn parse_values(&mut self) -> Result<Vec<Value>, ()> {
+    /// Parses the values of a declaration up to its end, and whether it's `!important`.
+    fn parse_values(&mut self) -> Result<(Vec<Token>, bool), ()> {
         let mut values = vec![];
+        let mut important = false;
 
         if let Ok(()) = (|| -> Result<(), ()> {
             loop {
@@ -525,22 +1016,38 @@ impl Parser {
                     break;
                 }
 
-                if let Ok(ok) = self.parse_value() {
-                    values.push(ok)
+                if self.skip_char_if_any(',')? {
+                    values.push(Token::Comma);
+                } else if self.skip_char_if_any('/')? {
+                    values.push(Token::Slash);
+                } else if self.next_char()? == '!' {
+                    self.consume_char()?;
+                    self.consume_whitespace()?;
+                    important = self.parse_identifier()?.eq_ignore_ascii_case("important");
+                } else {
+                    let start = self.pos;
+                    match self.parse_value() {
+                        Ok(Value::Keyword(ref k)) if k.is_empty() => {}
+                        Ok(ok) => values.push(Token::Value(ok)),
+                        Err(()) => {}
+                    }
+                    // Skip a character that can't start a value.
+                    if self.pos == start {
+                        self.consume_char()?;
+                    }
                 }
 
-                self.consume_while(|c| c == ' ' || c == '\t')?;
-                if self.skip_char_if_any('\n')? || self.next_char()? == '}' {
+                // A value may go on over several lines, like `grid-template-areas`.
+                self.consume_whitespace()?;
+                if self.next_char()? == '}' {
                     break;
                 }
-
-                self.skip_char_if_any(',')?;
             }
             Ok(())
         })()
         {}
 
-        Ok(values)
+        Ok((values, important))
     }
 
     fn parse_value(&mut self) -> Result<Value, ()> {
@@ -549,29 +1056,24 @@ impl Parser {
             '.' | '0'...'9' => self.parse_length(),
             '#' => self.parse_color(),
             '\"' | '\'' => self.parse_string(),
+            '[' => self.parse_line_names(),
             _ => {
                 //no-repeat center/100% url("assets/logo_homepage.normal.v108.svg"),linear-gradient(transparent, transparent)
                 //progid:DXImageTransform.Microsoft.BasicImage(rotation=1)
-                self.skip_char_if_any('!')?; // TODO: Is this correct?
                 self.skip_char_if_any('\\')?; // TODO: Is this correct?
 
                 let ident = self.parse_identifier_cheating()?;
                 match ident.as_str() {
-                    "rgb" => self.parse_rgb_color(),
-                    "rgba" => self.parse_rgba_color(),
-                    "url" => self.parse_url(),
+                    "rgb" | "rgba" | "hsl" | "hsla" | "hwb" => self.parse_color_function(&ident),
+                    "url" if !self.eof() && self.next_char()? == '(' => self.parse_url(),
+                    "repeat" | "minmax" | "fit-content"
+                        if !self.eof() && self.next_char()? == '(' =>
+                    {
+                        self.parse_function(ident)
+                    }
                     _ if !self.eof() && self.next_char()? == '(' => {
                         // TODO: Unsupported functions are ignored.
-                        let mut nest = 0;
-                        self.consume_while(|c| {
-                            match c {
-                                '(' => nest += 1,
-                                ')' => nest -= 1,
-                                _ => {}
-                            }
-                            nest > 0
-                        })?;
-                        assert_eq!(self.consume_char()?, ')');
+                        self.skip_function_arguments()?;
                         Ok(Value::Keyword(ident))
                     }
                     _ => Ok(Value::Keyword(ident)),
@@ -580,6 +1082,44 @@ impl Parser {
         }
     }
 
+    /// Parses the arguments of a function whose arguments are values, split at commas.
+    fn parse_function(&mut self, name: String) -> Result<Value, ()> {
+        assert_eq!(self.consume_char()?, '(');
+        let mut args = vec![vec![]];
+        loop {
+            self.consume_whitespace()?;
+            if self.eof() || self.skip_char_if_any(')')? {
+                break;
+            }
+            if self.skip_char_if_any(',')? {
+                args.push(vec![]);
+                continue;
+            }
+            let start = self.pos;
+            match self.parse_value() {
+                Ok(Value::Keyword(ref k)) if k.is_empty() => {}
+                Ok(value) => args.last_mut().unwrap().push(value),
+                Err(()) => {}
+            }
+            if self.pos == start {
+                self.consume_char()?;
+            }
+        }
+        Ok(Value::Function(name, args))
+    }
+
+    fn parse_line_names(&mut self) -> Result<Value, ()> {
+        assert_eq!(self.consume_char()?, '[');
+        let names = self.consume_while(|c| c != ']')?;
+        self.skip_char_if_any(']')?;
+        Ok(Value::LineNames(
+            names
+                .split_whitespace()
+                .map(|name| name.to_lowercase())
+                .collect(),
+        ))
+    }
+
     fn parse_length(&mut self) -> Result<Value, ()> {
         let num = self.parse_float()?;
         if !self.eof() && valid_alpha_percent_char(self.next_char()?) {
@@ -598,99 +1138,272 @@ impl Parser {
             .or_else(|_| Err(()));
 
         // TODO: Ignore expression
-        self.consume_while(|c| {
-            c.is_numeric() || c == '.' || c == '-' || c == '+' || c == '*' || c == '/'
-        })?;
+        self.consume_while(|c| c.is_numeric() || c == '.' || c == '-' || c == '+' || c == '*')?;
 
         n
     }
 
     fn parse_string(&mut self) -> Result<Value, ()> {
+        Ok(Value::Str(self.parse_string_contents()?))
+    }
+
+    fn parse_string_contents(&mut self) -> Result<String, ()> {
         let quote = self.consume_char()?;
-        self.consume_while(|c| c != quote)?;
-        assert_eq!(self.consume_char()?, quote);
-        // TODO: Implement correctly
-        Ok(Value::Num(0.0))
+        let mut s = "".to_string();
+        // An unclosed string is closed at the end of the stylesheet.
+        while !self.eof() {
+            match self.consume_char()? {
+                c if c == quote => break,
+                // An unescaped newline makes the string invalid.
+                '\n' => return Err(()),
+                '\\' => {
+                    if self.eof() {
+                        break;
+                    }
+                    // An escaped newline continues the string on the next line.
+                    if self.next_char()? == '\n' {
+                        self.consume_char()?;
+                        continue;
+                    }
+                    s.push(self.parse_escape()?);
+                }
+                c => s.push(c),
+            }
+        }
+        Ok(s)
+    }
+
+    /// Parse what follows a backslash: up to six hex digits and an optional whitespace, or a
+    /// single character that is taken literally.
+    fn parse_escape(&mut self) -> Result<char, ()> {
+        if !valid_hex_char(self.next_char()?) {
+            return self.consume_char();
+        }
+        let mut hex = "".to_string();
+        while hex.len() < 6 && !self.eof() && valid_hex_char(self.next_char()?) {
+            hex.push(self.consume_char()?);
+        }
+        if !self.eof() && self.next_char()?.is_whitespace() {
+            self.consume_char()?;
+        }
+        let code = u32::from_str_radix(hex.as_str(), 16).or(Err(()))?;
+        Ok(match ::std::char::from_u32(code) {
+            Some(c) if code != 0 => c,
+            _ => '\u{fffd}',
+        })
     }
 
     fn parse_unit(&mut self) -> Result<Unit, ()> {
         match &*self.parse_identifier_percent()? {
             "px" => Ok(Unit::Px),
             "pt" => Ok(Unit::Pt),
+            "pc" => Ok(Unit::Pc),
+            "in" => Ok(Unit::In),
+            "cm" => Ok(Unit::Cm),
+            "mm" => Ok(Unit::Mm),
+            "q" => Ok(Unit::Q),
             "%" => Ok(Unit::Percent),
             "em" => Ok(Unit::Em),
-            u => {
-                println!("unrecognized unit: {}", u);
-                // Unrecognized units are treated as Px
-                Ok(Unit::Pt)
+            "rem" => Ok(Unit::Rem),
+            "ex" => Ok(Unit::Ex),
+            "ch" => Ok(Unit::Ch),
+            "vw" => Ok(Unit::Vw),
+            "vh" => Ok(Unit::Vh),
+            "vmin" => Ok(Unit::Vmin),
+            "vmax" => Ok(Unit::Vmax),
+            "fr" => Ok(Unit::Fr),
+            // Values with unrecognized units are dropped.
+            _ => Err(()),
+        }
+    }
+
+    fn skip_function_arguments(&mut self) -> Result<(), ()> {
+        let mut nest = 0;
+        self.consume_while(|c| {
+            match c {
+                '(' => nest += 1,
+                ')' => nest -= 1,
+                _ => {}
+            }
+            nest > 0
+        })?;
+        assert_eq!(self.consume_char()?, ')');
+        Ok(())
+    }
+
+    /// Parses the arguments of `rgb()`, `rgba()`, `hsl()`, `hsla()` and `hwb()`, in both the
+    /// legacy comma-separated syntax and the modern space-separated syntax with `/ alpha`.
+    fn parse_color_function(&mut self, name: &str) -> Result<Value, ()> {
+        let start = self.pos;
+        match self.parse_color_function_args() {
+            Ok((components, alpha)) => color_from_function(name, &components, alpha)
+                .map(Value::Color)
+                .ok_or(()),
+            Err(()) => {
+                // Skip the whole function so that the following values can be parsed.
+                self.pos = start;
+                self.skip_function_arguments()?;
+                Err(())
             }
         }
     }
 
-    fn parse_rgb_color(&mut self) -> Result<Value, ()> {
-        assert_eq!(self.consume_char_ignore_whitescape()?, '(');
-        let r = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ',');
-        let g = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ',');
-        let b = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ')');
-        Ok(Value::Color(Color {
-            r: r as u8,
-            g: g as u8,
-            b: b as u8,
-            a: 255,
-        }))
+    fn parse_color_function_args(
+        &mut self,
+    ) -> Result<(Vec<ColorComponent>, Option<ColorComponent>), ()> {
+        if self.consume_char_ignore_whitescape()? != '(' {
+            return Err(());
+        }
+        let mut components = vec![];
+        let mut alpha = None;
+        loop {
+            self.consume_whitespace()?;
+            match self.next_char()? {
+                ')' => {
+                    self.consume_char()?;
+                    break;
+                }
+                ',' => {
+                    self.consume_char()?;
+                }
+                '/' => {
+                    self.consume_char()?;
+                    self.consume_whitespace()?;
+                    alpha = Some(self.parse_color_component()?);
+                }
+                _ => components.push(self.parse_color_component()?),
+            }
+        }
+        // The legacy syntax passes alpha as the fourth argument.
+        if alpha.is_none() && components.len() == 4 {
+            alpha = components.pop();
+        }
+        if components.len() != 3 {
+            return Err(());
+        }
+        Ok((components, alpha))
     }
 
-    fn parse_rgba_color(&mut self) -> Result<Value, ()> {
-        assert_eq!(self.consume_char_ignore_whitescape()?, '(');
-        let r = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ',');
-        let g = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ',');
-        let b = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ',');
-        let a = self.parse_float()?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ')');
-        Ok(Value::Color(Color {
-            r: r as u8,
-            g: g as u8,
-            b: b as u8,
-            a: (255.0 * a) as u8,
-        }))
+    fn parse_color_component(&mut self) -> Result<ColorComponent, ()> {
+        if self.next_char()?.is_alphabetic() {
+            // `none` is a missing component, which behaves as zero.
+            return match self.parse_identifier()?.as_str() {
+                "none" => Ok(ColorComponent::Number(0.0)),
+                _ => Err(()),
+            };
+        }
+        let num = self.parse_number()?;
+        if self.skip_char_if_any('%')? {
+            return Ok(ColorComponent::Percent(num));
+        }
+        if !self.eof() && self.next_char()?.is_alphabetic() {
+            // Hue angles are represented in degrees.
+            return match self.parse_identifier()?.as_str() {
+                "deg" => Ok(ColorComponent::Number(num)),
+                "rad" => Ok(ColorComponent::Number(num.to_degrees())),
+                "grad" => Ok(ColorComponent::Number(num * 0.9)),
+                "turn" => Ok(ColorComponent::Number(num * 360.0)),
+                _ => Err(()),
+            };
+        }
+        Ok(ColorComponent::Number(num))
+    }
+
+    /// Parses a plain number. Unlike `parse_float`, this doesn't swallow the characters following
+    /// the number.
+    fn parse_number(&mut self) -> Result<f64, ()> {
+        let mut s = "".to_string();
+        if self.next_char()? == '-' || self.next_char()? == '+' {
+            s.push(self.consume_char()?);
+        }
+        s.push_str(self.consume_while(|c| c.is_digit(10) || c == '.')?.as_str());
+        if !self.eof() && (self.next_char()? == 'e' || self.next_char()? == 'E')
+            && self.next2_char().map(|c| c.is_digit(10) || c == '-' || c == '+') == Ok(true)
+        {
+            s.push(self.consume_char()?);
+            s.push(self.consume_char()?);
+            s.push_str(self.consume_while(|c| c.is_digit(10))?.as_str());
+        }
+        s.parse().or(Err(()))
     }
 
     fn parse_url(&mut self) -> Result<Value, ()> {
-        // TODO: Implement correctly
-        assert_eq!(self.consume_char_ignore_whitescape()?, '(');
-        self.consume_while(|c| c != ')')?;
-        assert_eq!(self.consume_char_ignore_whitescape()?, ')');
-        Ok(Value::Num(0.0))
+        let start = self.pos;
+        match self.parse_url_contents() {
+            Ok(url) => Ok(Value::Url(self.resolve_url(url))),
+            Err(()) => {
+                // Skip the whole function so that the following values can be parsed.
+                self.pos = start;
+                self.consume_whitespace()?;
+                self.skip_function_arguments()?;
+                Err(())
+            }
+        }
+    }
+
+    fn parse_url_contents(&mut self) -> Result<String, ()> {
+        if self.consume_char_ignore_whitescape()? != '(' {
+            return Err(());
+        }
+        let url = match self.next_char()? {
+            '"' | '\'' => self.parse_string_contents()?,
+            _ => {
+                let mut url = "".to_string();
+                while !self.eof() {
+                    match self.next_char()? {
+                        ')' => break,
+                        c if c.is_whitespace() => break,
+                        '"' | '\'' | '(' => return Err(()),
+                        '\\' => {
+                            self.consume_char()?;
+                            url.push(self.parse_escape()?);
+                        }
+                        _ => url.push(self.consume_char()?),
+                    }
+                }
+                url
+            }
+        };
+        self.consume_whitespace()?;
+        // `url(` at the end of the stylesheet is closed implicitly.
+        if !self.eof() && self.consume_char()? != ')' {
+            return Err(());
+        }
+        Ok(url)
+    }
+
+    fn resolve_url(&self, url: String) -> String {
+        // `url("")` and fragment-only URLs refer to the document itself and are kept as is.
+        if url.is_empty() || url.starts_with('#') {
+            return url;
+        }
+        match self.base_url {
+            Some(ref base_url) => base_url
+                .join(url.as_str())
+                .map(|url| url.into_string())
+                .unwrap_or(url),
+            None => url,
+        }
     }
 
     fn parse_color(&mut self) -> Result<Value, ()> {
         assert_eq!(self.consume_char()?, '#');
         let hex_str = self.parse_hex_num()?;
-        let (r, g, b) = match hex_str.len() {
-            3 => {
-                let r = u8::from_str_radix(&hex_str[0..1], 16).unwrap();
-                let g = u8::from_str_radix(&hex_str[1..2], 16).unwrap();
-                let b = u8::from_str_radix(&hex_str[2..3], 16).unwrap();
-                (r * 16 + r, g * 16 + g, b * 16 + b)
-            }
-            6 => (
-                u8::from_str_radix(&hex_str[0..2], 16).unwrap(),
-                u8::from_str_radix(&hex_str[2..4], 16).unwrap(),
-                u8::from_str_radix(&hex_str[4..6], 16).unwrap(),
-            ),
-            _ => panic!(),
+        let hex = |s: &str| u8::from_str_radix(s, 16).or(Err(()));
+        let digit = |i: usize| hex(&hex_str[i..i + 1]).map(|d| d * 16 + d);
+        let pair = |i: usize| hex(&hex_str[i..i + 2]);
+        let (r, g, b, a) = match hex_str.len() {
+            3 => (digit(0)?, digit(1)?, digit(2)?, 0xff),
+            4 => (digit(0)?, digit(1)?, digit(2)?, digit(3)?),
+            6 => (pair(0)?, pair(2)?, pair(4)?, 0xff),
+            8 => (pair(0)?, pair(2)?, pair(4)?, pair(6)?),
+            _ => return Err(()),
         };
         Ok(Value::Color(Color {
             r: r,
             g: g,
             b: b,
-            a: 255,
+            a: a,
         }))
     }
 
@@ -793,6 +1506,10 @@ impl fmt::Display for Stylesheet {
                             universal = false;
                             try!(write!(f, "#{}", id));
                         }
+                        for pseudo_class in &selector.pseudo_classes {
+                            universal = false;
+                            try!(write!(f, ":{}", pseudo_class.name()));
+                        }
                         if universal {
                             try!(write!(f, "*"))
                         }
@@ -811,6 +1528,16 @@ impl fmt::Display for Stylesheet {
                             try!(write!(f, " > "));
                             show(f, &*b)
                         }
+                        &Selector::Adjacent(ref a, ref b) => {
+                            try!(show_simple(f, &*a));
+                            try!(write!(f, " + "));
+                            show(f, &*b)
+                        }
+                        &Selector::Sibling(ref a, ref b) => {
+                            try!(show_simple(f, &*a));
+                            try!(write!(f, " ~ "));
+                            show(f, &*b)
+                        }
                     }
                 }
                 try!(show(f, &selector));
@@ -823,21 +1550,10 @@ impl fmt::Display for Stylesheet {
             for decl in &rule.declarations {
                 try!(write!(f, "  {}:", decl.name,));
                 for value in &decl.values {
-                    try!(write!(
-                        f,
-                        " {}",
-                        match value {
-                            &Value::Keyword(ref kw) => kw.clone(),
-                            &Value::Length(ref f, Unit::Px) => format!("{}px", f),
-                            &Value::Length(ref f, Unit::Pt) => format!("{}pt", f),
-                            &Value::Length(ref f, Unit::Percent) => format!("{}%", f),
-                            &Value::Length(ref f, Unit::Em) => format!("{}em", f),
-                            &Value::Num(ref f) => format!("{}", f),
-                            &Value::Color(ref color) => {
-                                format!("rgba({}, {}, {}, {})", color.r, color.g, color.b, color.a)
-                            }
-                        }
-                    ))
+                    try!(write!(f, " {}", value))
+                }
+                if decl.important {
+                    try!(write!(f, " !important"));
                 }
                 try!(writeln!(f));
             }
@@ -847,6 +1563,34 @@ impl fmt::Display for Stylesheet {
     }
 }
 
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Keyword(ref kw) => write!(f, "{}", kw),
+            Value::Length(n, unit) => write!(f, "{}{}", n, unit.as_str()),
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Color(ref color) => {
+                write!(f, "rgba({}, {}, {}, {})", color.r, color.g, color.b, color.a)
+            }
+            Value::Str(ref s) => write!(f, "{:?}", s),
+            Value::Url(ref url) => write!(f, "url({:?})", url),
+            Value::Function(ref name, ref args) => {
+                try!(write!(f, "{}(", name));
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    for (j, value) in arg.iter().enumerate() {
+                        try!(write!(f, "{}{}", if j > 0 { " " } else { "" }, value));
+                    }
+                }
+                write!(f, ")")
+            }
+            Value::LineNames(ref names) => write!(f, "[{}]", names.join(" ")),
+        }
+    }
+}
+
 #[test]
 fn test1() {
     let src = "
@@ -871,6 +1615,8 @@ fn test1() {
                     tag_name: None,
                     id: Some("id".to_string()),
                     class: HashSet::new(),
+                    structural: false,
+                    pseudo_classes: vec![],
                 }),
                 Selector::Simple(SimpleSelector {
                     tag_name: None,
@@ -880,17 +1626,23 @@ fn test1() {
                         h.insert("class".to_string());
                         h
                     },
+                    structural: false,
+                    pseudo_classes: vec![],
                 }),
                 Selector::Child(
                     SimpleSelector {
                         tag_name: Some("p".to_string()),
                         id: None,
                         class: HashSet::new(),
+                        structural: false,
+                        pseudo_classes: vec![],
                     },
                     Box::new(Selector::Simple(SimpleSelector {
                         tag_name: Some("a".to_string()),
                         id: None,
                         class: HashSet::new(),
+                        structural: false,
+                        pseudo_classes: vec![],
                     })),
                 ),
                 Selector::Descendant(
@@ -898,49 +1650,64 @@ fn test1() {
                         tag_name: Some("div".to_string()),
                         id: None,
                         class: HashSet::new(),
+                        structural: false,
+                        pseudo_classes: vec![],
                     },
                     Box::new(Selector::Simple(SimpleSelector {
                         tag_name: Some("p".to_string()),
                         id: None,
                         class: HashSet::new(),
+                        structural: false,
+                        pseudo_classes: vec![],
                     })),
                 ),
                 Selector::Simple(SimpleSelector {
                     tag_name: Some("div".to_string()),
                     id: None,
                     class: HashSet::new(),
+                    structural: false,
+                    pseudo_classes: vec![],
                 }),
                 Selector::Simple(SimpleSelector {
                     tag_name: Some("h1".to_string()),
                     id: None,
                     class: HashSet::new(),
+                    structural: false,
+                    pseudo_classes: vec![],
                 }),
                 Selector::Simple(SimpleSelector {
                     tag_name: None,
                     id: None,
                     class: HashSet::new(),
+                    structural: false,
+                    pseudo_classes: vec![],
                 }),
             ],
             declarations: vec![
                 Declaration {
                     name: "width".to_string(),
                     values: vec![Value::Length(70.0, Unit::Percent)],
+                    important: false,
                 },
                 Declaration {
                     name: "height".to_string(),
                     values: vec![Value::Length(50.0, Unit::Px)],
+                    important: false,
                 },
                 Declaration {
                     name: "font-weight".to_string(),
                     values: vec![Value::Keyword("bold".to_string())],
+                    important: false,
                 },
                 Declaration {
                     name: "z-index".to_string(),
                     values: vec![Value::Num(2.0)],
+                    important: false,
                 },
                 Declaration {
                     name: "font-size".to_string(),
                     values: vec![Value::Length(10.0, Unit::Pt)],
+                    important: false,
                 },
                 Declaration {
                     name: "color".to_string(),
@@ -952,6 +1719,7 @@ fn test1() {
                             a: 0xff,
                         }),
                     ],
+                    important: false,
                 },
                 Declaration {
                     name: "background-color".to_string(),
@@ -963,23 +1731,33 @@ fn test1() {
                             a: 0xff,
                         }),
                     ],
+                    important: false,
                 },
                 Declaration {
                     name: "content".to_string(),
-                    values: vec![Value::Num(0.0)],
+                    values: vec![Value::Str("aa".to_string())],
+                    important: false,
                 },
                 Declaration {
                     name: "background-image".to_string(),
-                    values: vec![Value::Num(0.0)],
+                    values: vec![Value::Url("aaa".to_string())],
+                    important: false,
                 },
                 Declaration {
                     name: "unknown".to_string(),
                     values: vec![Value::Keyword("unknown".to_string())],
+                    important: false,
                 },
             ],
         },
     ];
-    assert_eq!(stylesheet, Stylesheet { rules: rules });
+    assert_eq!(
+        stylesheet,
+        Stylesheet {
+            rules: rules,
+            font_faces: vec![],
+        }
+    );
 }
 
 #[test]
@@ -988,18 +1766,41 @@ fn test2() {
     let decls = parse_attr_style(src.to_string());
 
     assert_eq!(
-        decls,
-        vec![
-            Declaration {
-                name: "color".to_string(),
-                values: vec![Value::Keyword("black".to_string())],
-            },
-            Declaration {
-                name: "background".to_string(),
-                values: vec![Value::Keyword("white".to_string())],
-            },
-        ]
+        decls[0],
+        Declaration {
+            name: "color".to_string(),
+            values: vec![Value::Keyword("black".to_string())],
+            important: false,
+        }
+    );
+    // `background` is expanded into its eight longhands.
+    assert_eq!(decls.len(), 9);
+    assert_eq!(
+        decls[8],
+        Declaration {
+            name: "background-color".to_string(),
+            values: vec![Value::Keyword("white".to_string())],
+            important: false,
+        }
+    );
+}
+
+#[test]
+fn test_important() {
+    let src = "color: red !important; margin: 0 ! IMPORTANT; top: 0";
+    let decls = parse_attr_style(src.to_string());
+    assert_eq!(
+        decls[0],
+        Declaration {
+            name: "color".to_string(),
+            values: vec![Value::Keyword("red".to_string())],
+            important: true,
+        }
     );
+    // Shorthands make all their longhands `!important`.
+    assert_eq!(decls.len(), 6);
+    assert!(decls[1..5].iter().all(|decl| decl.important));
+    assert!(!decls[5].important);
 }
 
 #[test]
@@ -1008,31 +1809,34 @@ fn test_rgb_rgba() {
     let decls = parse_attr_style(src.to_string());
 
     assert_eq!(
-        decls,
-        vec![
-            Declaration {
-                name: "color".to_string(),
-                values: vec![
-                    Value::Color(Color {
-                        r: 1,
-                        g: 2,
-                        b: 3,
-                        a: 255,
-                    }),
-                ],
-            },
-            Declaration {
-                name: "background".to_string(),
-                values: vec![
-                    Value::Color(Color {
-                        r: 250,
-                        g: 1,
-                        b: 250,
-                        a: (255.0 * 0.3) as u8,
-                    }),
-                ],
-            },
-        ]
+        decls[0],
+        Declaration {
+            name: "color".to_string(),
+            values: vec![
+                Value::Color(Color {
+                    r: 1,
+                    g: 2,
+                    b: 3,
+                    a: 255,
+                }),
+            ],
+            important: false,
+        }
+    );
+    assert_eq!(
+        decls.last(),
+        Some(&Declaration {
+            name: "background-color".to_string(),
+            values: vec![
+                Value::Color(Color {
+                    r: 250,
+                    g: 1,
+                    b: 250,
+                    a: (255.0 * 0.3) as u8,
+                }),
+            ],
+            important: false,
+        })
     );
 }
 
@@ -1059,6 +1863,52 @@ fn test_at_mark_rules() {
     );
 }
 
+#[test]
+fn test_font_face() {
+    use font::FontSlant;
+
+    let stylesheet = parse_with_base_url(
+        "
+          @font-face {
+            font-family: 'Web Sans';
+            src: local('Web Sans'), url(fonts/web.woff2) format('woff2'),
+                 url(fonts/web.woff) format('woff'),
+                 url('/web.ttf');
+            font-weight: 300 bold;
+            font-style: italic;
+            font-display: swap;
+          }
+          @font-face { font-family: Mono; src: url(mono.otf) }
+          @font-face { font-family: NoSource }
+          p { font-family: 'Web Sans' }
+        "
+            .to_string(),
+        Url::parse("file:///site/css/style.css").unwrap(),
+    );
+
+    assert_eq!(stylesheet.rules.len(), 1);
+    assert_eq!(
+        stylesheet.font_faces,
+        vec![
+            FontFace {
+                family: "Web Sans".to_string(),
+                sources: vec![
+                    "file:///site/css/fonts/web.woff".to_string(),
+                    "file:///web.ttf".to_string(),
+                ],
+                weight: (300.0, 700.0),
+                style: FontSlant::Italic,
+            },
+            FontFace {
+                family: "mono".to_string(),
+                sources: vec!["file:///site/css/mono.otf".to_string()],
+                weight: (400.0, 400.0),
+                style: FontSlant::Normal,
+            },
+        ]
+    );
+}
+
 #[test]
 fn test_pseudo() {
     parse(
@@ -1082,3 +1932,170 @@ fn test_attribute() {
             .to_string(),
     );
 }
+
+#[test]
+fn test_hex_colors() {
+    let src = "a: #f00; b: #f008; c: #00ff00; d: #0000ff80; e: #12345";
+    let decls = parse_attr_style(src.to_string());
+    let colors: Vec<Option<Color>> = decls
+        .iter()
+        .map(|decl| decl.values.get(0).and_then(|v| v.to_color()))
+        .collect();
+    assert_eq!(
+        colors,
+        vec![
+            Some(Color { r: 0xff, g: 0, b: 0, a: 0xff }),
+            Some(Color { r: 0xff, g: 0, b: 0, a: 0x88 }),
+            Some(Color { r: 0, g: 0xff, b: 0, a: 0xff }),
+            Some(Color { r: 0, g: 0, b: 0xff, a: 0x80 }),
+        ]
+    );
+}
+
+#[test]
+fn test_color_functions() {
+    let src = "a: rgb(100%, 0%, 50%); b: rgb(10 20 30 / 50%); c: hsl(120, 100%, 50%); \
+               d: hsla(240deg 100% 50% / 0.5); e: hwb(0 0% 0%); f: hwb(90 60% 60%); \
+               g: rgba(1, 2, 3, 0.3) red";
+    let decls = parse_attr_style(src.to_string());
+    let colors: Vec<Option<Color>> = decls.iter().map(|decl| decl.values[0].to_color()).collect();
+    assert_eq!(
+        colors,
+        vec![
+            Some(Color { r: 255, g: 0, b: 128, a: 255 }),
+            Some(Color { r: 10, g: 20, b: 30, a: 127 }),
+            Some(Color { r: 0, g: 255, b: 0, a: 255 }),
+            Some(Color { r: 0, g: 0, b: 255, a: 127 }),
+            Some(Color { r: 255, g: 0, b: 0, a: 255 }),
+            Some(Color { r: 128, g: 128, b: 128, a: 255 }),
+            Some(Color { r: 1, g: 2, b: 3, a: 76 }),
+        ]
+    );
+    assert_eq!(decls[6].values[1].to_color(), Some(RED));
+}
+
+#[test]
+fn test_named_colors() {
+    assert_eq!(
+        Value::Keyword("rebeccapurple".to_string()).to_color(),
+        Some(Color { r: 0x66, g: 0x33, b: 0x99, a: 0xff })
+    );
+    assert_eq!(
+        Value::Keyword("transparent".to_string()).to_color(),
+        Some(Color { r: 0, g: 0, b: 0, a: 0 })
+    );
+    assert_eq!(Value::Keyword("currentcolor".to_string()).to_color(), None);
+    assert_eq!(
+        Value::Keyword("currentcolor".to_string()).to_color_or_current(NAVY),
+        Some(NAVY)
+    );
+}
+
+#[test]
+fn test_length_units() {
+    let ctx = LengthContext {
+        font_size: 20.0,
+        root_font_size: 10.0,
+        viewport_width: 800.0,
+        viewport_height: 600.0,
+    };
+    let lengths = vec![
+        ("2em", 40.0),
+        ("1.5rem", 15.0),
+        ("2ex", 20.0),
+        ("1ch", 10.0),
+        ("10vw", 80.0),
+        ("50vh", 300.0),
+        ("10vmin", 60.0),
+        ("1vmax", 8.0),
+        ("1in", 96.0),
+        ("2.54cm", 96.0),
+        ("25.4mm", 96.0),
+        ("1pc", 16.0),
+        ("12px", 12.0),
+    ];
+    for (src, px) in lengths {
+        let value = parse_value(src.to_string());
+        assert_eq!(value.resolve_length(&ctx), Value::Length(px, Unit::Px), "{}", src);
+    }
+    assert_eq!(
+        parse_value("50%".to_string()).resolve_length(&ctx),
+        Value::Length(50.0, Unit::Percent)
+    );
+    assert!(parse_value("1vh".to_string()).is_viewport_relative());
+    assert_eq!(Unit::Rem.as_str(), "rem");
+}
+
+#[test]
+fn test_strings_and_urls() {
+    let src = r#"
+        a {
+            content: "say \"hi\"" 'caf\e9 \41 x' "\1F600";
+            background-image: url( img/bg.png );
+            cursor: url(\28 x\29.cur), url("../a b.png");
+            list-style-image: url(#marker)
+        }"#;
+    let base = Url::parse("http://example.com/css/site.css").unwrap();
+    let stylesheet = parse_with_base_url(src.to_string(), base);
+    let decls = &stylesheet.rules[0].declarations;
+    assert_eq!(
+        decls[0].values,
+        vec![
+            Value::Str("say \"hi\"".to_string()),
+            Value::Str("caf\u{e9}Ax".to_string()),
+            Value::Str("\u{1F600}".to_string()),
+        ]
+    );
+    assert_eq!(
+        decls[1].values,
+        vec![Value::Url("http://example.com/css/img/bg.png".to_string())]
+    );
+    assert_eq!(
+        decls[2].values,
+        vec![
+            Value::Url("http://example.com/css/(x).cur".to_string()),
+            Value::Url("http://example.com/a%20b.png".to_string()),
+        ]
+    );
+    assert_eq!(decls[3].values, vec![Value::Url("#marker".to_string())]);
+
+    let decls = parse_attr_style("background-image: url(a.png)".to_string());
+    assert_eq!(decls[0].values, vec![Value::Url("a.png".to_string())]);
+}
+
+#[test]
+fn test_grid_values() {
+    let src = r#"
+        a {
+            grid-template-areas: "head head"
+                                 "nav  main";
+            grid-template-columns: [a] repeat(2, 1fr) minmax(100px, auto)
+        }"#;
+    let stylesheet = parse(src.to_string());
+    let decls = &stylesheet.rules[0].declarations;
+    assert_eq!(
+        decls[0].values,
+        vec![
+            Value::Str("head head".to_string()),
+            Value::Str("nav  main".to_string()),
+        ]
+    );
+    assert_eq!(
+        decls[1].values,
+        vec![
+            Value::LineNames(vec!["a".to_string()]),
+            Value::Function(
+                "repeat".to_string(),
+                vec![vec![Value::Num(2.0)], vec![Value::Length(1.0, Unit::Fr)]],
+            ),
+            Value::Function(
+                "minmax".to_string(),
+                vec![
+                    vec![Value::Length(100.0, Unit::Px)],
+                    vec![Value::Keyword("auto".to_string())],
+                ],
+            ),
+        ]
+    );
+    assert_eq!(format!("{}", decls[1].values[1]), "repeat(2, 1fr)");
+}
@@ -1,198 +1,76 @@
-use css::*;
+use css::{self, Stylesheet};
+use selector_map::SelectorMap;
 
-use std::collections::HashSet;
 use std::cell::RefCell;
-
-pub fn default_style() -> Stylesheet {
-    Stylesheet {
-        rules: DEFAULT_RULES.with(|default_rules| default_rules.borrow().clone()),
-    }
-}
+use std::rc::Rc;
 
 thread_local!(
-    pub static DEFAULT_RULES: RefCell<Vec<Rule>> = {
-        let mut rules = vec![];
-        rule_universal(&mut rules);
-        rule_html(&mut rules);
-        // rule_body(&mut rules);
-        rule_span(&mut rules);
-        rule_h1(&mut rules);
-        rule_h2(&mut rules);
-        rule_h3(&mut rules);
-        rule_a(&mut rules);
-        rule_img(&mut rules);
-        rule_b(&mut rules);
-        rule_i(&mut rules);
-        rule_button(&mut rules);
-        rule_style(&mut rules);
-        rule_title(&mut rules);
-        rule_script(&mut rules);
-        RefCell::new(rules)
-    }
+    static DEFAULT_STYLE: Rc<SelectorMap> = {
+        let stylesheet = css::parse(include_str!("default_style.css").to_string());
+        Rc::new(SelectorMap::new(Rc::new(stylesheet)))
+    };
+    static USER_STYLE: RefCell<Rc<SelectorMap>> = {
+        RefCell::new(Rc::new(SelectorMap::new(Rc::new(Stylesheet {
+            rules: vec![],
+            font_faces: vec![],
+        }))))
+    };
 );
 
-macro_rules! tag_name { ($name:expr) => {
-    Selector::Simple(SimpleSelector {
-        tag_name: Some($name.to_string()), id: None, class: HashSet::new() })
-}}
-
-macro_rules! decl { ($name:expr, $( $val:expr ),*) => {
-    Declaration {
-        name: $name.to_string(),
-        values: vec![$($val)*],
-    }
-}}
-
-macro_rules! keyword { ($str:expr) => { Value::Keyword($str.to_string()) }}
-macro_rules! len_px  { ($val:expr) => { Value::Length($val, Unit::Px) }}
-// macro_rules! num     { ($val:expr) => { Value::Num($val) }}
-macro_rules! color   { ($clr:expr) => { Value::Color($clr) }}
-
-fn rule_universal(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![
-            Selector::Simple(SimpleSelector {
-                tag_name: None,
-                id: None,
-                class: HashSet::new(),
-            }),
-        ],
-        declarations: vec![decl!("display", keyword!("block"))],
-    });
-}
-
-fn rule_html(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("html")],
-        declarations: vec![
-            // decl!("width", keyword!("auto")),
-            // decl!("padding", len_px!(0f64)),
-            // decl!("margin", len_px!(0f64)),
-            decl!("background", color!(WHITE)),
-        ],
-    });
-}
-
-// fn rule_body(rules: &mut Vec<Rule>) {
-//     rules.push(Rule {
-//         selectors: vec![tag_name!("body")],
-//         declarations: vec![
-//             decl!("padding", len_px!(0f64)),
-//             decl!("margin", len_px!(0f64)),
-//         ],
-//     });
-// }
-
-fn rule_span(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("span")],
-        declarations: vec![decl!("display", keyword!("inline"))],
-    });
-}
-
-fn rule_h1(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("h1")],
-        declarations: vec![
-            decl!("font-size", len_px!(30f64)),
-            decl!("font-weight", keyword!("bold")),
-            decl!("padding", len_px!(10f64)),
-        ],
-    });
+/// The user agent stylesheet.
+pub fn default_style() -> Rc<SelectorMap> {
+    DEFAULT_STYLE.with(|default_style| default_style.clone())
 }
 
-fn rule_h2(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("h2")],
-        declarations: vec![
-            decl!("font-size", len_px!(24f64)),
-            decl!("font-weight", keyword!("bold")),
-            decl!("padding", len_px!(10f64)),
-        ],
-    });
+/// The stylesheet of the user, which overrides the user agent's and is overridden by authors'.
+pub fn user_style() -> Rc<SelectorMap> {
+    USER_STYLE.with(|user_style| user_style.borrow().clone())
 }
 
-fn rule_h3(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("h3")],
-        declarations: vec![
-            decl!("font-size", len_px!(19f64)),
-            decl!("font-weight", keyword!("bold")),
-            decl!("padding", len_px!(10f64)),
-        ],
+pub fn set_user_style(stylesheet: Stylesheet) {
+    USER_STYLE.with(|user_style| {
+        *user_style.borrow_mut() = Rc::new(SelectorMap::new(Rc::new(stylesheet)))
     });
 }
 
-fn rule_a(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("a")],
-        declarations: vec![
-            decl!("display", keyword!("inline")),
-            decl!(
-                "color",
-                color!(Color {
-                    r: 0,
-                    g: 0,
-                    b: 0xee,
-                    a: 0xff,
+#[test]
+fn test_default_style() {
+    use css::{Selector, Unit, Value};
+
+    let default_style = default_style();
+    let default_style = default_style.stylesheet();
+    let declarations = |tag_name: &str| {
+        default_style
+            .rules
+            .iter()
+            .filter(|rule| {
+                rule.selectors.iter().any(|selector| match *selector {
+                    Selector::Simple(ref simple) => simple.tag_name == Some(tag_name.to_string()),
+                    _ => false,
                 })
-            ),
-            decl!("text-decoration", keyword!("underline")),
-        ],
-    });
-}
-
-fn rule_img(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("img")],
-        declarations: vec![decl!("display", keyword!("inline"))],
-    });
-}
-
-fn rule_b(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("b")],
-        declarations: vec![
-            decl!("display", keyword!("inline")),
-            decl!("font-weight", keyword!("bold")),
-        ],
-    });
-}
-
-fn rule_i(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("i")],
-        declarations: vec![
-            decl!("display", keyword!("inline")),
-            decl!("font-style", keyword!("italic")),
-        ],
-    });
-}
-
-fn rule_button(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("button")],
-        declarations: vec![decl!("display", keyword!("inline"))],
-    });
-}
-
-fn rule_style(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("style")],
-        declarations: vec![decl!("display", keyword!("none"))],
-    });
-}
-
-fn rule_title(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("title")],
-        declarations: vec![decl!("display", keyword!("none"))],
-    });
-}
-
-fn rule_script(rules: &mut Vec<Rule>) {
-    rules.push(Rule {
-        selectors: vec![tag_name!("script")],
-        declarations: vec![decl!("display", keyword!("none"))],
-    });
+            })
+            .flat_map(|rule| rule.declarations.iter())
+            .map(|decl| (decl.name.as_str(), decl.values.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(declarations("p").contains(&("margin-top", vec![Value::Length(1.0, Unit::Em)])));
+    assert!(declarations("ul").contains(&("padding-left", vec![Value::Length(40.0, Unit::Px)])));
+    assert!(declarations("h5").contains(&("font-size", vec![Value::Length(0.83, Unit::Em)])));
+    assert!(declarations("strong").contains(&("font-weight", vec![
+        Value::Keyword("bolder".to_string()),
+    ])));
+    assert!(declarations("head").contains(&("display", vec![Value::Keyword("none".to_string())])));
+    // Shorthands are expanded like in any other stylesheet.
+    assert!(declarations("hr").contains(&("border-top-style", vec![
+        Value::Keyword("inset".to_string()),
+    ])));
+    // Elements are inline unless the stylesheet says otherwise.
+    assert!(declarations("span").is_empty());
+    // Links are styled by state, whatever their tag name.
+    assert!(declarations("a").is_empty());
+    assert!(default_style.rules.iter().any(|rule| {
+        rule.selectors[0] == css::parse(":visited {}".to_string()).rules[0].selectors[0]
+            && rule.declarations[0].name == "color"
+    }));
 }
@@ -157,6 +157,36 @@ impl Node {
         }
     }
 
+    /// The number of nodes in the tree rooted at `self`.
+    pub fn node_count(&self) -> usize {
+        1 + self.children
+            .iter()
+            .map(|child| child.node_count())
+            .sum::<usize>()
+    }
+
+    /// The nodes from `self` down to the node numbered `id`, with their numbers. The nodes of
+    /// the tree are numbered in tree order from `self`, which is 0.
+    pub fn path_to(&self, id: usize) -> Option<Vec<(usize, &Node)>> {
+        let mut path = vec![(0, self)];
+        let mut node_id = 0;
+        while node_id != id {
+            let mut child_id = node_id + 1;
+            let next = path.last().unwrap().1.children.iter().find(|child| {
+                let count = child.node_count();
+                if id < child_id + count {
+                    true
+                } else {
+                    child_id += count;
+                    false
+                }
+            })?;
+            path.push((child_id, next));
+            node_id = child_id;
+        }
+        Some(path)
+    }
+
     pub fn attr(&self, name: &str) -> Option<css::Value> {
         match self.data {
             NodeType::Element(ElementData { ref attrs, .. }) => attrs
@@ -180,6 +210,25 @@ impl ElementData {
             None => HashSet::new(),
         }
     }
+
+    /// Whether the element is a hyperlink, which `:link` or `:visited` match.
+    pub fn is_link(&self) -> bool {
+        match self.tag_name.as_str() {
+            "a" | "area" | "link" => self.attrs.contains_key("href"),
+            _ => false,
+        }
+    }
+
+    /// Whether the element can be focused by clicking it.
+    pub fn is_focusable(&self) -> bool {
+        match self.tag_name.as_str() {
+            "a" | "area" => self.attrs.contains_key("href"),
+            "button" | "input" | "select" | "textarea" | "iframe" | "summary" => {
+                !self.attrs.contains_key("disabled")
+            }
+            _ => self.attrs.contains_key("tabindex"),
+        }
+    }
 }
 
 // Functions for displaying
@@ -247,3 +296,32 @@ fn test_id() {
         None
     )
 }
+
+#[test]
+fn test_path_to() {
+    let tree = Node::elem(
+        "body".to_string(),
+        HashMap::new(),
+        vec![
+            Node::elem("p".to_string(), HashMap::new(), vec![Node::text("a".to_string())]),
+            Node::elem("div".to_string(), HashMap::new(), vec![Node::text("b".to_string())]),
+        ],
+    );
+    assert_eq!(tree.node_count(), 5);
+    let tag_names = |id| {
+        tree.path_to(id).map(|path| {
+            path.iter()
+                .map(|&(_, node)| match node.data {
+                    NodeType::Element(ref elem) => elem.tag_name.clone(),
+                    NodeType::Text(ref text) => text.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+    assert_eq!(tag_names(0), Some(vec!["body".to_string()]));
+    assert_eq!(
+        tag_names(4),
+        Some(vec!["body".to_string(), "div".to_string(), "b".to_string()])
+    );
+    assert_eq!(tag_names(5), None);
+}
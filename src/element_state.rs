@@ -0,0 +1,152 @@
+//! The state of the elements the user interacts with, which the dynamic pseudo-classes
+//! `:hover`, `:active`, `:focus` and `:focus-within` match.
+//!
+//! Nodes are identified by their number in tree order in the document, the root being 0, like
+//! the boxes built for them (`LayoutBox::node_id`).
+
+use dom::{Node, NodeType};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ElementState {
+    pub hover: bool,
+    pub active: bool,
+    pub focus: bool,
+    pub focus_within: bool,
+}
+
+/// The nodes under the pointer, being pressed and having the focus.
+#[derive(Clone, Default, PartialEq)]
+struct Targets {
+    hovered: Option<usize>,
+    active: Option<usize>,
+    focused: Option<usize>,
+}
+
+thread_local!(
+    static TARGETS: RefCell<Targets> = { RefCell::new(Targets::default()) };
+    // The state of the elements that have any, derived from TARGETS.
+    static STATES: RefCell<HashMap<usize, ElementState>> = { RefCell::new(HashMap::new()) };
+);
+
+pub fn state_of(node_id: usize) -> ElementState {
+    STATES.with(|states| states.borrow().get(&node_id).cloned().unwrap_or_default())
+}
+
+/// Forgets the state of the elements of the previous document.
+pub fn reset() {
+    TARGETS.with(|targets| *targets.borrow_mut() = Targets::default());
+    STATES.with(|states| states.borrow_mut().clear());
+}
+
+/// Moves the pointer over the node `node_id` of `document`. Returns the elements whose state
+/// changed, in tree order.
+pub fn set_hovered(document: &Node, node_id: Option<usize>) -> Vec<usize> {
+    update(document, |targets| targets.hovered = node_id)
+}
+
+/// Presses (or releases, if `node_id` is `None`) the node `node_id` of `document`.
+pub fn set_active(document: &Node, node_id: Option<usize>) -> Vec<usize> {
+    update(document, |targets| targets.active = node_id)
+}
+
+/// Focuses the element around the clicked node `node_id` that can be focused, or takes the
+/// focus away if there is none.
+pub fn set_focused(document: &Node, node_id: Option<usize>) -> Vec<usize> {
+    let focused = node_id
+        .and_then(|node_id| document.path_to(node_id))
+        .and_then(|path| {
+            path.into_iter()
+                .rev()
+                .find(|&(_, node)| match node.data {
+                    NodeType::Element(ref elem) => elem.is_focusable(),
+                    NodeType::Text(_) => false,
+                })
+                .map(|(id, _)| id)
+        });
+    update(document, |targets| targets.focused = focused)
+}
+
+fn update<F: FnOnce(&mut Targets)>(document: &Node, f: F) -> Vec<usize> {
+    let targets = TARGETS.with(|targets| {
+        let mut targets = targets.borrow_mut();
+        let old_targets = targets.clone();
+        f(&mut targets);
+        if *targets == old_targets {
+            None
+        } else {
+            Some(targets.clone())
+        }
+    });
+    let targets = match targets {
+        Some(targets) => targets,
+        None => return vec![],
+    };
+    let new_states = states(document, &targets);
+    STATES.with(|states| {
+        let mut states = states.borrow_mut();
+        let mut changed: Vec<usize> = states
+            .keys()
+            .chain(new_states.keys())
+            .filter(|id| states.get(id) != new_states.get(id))
+            .cloned()
+            .collect();
+        changed.sort();
+        changed.dedup();
+        *states = new_states;
+        changed
+    })
+}
+
+fn states(document: &Node, targets: &Targets) -> HashMap<usize, ElementState> {
+    let mut states: HashMap<usize, ElementState> = HashMap::new();
+    {
+        // `:hover`, `:active` and `:focus-within` also match the ancestors of the target.
+        let mut set_on_elements = |target: Option<usize>, set: fn(&mut ElementState)| {
+            let path = target.and_then(|target| document.path_to(target));
+            for (id, node) in path.unwrap_or_default() {
+                if let NodeType::Element(_) = node.data {
+                    set(states.entry(id).or_insert_with(ElementState::default));
+                }
+            }
+        };
+        set_on_elements(targets.hovered, |state| state.hover = true);
+        set_on_elements(targets.active, |state| state.active = true);
+        set_on_elements(targets.focused, |state| state.focus_within = true);
+    }
+    if let Some(focused) = targets.focused {
+        states
+            .entry(focused)
+            .or_insert_with(ElementState::default)
+            .focus = true;
+    }
+    states
+}
+
+#[test]
+fn test_element_state() {
+    use html;
+    use std::path::PathBuf;
+
+    let document = html::parse(
+        "<html><body><div><p>text</p><a href=\"a.html\">link</a></div></body></html>"
+            .to_string(),
+        PathBuf::from("test.html"),
+    );
+    // html 0, body 1, div 2, p 3, "text" 4, a 5, "link" 6
+    reset();
+    assert_eq!(set_hovered(&document, Some(4)), vec![0, 1, 2, 3]);
+    assert!(state_of(3).hover);
+    assert_eq!(set_hovered(&document, Some(6)), vec![3, 5]);
+    assert!(!state_of(3).hover && state_of(5).hover && state_of(2).hover);
+    assert_eq!(set_hovered(&document, Some(6)), vec![]);
+
+    // Only links and form controls get the focus.
+    assert_eq!(set_focused(&document, Some(4)), vec![]);
+    assert_eq!(set_focused(&document, Some(6)), vec![0, 1, 2, 5]);
+    assert!(state_of(5).focus && !state_of(2).focus && state_of(2).focus_within);
+    reset();
+    assert_eq!(state_of(5), ElementState::default());
+}
@@ -0,0 +1,832 @@
+//! Flex layout.
+//!
+//! The children of a flex container are its flex items, laid out in lines along its main axis,
+//! which is horizontal in rows and vertical in columns. The items of a line grow or shrink from
+//! their flex basis to fill it, and are aligned along the main axis with `justify-content` and
+//! across it with `align-items` and `align-self`. `align-content` places the lines.
+//! ref. https://www.w3.org/TR/css-flexbox-1/
+
+use layout::{BoxType, Dimensions, LayoutBox, LayoutInfo};
+use intrinsic::IntrinsicWidths;
+use sizing::clamp;
+use float::Floats;
+use margin::CollapsibleMargins;
+use dom::Node;
+use style::{BoxSizing, ComputedStyle, ContentDistribution, Display, FlexDirection, FlexWrap,
+            ItemAlignment, LengthOrPercentageOrAuto};
+
+use std::cmp::{max, min};
+
+use app_units::Au;
+
+/// A flex item being laid out. Sizes are along the main axis unless their name says otherwise,
+/// and margins are None while they are `auto`.
+#[derive(Clone, Debug)]
+struct FlexItem {
+    /// The index of the box of the item in the container.
+    index: usize,
+    grow: f64,
+    shrink: f64,
+    base_size: Au,
+    /// The minimum size, which is automatic unless it is specified: items don't shrink below
+    /// their content.
+    min_size: Au,
+    max_size: Option<Au>,
+    hypothetical_size: Au,
+    /// The size of the content box, once flexed.
+    target_size: Au,
+    frozen: bool,
+    /// The borders and paddings.
+    edges: Au,
+    margins: (Option<Au>, Option<Au>),
+    cross_size: Au,
+    cross_edges: Au,
+    cross_margins: (Option<Au>, Option<Au>),
+    /// The minimum and maximum cross sizes, which stretched items are limited to.
+    cross_limits: (Au, Option<Au>),
+    /// Whether the `width` of a column item or the `height` of a row item is `auto`, in which
+    /// case it can stretch.
+    has_auto_cross_size: bool,
+    /// The distance from the cross-start margin edge to the first baseline.
+    baseline: Au,
+    align: ItemAlignment,
+}
+
+impl FlexItem {
+    fn outer_hypothetical_size(&self) -> Au {
+        self.hypothetical_size + self.edges + margins(self.margins)
+    }
+
+    fn outer_target_size(&self) -> Au {
+        self.target_size + self.edges + margins(self.margins)
+    }
+
+    fn outer_cross_size(&self) -> Au {
+        self.cross_size + self.cross_edges + margins(self.cross_margins)
+    }
+}
+
+impl LayoutBox {
+    /// Adds a child to a flex container, or to a grid container, whose items are made alike. Text
+    /// is wrapped in anonymous items, and white space between the items is dropped.
+    /// ref. https://www.w3.org/TR/css-flexbox-1/#flex-items
+    pub fn push_flex_item(&mut self, child: LayoutBox) {
+        let follows_text = self.children
+            .last()
+            .map_or(false, |last| last.node_id.is_none());
+        match child.box_type {
+            _ if child.property.display == Display::None => {}
+            _ if child.is_whitespace() && !follows_text => {}
+            BoxType::TextNode(_) | BoxType::InlineNode => {
+                if !follows_text {
+                    let style = ComputedStyle {
+                        display: Display::Block,
+                        ..self.property.inherited()
+                    };
+                    self.children.push(LayoutBox::new(
+                        BoxType::BlockNode,
+                        Node::text("".to_string()),
+                        style,
+                        LayoutInfo::Generic,
+                    ));
+                }
+                self.children.last_mut().unwrap().push_child(child)
+            }
+            _ => self.children.push(child),
+        }
+    }
+
+    /// Lay out a flex container in normal flow, or in a line if it's an inline-level one.
+    pub fn layout_flex(
+        &mut self,
+        _floats: &mut Floats,
+        margins: Option<CollapsibleMargins>,
+        containing_block: Dimensions,
+        _saved_block: Dimensions,
+        viewport: Dimensions,
+    ) {
+        let cb_width = containing_block.content.width;
+        let is_inline = self.property.display == Display::InlineFlex;
+        if is_inline || cb_width < Au(0) {
+            self.calculate_shrink_to_fit_width(containing_block);
+        } else {
+            self.calculate_block_width(containing_block);
+        }
+        if !is_inline {
+            self.calculate_block_position(margins, containing_block);
+        }
+        // A flex container is a new formatting context, which floats outside don't enter.
+        self.floats = Floats::new();
+
+        self.layout_flex_items(containing_block, viewport);
+    }
+
+    /// Lays out the items of a flex container whose width is known, and sets its height.
+    fn layout_flex_items(&mut self, containing_block: Dimensions, viewport: Dimensions) {
+        let is_row = match self.property.flex_direction {
+            FlexDirection::Row | FlexDirection::RowReverse => true,
+            FlexDirection::Column | FlexDirection::ColumnReverse => false,
+        };
+        let is_reversed = match self.property.flex_direction {
+            FlexDirection::RowReverse | FlexDirection::ColumnReverse => true,
+            FlexDirection::Row | FlexDirection::Column => false,
+        };
+        let cb_width = containing_block.content.width;
+        let width = self.dimensions.content.width;
+        let height = self.property
+            .specified_height(cb_width, None)
+            .map(|height| self.property.clamp_height(height, cb_width, None));
+        let (main_size, cross_size) = if is_row {
+            (Some(width), height)
+        } else {
+            (height, Some(width))
+        };
+        // Percentages of an indefinite height are 0.
+        let column_gap = self.property.column_gap.to_au(width);
+        let row_gap = self.property.row_gap.to_au(height.unwrap_or(Au(0)));
+        let (main_gap, cross_gap) = if is_row {
+            (column_gap, row_gap)
+        } else {
+            (row_gap, column_gap)
+        };
+
+        // Items are laid out and painted in `order`, then in document order.
+        self.children.sort_by_key(|child| child.property.order);
+
+        let container = self.dimensions;
+        let align_items = self.property.align_items;
+        let mut items: Vec<FlexItem> = vec![];
+        for (index, child) in self.children.iter_mut().enumerate() {
+            // Absolutely positioned children aren't flex items.
+            if child.is_absolutely_positioned() {
+                continue;
+            }
+            items.push(child.flex_item(index, is_row, container, main_size, viewport));
+            items.last_mut().unwrap().align = match child.property.align_self {
+                Some(align) => align,
+                None => align_items,
+            };
+        }
+
+        // Collect the items into lines.
+        let mut lines: Vec<Vec<FlexItem>> = vec![];
+        let single_line = self.property.flex_wrap == FlexWrap::NoWrap;
+        let mut line_size = Au(0);
+        for item in items {
+            let outer_size = item.outer_hypothetical_size();
+            let breaks = match (lines.last(), main_size) {
+                (Some(line), Some(main_size)) if !single_line => {
+                    line_size + main_gap + outer_size > main_size && !line.is_empty()
+                }
+                (None, _) => true,
+                _ => false,
+            };
+            if breaks {
+                lines.push(vec![]);
+                line_size = outer_size;
+            } else {
+                line_size += main_gap + outer_size;
+            }
+            lines.last_mut().unwrap().push(item);
+        }
+
+        for line in &mut lines {
+            let gaps = main_gap * (line.len() as i32 - 1);
+            match main_size {
+                Some(main_size) => resolve_flexible_lengths(line, main_size, gaps),
+                None => for item in line.iter_mut() {
+                    item.target_size = item.hypothetical_size
+                },
+            }
+        }
+
+        // The cross sizes of the items follow from their main sizes.
+        for item in lines.iter_mut().flat_map(|line| line.iter_mut()) {
+            let child = &mut self.children[item.index];
+            if is_row {
+                child.layout_item(item.target_size, None, container, viewport);
+                item.cross_size = child.dimensions.content.height;
+                let d = child.dimensions;
+                item.baseline = d.margin.top + d.border.top + d.padding.top
+                    + child.content_inline_ascent();
+            } else {
+                let width = item.cross_size;
+                child.layout_item(width, Some(item.target_size), container, viewport);
+            }
+        }
+
+        // The sizes of the lines
+        let mut line_sizes: Vec<Au> = lines
+            .iter()
+            .map(|line| {
+                let (mut ascent, mut descent, mut size) = (Au(0), Au(0), Au(0));
+                for item in line {
+                    if is_row && item.align == ItemAlignment::Baseline {
+                        ascent = max(ascent, item.baseline);
+                        descent = max(descent, item.outer_cross_size() - item.baseline);
+                    } else {
+                        size = max(size, item.outer_cross_size());
+                    }
+                }
+                max(size, ascent + descent)
+            })
+            .collect();
+        let cross_gaps = cross_gap * (lines.len() as i32 - 1);
+        let lines_size = line_sizes.iter().fold(cross_gaps, |sum, &size| sum + size);
+        let cross_size = match cross_size {
+            Some(cross_size) => cross_size,
+            None => lines_size,
+        };
+        // A single line is as large as the container.
+        let (mut cross_position, mut between_lines) = (Au(0), cross_gap);
+        if single_line {
+            if let Some(size) = line_sizes.first_mut() {
+                *size = cross_size;
+            }
+        } else {
+            let free_space = cross_size - lines_size;
+            let (offset, spacing) = distribute(
+                self.property.align_content,
+                free_space,
+                lines.len(),
+                ContentDistribution::Stretch,
+            );
+            if self.property.align_content == ContentDistribution::Stretch
+                || self.property.align_content == ContentDistribution::Normal
+            {
+                if free_space > Au(0) {
+                    let each = free_space / lines.len() as i32;
+                    for size in &mut line_sizes {
+                        *size += each;
+                    }
+                }
+            }
+            cross_position = offset;
+            between_lines += spacing;
+        }
+
+        let main_size = match main_size {
+            Some(main_size) => main_size,
+            None => lines
+                .iter()
+                .map(|line| {
+                    line.iter().fold(main_gap * (line.len() as i32 - 1), |sum, item| {
+                        sum + item.outer_target_size()
+                    })
+                })
+                .max()
+                .unwrap_or(Au(0)),
+        };
+
+        for (line, &line_size) in lines.iter_mut().zip(&line_sizes) {
+            // Stretched items are as large as their line.
+            for item in line.iter_mut() {
+                let is_stretched = match item.align {
+                    ItemAlignment::Stretch | ItemAlignment::Normal => true,
+                    _ => false,
+                };
+                let has_auto_margin = item.cross_margins.0.is_none()
+                    || item.cross_margins.1.is_none();
+                if is_stretched && item.has_auto_cross_size && !has_auto_margin {
+                    let size = line_size - item.cross_edges - margins(item.cross_margins);
+                    let size = clamp(max(Au(0), size), item.cross_limits);
+                    item.cross_size = size;
+                    let child = &mut self.children[item.index];
+                    if is_row {
+                        child.layout_item(item.target_size, Some(size), container, viewport);
+                    } else {
+                        child.layout_item(size, Some(item.target_size), container, viewport);
+                    }
+                }
+            }
+
+            // Free space in the main axis goes to the `auto` margins, or is shared as
+            // `justify-content` says.
+            let gaps = main_gap * (line.len() as i32 - 1);
+            let used = line.iter().fold(gaps, |sum, item| sum + item.outer_target_size());
+            let mut free_space = main_size - used;
+            let auto_margins = line.iter().fold(0, |count, item| {
+                count + item.margins.0.is_none() as i32 + item.margins.1.is_none() as i32
+            });
+            for item in line.iter_mut() {
+                let each = if auto_margins > 0 && free_space > Au(0) {
+                    free_space / auto_margins
+                } else {
+                    Au(0)
+                };
+                item.margins = (
+                    Some(item.margins.0.unwrap_or(each)),
+                    Some(item.margins.1.unwrap_or(each)),
+                );
+            }
+            if auto_margins > 0 && free_space > Au(0) {
+                free_space = Au(0);
+            }
+            let (offset, spacing) = distribute(
+                self.property.justify_content,
+                free_space,
+                line.len(),
+                ContentDistribution::FlexStart,
+            );
+
+            let baseline = line.iter()
+                .filter(|item| item.align == ItemAlignment::Baseline)
+                .map(|item| item.baseline)
+                .max()
+                .unwrap_or(Au(0));
+            let mut position = offset;
+            for item in line.iter_mut() {
+                let outer_size = item.outer_target_size();
+                // Reversed lines start at the main-end edge.
+                let main_position = if is_reversed {
+                    main_size - position - outer_size
+                } else {
+                    position
+                };
+                position += outer_size + main_gap + spacing;
+
+                let free_space = line_size - item.outer_cross_size();
+                let auto_margins =
+                    item.cross_margins.0.is_none() as i32 + item.cross_margins.1.is_none() as i32;
+                let offset = if auto_margins > 0 {
+                    let each = max(Au(0), free_space) / auto_margins;
+                    item.cross_margins = (
+                        Some(item.cross_margins.0.unwrap_or(each)),
+                        Some(item.cross_margins.1.unwrap_or(each)),
+                    );
+                    Au(0)
+                } else {
+                    match item.align {
+                        ItemAlignment::FlexEnd => free_space,
+                        ItemAlignment::Center => free_space / 2,
+                        ItemAlignment::Baseline if is_row => baseline - item.baseline,
+                        _ => Au(0),
+                    }
+                };
+                // With `wrap-reverse`, lines start at the cross-end edge.
+                let outer_cross_size = item.outer_cross_size();
+                let cross_position = if self.property.flex_wrap == FlexWrap::WrapReverse {
+                    cross_size - cross_position - offset - outer_cross_size
+                } else {
+                    cross_position + offset
+                };
+
+                let margins = (item.margins.0.unwrap(), item.margins.1.unwrap());
+                let cross_margins = (item.cross_margins.0.unwrap(), item.cross_margins.1.unwrap());
+                let d = &mut self.children[item.index].dimensions;
+                if is_row {
+                    d.margin.left = margins.0;
+                    d.margin.right = margins.1;
+                    d.margin.top = cross_margins.0;
+                    d.margin.bottom = cross_margins.1;
+                    d.content.x = main_position + d.margin.left + d.border.left + d.padding.left;
+                    d.content.y = cross_position + d.margin.top + d.border.top + d.padding.top;
+                } else {
+                    d.margin.top = margins.0;
+                    d.margin.bottom = margins.1;
+                    d.margin.left = cross_margins.0;
+                    d.margin.right = cross_margins.1;
+                    d.content.x = cross_position + d.margin.left + d.border.left + d.padding.left;
+                    d.content.y = main_position + d.margin.top + d.border.top + d.padding.top;
+                }
+            }
+            cross_position += line_size + between_lines;
+        }
+
+        let height = if is_row { cross_size } else { main_size };
+        self.dimensions.content.height = self.property.clamp_height(height, cb_width, None);
+    }
+
+    /// Measures a flex item of a container whose content box is `container`, and whose main size
+    /// is `main_size` if it is definite.
+    /// ref. https://www.w3.org/TR/css-flexbox-1/#algo-main-item
+    fn flex_item(
+        &mut self,
+        index: usize,
+        is_row: bool,
+        container: Dimensions,
+        main_size: Option<Au>,
+        viewport: Dimensions,
+    ) -> FlexItem {
+        self.assign_padding(container);
+        self.assign_border_width();
+        let cb_width = container.content.width;
+        let style = &self.property;
+        let margin = |margin: LengthOrPercentageOrAuto| margin.to_au(cb_width);
+        let d = self.dimensions;
+        let horizontal_edges = d.border.left + d.border.right + d.padding.left + d.padding.right;
+        let vertical_edges = d.border.top + d.border.bottom + d.padding.top + d.padding.bottom;
+        let horizontal_margins = (margin(style.margin.left), margin(style.margin.right));
+        let vertical_margins = (margin(style.margin.top), margin(style.margin.bottom));
+
+        let mut item = FlexItem {
+            index: index,
+            grow: style.flex_grow,
+            shrink: style.flex_shrink,
+            base_size: Au(0),
+            min_size: Au(0),
+            max_size: None,
+            hypothetical_size: Au(0),
+            target_size: Au(0),
+            frozen: false,
+            edges: horizontal_edges,
+            margins: horizontal_margins,
+            cross_size: Au(0),
+            cross_edges: vertical_edges,
+            cross_margins: vertical_margins,
+            cross_limits: style.height_limits(cb_width, None),
+            has_auto_cross_size: if is_row {
+                style.height == LengthOrPercentageOrAuto::Auto
+            } else {
+                style.width == LengthOrPercentageOrAuto::Auto
+            },
+            baseline: Au(0),
+            align: ItemAlignment::Normal,
+        };
+        // The sizes are those of the content box, whatever `box-sizing` says.
+        let (specified_size, (min_size, max_size), min_size_is_auto) = if is_row {
+            (
+                style.specified_width(cb_width),
+                style.width_limits(Some(cb_width)),
+                style.min_width == LengthOrPercentageOrAuto::Auto,
+            )
+        } else {
+            (
+                style.specified_height(cb_width, main_size),
+                style.height_limits(cb_width, main_size),
+                style.min_height == LengthOrPercentageOrAuto::Auto,
+            )
+        };
+        let content_size = |size: Au| if is_row {
+            style.content_width(size, cb_width)
+        } else {
+            style.content_height(size, cb_width)
+        };
+        let basis = match (style.flex_basis, main_size) {
+            (LengthOrPercentageOrAuto::Auto, _) => specified_size,
+            (LengthOrPercentageOrAuto::Length(basis), _) => Some(content_size(basis)),
+            (basis, Some(main_size)) => basis.to_au(main_size).map(content_size),
+            _ => None,
+        };
+
+        let content_min_size = if is_row {
+            let widths = self.content_intrinsic_widths();
+            item.base_size = basis.unwrap_or(widths.max);
+            widths.min
+        } else {
+            // Columns are laid out at their width to measure their content.
+            item.edges = vertical_edges;
+            item.margins = vertical_margins;
+            item.cross_edges = horizontal_edges;
+            item.cross_margins = horizontal_margins;
+            item.cross_limits = style.width_limits(Some(cb_width));
+            let available = cb_width - horizontal_edges - margins(horizontal_margins);
+            let width = style
+                .specified_width(cb_width)
+                .unwrap_or_else(|| self.content_intrinsic_widths().shrink_to_fit(available));
+            item.cross_size = style.clamp_width(width, Some(cb_width));
+            let width = item.cross_size;
+            self.property.height = LengthOrPercentageOrAuto::Auto;
+            self.layout_item(width, None, container, viewport);
+            let content_size = self.dimensions.content.height;
+            item.base_size = basis.unwrap_or(content_size);
+            content_size
+        };
+        // The automatic minimum size is that of the content, or the specified size if it's
+        // smaller, but no more than the maximum size.
+        item.min_size = if min_size_is_auto {
+            let min_size = min(specified_size.unwrap_or(content_min_size), content_min_size);
+            clamp(min_size, (Au(0), max_size))
+        } else {
+            min_size
+        };
+        // A maximum size below the minimum size is the minimum size.
+        item.max_size = max_size.map(|max_size| max(max_size, item.min_size));
+        item.hypothetical_size = clamp(item.base_size, (item.min_size, item.max_size));
+        item
+    }
+
+    /// Lays out a flex or grid item `width` wide, and `height` tall if it's given, whatever its
+    /// own width and height. Its margins and position are set by its container afterwards.
+    pub fn layout_item(
+        &mut self,
+        width: Au,
+        height: Option<Au>,
+        container: Dimensions,
+        viewport: Dimensions,
+    ) {
+        // The used sizes replace the specified ones, which the layout of the box then uses.
+        // They are those of the content box, and `box-sizing` still applies to an `auto` height.
+        let cb_width = container.content.width;
+        let (width_edges, height_edges) = match self.property.box_sizing {
+            BoxSizing::ContentBox => (Au(0), Au(0)),
+            BoxSizing::BorderBox => (
+                self.property.horizontal_box_edges(cb_width),
+                self.property.vertical_box_edges(cb_width),
+            ),
+        };
+        self.property.width = LengthOrPercentageOrAuto::Length(width + width_edges);
+        self.property.min_width = LengthOrPercentageOrAuto::Auto;
+        self.property.max_width = None;
+        if let Some(height) = height {
+            self.property.height = LengthOrPercentageOrAuto::Length(height + height_edges);
+            self.property.min_height = LengthOrPercentageOrAuto::Auto;
+            self.property.max_height = None;
+        }
+        let mut containing_block = container;
+        containing_block.content.x = Au(0);
+        containing_block.content.y = Au(0);
+        containing_block.content.height = Au(0);
+        self.layout(
+            &mut Floats::new(),
+            None,
+            containing_block,
+            containing_block,
+            viewport,
+        );
+    }
+
+    /// The intrinsic widths of the content of a flex container, from those of its items.
+    pub fn flex_intrinsic_widths(&self) -> IntrinsicWidths {
+        let children: Vec<&LayoutBox> = self.children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .collect();
+        let items = children.iter().map(|child| child.intrinsic_widths());
+        match self.property.flex_direction {
+            FlexDirection::Row | FlexDirection::RowReverse => {
+                let gap = match children.len() {
+                    0 => Au(0),
+                    n => self.property.column_gap.to_au(Au(0)) * (n as i32 - 1),
+                };
+                let widths = items.fold(IntrinsicWidths::new(gap, gap), |sum, item| {
+                    IntrinsicWidths::new(sum.min + item.min, sum.max + item.max)
+                });
+                // Items can wrap onto a line each.
+                if self.property.flex_wrap == FlexWrap::NoWrap {
+                    widths
+                } else {
+                    let min_width = children
+                        .iter()
+                        .map(|child| child.intrinsic_widths().min)
+                        .max()
+                        .unwrap_or(Au(0));
+                    IntrinsicWidths::new(min_width, widths.max)
+                }
+            }
+            FlexDirection::Column | FlexDirection::ColumnReverse => {
+                items.fold(IntrinsicWidths::default(), |widths, item| {
+                    IntrinsicWidths::new(max(widths.min, item.min), max(widths.max, item.max))
+                })
+            }
+        }
+    }
+}
+
+/// Resolves the main sizes of the items of a line `available` long, with `gaps` between them.
+/// Items grow in proportion to their `flex-grow`, or shrink in proportion to their
+/// `flex-shrink` times their base size, and are frozen at their minimum or maximum size if they
+/// would get past it.
+/// ref. https://www.w3.org/TR/css-flexbox-1/#resolve-flexible-lengths
+fn resolve_flexible_lengths(items: &mut [FlexItem], available: Au, gaps: Au) {
+    let outer_hypothetical = items
+        .iter()
+        .fold(gaps, |sum, item| sum + item.outer_hypothetical_size());
+    let is_growing = outer_hypothetical < available;
+    let factor = |item: &FlexItem| if is_growing { item.grow } else { item.shrink };
+
+    for item in items.iter_mut() {
+        item.target_size = item.hypothetical_size;
+        item.frozen = factor(item) == 0.0
+            || (is_growing && item.base_size > item.hypothetical_size)
+            || (!is_growing && item.base_size < item.hypothetical_size);
+    }
+    let free_space = |items: &[FlexItem]| {
+        let used = items.iter().fold(gaps, |sum, item| {
+            let size = if item.frozen {
+                item.target_size
+            } else {
+                item.base_size
+            };
+            sum + size + item.edges + margins(item.margins)
+        });
+        available - used
+    };
+    let initial_free_space = free_space(items);
+
+    while items.iter().any(|item| !item.frozen) {
+        let mut remaining = free_space(items);
+        let factors: f64 = items
+            .iter()
+            .filter(|item| !item.frozen)
+            .map(|item| factor(item))
+            .sum();
+        if factors < 1.0 {
+            let scaled = Au::from_f64_px(initial_free_space.to_f64_px() * factors);
+            if scaled.0.abs() < remaining.0.abs() {
+                remaining = scaled;
+            }
+        }
+
+        let scaled_shrink: f64 = items
+            .iter()
+            .filter(|item| !item.frozen)
+            .map(|item| item.shrink * item.base_size.to_f64_px())
+            .sum();
+        let mut violations = vec![];
+        for item in items.iter_mut().filter(|item| !item.frozen) {
+            let ratio = if is_growing {
+                item.grow / factors
+            } else if scaled_shrink > 0.0 {
+                item.shrink * item.base_size.to_f64_px() / scaled_shrink
+            } else {
+                0.0
+            };
+            let size = item.base_size + Au::from_f64_px(remaining.to_f64_px() * ratio);
+            item.target_size = clamp(size, (item.min_size, item.max_size));
+            violations.push(item.target_size - size);
+        }
+
+        // Items clamped up are frozen if the sizes were clamped up in total, those clamped down
+        // if they were clamped down, or all of them if none is.
+        let violation = violations.iter().fold(Au(0), |sum, &violation| sum + violation);
+        let unfrozen = items.iter_mut().filter(|item| !item.frozen);
+        for (item, item_violation) in unfrozen.zip(violations) {
+            item.frozen = violation == Au(0)
+                || (violation > Au(0) && item_violation > Au(0))
+                || (violation < Au(0) && item_violation < Au(0));
+        }
+    }
+}
+
+/// The offset of the first of `count` boxes and the extra spacing between them that share
+/// `free_space` as `distribution` says. `normal` behaves as `normal_as`, and the distributions
+/// that can't share negative space fall back to the start or the center.
+/// ref. https://www.w3.org/TR/css-align-3/#distribution-values
+pub fn distribute(
+    distribution: ContentDistribution,
+    free_space: Au,
+    count: usize,
+    normal_as: ContentDistribution,
+) -> (Au, Au) {
+    let count = count as i32;
+    let distribution = match distribution {
+        ContentDistribution::Normal => normal_as,
+        distribution => distribution,
+    };
+    match distribution {
+        ContentDistribution::FlexEnd => (free_space, Au(0)),
+        ContentDistribution::Center => (free_space / 2, Au(0)),
+        ContentDistribution::SpaceBetween if free_space > Au(0) && count > 1 => {
+            (Au(0), free_space / (count - 1))
+        }
+        ContentDistribution::SpaceAround if free_space > Au(0) && count > 0 => {
+            (free_space / count / 2, free_space / count)
+        }
+        ContentDistribution::SpaceEvenly if free_space > Au(0) => {
+            (free_space / (count + 1), free_space / (count + 1))
+        }
+        ContentDistribution::SpaceAround | ContentDistribution::SpaceEvenly => {
+            (free_space / 2, Au(0))
+        }
+        _ => (Au(0), Au(0)),
+    }
+}
+
+/// The sum of two margins, `auto` ones being 0.
+fn margins(margins: (Option<Au>, Option<Au>)) -> Au {
+    margins.0.unwrap_or(Au(0)) + margins.1.unwrap_or(Au(0))
+}
+
+#[test]
+fn test_resolve_flexible_lengths() {
+    let px = Au::from_px;
+    let item = |grow, shrink, base_size, min_size| FlexItem {
+        index: 0,
+        grow: grow,
+        shrink: shrink,
+        base_size: px(base_size),
+        min_size: px(min_size),
+        max_size: None,
+        hypothetical_size: max(px(base_size), px(min_size)),
+        target_size: Au(0),
+        frozen: false,
+        edges: px(10),
+        margins: (Some(px(5)), None),
+        cross_size: Au(0),
+        cross_edges: Au(0),
+        cross_margins: (None, None),
+        cross_limits: (Au(0), None),
+        has_auto_cross_size: true,
+        baseline: Au(0),
+        align: ItemAlignment::Normal,
+    };
+    let sizes = |items: &[FlexItem]| {
+        items
+            .iter()
+            .map(|item| item.target_size)
+            .collect::<Vec<Au>>()
+    };
+
+    // 300px - 45px of edges and margins - 10px of gaps - 100px of base sizes = 145px to share.
+    let mut items = vec![item(1.0, 1.0, 0, 0), item(2.0, 1.0, 100, 0), item(0.0, 1.0, 0, 0)];
+    resolve_flexible_lengths(&mut items, px(300), px(10));
+    assert_eq!(sizes(&items), vec![Au(2900), Au(11800), Au(0)]);
+
+    // Grow factors adding up to less than 1 take that part of the free space.
+    let mut items = vec![item(0.25, 1.0, 0, 0), item(0.25, 1.0, 0, 0)];
+    resolve_flexible_lengths(&mut items, px(230), Au(0));
+    assert_eq!(sizes(&items), vec![px(50), px(50)]);
+
+    // Shrinking in proportion to the base sizes, down to the minimum sizes.
+    let mut items = vec![item(0.0, 1.0, 100, 0), item(0.0, 1.0, 300, 0)];
+    resolve_flexible_lengths(&mut items, px(230), Au(0));
+    assert_eq!(sizes(&items), vec![px(50), px(150)]);
+    let mut items = vec![item(0.0, 1.0, 100, 80), item(0.0, 1.0, 300, 0)];
+    resolve_flexible_lengths(&mut items, px(230), Au(0));
+    assert_eq!(sizes(&items), vec![px(80), px(120)]);
+
+    // Growing up to the maximum sizes, the rest going to the other items.
+    let mut items = vec![item(1.0, 1.0, 0, 0), item(1.0, 1.0, 0, 0)];
+    items[0].max_size = Some(px(50));
+    resolve_flexible_lengths(&mut items, px(230), Au(0));
+    assert_eq!(sizes(&items), vec![px(50), px(150)]);
+
+    // A minimum size above the maximum size wins.
+    let mut items = vec![item(1.0, 1.0, 0, 100), item(0.0, 1.0, 0, 0)];
+    items[0].max_size = Some(px(50));
+    resolve_flexible_lengths(&mut items, px(230), Au(0));
+    assert_eq!(sizes(&items), vec![px(100), px(0)]);
+}
+
+#[test]
+fn test_distribute() {
+    let px = Au::from_px;
+    let distribute = |distribution, free_space| {
+        distribute(distribution, px(free_space), 4, ContentDistribution::FlexStart)
+    };
+    assert_eq!(distribute(ContentDistribution::Normal, 100), (px(0), px(0)));
+    assert_eq!(distribute(ContentDistribution::FlexEnd, 100), (px(100), px(0)));
+    assert_eq!(distribute(ContentDistribution::SpaceBetween, 90), (px(0), px(30)));
+    assert_eq!(distribute(ContentDistribution::SpaceAround, 100), (Au(750), px(25)));
+    assert_eq!(distribute(ContentDistribution::SpaceEvenly, 100), (px(20), px(20)));
+    assert_eq!(distribute(ContentDistribution::SpaceEvenly, -100), (px(-50), px(0)));
+}
+
+#[test]
+fn test_flex_item_sizes() {
+    use layout::block;
+
+    let px = Au::from_px;
+    let mut viewport = Dimensions::default();
+    viewport.content.width = px(800);
+    let widths = |container: &LayoutBox| {
+        container
+            .children
+            .iter()
+            .map(|child| child.dimensions.content.width)
+            .collect::<Vec<Au>>()
+    };
+
+    // Sizes are those of the box `box-sizing` refers to, and limited by `max-width`.
+    let mut container = block(
+        "display: flex; width: 300px",
+        vec![
+            block("width: 100px; box-sizing: border-box; padding: 0 10px", vec![]),
+            block("flex-grow: 1; max-width: 50px", vec![]),
+            block("flex-grow: 1", vec![]),
+        ],
+    );
+    container.layout(&mut Floats::new(), None, viewport, viewport, viewport);
+    assert_eq!(widths(&container), vec![px(80), px(50), px(150)]);
+
+    // So are the sizes of the items of a grid, and of inline-level containers.
+    let mut container = block(
+        "display: grid; width: 300px",
+        vec![block("max-width: 100px", vec![])],
+    );
+    container.layout(&mut Floats::new(), None, viewport, viewport, viewport);
+    assert_eq!(widths(&container), vec![px(100)]);
+
+    let mut container = block(
+        "display: inline-flex; box-sizing: border-box; width: 100px; padding: 0 10px; \
+         max-width: 60px",
+        vec![],
+    );
+    container.calculate_shrink_to_fit_width(viewport);
+    assert_eq!(container.dimensions.content.width, px(40));
+
+    // The heights of flex and grid containers are limited as well.
+    let height = |style: &str| {
+        let mut container = block(style, vec![block("height: 50px", vec![])]);
+        container.layout(&mut Floats::new(), None, viewport, viewport, viewport);
+        container.dimensions.content.height
+    };
+    let border_box = "height: 100px; box-sizing: border-box; padding: 10px";
+    assert_eq!(height(&format!("display: flex; {}", border_box)), px(80));
+    assert_eq!(height("display: flex; min-height: 70px"), px(70));
+    assert_eq!(height("display: flex; flex-direction: column; max-height: 30px"), px(30));
+    assert_eq!(height(&format!("display: grid; {}", border_box)), px(80));
+    assert_eq!(height("display: grid; min-height: 70px"), px(70));
+    assert_eq!(height("display: grid; max-height: 30px"), px(30));
+}
@@ -1,4 +1,5 @@
-use layout::{BoxType, Dimensions, EdgeSizes, LayoutBox, LayoutInfo, Rect};
+use layout::{Dimensions, EdgeSizes, LayoutBox, LayoutInfo, Rect};
+use margin::CollapsibleMargins;
 use style;
 
 use std::cmp::{max, min};
@@ -145,51 +146,22 @@ impl LayoutBox {
     pub fn layout_float(
         &mut self,
         floats: &mut Floats,
-        _last_margin_bottom: Au,
+        _margins: Option<CollapsibleMargins>,
         containing_block: Dimensions,
         _saved_block: Dimensions,
         viewport: Dimensions,
     ) {
-        self.assign_padding();
+        self.assign_padding(containing_block);
         self.assign_border_width();
-        self.assign_margin();
+        self.assign_margin(containing_block);
 
         // TODO: Implement correctly
         match self.info {
             LayoutInfo::Image(_) => self.load_image(containing_block),
             LayoutInfo::Generic | LayoutInfo::Anker => {
-                let width_not_specified = self.calculate_float_width(containing_block);
-
-                if width_not_specified {
-                    // When dimensions.content.width < Au(0), LineBreaker inside layout_float_children()
-                    // calculates shrink-to-fit width.
-                    self.dimensions.content.width = Au(-1);
-                }
-
+                self.calculate_float_width(containing_block);
                 self.layout_float_children(viewport);
-
-                if width_not_specified {
-                    self.dimensions.content.width = Au(0);
-
-                    for child in &self.children {
-                        match child.box_type {
-                            BoxType::BlockNode | BoxType::AnonymousBlock => {
-                                self.dimensions.content.width = max(
-                                    self.dimensions.content.width,
-                                    child.dimensions.border_box().width,
-                                );
-                            }
-                            BoxType::Float => {
-                                // Ignore whether the float is on left or right
-                                self.dimensions.content.width +=
-                                    child.dimensions.border_box().width;
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-
-                self.calculate_block_height();
+                self.calculate_block_height(containing_block);
             }
             _ => unimplemented!("{:?}", self.info),
         };
@@ -201,12 +173,12 @@ impl LayoutBox {
 
         floats.add_float(Float::new(
             self.dimensions.margin_box(),
-            self.get_style_node().float(),
+            self.get_style_node().float,
         ));
     }
 
     pub fn layout_float_children(&mut self, viewport: Dimensions) {
-        self.layout_block_children(viewport);
+        self.layout_block_children(CollapsibleMargins::default(), viewport);
         // The height of float children in a float element is noticed.
         self.dimensions.content.height = max(
             self.dimensions.content.height,
@@ -224,7 +196,7 @@ impl LayoutBox {
                 margin_box.height,
             );
             if margin_box.width <= available_area.width {
-                self.dimensions.content.x = match self.property.float() {
+                self.dimensions.content.x = match self.property.float {
                     style::FloatType::Left => self.dimensions.left_offset() + available_area.x,
                     style::FloatType::Right => {
                         available_area.width + available_area.x - self.dimensions.content.width
@@ -245,27 +217,16 @@ impl LayoutBox {
         }
     }
 
-    /// Calculate the width of a float (non-replaced) element.
-    /// Sets the horizontal margin/padding/border dimensions, and the `width`.
-    /// Returns if the width of this float element is NOT specified.
-    /// ref. https://www.w3.org/TR/2007/CR-CSS21-20070719/visudet.html#float-width
-    // TODO: Implement correctly!
-    pub fn calculate_float_width(&mut self, containing_block: Dimensions) -> bool {
-        let cb_width = containing_block.content.width.to_f64_px();
-
-        let d = &mut self.dimensions;
-
-        if let Some(x) = self.property.value("width") {
-            let width = x[0].clone();
-            if let Some(width) = width.maybe_percent_to_px(cb_width) {
-                d.content.width = Au::from_f64_px(width)
-            }
-            false
-        } else {
-            // width == auto
-            d.content.width = containing_block.content.width;
-            // width not specified
-            true
-        }
+    /// Calculate the width of a float (non-replaced) element, shrink-to-fit if it's `auto`.
+    /// The horizontal margin/padding/border dimensions are set beforehand.
+    /// ref. https://www.w3.org/TR/CSS22/visudet.html#float-width
+    pub fn calculate_float_width(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
+        let width = match self.property.specified_width(cb_width) {
+            Some(width) => width,
+            None => self.shrink_to_fit_width(containing_block),
+        };
+        self.dimensions.content.width = self.property.clamp_width(width, Some(cb_width));
     }
+
 }
@@ -3,6 +3,9 @@ use pango;
 use pangocairo;
 
 use css::px2pt;
+use font_face;
+use line_break;
+use style::ComputedStyle;
 
 use std::cell::RefCell;
 use pango::{ContextExt, LayoutExt};
@@ -21,52 +24,184 @@ thread_local!(
     }
 );
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Font {
+    pub family: Vec<FontFamily>,
     pub size: Au,
     pub weight: FontWeight,
     pub slant: FontSlant,
+    pub variant: FontVariant,
+    pub stretch: FontStretch,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum FontWeight {
-    Normal,
-    Bold,
+/// An entry of a `font-family` list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FontFamily {
+    Named(String),
+    Serif,
+    SansSerif,
+    Monospace,
+    Cursive,
+    Fantasy,
+    SystemUi,
 }
 
+/// A weight from 1 to 1000.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontWeight(pub f64);
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FontSlant {
     Normal,
     Italic,
+    Oblique,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontVariant {
+    Normal,
+    SmallCaps,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontStretch {
+    UltraCondensed,
+    ExtraCondensed,
+    Condensed,
+    SemiCondensed,
+    Normal,
+    SemiExpanded,
+    Expanded,
+    ExtraExpanded,
+    UltraExpanded,
+}
+
+impl FontWeight {
+    pub const NORMAL: FontWeight = FontWeight(400.0);
+    pub const BOLD: FontWeight = FontWeight(700.0);
+
+    /// The weight `bolder` computes to when this is the inherited weight.
+    /// ref. https://www.w3.org/TR/css-fonts-4/#relative-weights
+    pub fn bolder(&self) -> FontWeight {
+        FontWeight(match self.0 {
+            w if w < 350.0 => 400.0,
+            w if w < 550.0 => 700.0,
+            w if w < 900.0 => 900.0,
+            w => w,
+        })
+    }
+
+    /// The weight `lighter` computes to when this is the inherited weight.
+    pub fn lighter(&self) -> FontWeight {
+        FontWeight(match self.0 {
+            w if w < 100.0 => w,
+            w if w < 550.0 => 100.0,
+            w if w < 750.0 => 400.0,
+            _ => 700.0,
+        })
+    }
+}
+
+impl FontStretch {
+    /// The keyword closest to a percentage of the normal width.
+    pub fn from_percentage(percentage: f64) -> FontStretch {
+        match percentage {
+            p if p < 56.25 => FontStretch::UltraCondensed,
+            p if p < 68.75 => FontStretch::ExtraCondensed,
+            p if p < 81.25 => FontStretch::Condensed,
+            p if p < 93.75 => FontStretch::SemiCondensed,
+            p if p < 106.25 => FontStretch::Normal,
+            p if p < 118.75 => FontStretch::SemiExpanded,
+            p if p < 137.5 => FontStretch::Expanded,
+            p if p < 175.0 => FontStretch::ExtraExpanded,
+            _ => FontStretch::UltraExpanded,
+        }
+    }
+}
+
+impl FontFamily {
+    /// The generic families are mapped to the fontconfig aliases of the same name.
+    pub fn to_pango_family(&self) -> &str {
+        match *self {
+            FontFamily::Named(ref name) => name.as_str(),
+            FontFamily::Serif => "serif",
+            FontFamily::SansSerif => "sans-serif",
+            FontFamily::Monospace => "monospace",
+            FontFamily::Cursive => "cursive",
+            FontFamily::Fantasy => "fantasy",
+            // GTK's UI font is the fontconfig default sans-serif unless configured otherwise.
+            FontFamily::SystemUi => "sans-serif",
+        }
+    }
 }
 
 impl Font {
-    pub fn new(size: Au, weight: FontWeight, slant: FontSlant) -> Font {
+    pub fn new(
+        family: Vec<FontFamily>,
+        size: Au,
+        weight: FontWeight,
+        slant: FontSlant,
+        variant: FontVariant,
+        stretch: FontStretch,
+    ) -> Font {
+        let font = Font {
+            family: family,
+            size: size,
+            weight: weight,
+            slant: slant,
+            variant: variant,
+            stretch: stretch,
+        };
+
         FONT_DESC.with(|font_desc| {
             let mut font_desc = font_desc.borrow_mut();
-            font_desc.set_size(pango::units_from_double(px2pt(size.to_f64_px())));
-            font_desc.set_style(slant.to_pango_font_slant());
-            font_desc.set_weight(weight.to_pango_font_weight());
+            font.update_font_description(&mut font_desc);
             PANGO_LAYOUT.with(|layout| {
                 layout.borrow_mut().set_font_description(Some(&*font_desc));
             })
         });
 
-        Font {
-            size: size,
-            weight: weight,
-            slant: slant,
-        }
+        font
     }
 
     pub fn new_empty() -> Font {
         Font {
+            family: vec![],
             size: Au(0),
-            weight: FontWeight::Normal,
+            weight: FontWeight::NORMAL,
             slant: FontSlant::Normal,
+            variant: FontVariant::Normal,
+            stretch: FontStretch::Normal,
         }
     }
 
+    /// Makes `font_desc` describe this font. The whole family list is given to Pango, which
+    /// falls back along it for the characters that the first available face lacks.
+    pub fn update_font_description(&self, font_desc: &mut pango::FontDescription) {
+        let family = self.family
+            .iter()
+            .map(|family| match *family {
+                // A family loaded with `@font-face` is known to fontconfig by its own name.
+                FontFamily::Named(ref name) => font_face::family_for(name, self.weight, self.slant)
+                    .unwrap_or_else(|| name.clone()),
+                _ => family.to_pango_family().to_string(),
+            })
+            // Pango separates the families with commas, so a name can't contain one.
+            .filter(|name| !name.contains(','))
+            .collect::<Vec<String>>()
+            .join(",");
+        font_desc.set_family(if family.is_empty() {
+            "sans-serif"
+        } else {
+            family.as_str()
+        });
+        font_desc.set_size(pango::units_from_double(px2pt(self.size.to_f64_px())));
+        font_desc.set_style(self.slant.to_pango_font_slant());
+        font_desc.set_weight(self.weight.to_pango_font_weight());
+        font_desc.set_variant(self.variant.to_pango_font_variant());
+        font_desc.set_stretch(self.stretch.to_pango_font_stretch());
+    }
+
     pub fn text_width(&self, text: &str) -> f64 {
         PANGO_LAYOUT.with(|layout| {
             let layout = layout.borrow_mut();
@@ -91,44 +226,106 @@ impl Font {
         })
     }
 
-    pub fn compute_max_chars_and_width(&self, s: &str, max_width: f64) -> (usize, f64) {
-        if max_width < 0f64 {
-            return (0, 0.0);
+    /// The length of the longest start of `s` that fits in `max_width` and ends where `style`
+    /// lets the line break, and its width without the spaces at its end, which hang. When
+    /// nothing fits, a line that is still empty gets a broken word if `overflow-wrap` allows it,
+    /// or else the first unbreakable part, which overflows.
+    pub fn compute_max_chars_and_width(
+        &self,
+        s: &str,
+        max_width: f64,
+        style: &ComputedStyle,
+        line_is_empty: bool,
+    ) -> (usize, f64) {
+        let opportunities = line_break::break_opportunities(s, style);
+        let fitting = self.fit(s, &opportunities, max_width);
+        if fitting.0 > 0 || !line_is_empty {
+            return fitting;
         }
 
-        PANGO_LAYOUT.with(|layout| {
-            let layout = layout.borrow_mut();
-            // TODO: Inefficient implementation!
-            let mut text_width = 0.0;
-            let mut last_splittable_pos = None;
-            let mut last_pos = 0;
-            for (pos, c) in s.char_indices() {
-                if c.is_whitespace() || c.is_ascii_punctuation() {
-                    last_splittable_pos = Some(pos);
-                }
-
-                layout.set_text(c.to_string().as_str());
-                let c_width = pango::units_to_double(layout.get_size().0);
-                text_width += c_width;
-
-                if text_width > max_width {
-                    if let Some(pos) = last_splittable_pos {
-                        return (pos + 1, text_width - c_width); // '1' means whitespace or punctuation.
-                    } else {
-                        if pos == 0 {
-                            break;
-                        }
-                        if pos - last_pos > 1 {
-                            // if c is multi-byte character
-                            return (pos, text_width - c_width);
-                        }
-                    }
-                }
-
-                last_pos = pos;
+        let opportunities = if line_break::breaks_overflowing_words(style) {
+            line_break::character_boundaries(s)
+        } else {
+            opportunities
+        };
+        match self.fit(s, &opportunities, max_width) {
+            (0, _) => {
+                let end = opportunities.first().map_or(s.len(), |&end| end);
+                (end, self.text_width(s[..end].trim_end()))
             }
+            fitting => fitting,
+        }
+    }
 
-            (if s.is_empty() { 0 } else { 1 }, text_width)
-        })
+    /// The longest start of `s` that ends at one of `opportunities` or at the end, and whose
+    /// width without trailing spaces is at most `max_width`.
+    fn fit(&self, s: &str, opportunities: &[usize], max_width: f64) -> (usize, f64) {
+        let mut fitting = (0, 0.0);
+        let mut width = 0.0;
+        let mut start = 0;
+        for &end in opportunities.iter().chain(&[s.len()]) {
+            let segment = &s[start..end];
+            let visible = segment.trim_end();
+            let visible_width = width + self.text_width(visible);
+            if visible_width > max_width {
+                break;
+            }
+            fitting = (end, visible_width);
+            width = if visible.len() < segment.len() {
+                visible_width + self.text_width(&segment[visible.len()..])
+            } else {
+                visible_width
+            };
+            start = end;
+        }
+        fitting
+    }
+}
+
+#[test]
+fn test_compute_max_chars_and_width() {
+    use style::OverflowWrap;
+
+    let font = Font::new_empty();
+    let style = ComputedStyle::default();
+    let text = "ab cd";
+    let width = |s: &str| font.text_width(s);
+    // The spaces at the end of a line hang.
+    assert_eq!(
+        font.compute_max_chars_and_width(text, width("ab c"), &style, true),
+        (3, width("ab"))
+    );
+    assert_eq!(
+        font.compute_max_chars_and_width(text, width(text), &style, false),
+        (5, width(text))
+    );
+    // A word that doesn't fit overflows an empty line, and goes to the next one otherwise.
+    assert_eq!(
+        font.compute_max_chars_and_width(text, width("a"), &style, true),
+        (3, width("ab"))
+    );
+    assert_eq!(
+        font.compute_max_chars_and_width(text, width("a"), &style, false),
+        (0, 0.0)
+    );
+    // Unless `overflow-wrap` breaks it.
+    for &overflow_wrap in &[OverflowWrap::BreakWord, OverflowWrap::Anywhere] {
+        let style = ComputedStyle {
+            overflow_wrap: overflow_wrap,
+            ..ComputedStyle::default()
+        };
+        assert_eq!(
+            font.compute_max_chars_and_width(text, width("a"), &style, true),
+            (1, width("a"))
+        );
+        assert_eq!(
+            font.compute_max_chars_and_width(text, width("a"), &style, false),
+            (0, 0.0)
+        );
+        // Even then, a line keeps at least one character.
+        assert_eq!(
+            font.compute_max_chars_and_width(text, 0.0, &style, true),
+            (1, width("a"))
+        );
     }
 }
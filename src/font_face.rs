@@ -0,0 +1,392 @@
+//! Web fonts declared with `@font-face`.
+//!
+//! The fonts of a document are downloaded in the background and registered with fontconfig
+//! (as application fonts, which live until the next document is loaded). Nothing waits for
+//! them: text is shown in a fallback font meanwhile and laid out again once a face arrives,
+//! which is `font-display: swap` whatever the stylesheet asks for.
+
+use css::Value;
+use font::{FontSlant, FontWeight};
+use property::Token;
+use interface::try_download_url;
+
+use reqwest::Url;
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::Read;
+use std::os::raw::{c_char, c_int, c_uchar, c_void};
+use std::ptr;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub family: String,
+    /// Absolute URLs, in order of preference.
+    pub sources: Vec<String>,
+    /// The range of weights the face covers.
+    pub weight: (f64, f64),
+    pub style: FontSlant,
+}
+
+/// A face whose file has been registered with fontconfig.
+#[derive(Debug, Clone)]
+struct LoadedFace {
+    face: FontFace,
+    /// The family name in the font file itself, which is what fontconfig knows it by.
+    internal_family: String,
+}
+
+thread_local!(
+    static LOADED_FACES: RefCell<Vec<LoadedFace>> = { RefCell::new(vec![]) };
+    static PENDING_FACES: RefCell<Option<Receiver<(FontFace, String)>>> = { RefCell::new(None) };
+);
+
+#[link(name = "fontconfig")]
+extern "C" {
+    fn FcConfigAppFontAddFile(config: *mut c_void, file: *const c_uchar) -> c_int;
+    fn FcConfigAppFontClear(config: *mut c_void);
+    fn FcFreeTypeQuery(
+        file: *const c_uchar,
+        id: c_int,
+        blanks: *mut c_void,
+        count: *mut c_int,
+    ) -> *mut c_void;
+    fn FcPatternGetString(
+        pattern: *const c_void,
+        object: *const c_char,
+        n: c_int,
+        s: *mut *mut c_uchar,
+    ) -> c_int;
+    fn FcPatternDestroy(pattern: *mut c_void);
+}
+
+#[link(name = "pangocairo-1.0")]
+extern "C" {
+    fn pango_cairo_font_map_get_default() -> *mut c_void;
+}
+
+#[link(name = "pangoft2-1.0")]
+extern "C" {
+    fn pango_fc_font_map_config_changed(font_map: *mut c_void);
+}
+
+const FC_RESULT_MATCH: c_int = 0;
+
+impl FontFace {
+    /// Builds a face from the descriptors of a `@font-face` rule. A face needs a family and at
+    /// least one source we can load.
+    pub fn from_descriptors(descriptors: Vec<(String, Vec<Token>)>) -> Option<FontFace> {
+        let mut family = None;
+        let mut sources = vec![];
+        let mut weight = (FontWeight::NORMAL.0, FontWeight::NORMAL.0);
+        let mut style = FontSlant::Normal;
+
+        for (name, tokens) in descriptors {
+            let values: Vec<&Value> = tokens
+                .iter()
+                .filter_map(|token| match *token {
+                    Token::Value(ref value) => Some(value),
+                    _ => None,
+                })
+                .collect();
+            match name.as_str() {
+                "font-family" => family = parse_family(&values),
+                "src" => sources = parse_sources(&tokens),
+                "font-weight" => weight = parse_weight_range(&values).unwrap_or(weight),
+                "font-style" => {
+                    style = values
+                        .get(0)
+                        .and_then(|value| value.to_font_slant())
+                        .unwrap_or(style)
+                }
+                // Faces are always swapped in when they arrive.
+                _ => {}
+            }
+        }
+
+        if sources.is_empty() {
+            return None;
+        }
+        Some(FontFace {
+            family: family?,
+            sources: sources,
+            weight: weight,
+            style: style,
+        })
+    }
+}
+
+fn parse_family(values: &[&Value]) -> Option<String> {
+    if let Some(&&Value::Str(ref name)) = values.get(0) {
+        return if values.len() == 1 {
+            Some(name.clone())
+        } else {
+            None
+        };
+    }
+    let mut words = vec![];
+    for value in values {
+        words.push(value.keyword()?);
+    }
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// The `url()`s of a `src` list. `local()` sources and `format()` hints are dropped by the
+/// parser, so formats we can't load are told apart by their extension here and by their
+/// contents once downloaded.
+fn parse_sources(tokens: &[Token]) -> Vec<String> {
+    tokens
+        .split(|token| *token == Token::Comma)
+        .filter_map(|source| {
+            source
+                .iter()
+                .filter_map(|token| match *token {
+                    Token::Value(Value::Url(ref url)) => Some(url.clone()),
+                    _ => None,
+                })
+                .next()
+        })
+        .filter(|url| {
+            let path = url.split(|c| c == '?' || c == '#').next().unwrap().to_ascii_lowercase();
+            ![".woff2", ".eot", ".svg", ".svgz"]
+                .iter()
+                .any(|ext| path.ends_with(*ext))
+        })
+        .collect()
+}
+
+fn parse_weight_range(values: &[&Value]) -> Option<(f64, f64)> {
+    fn weight(value: &Value) -> Option<f64> {
+        match *value {
+            Value::Num(w) if 1.0 <= w && w <= 1000.0 => Some(w),
+            Value::Keyword(ref k) if k == "normal" => Some(FontWeight::NORMAL.0),
+            Value::Keyword(ref k) if k == "bold" => Some(FontWeight::BOLD.0),
+            _ => None,
+        }
+    }
+    match values.len() {
+        1 => weight(values[0]).map(|w| (w, w)),
+        2 => {
+            let (min, max) = (weight(values[0])?, weight(values[1])?);
+            Some((min.min(max), min.max(max)))
+        }
+        _ => None,
+    }
+}
+
+/// Whether `data` starts like a TrueType, OpenType or WOFF file.
+fn is_supported_font_data(data: &[u8]) -> bool {
+    data.len() >= 4
+        && match &data[0..4] {
+            b"\x00\x01\x00\x00" | b"true" | b"OTTO" | b"wOFF" => true,
+            _ => false,
+        }
+}
+
+/// Starts loading the faces of a new document, dropping the faces of the previous one.
+pub fn load(faces: &[FontFace]) {
+    unsafe {
+        FcConfigAppFontClear(ptr::null_mut());
+        pango_fc_font_map_config_changed(pango_cairo_font_map_get_default());
+    }
+    LOADED_FACES.with(|loaded| loaded.borrow_mut().clear());
+
+    // Replacing the receiver makes the loaders still running for the previous document fail
+    // to send.
+    let (sender, receiver) = channel();
+    PENDING_FACES.with(|pending| *pending.borrow_mut() = Some(receiver));
+
+    for face in faces {
+        let face = face.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            for source in &face.sources {
+                let url = match Url::parse(source.as_str()) {
+                    Ok(url) => url,
+                    Err(_) => continue,
+                };
+                let file_name = match try_download_url(url) {
+                    Some((file_name, _)) => file_name,
+                    None => continue,
+                };
+                let mut data = vec![];
+                if let Ok(mut file) = File::open(file_name.as_str()) {
+                    if file.read_to_end(&mut data).is_ok() && is_supported_font_data(&data) {
+                        sender.send((face.clone(), file_name)).ok();
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Registers the faces that have been downloaded since the last call. Returns true if any
+/// was, in which case text has to be laid out again.
+pub fn register_loaded_fonts() -> bool {
+    let arrived: Vec<(FontFace, String)> = PENDING_FACES.with(|pending| match *pending.borrow() {
+        Some(ref receiver) => receiver.try_iter().collect(),
+        None => vec![],
+    });
+
+    let mut registered = false;
+    for (face, file_name) in arrived {
+        if let Some(internal_family) = register_font_file(file_name.as_str()) {
+            LOADED_FACES.with(|loaded| {
+                loaded.borrow_mut().push(LoadedFace {
+                    face: face,
+                    internal_family: internal_family,
+                })
+            });
+            registered = true;
+        }
+    }
+
+    if registered {
+        unsafe { pango_fc_font_map_config_changed(pango_cairo_font_map_get_default()) }
+    }
+    registered
+}
+
+/// Adds a font file to fontconfig and returns the family name it is registered under.
+fn register_font_file(file_name: &str) -> Option<String> {
+    let path = CString::new(file_name).ok()?;
+    let file_name = path.as_ptr() as *const c_uchar;
+    unsafe {
+        let mut count = 0;
+        let pattern = FcFreeTypeQuery(file_name, 0, ptr::null_mut(), &mut count);
+        if pattern.is_null() {
+            return None;
+        }
+        let mut family: *mut c_uchar = ptr::null_mut();
+        let object = b"family\0".as_ptr() as *const c_char;
+        let internal_family = if FcPatternGetString(pattern, object, 0, &mut family)
+            == FC_RESULT_MATCH
+        {
+            Some(CStr::from_ptr(family as *const c_char).to_string_lossy().into_owned())
+        } else {
+            None
+        };
+        FcPatternDestroy(pattern);
+
+        if internal_family.is_some() && FcConfigAppFontAddFile(ptr::null_mut(), file_name) == 0 {
+            return None;
+        }
+        internal_family
+    }
+}
+
+/// The family to ask Pango for to get the `@font-face` face that best matches `family`,
+/// `weight` and `slant`, if one has been loaded.
+pub fn family_for(family: &str, weight: FontWeight, slant: FontSlant) -> Option<String> {
+    LOADED_FACES.with(|loaded| {
+        let loaded = loaded.borrow();
+        let faces: Vec<&FontFace> = loaded.iter().map(|loaded| &loaded.face).collect();
+        best_match(&faces, family, weight, slant).map(|i| loaded[i].internal_family.clone())
+    })
+}
+
+/// The index of the face in `faces` chosen by the CSS font matching algorithm: the family has
+/// to match, then the closest style wins, then the closest weight.
+fn best_match(
+    faces: &[&FontFace],
+    family: &str,
+    weight: FontWeight,
+    slant: FontSlant,
+) -> Option<usize> {
+    let style_order = match slant {
+        FontSlant::Italic => [FontSlant::Italic, FontSlant::Oblique, FontSlant::Normal],
+        FontSlant::Oblique => [FontSlant::Oblique, FontSlant::Italic, FontSlant::Normal],
+        FontSlant::Normal => [FontSlant::Normal, FontSlant::Oblique, FontSlant::Italic],
+    };
+    let key = |face: &FontFace| {
+        let style = style_order.iter().position(|s| *s == face.style).unwrap();
+        let (tier, distance) = weight_distance(face.weight, weight.0);
+        (style, tier, distance)
+    };
+
+    faces
+        .iter()
+        .enumerate()
+        .filter(|&(_, face)| face.family.eq_ignore_ascii_case(family))
+        .min_by(|&(_, a), &(_, b)| {
+            key(a).partial_cmp(&key(b)).unwrap_or(::std::cmp::Ordering::Equal)
+        })
+        .map(|(i, _)| i)
+}
+
+/// How far the weight range `(min, max)` is from `desired`, as a search tier and a distance
+/// within it. Between 400 and 500, heavier weights up to 500 are tried first, then lighter
+/// ones, then the rest; below 400 lighter weights come first, and above 500 heavier ones.
+fn weight_distance((min, max): (f64, f64), desired: f64) -> (u8, f64) {
+    if min <= desired && desired <= max {
+        (0, 0.0)
+    } else if 400.0 <= desired && desired <= 500.0 {
+        if desired < min && min <= 500.0 {
+            (1, min - desired)
+        } else if max < desired {
+            (2, desired - max)
+        } else {
+            (3, min - desired)
+        }
+    } else if desired < 400.0 {
+        if max < desired {
+            (1, desired - max)
+        } else {
+            (2, min - desired)
+        }
+    } else if desired < min {
+        (1, min - desired)
+    } else {
+        (2, desired - max)
+    }
+}
+
+#[test]
+fn test_font_face_matching() {
+    fn face(family: &str, weight: (f64, f64), style: FontSlant) -> FontFace {
+        FontFace {
+            family: family.to_string(),
+            sources: vec!["file:///fonts/a.ttf".to_string()],
+            weight: weight,
+            style: style,
+        }
+    }
+    let regular = face("Web", (400.0, 400.0), FontSlant::Normal);
+    let bold = face("Web", (700.0, 700.0), FontSlant::Normal);
+    let light = face("Web", (300.0, 300.0), FontSlant::Normal);
+    let italic = face("Web", (400.0, 400.0), FontSlant::Italic);
+    let medium = face("Web", (500.0, 500.0), FontSlant::Normal);
+    let faces = [&regular, &bold, &light, &italic];
+
+    let find = |faces: &[&FontFace], family, weight, slant| {
+        best_match(faces, family, FontWeight(weight), slant)
+    };
+    assert_eq!(find(&faces, "web", 400.0, FontSlant::Normal), Some(0));
+    assert_eq!(find(&faces, "Web", 600.0, FontSlant::Normal), Some(1));
+    assert_eq!(find(&faces, "Web", 350.0, FontSlant::Normal), Some(2));
+    assert_eq!(find(&faces, "Web", 700.0, FontSlant::Italic), Some(3));
+    // Oblique falls back to italic before normal.
+    assert_eq!(find(&faces, "Web", 400.0, FontSlant::Oblique), Some(3));
+    assert_eq!(find(&faces, "Other", 400.0, FontSlant::Normal), None);
+    // From 400 to 500, heavier weights up to 500 are preferred to lighter ones.
+    assert_eq!(find(&[&light, &medium, &bold], "Web", 450.0, FontSlant::Normal), Some(1));
+    assert_eq!(find(&[&light, &bold], "Web", 450.0, FontSlant::Normal), Some(0));
+    assert_eq!(find(&[&light, &bold], "Web", 600.0, FontSlant::Normal), Some(1));
+
+    let variable = face("Web", (100.0, 900.0), FontSlant::Normal);
+    assert_eq!(find(&[&bold, &variable], "Web", 650.0, FontSlant::Normal), Some(1));
+
+    assert!(is_supported_font_data(b"\x00\x01\x00\x00\x00\x10"));
+    assert!(is_supported_font_data(b"OTTO\x00\x0a"));
+    assert!(is_supported_font_data(b"wOFF\x00\x01"));
+    assert!(!is_supported_font_data(b"wOF2\x00\x01"));
+    assert!(!is_supported_font_data(b"<svg"));
+}
@@ -0,0 +1,1095 @@
+//! Grid layout.
+//!
+//! The children of a grid container are its grid items, each in an area of one or more cells of
+//! a grid of rows and columns. `grid-row-start`, `grid-column-end`... place an item between lines
+//! of the grid, by number or by name, and items without a definite area are auto-placed in the
+//! free cells in turn. The tracks (rows and columns) are sized as `grid-template-columns`,
+//! `grid-template-rows`, `grid-auto-columns` and `grid-auto-rows` say, from the items in them,
+//! and then the items are aligned in their areas with `justify-self` and `align-self`.
+//! ref. https://www.w3.org/TR/css-grid-1/
+
+use layout::{Dimensions, LayoutBox, LayoutInfo};
+use intrinsic::IntrinsicWidths;
+use float::Floats;
+use margin::CollapsibleMargins;
+use flex::distribute;
+use style::{ContentDistribution, Display, GridLine, GridTemplate, ItemAlignment,
+            LengthOrPercentage, LengthOrPercentageOrAuto, TrackBreadth, TrackList, TrackSize};
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+
+use app_units::Au;
+
+/// Where an item goes along one axis: between two lines, or anywhere it fits while it's
+/// auto-placed, as many tracks long as the span. Lines are counted from 0, the first line of the
+/// explicit grid, and may be negative before the items are placed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Placement {
+    Lines(i32, i32),
+    Auto(i32),
+}
+
+/// A track being sized.
+#[derive(Clone, Copy, Debug)]
+struct Track {
+    min: TrackBreadth,
+    max: TrackBreadth,
+    /// The limit of `fit-content()`.
+    limit: Option<LengthOrPercentage>,
+    base_size: Au,
+    /// None while it's infinite.
+    growth_limit: Option<Au>,
+    /// Whether it's an `auto-fit` track without items, which takes no space and no gaps.
+    collapsed: bool,
+}
+
+/// The space tracks are sized in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Available {
+    Definite(Au),
+    MinContent,
+    MaxContent,
+}
+
+/// The tracks of a grid container before they are sized, and the rows and the columns the area
+/// of each of its children is between. Absolutely positioned children, which aren't grid items,
+/// have no area.
+struct Grid {
+    rows: Vec<Track>,
+    columns: Vec<Track>,
+    areas: Vec<Option<((usize, usize), (usize, usize))>>,
+}
+
+impl Track {
+    fn new(size: TrackSize) -> Track {
+        let (min, max, limit) = match size {
+            TrackSize::MinMax(min, max) => (min, max, None),
+            TrackSize::FitContent(limit) => {
+                (TrackBreadth::Auto, TrackBreadth::MaxContent, Some(limit))
+            }
+        };
+        Track {
+            min: min,
+            max: max,
+            limit: limit,
+            base_size: Au(0),
+            growth_limit: None,
+            collapsed: false,
+        }
+    }
+
+    fn flex_factor(&self) -> Option<f64> {
+        match self.max {
+            TrackBreadth::Flex(factor) => Some(factor),
+            _ => None,
+        }
+    }
+
+    fn has_intrinsic_min(&self) -> bool {
+        match self.min {
+            TrackBreadth::Length(_) => false,
+            _ => true,
+        }
+    }
+
+    fn has_intrinsic_max(&self) -> bool {
+        match self.max {
+            TrackBreadth::MinContent | TrackBreadth::MaxContent | TrackBreadth::Auto => true,
+            TrackBreadth::Length(_) | TrackBreadth::Flex(_) => false,
+        }
+    }
+}
+
+impl LayoutBox {
+    /// Lay out a grid container in normal flow, or in a line if it's an inline-level one.
+    pub fn layout_grid(
+        &mut self,
+        _floats: &mut Floats,
+        margins: Option<CollapsibleMargins>,
+        containing_block: Dimensions,
+        _saved_block: Dimensions,
+        viewport: Dimensions,
+    ) {
+        let cb_width = containing_block.content.width;
+        let is_inline = self.property.display == Display::InlineGrid;
+        if is_inline || cb_width < Au(0) {
+            self.calculate_shrink_to_fit_width(containing_block);
+        } else {
+            self.calculate_block_width(containing_block);
+        }
+        if !is_inline {
+            self.calculate_block_position(margins, containing_block);
+        }
+        // A grid container is a new formatting context, which floats outside don't enter.
+        self.floats = Floats::new();
+
+        self.layout_grid_items(containing_block, viewport);
+    }
+
+    /// Lays out the items of a grid container whose width is known, and sets its height.
+    fn layout_grid_items(&mut self, containing_block: Dimensions, viewport: Dimensions) {
+        let cb_width = containing_block.content.width;
+        let width = self.dimensions.content.width;
+        let height = self.property
+            .specified_height(cb_width, None)
+            .map(|height| self.property.clamp_height(height, cb_width, None));
+        // Percentages of an indefinite height are 0.
+        let column_gap = self.property.column_gap.to_au(width);
+        let row_gap = self.property.row_gap.to_au(height.unwrap_or(Au(0)));
+
+        // Items are painted in `order`, then in document order.
+        self.children.sort_by_key(|child| child.property.order);
+        let Grid {
+            mut rows,
+            mut columns,
+            areas,
+        } = self.grid(Some(width), height, column_gap, row_gap);
+
+        let stretches = |distribution| {
+            distribution == ContentDistribution::Normal
+                || distribution == ContentDistribution::Stretch
+        };
+        let column_items: Vec<((usize, usize), IntrinsicWidths)> = areas
+            .iter()
+            .zip(&self.children)
+            .filter_map(|(area, child)| area.map(|area| (area.1, child.intrinsic_widths())))
+            .collect();
+        size_tracks(
+            &mut columns,
+            &column_items,
+            Available::Definite(width),
+            column_gap,
+            stretches(self.property.justify_content),
+        );
+        let column_positions =
+            track_positions(&columns, column_gap, width, self.property.justify_content);
+
+        // The items are laid out at the widths of their columns, and the rows are sized for
+        // their heights.
+        let container = self.dimensions;
+        let justify_items = self.property.justify_items;
+        let mut horizontal = vec![];
+        let mut row_items = vec![];
+        for (child, area) in self.children.iter_mut().zip(&areas) {
+            let area = match *area {
+                Some(area) => area,
+                None => continue,
+            };
+            let area_width = area_size(&columns, &column_positions, area.1);
+            let mut containing_block = container;
+            containing_block.content.width = area_width;
+            child.assign_padding(containing_block);
+            child.assign_border_width();
+            let d = child.dimensions;
+            let edges = d.border.left + d.border.right + d.padding.left + d.padding.right;
+            let margin = |margin: LengthOrPercentageOrAuto| margin.to_au(area_width);
+            let margins = (
+                margin(child.property.margin.left),
+                margin(child.property.margin.right),
+            );
+            let align = child.property.justify_self.unwrap_or(justify_items);
+            let available = area_width - edges - margins.0.unwrap_or(Au(0))
+                - margins.1.unwrap_or(Au(0));
+            let (width, stretches) = match child.property.specified_width(area_width) {
+                Some(width) => (width, false),
+                None => (
+                    child.content_intrinsic_widths().shrink_to_fit(available),
+                    child.stretches(align),
+                ),
+            };
+            let mut placed = align_in_area(area_width, width, stretches, edges, margins, align);
+            // The item is aligned again at the limit its width violates.
+            let clamped_width = child.property.clamp_width(placed.0, Some(area_width));
+            if clamped_width != placed.0 {
+                placed = align_in_area(area_width, clamped_width, false, edges, margins, align);
+            }
+            child.layout_item(placed.0, None, containing_block, viewport);
+
+            let d = child.dimensions;
+            let vertical_margins = (
+                margin(child.property.margin.top),
+                margin(child.property.margin.bottom),
+            );
+            let outer_height = d.content.height + d.border.top + d.border.bottom + d.padding.top
+                + d.padding.bottom + vertical_margins.0.unwrap_or(Au(0))
+                + vertical_margins.1.unwrap_or(Au(0));
+            row_items.push((area.0, IntrinsicWidths::new(outer_height, outer_height)));
+            horizontal.push((placed, vertical_margins, containing_block));
+        }
+        let available = match height {
+            Some(height) => Available::Definite(height),
+            None => Available::MaxContent,
+        };
+        size_tracks(
+            &mut rows,
+            &row_items,
+            available,
+            row_gap,
+            stretches(self.property.align_content),
+        );
+        let height = height.unwrap_or_else(|| tracks_size(&rows, row_gap));
+        let row_positions = track_positions(&rows, row_gap, height, self.property.align_content);
+
+        // Items are stretched to the height of their rows, and aligned in them.
+        let align_items = self.property.align_items;
+        let items = self.children
+            .iter_mut()
+            .zip(&areas)
+            .filter_map(|(child, area)| area.map(|area| (child, area)));
+        for ((child, area), horizontal) in items.zip(horizontal) {
+            let ((width, margins, x), vertical_margins, containing_block) = horizontal;
+            let area_height = area_size(&rows, &row_positions, area.0);
+            let d = child.dimensions;
+            let edges = d.border.top + d.border.bottom + d.padding.top + d.padding.bottom;
+            let align = child.property.align_self.unwrap_or(align_items);
+            let stretches = child.property.height == LengthOrPercentageOrAuto::Auto
+                && child.stretches(align);
+            let placed = align_in_area(
+                area_height,
+                d.content.height,
+                stretches,
+                edges,
+                vertical_margins,
+                align,
+            );
+            let clamped_height = child.property.clamp_height(
+                placed.0,
+                containing_block.content.width,
+                Some(area_height),
+            );
+            let (height, vertical_margins, y) = if clamped_height != placed.0 {
+                align_in_area(area_height, clamped_height, false, edges, vertical_margins, align)
+            } else {
+                placed
+            };
+            if height != d.content.height {
+                child.layout_item(width, Some(height), containing_block, viewport);
+            }
+
+            let x = column_positions[(area.1).0] + x;
+            let y = row_positions[(area.0).0] + y;
+            let d = &mut child.dimensions;
+            d.margin.left = margins.0;
+            d.margin.right = margins.1;
+            d.margin.top = vertical_margins.0;
+            d.margin.bottom = vertical_margins.1;
+            d.content.x = x + d.margin.left + d.border.left + d.padding.left;
+            d.content.y = y + d.margin.top + d.border.top + d.padding.top;
+        }
+
+        self.dimensions.content.height = self.property.clamp_height(height, cb_width, None);
+    }
+
+    /// Whether a grid item with an `auto` size is stretched to its area when aligned as `align`
+    /// says. Images are only stretched if asked to.
+    fn stretches(&self, align: ItemAlignment) -> bool {
+        match (align, &self.info) {
+            (ItemAlignment::Stretch, _) => true,
+            (ItemAlignment::Normal, &LayoutInfo::Image(_)) => false,
+            (ItemAlignment::Normal, _) => true,
+            _ => false,
+        }
+    }
+
+    /// The explicit grid of the container, `width` wide and `height` tall if they're definite,
+    /// with its items placed in it, and the implicit tracks the items need around it.
+    /// ref. https://www.w3.org/TR/css-grid-1/#placement
+    fn grid(&self, width: Option<Au>, height: Option<Au>, column_gap: Au, row_gap: Au) -> Grid {
+        let style = &self.property;
+        let template_areas = &style.grid_template_areas;
+        let column_repeats = auto_repetitions(&style.grid_template_columns, width, column_gap);
+        let row_repeats = auto_repetitions(&style.grid_template_rows, height, row_gap);
+        let column_list = style.grid_template_columns.expand(column_repeats);
+        let row_list = style.grid_template_rows.expand(row_repeats);
+        let column_count = max(column_list.sizes.len(), template_areas.columns);
+        let row_count = max(row_list.sizes.len(), template_areas.rows);
+        let column_names = line_names(
+            &column_list,
+            column_count,
+            template_areas
+                .areas
+                .iter()
+                .map(|area| (area.name.as_str(), area.columns))
+                .collect(),
+        );
+        let row_names = line_names(
+            &row_list,
+            row_count,
+            template_areas
+                .areas
+                .iter()
+                .map(|area| (area.name.as_str(), area.rows))
+                .collect(),
+        );
+
+        // Items are placed in `order`, then in document order.
+        let mut order: Vec<usize> = (0..self.children.len())
+            .filter(|&i| !self.children[i].is_absolutely_positioned())
+            .collect();
+        order.sort_by_key(|&i| self.children[i].property.order);
+        let mut placements: Vec<(Placement, Placement)> = order
+            .iter()
+            .map(|&i| {
+                let style = &self.children[i].property;
+                (
+                    resolve_placement(&style.grid_row_start, &style.grid_row_end, &row_names),
+                    resolve_placement(
+                        &style.grid_column_start,
+                        &style.grid_column_end,
+                        &column_names,
+                    ),
+                )
+            })
+            .collect();
+
+        // Implicit tracks before the explicit grid move its lines.
+        let first_line = |placement: Placement| match placement {
+            Placement::Lines(start, _) => min(start, 0),
+            Placement::Auto(_) => 0,
+        };
+        let row_offset = -placements.iter().map(|p| first_line(p.0)).min().unwrap_or(0);
+        let column_offset = -placements.iter().map(|p| first_line(p.1)).min().unwrap_or(0);
+        let shift = |placement: Placement, offset: i32| match placement {
+            Placement::Lines(start, end) => Placement::Lines(start + offset, end + offset),
+            auto => auto,
+        };
+        for placement in &mut placements {
+            *placement = (shift(placement.0, row_offset), shift(placement.1, column_offset));
+        }
+
+        // Auto-placement fills the rows, or the columns with `grid-auto-flow: column`.
+        let is_column_flow = style.grid_auto_flow.column;
+        if is_column_flow {
+            for placement in &mut placements {
+                *placement = (placement.1, placement.0);
+            }
+        }
+        let across_explicit = if is_column_flow {
+            row_offset + row_count as i32
+        } else {
+            column_offset + column_count as i32
+        };
+        let across_count = placements
+            .iter()
+            .map(|p| match p.1 {
+                Placement::Lines(_, end) => end,
+                Placement::Auto(span) => span,
+            })
+            .fold(across_explicit, max);
+        let mut placed = place_items(&placements, across_count, style.grid_auto_flow.dense);
+        if is_column_flow {
+            for area in &mut placed {
+                *area = (area.1, area.0);
+            }
+        }
+
+        let mut areas = vec![None; self.children.len()];
+        for (&i, area) in order.iter().zip(&placed) {
+            areas[i] = Some((
+                ((area.0).0 as usize, (area.0).1 as usize),
+                ((area.1).0 as usize, (area.1).1 as usize),
+            ));
+        }
+        let row_total = placed
+            .iter()
+            .map(|area| (area.0).1)
+            .fold(row_offset + row_count as i32, max);
+        let column_total = placed
+            .iter()
+            .map(|area| (area.1).1)
+            .fold(column_offset + column_count as i32, max);
+
+        let mut rows = tracks(&row_list, &style.grid_auto_rows, row_offset, row_total);
+        let mut columns = tracks(
+            &column_list,
+            &style.grid_auto_columns,
+            column_offset,
+            column_total,
+        );
+        // The repeated `auto-fit` tracks without items collapse.
+        collapse_empty_tracks(
+            &mut rows,
+            &style.grid_template_rows,
+            row_repeats,
+            row_offset,
+            areas.iter().filter_map(|area| area.map(|area| area.0)).collect(),
+        );
+        collapse_empty_tracks(
+            &mut columns,
+            &style.grid_template_columns,
+            column_repeats,
+            column_offset,
+            areas.iter().filter_map(|area| area.map(|area| area.1)).collect(),
+        );
+
+        Grid {
+            rows: rows,
+            columns: columns,
+            areas: areas,
+        }
+    }
+
+    /// The intrinsic widths of the content of a grid container: the widths of its columns sized
+    /// for the min-content and for the max-content contributions of its items.
+    pub fn grid_intrinsic_widths(&self) -> IntrinsicWidths {
+        let gap = self.property.column_gap.to_au(Au(0));
+        let grid = self.grid(None, None, gap, Au(0));
+        let items: Vec<((usize, usize), IntrinsicWidths)> = grid.areas
+            .iter()
+            .zip(&self.children)
+            .filter_map(|(area, child)| area.map(|area| (area.1, child.intrinsic_widths())))
+            .collect();
+        let mut min_columns = grid.columns.clone();
+        size_tracks(&mut min_columns, &items, Available::MinContent, gap, false);
+        let mut max_columns = grid.columns;
+        size_tracks(&mut max_columns, &items, Available::MaxContent, gap, false);
+        IntrinsicWidths::new(tracks_size(&min_columns, gap), tracks_size(&max_columns, gap))
+    }
+}
+
+/// How many times `repeat(auto-fill, ...)` or `repeat(auto-fit, ...)` repeats in `available`:
+/// as many times as the tracks fit, but at least once.
+/// ref. https://www.w3.org/TR/css-grid-1/#auto-repeat
+fn auto_repetitions(template: &GridTemplate, available: Option<Au>, gap: Au) -> usize {
+    let auto_repeat = match template.auto_repeat {
+        Some(ref auto_repeat) => auto_repeat,
+        None => return 0,
+    };
+    let available = match available {
+        Some(available) => available,
+        None => return 1,
+    };
+    // Tracks count as their maximum if it's fixed, otherwise as their minimum.
+    let size = |size: &TrackSize| match *size {
+        TrackSize::MinMax(_, TrackBreadth::Length(max)) => max.to_au(available),
+        TrackSize::MinMax(TrackBreadth::Length(min), _) => min.to_au(available),
+        _ => Au(0),
+    };
+    let sum = |sizes: &[TrackSize]| sizes.iter().fold(Au(0), |sum, s| sum + size(s) + gap);
+    let others = sum(&template.tracks.sizes) + sum(&template.after.sizes);
+    let repetition = sum(&auto_repeat.tracks.sizes);
+    if repetition <= Au(0) {
+        return 1;
+    }
+    max(1, ((available + gap - others).0 / repetition.0) as usize)
+}
+
+/// The names of the lines of an axis of the explicit grid, `count` tracks long. The areas of
+/// `grid-template-areas` name their lines `<name>-start` and `<name>-end`.
+fn line_names(
+    tracks: &TrackList,
+    count: usize,
+    areas: Vec<(&str, (usize, usize))>,
+) -> Vec<Vec<String>> {
+    let mut names = tracks.line_names.clone();
+    names.resize(count + 1, vec![]);
+    for (name, (start, end)) in areas {
+        names[start].push(format!("{}-start", name));
+        names[end].push(format!("{}-end", name));
+    }
+    names
+}
+
+/// The line `n` lines after `from`, or before it if `n` is negative, only counting the lines
+/// with `name` if it's given. The lines outside the explicit grid, which `names` are of, have
+/// every name.
+fn nth_line(names: &[Vec<String>], name: Option<&str>, n: i32, from: i32) -> i32 {
+    let name = match name {
+        Some(name) => name,
+        None => return from + n,
+    };
+    let last = names.len() as i32 - 1;
+    let has_name = |line: i32| names[line as usize].iter().any(|n| n == name);
+    let mut count = 0;
+    if n > 0 {
+        for line in max(from + 1, 0)..last + 1 {
+            if has_name(line) {
+                count += 1;
+                if count == n {
+                    return line;
+                }
+            }
+        }
+        max(from, last) + n - count
+    } else {
+        for line in (0..min(from, last + 1)).rev() {
+            if has_name(line) {
+                count += 1;
+                if count == -n {
+                    return line;
+                }
+            }
+        }
+        min(from, 0) + n + count
+    }
+}
+
+/// Resolves where an item goes along an axis from its start and end lines, with `names` for the
+/// lines of the explicit grid.
+/// ref. https://www.w3.org/TR/css-grid-1/#line-placement
+fn resolve_placement(start: &GridLine, end: &GridLine, names: &[Vec<String>]) -> Placement {
+    let last = names.len() as i32 - 1;
+    let line = |line: &GridLine, side: &str| match *line {
+        GridLine::Line(n, ref name) => {
+            let from = if n > 0 { -1 } else { last + 1 };
+            Some(nth_line(names, name.as_ref().map(String::as_str), n, from))
+        }
+        // The edge of the area, or else the first line with the name.
+        GridLine::Name(ref name) => {
+            let area_line = format!("{}-{}", name, side);
+            let has_area_line = names.iter().any(|names| names.contains(&area_line));
+            let name = if has_area_line { &area_line } else { name };
+            Some(nth_line(names, Some(name), 1, -1))
+        }
+        GridLine::Auto | GridLine::Span(..) => None,
+    };
+    let span = |name: &Option<String>, n: i32, from: i32| {
+        nth_line(names, name.as_ref().map(String::as_str), n, from)
+    };
+    match (line(start, "start"), line(end, "end")) {
+        (Some(start), Some(end)) if start == end => Placement::Lines(start, start + 1),
+        (Some(start), Some(end)) => Placement::Lines(min(start, end), max(start, end)),
+        (Some(start), None) => match *end {
+            GridLine::Span(n, ref name) => Placement::Lines(start, span(name, n, start)),
+            _ => Placement::Lines(start, start + 1),
+        },
+        (None, Some(end)) => match *start {
+            GridLine::Span(n, ref name) => Placement::Lines(span(name, -n, end), end),
+            _ => Placement::Lines(end - 1, end),
+        },
+        // Spans of named lines are 1 track long when auto-placed.
+        (None, None) => match (start, end) {
+            (&GridLine::Span(n, None), _) | (&GridLine::Auto, &GridLine::Span(n, None)) => {
+                Placement::Auto(n)
+            }
+            _ => Placement::Auto(1),
+        },
+    }
+}
+
+/// Places items, given where they go along the axis auto-placement moves down (the rows, with
+/// `grid-auto-flow: row`) and the one it fills first, which is `across_count` tracks long.
+/// Items with a definite area keep it, those with a definite row (with `row` flow) come next,
+/// and the rest go in the first place they fit in turn, after the previous item unless `dense`.
+/// Returns the lines each item is between along each axis.
+/// ref. https://www.w3.org/TR/css-grid-1/#auto-placement-algo
+fn place_items(
+    items: &[(Placement, Placement)],
+    across_count: i32,
+    dense: bool,
+) -> Vec<((i32, i32), (i32, i32))> {
+    type Area = ((i32, i32), (i32, i32));
+    let mut areas: Vec<Option<Area>> = vec![None; items.len()];
+    let overlaps = |areas: &[Option<Area>], along: (i32, i32), across: (i32, i32)| {
+        areas.iter().filter_map(|&area| area).any(|(a, c)| {
+            a.0 < along.1 && along.0 < a.1 && c.0 < across.1 && across.0 < c.1
+        })
+    };
+
+    for (area, item) in areas.iter_mut().zip(items) {
+        if let (Placement::Lines(a0, a1), Placement::Lines(c0, c1)) = *item {
+            *area = Some(((a0, a1), (c0, c1)));
+        }
+    }
+
+    // The items locked to a row go after the items already placed in it by this step.
+    let mut cursors: HashMap<i32, i32> = HashMap::new();
+    for i in 0..items.len() {
+        if let (Placement::Lines(a0, a1), Placement::Auto(span)) = items[i] {
+            let mut across = if dense {
+                0
+            } else {
+                *cursors.get(&a0).unwrap_or(&0)
+            };
+            while overlaps(&areas, (a0, a1), (across, across + span)) {
+                across += 1;
+            }
+            areas[i] = Some(((a0, a1), (across, across + span)));
+            cursors.insert(a0, across + span);
+        }
+    }
+
+    let (mut along, mut across) = (0, 0);
+    for i in 0..items.len() {
+        match items[i] {
+            (Placement::Auto(along_span), Placement::Lines(c0, c1)) => {
+                if dense {
+                    along = 0;
+                } else if c0 < across {
+                    along += 1;
+                }
+                across = c0;
+                while overlaps(&areas, (along, along + along_span), (c0, c1)) {
+                    along += 1;
+                }
+                areas[i] = Some(((along, along + along_span), (c0, c1)));
+            }
+            (Placement::Auto(along_span), Placement::Auto(span)) => {
+                if dense {
+                    along = 0;
+                    across = 0;
+                }
+                loop {
+                    if across > 0 && across + span > across_count {
+                        along += 1;
+                        across = 0;
+                    } else if overlaps(&areas, (along, along + along_span), (across, across + span))
+                    {
+                        across += 1;
+                    } else {
+                        break;
+                    }
+                }
+                areas[i] = Some(((along, along + along_span), (across, across + span)));
+                across += span;
+            }
+            _ => {}
+        }
+    }
+
+    areas.into_iter().map(|area| area.unwrap()).collect()
+}
+
+/// The tracks along an axis, `total` long. The explicit ones start at `offset`, and the
+/// implicit ones before and after them repeat the sizes in `auto`.
+fn tracks(list: &TrackList, auto: &[TrackSize], offset: i32, total: i32) -> Vec<Track> {
+    let explicit = list.sizes.len() as i32;
+    let repeats = auto.len() as i32;
+    (0..total)
+        .map(|i| {
+            let i = i - offset;
+            Track::new(if 0 <= i && i < explicit {
+                list.sizes[i as usize]
+            } else if i >= explicit {
+                auto[((i - explicit) % repeats) as usize]
+            } else {
+                auto[((repeats - (-i % repeats)) % repeats) as usize]
+            })
+        })
+        .collect()
+}
+
+/// Collapses the tracks `repeat(auto-fit, ...)` repeated `repeats` times that no item is in.
+fn collapse_empty_tracks(
+    tracks: &mut [Track],
+    template: &GridTemplate,
+    repeats: usize,
+    offset: i32,
+    areas: Vec<(usize, usize)>,
+) {
+    match template.auto_repeat {
+        Some(ref auto_repeat) if auto_repeat.fit => {
+            let start = offset as usize + template.tracks.sizes.len();
+            let end = start + repeats * auto_repeat.tracks.sizes.len();
+            for i in start..end {
+                if !areas.iter().any(|&(s, e)| s <= i && i < e) {
+                    let zero = TrackBreadth::Length(LengthOrPercentage::Length(Au(0)));
+                    tracks[i] = Track {
+                        collapsed: true,
+                        ..Track::new(TrackSize::MinMax(zero, zero))
+                    };
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sizes `tracks` with `gap`s between them for the items in them, given the tracks each item
+/// spans and its min-content and max-content contributions, in the `available` space. The
+/// tracks with an `auto` maximum share the space left if `stretch`.
+/// ref. https://www.w3.org/TR/css-grid-1/#algo-track-sizing
+fn size_tracks(
+    tracks: &mut [Track],
+    items: &[((usize, usize), IntrinsicWidths)],
+    available: Available,
+    gap: Au,
+    stretch: bool,
+) {
+    let basis = match available {
+        Available::Definite(available) => Some(available),
+        Available::MinContent | Available::MaxContent => None,
+    };
+    // Percentages of an indefinite size behave as `auto`.
+    let fixed = |breadth: TrackBreadth| match (breadth, basis) {
+        (TrackBreadth::Length(LengthOrPercentage::Length(length)), _) => Some(length),
+        (TrackBreadth::Length(percentage), Some(basis)) => Some(percentage.to_au(basis)),
+        _ => None,
+    };
+    for track in tracks.iter_mut() {
+        if let TrackBreadth::Length(_) = track.min {
+            if fixed(track.min).is_none() {
+                track.min = TrackBreadth::Auto;
+            }
+        }
+        if let TrackBreadth::Length(_) = track.max {
+            if fixed(track.max).is_none() {
+                track.max = TrackBreadth::Auto;
+            }
+        }
+        track.base_size = fixed(track.min).unwrap_or(Au(0));
+        track.growth_limit = fixed(track.max).map(|limit| max(limit, track.base_size));
+    }
+
+    // The tracks with an intrinsic size grow to fit their items, those with one track first.
+    let mut items: Vec<&((usize, usize), IntrinsicWidths)> = items.iter().collect();
+    items.sort_by_key(|&&((start, end), _)| end - start);
+    for &&((start, end), contribution) in &items {
+        let gaps = gap * (end - start - 1) as i32;
+        let spanned = &mut tracks[start..end];
+        let is_flexible = spanned.iter().any(|track| track.flex_factor().is_some());
+        if let [ref mut track] = *spanned {
+            track.base_size = match track.min {
+                TrackBreadth::MinContent | TrackBreadth::Auto => {
+                    max(track.base_size, contribution.min)
+                }
+                TrackBreadth::MaxContent => max(track.base_size, contribution.max),
+                _ => track.base_size,
+            };
+            let limit = match (track.max, fit_content_limit(track.limit, basis)) {
+                (TrackBreadth::MinContent, _) => contribution.min,
+                (TrackBreadth::MaxContent, Some(limit)) => {
+                    max(contribution.min, min(contribution.max, limit))
+                }
+                (TrackBreadth::MaxContent, None) | (TrackBreadth::Auto, _) => contribution.max,
+                _ => continue,
+            };
+            track.growth_limit = Some(max(track.growth_limit.unwrap_or(Au(0)), limit));
+            continue;
+        }
+
+        // Items across several tracks spread what the tracks lack evenly. Those across
+        // flexible tracks only grow the flexible ones.
+        let grows = |track: &Track| {
+            track.has_intrinsic_min() && (!is_flexible || track.flex_factor().is_some())
+        };
+        let sum = spanned.iter().fold(gaps, |sum, track| sum + track.base_size);
+        let count = spanned.iter().filter(|track| grows(track)).count() as i32;
+        if contribution.min > sum && count > 0 {
+            let each = (contribution.min - sum) / count;
+            for track in spanned.iter_mut().filter(|track| grows(track)) {
+                track.base_size += each;
+            }
+        }
+        if is_flexible {
+            continue;
+        }
+        let sum = spanned.iter().fold(gaps, |sum, track| {
+            sum + track.growth_limit.unwrap_or(track.base_size)
+        });
+        let count = spanned.iter().filter(|track| track.has_intrinsic_max()).count() as i32;
+        if contribution.max > sum && count > 0 {
+            let each = (contribution.max - sum) / count;
+            for track in spanned.iter_mut().filter(|track| track.has_intrinsic_max()) {
+                track.growth_limit = Some(track.growth_limit.unwrap_or(track.base_size) + each);
+            }
+        }
+    }
+    for track in tracks.iter_mut() {
+        track.growth_limit = Some(max(
+            track.growth_limit.unwrap_or(track.base_size),
+            track.base_size,
+        ));
+    }
+
+    // The tracks grow up to their growth limits in the free space.
+    match available {
+        Available::Definite(available) => {
+            let mut free_space = available - tracks_size(tracks, gap);
+            loop {
+                let growing: Vec<usize> = (0..tracks.len())
+                    .filter(|&i| {
+                        tracks[i].flex_factor().is_none()
+                            && tracks[i].growth_limit.unwrap() > tracks[i].base_size
+                    })
+                    .collect();
+                if free_space <= Au(0) || growing.is_empty() {
+                    break;
+                }
+                let each = max(Au(1), free_space / growing.len() as i32);
+                for i in growing {
+                    let growth = min(min(each, free_space), tracks[i].growth_limit.unwrap()
+                        - tracks[i].base_size);
+                    tracks[i].base_size += growth;
+                    free_space -= growth;
+                }
+            }
+        }
+        Available::MaxContent => for track in tracks.iter_mut() {
+            if track.flex_factor().is_none() {
+                track.base_size = track.growth_limit.unwrap();
+            }
+        },
+        Available::MinContent => {}
+    }
+
+    // The flexible tracks share what's left, or are as large as their items need when the
+    // size is indefinite.
+    if tracks.iter().any(|track| track.flex_factor().is_some()) {
+        let fr = match available {
+            Available::Definite(available) => {
+                let gaps = tracks_size(tracks, gap)
+                    - tracks.iter().fold(Au(0), |sum, track| sum + track.base_size);
+                fr_size(tracks, available - gaps)
+            }
+            Available::MaxContent => {
+                let mut fr = tracks
+                    .iter()
+                    .filter_map(|track| {
+                        track.flex_factor().map(|factor| {
+                            track.base_size.0 as f64 / if factor > 1.0 { factor } else { 1.0 }
+                        })
+                    })
+                    .fold(0.0, f64::max);
+                for &&((start, end), contribution) in &items {
+                    let spanned = &tracks[start..end];
+                    if spanned.iter().any(|track| track.flex_factor().is_some()) {
+                        let gaps = gap * (end - start - 1) as i32;
+                        fr = fr.max(fr_size(spanned, contribution.max - gaps));
+                    }
+                }
+                fr
+            }
+            Available::MinContent => 0.0,
+        };
+        for track in tracks.iter_mut() {
+            if let Some(factor) = track.flex_factor() {
+                track.base_size = max(track.base_size, Au((fr * factor) as i32));
+            }
+        }
+    }
+
+    // Tracks with an `auto` maximum stretch to fill the container.
+    if let Available::Definite(available) = available {
+        let free_space = available - tracks_size(tracks, gap);
+        let count = tracks
+            .iter()
+            .filter(|track| track.max == TrackBreadth::Auto && !track.collapsed)
+            .count() as i32;
+        if stretch && free_space > Au(0) && count > 0 {
+            for track in tracks.iter_mut() {
+                if track.max == TrackBreadth::Auto && !track.collapsed {
+                    track.base_size += free_space / count;
+                }
+            }
+        }
+    }
+}
+
+/// The limit of `fit-content()` if it's definite.
+fn fit_content_limit(limit: Option<LengthOrPercentage>, basis: Option<Au>) -> Option<Au> {
+    match (limit, basis) {
+        (Some(LengthOrPercentage::Length(limit)), _) => Some(limit),
+        (Some(percentage), Some(basis)) => Some(percentage.to_au(basis)),
+        _ => None,
+    }
+}
+
+/// The size of `1fr` for flexible tracks to fill `space` along with the others. Flexible tracks
+/// whose base size is larger than their share keep it, like the inflexible tracks do.
+/// ref. https://www.w3.org/TR/css-grid-1/#algo-find-fr-size
+fn fr_size(tracks: &[Track], space: Au) -> f64 {
+    let mut inflexible: Vec<bool> = tracks
+        .iter()
+        .map(|track| track.flex_factor().is_none())
+        .collect();
+    loop {
+        let leftover = tracks
+            .iter()
+            .zip(&inflexible)
+            .filter(|&(_, &inflexible)| inflexible)
+            .fold(space, |leftover, (track, _)| leftover - track.base_size);
+        let factors: f64 = tracks
+            .iter()
+            .zip(&inflexible)
+            .filter(|&(_, &inflexible)| !inflexible)
+            .map(|(track, _)| track.flex_factor().unwrap())
+            .sum();
+        let fr = leftover.0 as f64 / factors.max(1.0);
+        let mut changed = false;
+        for (track, inflexible) in tracks.iter().zip(inflexible.iter_mut()) {
+            if !*inflexible && track.base_size.0 as f64 > fr * track.flex_factor().unwrap() {
+                *inflexible = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return fr.max(0.0);
+        }
+    }
+}
+
+/// The size of the tracks and the gaps between them.
+fn tracks_size(tracks: &[Track], gap: Au) -> Au {
+    let visible = tracks.iter().filter(|track| !track.collapsed).count() as i32;
+    tracks
+        .iter()
+        .fold(gap * max(0, visible - 1), |sum, track| sum + track.base_size)
+}
+
+/// Where each track starts in the container `size` long, with the gaps between the tracks and
+/// the free space shared as `distribution` says.
+fn track_positions(
+    tracks: &[Track],
+    gap: Au,
+    size: Au,
+    distribution: ContentDistribution,
+) -> Vec<Au> {
+    let visible = tracks.iter().filter(|track| !track.collapsed).count();
+    let free_space = size - tracks_size(tracks, gap);
+    let (mut position, spacing) = distribute(
+        distribution,
+        free_space,
+        visible,
+        ContentDistribution::FlexStart,
+    );
+    let mut positions = vec![];
+    for track in tracks {
+        positions.push(position);
+        if !track.collapsed {
+            position += track.base_size + gap + spacing;
+        }
+    }
+    positions
+}
+
+/// The size of the area between the `lines` of the tracks at `positions`.
+fn area_size(tracks: &[Track], positions: &[Au], lines: (usize, usize)) -> Au {
+    let last = lines.1 - 1;
+    positions[last] + tracks[last].base_size - positions[lines.0]
+}
+
+/// Aligns an item in its area `area` long along an axis, as `align` says. `size` is the size of
+/// its content box unless it `stretches`, `edges` its borders and paddings, and `margins` are
+/// None while they're `auto`. Returns the size of the content box, the margins, and the offset
+/// of the margin box in the area.
+/// ref. https://www.w3.org/TR/css-align-3/#justify-self-property
+fn align_in_area(
+    area: Au,
+    size: Au,
+    stretches: bool,
+    edges: Au,
+    margins: (Option<Au>, Option<Au>),
+    align: ItemAlignment,
+) -> (Au, (Au, Au), Au) {
+    let has_auto_margin = margins.0.is_none() || margins.1.is_none();
+    let used_margins = margins.0.unwrap_or(Au(0)) + margins.1.unwrap_or(Au(0));
+    let size = if stretches && !has_auto_margin {
+        max(Au(0), area - edges - used_margins)
+    } else {
+        size
+    };
+    let free_space = area - size - edges - used_margins;
+    if has_auto_margin {
+        let auto_margins = margins.0.is_none() as i32 + margins.1.is_none() as i32;
+        let each = max(Au(0), free_space) / auto_margins;
+        let margins = (margins.0.unwrap_or(each), margins.1.unwrap_or(each));
+        return (size, margins, Au(0));
+    }
+    let offset = match align {
+        ItemAlignment::FlexEnd => free_space,
+        ItemAlignment::Center => free_space / 2,
+        _ => Au(0),
+    };
+    (size, (margins.0.unwrap(), margins.1.unwrap()), offset)
+}
+
+#[test]
+fn test_place_items() {
+    use self::Placement::{Auto, Lines};
+
+    // Definite areas first, then the items locked to a row, then the others in turn.
+    let items = [
+        (Auto(1), Auto(1)),
+        (Lines(0, 1), Lines(1, 3)),
+        (Auto(1), Auto(2)),
+        (Lines(1, 2), Auto(1)),
+        (Auto(1), Auto(1)),
+    ];
+    assert_eq!(
+        place_items(&items, 3, false),
+        vec![
+            ((0, 1), (0, 1)),
+            ((0, 1), (1, 3)),
+            ((1, 2), (1, 3)),
+            ((1, 2), (0, 1)),
+            ((2, 3), (0, 1)),
+        ]
+    );
+    // `dense` fills the holes left behind.
+    let items = [(Auto(1), Auto(2)), (Auto(1), Auto(2)), (Auto(1), Auto(1))];
+    assert_eq!(
+        place_items(&items, 3, false),
+        vec![((0, 1), (0, 2)), ((1, 2), (0, 2)), ((1, 2), (2, 3))]
+    );
+    assert_eq!(
+        place_items(&items, 3, true),
+        vec![((0, 1), (0, 2)), ((1, 2), (0, 2)), ((0, 1), (2, 3))]
+    );
+}
+
+#[test]
+fn test_resolve_placement() {
+    let names = vec![
+        vec!["a".to_string()],
+        vec!["main-start".to_string()],
+        vec![],
+        vec!["main-end".to_string(), "a".to_string()],
+    ];
+    let line = |n| GridLine::Line(n, None);
+    let name = |name: &str| Some(name.to_string());
+    let resolve = |start, end| resolve_placement(&start, &end, &names);
+    assert_eq!(resolve(line(2), line(-1)), Placement::Lines(1, 3));
+    assert_eq!(resolve(line(3), line(1)), Placement::Lines(0, 2));
+    assert_eq!(resolve(line(2), GridLine::Span(2, None)), Placement::Lines(1, 3));
+    assert_eq!(resolve(GridLine::Span(2, None), line(-1)), Placement::Lines(1, 3));
+    assert_eq!(resolve(GridLine::Span(3, None), GridLine::Auto), Placement::Auto(3));
+    assert_eq!(
+        resolve(GridLine::Name("main".to_string()), GridLine::Name("main".to_string())),
+        Placement::Lines(1, 3)
+    );
+    assert_eq!(resolve(GridLine::Line(2, name("a")), line(1)), Placement::Lines(0, 3));
+    // Lines outside the explicit grid have every name.
+    assert_eq!(resolve(GridLine::Line(3, name("a")), GridLine::Auto), Placement::Lines(4, 5));
+    assert_eq!(resolve(line(-5), GridLine::Auto), Placement::Lines(-1, 0));
+}
+
+#[test]
+fn test_size_tracks() {
+    let px = Au::from_px;
+    let length = |px: i32| TrackBreadth::Length(LengthOrPercentage::Length(Au::from_px(px)));
+    let track = |min, max| Track::new(TrackSize::MinMax(min, max));
+    let sizes = |tracks: &[Track]| tracks.iter().map(|t| t.base_size).collect::<Vec<Au>>();
+
+    // 100px, auto and 1fr 2fr in 600px with 10px gaps: the auto column fits its item and
+    // the flexible ones share the rest.
+    let mut tracks = vec![
+        track(length(100), length(100)),
+        track(TrackBreadth::Auto, TrackBreadth::Auto),
+        track(TrackBreadth::Auto, TrackBreadth::Flex(1.0)),
+        track(TrackBreadth::Auto, TrackBreadth::Flex(2.0)),
+    ];
+    let items = [((1, 2), IntrinsicWidths::new(px(50), px(80)))];
+    size_tracks(&mut tracks, &items, Available::Definite(px(600)), px(10), true);
+    assert_eq!(sizes(&tracks), vec![px(100), px(80), px(130), px(260)]);
+
+    // Without flexible tracks, `auto` tracks stretch into the free space.
+    let mut tracks = vec![
+        track(length(100), length(100)),
+        track(TrackBreadth::Auto, TrackBreadth::Auto),
+    ];
+    size_tracks(&mut tracks, &items, Available::Definite(px(300)), Au(0), true);
+    assert_eq!(sizes(&tracks), vec![px(100), px(200)]);
+
+    // An item across two `auto` tracks spreads its contribution over them.
+    let mut tracks = vec![
+        track(TrackBreadth::Auto, TrackBreadth::Auto),
+        track(TrackBreadth::Auto, TrackBreadth::Auto),
+    ];
+    let items = [((0, 2), IntrinsicWidths::new(px(50), px(110)))];
+    size_tracks(&mut tracks, &items, Available::MinContent, px(10), false);
+    assert_eq!(sizes(&tracks), vec![px(20), px(20)]);
+    size_tracks(&mut tracks, &items, Available::MaxContent, px(10), false);
+    assert_eq!(sizes(&tracks), vec![px(50), px(50)]);
+}
@@ -0,0 +1,50 @@
+//! The pages visited in this session, which `:visited` matches the links to.
+
+use interface::link_url;
+
+use reqwest::Url;
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+thread_local!(
+    static VISITED: RefCell<HashSet<String>> = { RefCell::new(HashSet::new()) };
+);
+
+pub fn add(url: &Url) {
+    VISITED.with(|visited| visited.borrow_mut().insert(without_fragment(url)));
+}
+
+/// Whether the link `href` of the current page points to a visited page.
+pub fn is_visited(href: &str) -> bool {
+    link_url(href).map_or(false, |url| {
+        VISITED.with(|visited| visited.borrow().contains(&without_fragment(&url)))
+    })
+}
+
+/// `url` without its fragment, as the parts of a page are visited with it.
+fn without_fragment(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.into_string()
+}
+
+#[test]
+fn test_history() {
+    add(&Url::parse("https://example.com/index.html").unwrap());
+    assert!(is_visited("https://example.com/index.html#top"));
+    assert!(!is_visited("https://example.com/about.html"));
+}
+
+#[test]
+fn test_visited_relative_links() {
+    use interface::resolve_url;
+
+    add(&resolve_url("https://example.com/docs/guide.html?lang=en"));
+    assert!(is_visited("#top"));
+    assert!(is_visited("guide.html?lang=en#install"));
+    assert!(is_visited("../docs/guide.html?lang=en"));
+    assert!(is_visited("/docs/guide.html?lang=en"));
+    assert!(!is_visited("guide.html"));
+    assert!(!is_visited("/guide.html?lang=en"));
+}
@@ -191,26 +191,29 @@ impl Parser {
         Ok(attributes)
     }
 
+    /// Attributes without a value, like `<ol reversed>`, have an empty one.
     fn parse_attr(&mut self) -> Result<(String, String), ()> {
         let name = self.parse_tag_name()?;
-        if self.consume_char()? != '=' {
-            return Err(());
+        if self.next_char()? != '=' {
+            if name.is_empty() {
+                self.consume_char()?;
+                return Err(());
+            }
+            return Ok((name, "".to_string()));
         }
+        self.consume_char()?; // =
         let value = self.parse_attr_value()?;
         Ok((name, value))
     }
 
     fn parse_attr_value(&mut self) -> Result<String, ()> {
         let open_quote = self.next_char()?;
-        let mut open_quote_appeared = false;
-        if open_quote == '"' || open_quote == '\'' {
-            open_quote_appeared = true;
-            self.consume_char()?; // " or '
+        if open_quote != '"' && open_quote != '\'' {
+            return self.consume_while(|c| !c.is_whitespace() && c != '>');
         }
+        self.consume_char()?; // " or '
         let value = self.consume_while(|c| c != open_quote && c != '>')?;
-        if open_quote_appeared {
-            self.consume_char()?; // Maybe " or '
-        }
+        self.consume_char()?; // Maybe " or '
         Ok(value)
     }
 
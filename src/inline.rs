@@ -1,8 +1,8 @@
-use css::Value;
 use dom::NodeType;
-use font::Font;
 use layout::{BoxType, Dimensions, ImageData, LayoutBox, LayoutInfo, Rect, Text};
 use float::Floats;
+use margin::CollapsibleMargins;
+use style::{ComputedStyle, TextAlign};
 
 use std::ops::Range;
 use std::collections::{HashMap, VecDeque};
@@ -113,12 +113,22 @@ impl LineMaker {
 
             update_available_zone!();
 
+            // Absolutely positioned boxes take no room in the line, and are only placed at
+            // their static position here.
+            if layoutbox.is_absolutely_positioned() {
+                let mut layoutbox = layoutbox;
+                layoutbox.dimensions = Default::default();
+                self.new_boxes.push(layoutbox);
+                self.end += 1;
+                continue;
+            }
+
             match layoutbox.box_type {
                 BoxType::TextNode(_) => while self.pending.range.len() > 0 {
                     self.run_on_text_node(&layoutbox, max_width_considered_float);
                     update_available_zone!()
                 },
-                BoxType::InlineBlockNode => {
+                BoxType::InlineBlockNode | BoxType::Table | BoxType::Flex | BoxType::Grid => {
                     self.run_on_inline_block_node(layoutbox, max_width_considered_float)
                 }
                 BoxType::InlineNode => self.run_on_inline_node(
@@ -174,16 +184,19 @@ impl LineMaker {
                 }
 
                 // TODO: Refine
-                let text_align = new_box.property.text_align();
+                let text_align = new_box.property.text_align;
                 let init_width = match text_align {
-                    Value::Keyword(ref k) => match k.as_str() {
-                        "center" => (max_width_considered_float - line.width) / 2,
-                        "right" => max_width_considered_float - line.width,
-                        "left" | _ => Au(0),
-                    },
-                    _ => Au(0),
+                    TextAlign::Center => (max_width_considered_float - line.width) / 2,
+                    TextAlign::Right => max_width_considered_float - line.width,
+                    TextAlign::Left | TextAlign::Justify => Au(0),
                 } + left_floats_width;
 
+                if new_box.is_absolutely_positioned() {
+                    new_box.dimensions.content.x = init_width + self.cur_width;
+                    new_box.dimensions.content.y = self.cur_height;
+                    continue;
+                }
+
                 new_box.dimensions.content.x = init_width + self.cur_width
                     + new_box.dimensions.padding.left
                     + new_box.dimensions.border.left
@@ -219,9 +232,9 @@ impl LineMaker {
                 .push_back(VecDeque::from(layoutbox.children.clone()));
             layoutbox.children.clear();
 
-            layoutbox.assign_padding();
+            layoutbox.assign_padding(containing_block);
             layoutbox.assign_border_width();
-            layoutbox.assign_margin();
+            layoutbox.assign_margin(containing_block);
 
             let start = linemaker.end;
 
@@ -425,10 +438,11 @@ impl LineMaker {
 
     fn run_on_inline_block_node(&mut self, mut layoutbox: LayoutBox, max_width: Au) {
         let mut containing_block: Dimensions = ::std::default::Default::default();
-        containing_block.content.width = max_width - self.cur_width;
+        // A negative width, when lines aren't broken, makes the box as wide as its content.
+        containing_block.content.width = max_width;
         layoutbox.layout(
             &mut self.floats,
-            Au(0),
+            None,
             containing_block,
             containing_block,
             containing_block,
@@ -470,12 +484,9 @@ impl LineMaker {
 
         let mut new_layoutbox = layoutbox.clone();
 
-        let font_size = new_layoutbox.property.font_size();
         let line_height = new_layoutbox.property.line_height();
-        let font_weight = new_layoutbox.property.font_weight();
-        let font_slant = new_layoutbox.property.font_style();
 
-        let my_font = Font::new(font_size, font_weight, font_slant);
+        let my_font = new_layoutbox.property.font();
         let text_width = Au::from_f64_px(my_font.text_width(text));
         let (ascent, descent) = my_font.get_ascent_descent();
 
@@ -491,15 +502,19 @@ impl LineMaker {
         );
 
         if !shrink_to_fit && self.cur_width + text_width > max_width {
-            let remaining_width = max_width - self.cur_width; // Is this correc?
-            let (max_chars, text_width) =
-                my_font.compute_max_chars_and_width(text, remaining_width.to_f64_px());
+            let remaining_width = max_width - self.cur_width;
+            let (max_chars, text_width) = my_font.compute_max_chars_and_width(
+                text,
+                remaining_width.to_f64_px(),
+                &layoutbox.property,
+                self.cur_width == Au(0),
+            );
 
             new_layoutbox.dimensions.content.width = Au::from_f64_px(text_width);
             new_layoutbox.dimensions.content.height = ascent + descent;
 
             new_layoutbox.set_text_info(
-                Font::new(font_size, font_weight, font_slant),
+                my_font.clone(),
                 self.pending.range.start..self.pending.range.start + max_chars,
             );
             self.new_boxes.push(new_layoutbox);
@@ -515,7 +530,7 @@ impl LineMaker {
             new_layoutbox.dimensions.content.height = ascent + descent;
 
             new_layoutbox.set_text_info(
-                Font::new(font_size, font_weight, font_slant),
+                my_font.clone(),
                 self.pending.range.start..text.len() + self.pending.range.start,
             );
             self.new_boxes.push(new_layoutbox);
@@ -532,9 +547,9 @@ impl LayoutBox {
     pub fn layout_inline(&mut self, _floats: &mut Floats, containing_block: Dimensions) {
         self.load_image(containing_block);
 
-        self.assign_padding();
+        self.assign_padding(containing_block);
         self.assign_border_width();
-        self.assign_margin();
+        self.assign_margin(containing_block);
 
         self.assign_replaced_width_if_necessary();
         self.assign_replaced_height_if_necessary();
@@ -560,7 +575,7 @@ impl LayoutBox {
         let height = self.dimensions.content.height;
         match self.get_first_text_node() {
             Some(node) => match node.box_type {
-                BoxType::TextNode(Text { font, .. }) => font.get_ascent_descent().0,
+                BoxType::TextNode(Text { ref font, .. }) => font.get_ascent_descent().0,
                 _ => unreachable!(),
             },
             None => height,
@@ -574,7 +589,7 @@ impl LayoutBox {
     pub fn layout_inline_block(
         &mut self,
         _floats: &mut Floats,
-        _last_margin_bottom: Au,
+        _margins: Option<CollapsibleMargins>,
         containing_block: Dimensions,
         _saved_block: Dimensions,
         viewport: Dimensions,
@@ -583,49 +598,50 @@ impl LayoutBox {
         // laying out its children.
         self.calculate_inline_block_width(containing_block);
 
-        self.assign_padding();
+        self.assign_padding(containing_block);
         self.assign_border_width();
-        self.assign_margin();
-        // self.calculate_block_position(last_margin_bottom, containing_block);
+        self.assign_margin(containing_block);
+        // self.calculate_block_position(margins, containing_block);
 
-        self.layout_block_children(viewport);
+        self.layout_block_children(CollapsibleMargins::default(), viewport);
 
         // Parent height can depend on child height, so `calculate_height` must be called after the
         // children are laid out.
-        self.calculate_block_height();
+        self.calculate_block_height(containing_block);
     }
 
-    /// Calculate the width of a block-level non-replaced element in normal flow.
-    /// Sets the horizontal margin/padding/border dimensions, and the `width`.
+    /// Calculate the width of an inline-block (non-replaced) element, shrink-to-fit if it's
+    /// `auto`.
     /// ref. https://www.w3.org/TR/CSS2/visudet.html#inlineblock-width
-    pub fn calculate_inline_block_width(&mut self, _containing_block: Dimensions) {
-        // `width` has initial value `auto`.
-        // TODO: Implement calculating shrink-to-fit width
-        if let Some(x) = self.property.value("width") {
-            self.dimensions.content.width = Au::from_f64_px(x[0].to_px().unwrap());
-        } else {
-            // TODO
-            // width == auto
-            panic!("calculating shrink-to-fit width is unsupported.");
+    pub fn calculate_inline_block_width(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
+        let width = match self.property.specified_width(cb_width) {
+            Some(width) => width,
+            None => self.shrink_to_fit_width(containing_block),
         };
+        self.dimensions.content.width = self.property.clamp_width(width, Some(cb_width));
     }
+
 }
 
 use dom::Node;
 
-pub fn get_image(node: &Node, imgdata: &mut ImageData, containing_block: Dimensions) {
-    let cb_width = containing_block.content.width.to_f64_px();
-    let cb_height = containing_block.content.height.to_f64_px();
-
+pub fn get_image(
+    node: &Node,
+    style: &ComputedStyle,
+    imgdata: &mut ImageData,
+    containing_block: Dimensions,
+) {
     let pixbuf = imgdata.pixbuf.get_or_insert_with(|| get_pixbuf(node));
 
-    let specified_width_px = node.attr("width")
-        .and_then(|w| w.maybe_percent_to_px(cb_width));
-    // The same as above
-    let specified_height_px = node.attr("height")
-        .and_then(|h| h.maybe_percent_to_px(cb_height));
+    // `width` and `height` attributes are mapped to the properties as presentational hints.
+    let cb_width = containing_block.content.width;
+    let specified_width_px = style.specified_width(cb_width).map(|w| w.to_f64_px());
+    let specified_height_px = style
+        .specified_height(cb_width, Some(containing_block.content.height))
+        .map(|h| h.to_f64_px());
 
-    let (width, height) = match (specified_width_px, specified_height_px) {
+    let size = match (specified_width_px, specified_height_px) {
         (Some(width), Some(height)) => (Au::from_f64_px(width), Au::from_f64_px(height)),
         (Some(width), None) => (
             Au::from_f64_px(width),
@@ -640,6 +656,8 @@ pub fn get_image(node: &Node, imgdata: &mut ImageData, containing_block: Dimensi
             Au::from_f64_px(pixbuf.get_height() as f64),
         ),
     };
+    let (width, height) =
+        style.constrain_replaced_size(size, cb_width, Some(containing_block.content.height));
 
     imgdata.metadata.width = width;
     imgdata.metadata.height = height;
@@ -658,14 +676,18 @@ thread_local!(
 use interface::download;
 
 pub fn get_pixbuf(node: &Node) -> gdk_pixbuf::Pixbuf {
+    load_pixbuf(node.image_url().unwrap()).unwrap()
+}
+
+/// The image at `image_url`, or None if it isn't one.
+pub fn load_pixbuf(image_url: &str) -> Option<gdk_pixbuf::Pixbuf> {
     IMG_CACHE.with(|c| {
-        let image_url = node.image_url().unwrap();
-        c.borrow_mut()
-            .entry(image_url.clone())
-            .or_insert_with(|| {
-                let (cache_name, _) = download(image_url.as_str());
-                gdk_pixbuf::Pixbuf::new_from_file(cache_name.as_str()).unwrap()
-            })
-            .clone()
+        if let Some(pixbuf) = c.borrow().get(image_url) {
+            return Some(pixbuf.clone());
+        }
+        let (cache_name, _) = download(image_url);
+        let pixbuf = gdk_pixbuf::Pixbuf::new_from_file(cache_name.as_str()).ok()?;
+        c.borrow_mut().insert(image_url.to_string(), pixbuf.clone());
+        Some(pixbuf)
     })
 }
@@ -4,13 +4,19 @@ use css;
 use layout;
 use painter;
 use window;
+use default_style;
+use font_face;
+use element_state;
+use history;
+use selector_map::SelectorMap;
 
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
 extern crate gtk;
-use gtk::WidgetExt;
+use gtk::{AdjustmentExt, ScrolledWindowExt, WidgetExt};
+use glib::prelude::*;
 
 extern crate app_units;
 use app_units::Au;
@@ -34,60 +40,178 @@ macro_rules! debug_println {
 /// If ``url_str`` starts with ``file://``, does nothing especially.
 ///  Just returns (local file name, local file path).
 pub fn download(url_str: &str) -> (String, PathBuf) {
-    let url = HTML_SRC_URL.with(|html_src_url| {
+    download_url(resolve_url(url_str))
+}
+
+/// Makes ``url_str`` absolute using the URL of the current HTML source.
+///  If ``url_str`` is already absolute, it becomes the new URL of the HTML source.
+pub fn resolve_url(url_str: &str) -> Url {
+    HTML_SRC_URL.with(|html_src_url| {
         let mut html_src_url = html_src_url.borrow_mut();
         if let Ok(parsed) = Url::parse(url_str) {
             // If url_str is absolute URL(starts with scheme://)
             *html_src_url = Some(url_str.to_string());
             return parsed;
         } else if let Some(ref mut html_src_url) = *html_src_url {
-            let mut url = Url::parse(html_src_url.as_str()).unwrap();
-            let v: Vec<&str> = url_str.split("?").collect();
-            url.set_path(v.get(0).unwrap());
-            if v.len() > 1{
-              if let Some(s) = v.get(1){
-                 url.set_query(Some(s.replace("&amp;","&").as_str()));       
-              }           
-            }
-            
-            return url;
+            return relative_url(Url::parse(html_src_url.as_str()).unwrap(), url_str);
         }
         *html_src_url = Some(url_str.to_string());
         Url::parse(url_str).unwrap()
-    });
+    })
+}
+
+/// Makes the link ``href`` absolute like ``resolve_url``, without navigating to it.
+pub fn link_url(href: &str) -> Option<Url> {
+    if let Ok(url) = Url::parse(href) {
+        return Some(url);
+    }
+    HTML_SRC_URL.with(|html_src_url| {
+        html_src_url
+            .borrow()
+            .as_ref()
+            .and_then(|html_src_url| Url::parse(html_src_url.as_str()).ok())
+            .map(|base| relative_url(base, href))
+    })
+}
+
+/// Resolves `url_str` against `base`, like a browser resolves the links of a page.
+fn relative_url(base: Url, url_str: &str) -> Url {
+    // Attribute values are not unescaped by the HTML parser.
+    let url_str = url_str.replace("&amp;", "&");
+    base.join(url_str.as_str()).unwrap_or(base)
+}
+
+pub fn download_url(url: Url) -> (String, PathBuf) {
+    try_download_url(url.clone()).unwrap_or_else(|| panic!("cannot download {}", url))
+}
 
-    match url.scheme().to_ascii_lowercase().as_str() {
-        "file" => (url.path().to_string(), Path::new(url.path()).to_path_buf()),
+/// Like ``download_url``, but returns None when ``url`` can't be fetched or its scheme isn't
+/// supported. ``data:`` URLs are decoded into a file too.
+pub fn try_download_url(url: Url) -> Option<(String, PathBuf)> {
+    let (content, extension) = match url.scheme().to_ascii_lowercase().as_str() {
+        "file" => return Some((url.path().to_string(), Path::new(url.path()).to_path_buf())),
         "http" | "https" => {
             let mut content: Vec<u8> = vec![];
-            reqwest::get(url.clone())
-                .unwrap()
-                .copy_to(&mut content)
-                .unwrap();
-
-            let path = Path::new(url.path());
-            let tmpfile_name = format!(
-                "cache/{}.{}",
-                rand::thread_rng()
-                    .sample_iter(&rand::distributions::Alphanumeric)
-                    .take(8)
-                    .collect::<String>(),
-                if let Some(ext) = path.extension() {
-                    ext.to_str().unwrap()
-                } else {
-                    "html"
-                }
-            );
+            let response = reqwest::get(url.clone()).and_then(|response| {
+                response.error_for_status()
+            });
+            if let Err(e) = response.and_then(|mut response| response.copy_to(&mut content)) {
+                debug_println!("cannot download {}: {:?}", url.as_str(), e);
+                return None;
+            }
+
+            let extension = match Path::new(url.path()).extension() {
+                Some(ext) => ext.to_str().unwrap().to_string(),
+                None => "html".to_string(),
+            };
+            (content, extension)
+        }
+        "data" => {
+            let content = decode_data_url(url.path())?;
+            // The subtype of the media type, e.g. `woff` for `font/woff`.
+            let media_type = url.path().split(|c| c == ',' || c == ';').next().unwrap();
+            let extension = match media_type.find('/') {
+                Some(slash) => media_type[slash + 1..].trim().to_string(),
+                None => "txt".to_string(),
+            };
+            (content, extension)
+        }
+        _ => return None,
+    };
+
+    let path = Path::new(url.path());
+    let tmpfile_name = format!(
+        "cache/{}.{}",
+        rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(8)
+            .collect::<String>(),
+        extension
+    );
 
-            debug_println!("downloaded {}", url.as_str());
+    debug_println!("downloaded {}", url.as_str());
 
-            let mut f = BufWriter::new(fs::File::create(tmpfile_name.as_str()).unwrap());
-            f.write_all(content.as_slice()).unwrap();
+    let mut f = BufWriter::new(fs::File::create(tmpfile_name.as_str()).ok()?);
+    f.write_all(content.as_slice()).ok()?;
+
+    Some((tmpfile_name, path.to_path_buf()))
+}
+
+/// The data of the ``data:`` URL whose path is ``path``. It's percent-encoded, and base64 encoded
+/// too if the media type ends with ``;base64``.
+/// ref. https://fetch.spec.whatwg.org/#data-urls
+fn decode_data_url(path: &str) -> Option<Vec<u8>> {
+    let comma = path.find(',')?;
+    let data = percent_decode(&path[comma + 1..]);
+    if path[..comma].trim().to_ascii_lowercase().ends_with(";base64") {
+        decode_base64(&data)
+    } else {
+        Some(data)
+    }
+}
 
-            (tmpfile_name, path.to_path_buf())
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.chars().all(|c| c.is_digit(16)))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
         }
-        _ => unimplemented!(),
     }
+    decoded
+}
+
+/// Decodes base64 ignoring whitespace, with or without the padding. None if it's malformed.
+fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    let mut data: Vec<u8> = data
+        .iter()
+        .cloned()
+        .filter(|&byte| !(byte as char).is_ascii_whitespace())
+        .collect();
+    if data.len() % 4 == 0 {
+        for _ in 0..2 {
+            if data.last() == Some(&b'=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = vec![];
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for byte in data {
+        let value = match byte {
+            b'A'...b'Z' => byte - b'A',
+            b'a'...b'z' => byte - b'a' + 26,
+            b'0'...b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            decoded.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(decoded)
 }
 
 use std::cell::RefCell;
@@ -95,15 +219,21 @@ use std::rc::Rc;
 
 thread_local!(
     static LAYOUT_SAVER: RefCell<(Au, Au, painter::DisplayList)> = { RefCell::new((Au(0), Au(0), vec![])) };
+    // The last layout tree, the area of it that was visible and how far its scroll containers
+    // were scrolled, when some of its boxes are painted where the page or a box is scrolled to.
+    static SCROLL_DEPENDENT_LAYOUT: RefCell<Option<(layout::LayoutBox, layout::Rect, window::ScrollOffsets)>> = { RefCell::new(None) };
     static HTML_SRC_URL: RefCell<Option<String>> = { RefCell::new(None) };
     static HTML_TREE:    Rc<RefCell<Option<dom::Node>>> = { Rc::new(RefCell::new(None)) };
-    static STYLESHEET:   Rc<RefCell<Option<css::Stylesheet>>> = { Rc::new(RefCell::new(None)) };
+    // The stylesheet of the document, whose selectors are indexed once for all its layouts.
+    static STYLESHEET:   Rc<RefCell<Option<Rc<SelectorMap>>>> = { Rc::new(RefCell::new(None)) };
 );
 
 static mut SRC_UPDATED: bool = false;
 
 pub fn update_html_source(html_src: String) {
-    let (html_src_cache_name, html_src_path) = download(html_src.as_str());
+    let html_src_url = resolve_url(html_src.as_str());
+    let (html_src_cache_name, html_src_path) = download_url(html_src_url.clone());
+    history::add(&html_src_url);
 
     //debug_println!("HTML:");
     let mut html_source = "".to_string();
@@ -119,8 +249,11 @@ pub fn update_html_source(html_src: String) {
 
     debug_println!("CSS:");
     let mut css_source = "".to_string();
+    // url()s in the stylesheet are relative to the stylesheet itself.
+    let mut css_url = html_src_url;
     if let Some(stylesheet_path) = html_tree.find_stylesheet_path() {
-        let (css_cache_name, _) = download(stylesheet_path.to_str().unwrap());
+        css_url = resolve_url(stylesheet_path.to_str().unwrap());
+        let (css_cache_name, _) = download_url(css_url.clone());
         OpenOptions::new()
             .read(true)
             .open(css_cache_name)
@@ -133,14 +266,25 @@ pub fn update_html_source(html_src: String) {
     } else {
         debug_println!("*** Not found any stylesheet but continue ***");
     }
-    let stylesheet = css::parse(css_source);
+    let stylesheet = css::parse_with_base_url(css_source, css_url);
     debug_println!("{}", stylesheet);
+    font_face::load(&stylesheet.font_faces);
 
     HTML_TREE.with(|h| {
         *h.borrow_mut() = Some(html_tree);
     });
-    STYLESHEET.with(|s| *s.borrow_mut() = Some(stylesheet));
+    element_state::reset();
+    STYLESHEET.with(|s| *s.borrow_mut() = Some(Rc::new(SelectorMap::new(Rc::new(stylesheet)))));
+
+    layout::LAYOUTBOX.with(|lb| *lb.borrow_mut() = None);
+
+    unsafe {
+        SRC_UPDATED = true;
+    }
+}
 
+/// Makes the next draw lay the page out again, e.g. when a web font has been loaded.
+pub fn invalidate_layout() {
     layout::LAYOUTBOX.with(|lb| *lb.borrow_mut() = None);
 
     unsafe {
@@ -148,14 +292,109 @@ pub fn update_html_source(html_src: String) {
     }
 }
 
-pub fn run_with_url(html_src: String) {
-    let main_browser_process = ::std::thread::spawn(|| {
+/// Changes the state of the elements of the document with `f`, which returns the elements
+/// whose state changed, and builds the boxes of these again. Returns whether the page has to
+/// be repainted.
+pub fn update_element_state<F: FnOnce(&dom::Node) -> Vec<usize>>(f: F) -> bool {
+    HTML_TREE.with(|h| {
+        let html_tree = h.borrow();
+        let html_tree = match *html_tree {
+            Some(ref html_tree) => html_tree,
+            None => return false,
+        };
+        let changed = f(html_tree);
+        if changed.is_empty() {
+            return false;
+        }
+
+        let viewport_size = LAYOUT_SAVER.with(|x| {
+            let (width, height, _) = *x.borrow();
+            (width.to_f64_px(), height.to_f64_px())
+        });
+        let restyled = STYLESHEET.with(|s| match *s.borrow() {
+            Some(ref stylesheet) => {
+                layout::restyle(html_tree, stylesheet, &changed, viewport_size)
+            }
+            None => false,
+        });
+        if !restyled {
+            layout::LAYOUTBOX.with(|lb| *lb.borrow_mut() = None);
+        }
+
+        unsafe {
+            SRC_UPDATED = true;
+        }
+        true
+    })
+}
+
+/// Reads the user stylesheet from `path`, which is a local path or a URL. One that can't be
+/// found or read is left empty.
+fn load_user_stylesheet(path: &str) {
+    let url = match Url::parse(path) {
+        Ok(url) => url,
+        Err(_) => match fs::canonicalize(path).ok().and_then(|p| Url::from_file_path(p).ok()) {
+            Some(url) => url,
+            None => {
+                debug_println!("cannot find the user stylesheet {}", path);
+                return;
+            }
+        },
+    };
+    let mut css_source = "".to_string();
+    let read = try_download_url(url.clone()).and_then(|(css_cache_name, _)| {
+        OpenOptions::new()
+            .read(true)
+            .open(css_cache_name)
+            .and_then(|mut file| file.read_to_string(&mut css_source))
+            .ok()
+    });
+    if read.is_none() {
+        debug_println!("cannot read the user stylesheet {}", path);
+        css_source.clear();
+    }
+    default_style::set_user_style(css::parse_with_base_url(css_source, url));
+}
+
+pub fn run_with_url(html_src: String, user_stylesheet: Option<String>) {
+    let main_browser_process = ::std::thread::spawn(move || {
+        if let Some(path) = user_stylesheet {
+            load_user_stylesheet(path.as_str());
+        }
         update_html_source(html_src);
 
         window::render(move |widget| {
             let mut viewport: layout::Dimensions = ::std::default::Default::default();
             viewport.content.width = Au::from_f64_px(widget.get_allocated_width() as f64);
-            viewport.content.height = Au::from_f64_px(widget.get_allocated_height() as f64);
+            // The drawing area grows with the page, so the visible height is that of the
+            // ScrolledWindow around it (DrawingArea -> Overlay -> Viewport -> ScrolledWindow).
+            let scrolled_window = widget
+                .get_parent()
+                .and_then(|overlay| overlay.get_parent())
+                .and_then(|viewport| viewport.get_parent());
+            let visible_height = scrolled_window
+                .as_ref()
+                .map_or(widget.get_allocated_height(), |scrolled_window| {
+                    scrolled_window.get_allocated_height()
+                });
+            viewport.content.height = Au::from_f64_px(visible_height as f64);
+            // Where the page is scrolled to, which `fixed` and `sticky` boxes are painted for.
+            let scroll = |adjustment: Option<gtk::Adjustment>| {
+                adjustment.map_or(Au(0), |adjustment| Au::from_f64_px(adjustment.get_value()))
+            };
+            let (scroll_x, scroll_y) = scrolled_window
+                .and_then(|scrolled_window| scrolled_window.downcast::<gtk::ScrolledWindow>().ok())
+                .map_or((Au(0), Au(0)), |scrolled_window| {
+                    (
+                        scroll(scrolled_window.get_hadjustment()),
+                        scroll(scrolled_window.get_vadjustment()),
+                    )
+                });
+            let visible = layout::Rect {
+                x: scroll_x,
+                y: scroll_y,
+                ..viewport.content
+            };
 
             LAYOUT_SAVER.with(|x| {
                 let (ref mut last_width, ref mut last_height, ref mut last_displays) =
@@ -163,6 +402,18 @@ pub fn run_with_url(html_src: String) {
                 if *last_width == viewport.content.width && *last_height == viewport.content.height
                     && unsafe { !SRC_UPDATED }
                 {
+                    SCROLL_DEPENDENT_LAYOUT.with(|saved| {
+                        if let Some((ref layout_tree, ref mut last_visible, ref mut last_offsets)) =
+                            *saved.borrow_mut()
+                        {
+                            let offsets = window::SCROLL_OFFSETS.with(|o| o.borrow().clone());
+                            if *last_visible != visible || *last_offsets != offsets {
+                                *last_visible = visible;
+                                *last_offsets = offsets;
+                                *last_displays = painter::build_display_list(layout_tree, visible);
+                            }
+                        }
+                    });
                     last_displays.clone()
                 } else {
                     unsafe {
@@ -174,13 +425,21 @@ pub fn run_with_url(html_src: String) {
 
                     let html_tree = HTML_TREE.with(|h| (*h.borrow()).clone().unwrap());
                     let stylesheet = STYLESHEET.with(|s| (*s.borrow()).clone().unwrap());
-                    let mut layout_tree = layout::layout_tree(&html_tree, &stylesheet, viewport);
+                    let layout_tree = layout::layout_tree(&html_tree, &stylesheet, viewport);
                     // debug_println!("LAYOUT:\n{}", layout_tree);
 
-                    let display_command = painter::build_display_list(&mut layout_tree);
+                    let display_command = painter::build_display_list(&layout_tree, visible);
                     // debug_println!("DISPLAY:\n{:?}", display_command);
 
                     *last_displays = display_command.clone();
+                    SCROLL_DEPENDENT_LAYOUT.with(|saved| {
+                        *saved.borrow_mut() = if layout_tree.has_scroll_dependent_boxes() {
+                            let offsets = window::SCROLL_OFFSETS.with(|o| o.borrow().clone());
+                            Some((layout_tree, visible, offsets))
+                        } else {
+                            None
+                        }
+                    });
 
                     display_command
                 }
@@ -207,3 +466,14 @@ pub fn run_with_url(html_src: String) {
         }
     }
 }
+
+#[test]
+fn test_decode_data_url() {
+    assert_eq!(decode_data_url("text/plain,a%20b%2"), Some(b"a b%2".to_vec()));
+    assert_eq!(decode_data_url(",%E3%81%82"), Some("あ".as_bytes().to_vec()));
+    assert_eq!(decode_data_url("font/woff;base64,d09GRg=="), Some(b"wOFF".to_vec()));
+    assert_eq!(decode_data_url("font/woff;base64,d09G Rg"), Some(b"wOFF".to_vec()));
+    assert_eq!(decode_data_url("font/woff;base64,d09GR"), None);
+    assert_eq!(decode_data_url("font/woff;base64,d0*G"), None);
+    assert_eq!(decode_data_url("text/plain"), None);
+}
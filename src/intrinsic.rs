@@ -0,0 +1,230 @@
+//! The intrinsic widths of boxes, which the widths of tables and of shrink-to-fit boxes are
+//! based on: the min-content width, below which the content overflows, and the max-content
+//! width, at which no line has to be broken.
+//! ref. https://www.w3.org/TR/css-sizing-3/#intrinsic-sizes
+
+use layout::{BoxType, Dimensions, LayoutBox, LayoutInfo};
+use inline::{load_pixbuf, LineMaker};
+use float::Floats;
+use dom::NodeType;
+use line_break;
+use style::{LengthOrPercentage, LengthOrPercentageOrAuto};
+
+use std::cmp::{max, min};
+
+use gdk_pixbuf::PixbufExt;
+
+use app_units::Au;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IntrinsicWidths {
+    pub min: Au,
+    pub max: Au,
+}
+
+impl IntrinsicWidths {
+    pub fn new(min: Au, max: Au) -> IntrinsicWidths {
+        IntrinsicWidths { min: min, max: max }
+    }
+
+    /// The width of a box that shrinks to fit in `available`, but no narrower than its
+    /// min-content width.
+    pub fn shrink_to_fit(self, available: Au) -> Au {
+        min(max(self.min, available), self.max)
+    }
+
+    /// The widths of `self` and `other` on the same line.
+    fn beside(self, other: IntrinsicWidths) -> IntrinsicWidths {
+        IntrinsicWidths::new(max(self.min, other.min), self.max + other.max)
+    }
+
+    /// The widths of `self` and `other` stacked vertically.
+    fn above(self, other: IntrinsicWidths) -> IntrinsicWidths {
+        IntrinsicWidths::new(max(self.min, other.min), max(self.max, other.max))
+    }
+}
+
+impl LayoutBox {
+    /// The intrinsic widths of the margin box, before layout. Percentages, which depend on the
+    /// containing block, are left out.
+    pub fn intrinsic_widths(&self) -> IntrinsicWidths {
+        let content = self.content_intrinsic_widths();
+        let content = match self.property.width {
+            LengthOrPercentageOrAuto::Length(width) => match self.box_type {
+                // Cells and tables don't get narrower than their content.
+                BoxType::TableCell => IntrinsicWidths::new(content.min, max(content.min, width)),
+                BoxType::Table => {
+                    let width = max(content.min, width);
+                    IntrinsicWidths::new(width, width)
+                }
+                _ => {
+                    let width = self.property.content_width(width, Au(0));
+                    IntrinsicWidths::new(width, width)
+                }
+            },
+            _ => content,
+        };
+        // The effect of `min-width` and `max-width` on tables is undefined in CSS 2.
+        let content = match self.box_type {
+            BoxType::TableCell | BoxType::Table => content,
+            _ => IntrinsicWidths::new(
+                self.property.clamp_width(content.min, None),
+                self.property.clamp_width(content.max, None),
+            ),
+        };
+        let edges = self.horizontal_edges();
+        IntrinsicWidths::new(content.min + edges, content.max + edges)
+    }
+
+    pub fn content_intrinsic_widths(&self) -> IntrinsicWidths {
+        if let LayoutInfo::Image(ref imgdata) = self.info {
+            let width = if imgdata.metadata.width > Au(0) {
+                imgdata.metadata.width
+            } else {
+                self.node
+                    .image_url()
+                    .and_then(|url| load_pixbuf(url))
+                    .map_or(Au(0), |pixbuf| Au::from_px(pixbuf.get_width()))
+            };
+            return IntrinsicWidths::new(width, width);
+        }
+        match self.box_type {
+            BoxType::TextNode(ref text) => {
+                let text = match self.node.data {
+                    NodeType::Text(ref s) => &s[text.range.clone()],
+                    NodeType::Element(_) => return IntrinsicWidths::default(),
+                };
+                let font = self.property.font();
+                // The min-content width is that of the widest part lines can't break in.
+                let opportunities = if line_break::min_content_breaks_anywhere(&self.property) {
+                    line_break::character_boundaries(text)
+                } else {
+                    line_break::break_opportunities(text, &self.property)
+                };
+                let mut widest_part: f64 = 0.0;
+                let mut start = 0;
+                for &end in opportunities.iter().chain(&[text.len()]) {
+                    widest_part = widest_part.max(font.text_width(text[start..end].trim()));
+                    start = end;
+                }
+                IntrinsicWidths::new(
+                    Au::from_f64_px(widest_part),
+                    Au::from_f64_px(font.text_width(text)),
+                )
+            }
+            BoxType::InlineNode => self.inline_intrinsic_widths(),
+            BoxType::AnonymousBlock => {
+                // The max-content width is that of the longest line the content is laid out in
+                // when lines are only broken where they have to be.
+                let mut linemaker = LineMaker::new(self.children.clone(), Floats::new());
+                linemaker.run(Au(-1), Dimensions::default());
+                linemaker.end_of_lines();
+                let min_width = self.inline_intrinsic_widths().min;
+                IntrinsicWidths::new(min_width, max(min_width, linemaker.intrinsic_width()))
+            }
+            BoxType::Table => self.table_intrinsic_widths(),
+            BoxType::Flex => self.flex_intrinsic_widths(),
+            BoxType::Grid => self.grid_intrinsic_widths(),
+            BoxType::BlockNode
+            | BoxType::InlineBlockNode
+            | BoxType::Float
+            | BoxType::TableRowGroup
+            | BoxType::TableRow
+            | BoxType::TableCell => self.children
+                .iter()
+                .filter(|child| !child.is_absolutely_positioned())
+                .fold(IntrinsicWidths::default(), |widths, child| {
+                    widths.above(child.intrinsic_widths())
+                }),
+            BoxType::TableColumn | BoxType::None => IntrinsicWidths::default(),
+        }
+    }
+
+    /// The intrinsic widths of inline-level children on the same line.
+    fn inline_intrinsic_widths(&self) -> IntrinsicWidths {
+        self.children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .fold(IntrinsicWidths::default(), |widths, child| {
+                widths.beside(child.intrinsic_widths())
+            })
+    }
+
+    /// The shrink-to-fit width of the content box in `containing_block`, for floats and
+    /// inline-blocks whose `width` is `auto`. A negative containing block width, which
+    /// content is measured in, gives the max-content width.
+    /// ref. https://www.w3.org/TR/CSS22/visudet.html#shrink-to-fit-float
+    pub fn shrink_to_fit_width(&self, containing_block: Dimensions) -> Au {
+        let widths = self.content_intrinsic_widths();
+        let cb_width = containing_block.content.width;
+        if cb_width < Au(0) {
+            return widths.max;
+        }
+        let style = &self.property;
+        let margin = |margin: LengthOrPercentageOrAuto| margin.to_au(cb_width).unwrap_or(Au(0));
+        let available = cb_width - margin(style.margin.left) - margin(style.margin.right)
+            - style.horizontal_box_edges(cb_width);
+        widths.shrink_to_fit(available)
+    }
+
+    /// Sets the horizontal margins, borders and paddings of a flex or grid container that
+    /// shrinks to fit its items, as an inline-level one does, and the width of its content box.
+    pub fn calculate_shrink_to_fit_width(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
+        self.assign_padding(containing_block);
+        self.assign_border_width();
+        self.assign_margin(containing_block);
+        let d = self.dimensions;
+        let available = cb_width - d.margin.left - d.margin.right - d.border.left
+            - d.border.right - d.padding.left - d.padding.right;
+        // Percentages of the negative width content is measured in are left out.
+        let cb_width = if cb_width >= Au(0) { Some(cb_width) } else { None };
+        let specified_width = match (self.property.width, cb_width) {
+            (LengthOrPercentageOrAuto::Percentage(_), None) => None,
+            (_, cb_width) => self.property.specified_width(cb_width.unwrap_or(Au(0))),
+        };
+        let width = specified_width
+            .unwrap_or_else(|| self.content_intrinsic_widths().shrink_to_fit(available));
+        self.dimensions.content.width = self.property.clamp_width(width, cb_width);
+    }
+
+    /// The horizontal margins, borders and paddings that aren't percentages.
+    fn horizontal_edges(&self) -> Au {
+        let style = &self.property;
+        let padding = |padding: LengthOrPercentage| match padding {
+            LengthOrPercentage::Length(au) => au,
+            LengthOrPercentage::Percentage(_) => Au(0),
+        };
+        let margin = |margin: LengthOrPercentageOrAuto| match margin {
+            LengthOrPercentageOrAuto::Length(au) => au,
+            _ => Au(0),
+        };
+        margin(style.margin.left) + margin(style.margin.right) + style.border_width.left
+            + style.border_width.right + padding(style.padding.left)
+            + padding(style.padding.right)
+    }
+}
+
+#[test]
+fn test_shrink_to_fit_width() {
+    use layout::block;
+
+    let px = Au::from_px;
+    let containing_block = |width: i32| {
+        let mut containing_block = Dimensions::default();
+        containing_block.content.width = px(width);
+        containing_block
+    };
+
+    let mut inline_block = block(
+        "display: inline-block; padding-left: 20px",
+        vec![block("width: 100px", vec![]), block("width: 50%", vec![])],
+    );
+    assert_eq!(inline_block.shrink_to_fit_width(containing_block(400)), px(100));
+    // The content doesn't get narrower than its min-content width.
+    assert_eq!(inline_block.shrink_to_fit_width(containing_block(50)), px(100));
+
+    // An `auto` width is shrink-to-fit instead of making the layout panic.
+    inline_block.calculate_inline_block_width(containing_block(400));
+    assert_eq!(inline_block.dimensions.content.width, px(100));
+}
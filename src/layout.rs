@@ -1,13 +1,19 @@
-use style::{Display, Style};
+use style::{ComputedStyle, Display, ListStylePosition, Position, Style};
 use dom::{ElementData, LayoutType, Node, NodeType};
 use float::Floats;
-use font::{Font, FontSlant, FontWeight};
-use inline::LineMaker;
+use margin::CollapsibleMargins;
+use font::{Font, FontSlant, FontStretch, FontVariant, FontWeight};
+use inline::{load_pixbuf, LineMaker};
 use style;
 use default_style;
-use css::{parse_attr_style, Declaration, Rule, Selector, SimpleSelector, Specificity, Stylesheet,
-          Value};
-
+use presentational_hints::presentational_hints;
+use css::{parse_attr_style, Declaration, LengthContext, Value};
+use selector_map::{Ancestors, MatchedRule, SelectorMap};
+use style_sharing::{can_share_style, StyleSharingCache};
+use element_state::{state_of, ElementState};
+use list::{marker_text, ListCounter};
+
+use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::default::Default;
 use std::fmt;
@@ -77,6 +83,19 @@ pub enum BoxType {
     Float,
     TextNode(Text),
     AnonymousBlock,
+    /// A block-level or inline-level table, whose children are its captions, column groups,
+    /// columns and row groups.
+    Table,
+    /// The rows of a row group, header group or footer group.
+    TableRowGroup,
+    TableRow,
+    TableCell,
+    /// A column, or a column group holding columns.
+    TableColumn,
+    /// A block-level or inline-level flex container, whose children are its flex items.
+    Flex,
+    /// A block-level or inline-level grid container, whose children are its grid items.
+    Grid,
     None, // TODO: Is this really needed?
 }
 
@@ -84,13 +103,18 @@ pub enum BoxType {
 #[derive(Clone, Debug)]
 pub struct LayoutBox {
     pub node: Node,
-    pub property: Style,
+    /// The number of `node` in tree order in the document, or `None` for anonymous boxes.
+    pub node_id: Option<usize>,
+    pub property: ComputedStyle,
     pub dimensions: Dimensions,
+    /// The level of the box in its stacking context, which only positioned boxes have.
     pub z_index: i32,
     pub box_type: BoxType,
     pub info: LayoutInfo,
     pub floats: Floats,
     pub children: Vec<LayoutBox>,
+    /// The anonymous block holding the marker of an `outside` list item, which isn't in the flow.
+    pub marker: Option<Box<LayoutBox>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -122,20 +146,31 @@ impl ImageMetaData {
 }
 
 impl LayoutBox {
-    pub fn new(box_type: BoxType, node: Node, property: Style, info: LayoutInfo) -> LayoutBox {
+    pub fn new(
+        box_type: BoxType,
+        node: Node,
+        property: ComputedStyle,
+        info: LayoutInfo,
+    ) -> LayoutBox {
+        let z_index = match property.position {
+            Position::Static => 0,
+            _ => property.z_index.unwrap_or(0),
+        };
         LayoutBox {
             node: node,
+            node_id: None,
             property: property,
             box_type: box_type,
             info: info,
-            z_index: 0,
+            z_index: z_index,
             floats: Floats::new(),
             dimensions: Default::default(),
             children: Vec::with_capacity(16),
+            marker: None,
         }
     }
 
-    pub fn get_style_node(&self) -> &Style {
+    pub fn get_style_node(&self) -> &ComputedStyle {
         &self.property
     }
 
@@ -147,316 +182,384 @@ impl LayoutBox {
     }
 
     pub fn in_normal_flow(&self) -> bool {
-        self.box_type != BoxType::Float
+        self.box_type != BoxType::Float && !self.is_absolutely_positioned()
+    }
+
+    /// Whether this is the box of a text with only white space, which some boxes drop.
+    pub fn is_whitespace(&self) -> bool {
+        match (&self.box_type, &self.node.data) {
+            (&BoxType::TextNode(_), &NodeType::Text(ref text)) => text.trim().is_empty(),
+            _ => false,
+        }
     }
 }
 
 /// Build the tree of LayoutBoxes, but don't perform any layout calculations yet.
-fn build_layout_tree(
-    node: &Node,
-    stylesheet: &Stylesheet,
-    default_style: &Stylesheet,
-    inherited_property: &Style,
-    parent_specified_values: &Style,
-    appeared_elements: &Vec<SimpleSelector>,
-    // id: &mut usize,
+/// `table` is the nearest `table` element around `node`, whose attributes can style cells.
+/// `sharing_cache` holds the styles of the previous siblings of `node`, and `list_counter` numbers
+/// the list items among them. `id` is the number of `node` in tree order, which is advanced past
+/// its descendants.
+fn build_layout_tree<'a>(
+    node: &'a Node,
+    stylesheet: &SelectorMap,
+    default_style: &SelectorMap,
+    user_style: &SelectorMap,
+    parent_style: &ComputedStyle,
+    ancestors: &mut Ancestors,
+    sharing_cache: &mut StyleSharingCache<'a>,
+    list_counter: &mut ListCounter,
+    table: Option<&'a ElementData>,
+    length_ctx: &LengthContext,
+    id: &mut usize,
 ) -> LayoutBox {
-    let mut appeared_elements = appeared_elements.clone();
-    let specified_values = match node.data {
+    let node_id = *id;
+    *id += 1;
+    let is_root = ancestors.is_empty();
+    let computed_style = match node.data {
         NodeType::Element(ref elem) => {
-            let values = specified_values(
-                elem,
-                default_style,
-                stylesheet,
-                inherited_property,
-                &appeared_elements,
-            );
-            appeared_elements.push(SimpleSelector {
-                tag_name: Some(elem.tag_name.clone()),
-                id: elem.id().and_then(|id| Some(id.clone())),
-                class: elem.classes().iter().map(|x| x.to_string()).collect(),
-            });
-            values
-        }
-        NodeType::Text(_) => {
-            Style::new_with(
-                if let Some(display) = parent_specified_values.property.get("display") {
-                    match display[0] {
-                        // If the parent element is an inline element, inherites the parent's properties.
-                        Value::Keyword(ref k) if k == "inline" => parent_specified_values.clone(),
-                        _ => inherited_property.clone(),
+            let state = state_of(node_id);
+            let shareable = can_share_style(elem, &[default_style, user_style, stylesheet]);
+            let shared_style = if shareable {
+                sharing_cache.lookup(elem, state)
+            } else {
+                None
+            };
+            match shared_style {
+                Some(style) => style,
+                None => {
+                    let values = specified_values(
+                        elem,
+                        state,
+                        default_style,
+                        user_style,
+                        stylesheet,
+                        ancestors,
+                        table,
+                    );
+                    if values.uses_viewport_relative_lengths() {
+                        LAYOUTBOX_VIEWPORT.with(|viewport| {
+                            viewport
+                                .set(Some((length_ctx.viewport_width, length_ctx.viewport_height)))
+                        });
+                    }
+                    let style = ComputedStyle::compute(&values, parent_style, length_ctx);
+                    if shareable {
+                        sharing_cache.insert(elem, state, style.clone());
                     }
-                } else {
-                    inherited_property.clone()
-                }.property
-                    .into_iter()
-                    .filter(|&(ref name, _)| name != "float")
-                    .collect(),
-            )
+                    style
+                }
+            }
+        }
+        // Text has no declarations of its own and only inherits from the parent.
+        NodeType::Text(_) => parent_style.inherited(),
+    };
+    // The children of flex and grid containers and absolutely positioned boxes are block-level,
+    // and don't float.
+    let is_item = match (parent_style.display, &node.data) {
+        (Display::Flex, &NodeType::Element(_))
+        | (Display::InlineFlex, &NodeType::Element(_))
+        | (Display::Grid, &NodeType::Element(_))
+        | (Display::InlineGrid, &NodeType::Element(_)) => true,
+        _ => false,
+    };
+    let is_out_of_flow = match computed_style.position {
+        Position::Absolute | Position::Fixed => true,
+        _ => false,
+    };
+    let computed_style = if is_item || is_out_of_flow {
+        ComputedStyle {
+            display: computed_style.display.blockified(),
+            float: style::FloatType::None,
+            ..computed_style
         }
+    } else {
+        computed_style
     };
 
     // Create the root box.
     let mut root = LayoutBox::new(
-        match specified_values.display() {
-            Display::Block => BoxType::BlockNode,
-            Display::Inline => match node.data {
-                NodeType::Element(_) => BoxType::InlineNode,
-                NodeType::Text(ref s) => BoxType::TextNode(Text {
-                    font: Font::new_empty(),
-                    range: 0..s.len(),
-                }),
-            },
-            Display::InlineBlock => match node.data {
-                NodeType::Element(_) => BoxType::InlineBlockNode,
-                NodeType::Text(_) => panic!(),
-            },
-            Display::None => BoxType::None, // TODO
-        },
+        box_type(&computed_style, node),
         node.clone(),
-        specified_values.clone(),
+        computed_style.clone(),
         match node.layout_type() {
             LayoutType::Generic => LayoutInfo::Generic,
             LayoutType::Text => LayoutInfo::Text,
             LayoutType::Image => LayoutInfo::Image(ImageData::new_empty()),
             LayoutType::Anker => LayoutInfo::Anker,
-            LayoutType::Button => LayoutInfo::Button(None, 0 /* *id */),
+            LayoutType::Button => LayoutInfo::Button(None, node_id),
         },
     );
+    root.node_id = Some(node_id);
 
     if root.box_type == BoxType::None {
+        *id += node.node_count() - 1;
+        // It is still a sibling that `+` and `~` match.
+        if let NodeType::Element(ref elem) = node.data {
+            ancestors.push_sibling(elem, state_of(node_id));
+        }
         return root;
     }
 
-    match specified_values.float() {
-        style::FloatType::None => {}
-        style::FloatType::Left | style::FloatType::Right => root.box_type = BoxType::Float,
-    }
+    let font_size = computed_style.font_size.to_f64_px();
+    let length_ctx = LengthContext {
+        font_size: font_size,
+        root_font_size: if is_root {
+            font_size
+        } else {
+            length_ctx.root_font_size
+        },
+        ..*length_ctx
+    };
 
-    let inherited_property = inherit_peoperties(
-        &specified_values,
-        vec![
-            "font-size",
-            "line-height",
-            "font-weight",
-            "font-style",
-            "text-align",
-            "color",
-        ],
-    );
+    let table = match node.data {
+        NodeType::Element(ref elem) if elem.tag_name == "table" => Some(elem),
+        _ => table,
+    };
+    if let NodeType::Element(ref elem) = node.data {
+        if computed_style.display == Display::ListItem {
+            let ordinal = list_counter.next(elem);
+            if let Some(marker) = marker_box(&computed_style, ordinal) {
+                match computed_style.list_style_position {
+                    ListStylePosition::Inside => root.get_inline_container().children.push(marker),
+                    ListStylePosition::Outside => {
+                        let mut block = LayoutBox::new(
+                            BoxType::AnonymousBlock,
+                            Node::text("".to_string()),
+                            computed_style.inherited(),
+                            LayoutInfo::Generic,
+                        );
+                        block.children.push(marker);
+                        root.marker = Some(Box::new(block));
+                    }
+                }
+            }
+        }
+        ancestors.push(elem, state_of(node_id));
+    }
 
     // Create the descendant boxes.
+    let mut sharing_cache = StyleSharingCache::new();
+    let mut list_counter = ListCounter::new(node);
     for child in node.children.iter() {
-        // *id += 1;
         let child = build_layout_tree(
             child,
             stylesheet,
             default_style,
-            &inherited_property,
-            &specified_values,
-            &appeared_elements,
-            // id,
+            user_style,
+            &computed_style,
+            ancestors,
+            &mut sharing_cache,
+            &mut list_counter,
+            table,
+            &length_ctx,
+            id,
         );
+        root.push_child(child);
+    }
 
-        match (child.property.display(), child.property.float()) {
-            (Display::Block, style::FloatType::None) => {
-                root.children.push(child);
-            }
-            (Display::Inline, style::FloatType::None)
-            | (Display::InlineBlock, style::FloatType::None) => {
-                root.get_inline_container().children.push(child);
-            }
-            (_, style::FloatType::Left) | (_, style::FloatType::Right) => {
-                root.children.push(child);
-            }
-            (Display::None, _) => {} // Don't lay out nodes with `display: none;`
-        }
+    if let NodeType::Element(_) = node.data {
+        ancestors.pop();
     }
 
     root
 }
 
-fn inherit_peoperties(specified_values: &Style, property_list: Vec<&str>) -> Style {
-    let mut inherited_property = HashMap::new();
-    let specified_values = &specified_values.property;
-    for property in property_list {
-        if let Some(value) = specified_values.get(property) {
-            inherited_property.insert(property.to_string(), value.clone());
+/// The type of the box generated for `node` in `style`.
+fn box_type(style: &ComputedStyle, node: &Node) -> BoxType {
+    if style.display != Display::None && style.float != style::FloatType::None {
+        return BoxType::Float;
+    }
+    match style.display {
+        Display::Block | Display::ListItem | Display::TableCaption => BoxType::BlockNode,
+        Display::Inline => match node.data {
+            NodeType::Element(_) => BoxType::InlineNode,
+            NodeType::Text(ref s) => BoxType::TextNode(Text {
+                font: Font::new_empty(),
+                range: 0..s.len(),
+            }),
+        },
+        Display::InlineBlock => match node.data {
+            NodeType::Element(_) => BoxType::InlineBlockNode,
+            NodeType::Text(_) => panic!(),
+        },
+        Display::Table | Display::InlineTable => BoxType::Table,
+        Display::TableRowGroup | Display::TableHeaderGroup | Display::TableFooterGroup => {
+            BoxType::TableRowGroup
         }
+        Display::TableRow => BoxType::TableRow,
+        Display::TableCell => BoxType::TableCell,
+        Display::TableColumn | Display::TableColumnGroup => BoxType::TableColumn,
+        Display::Flex | Display::InlineFlex => BoxType::Flex,
+        Display::Grid | Display::InlineGrid => BoxType::Grid,
+        Display::None => BoxType::None, // TODO
+    }
+}
+
+/// The marker of a list item in `style` numbered `ordinal`: an inline box with the image or the
+/// text of the marker, if there is one.
+fn marker_box(style: &ComputedStyle, ordinal: i32) -> Option<LayoutBox> {
+    let marker_style = style.inherited();
+    let image = style
+        .list_style_image
+        .as_ref()
+        .and_then(|url| load_pixbuf(url).map(|pixbuf| (url, pixbuf)));
+    // Images are separated from the content with a space, as bullets are.
+    let text = match image {
+        Some(_) => " ".to_string(),
+        None => marker_text(style.list_style_type, ordinal)?,
+    };
+
+    let mut marker = LayoutBox::new(
+        BoxType::InlineNode,
+        Node::elem("::marker".to_string(), HashMap::new(), vec![]),
+        marker_style.clone(),
+        LayoutInfo::Generic,
+    );
+    if let Some((url, pixbuf)) = image {
+        let mut attrs = HashMap::new();
+        attrs.insert("src".to_string(), url.clone());
+        marker.children.push(LayoutBox::new(
+            BoxType::InlineNode,
+            Node::elem("img".to_string(), attrs, vec![]),
+            marker_style.inherited(),
+            LayoutInfo::Image(ImageData::new(
+                Some(pixbuf),
+                ImageMetaData::new(Au(0), Au(0)),
+            )),
+        ));
     }
-    Style::new_with(inherited_property)
+    marker.children.push(LayoutBox::new(
+        BoxType::TextNode(Text {
+            font: Font::new_empty(),
+            range: 0..text.len(),
+        }),
+        Node::text(text),
+        marker_style.inherited(),
+        LayoutInfo::Text,
+    ));
+    Some(marker)
 }
 
 fn specified_values(
     elem: &ElementData,
-    default_style: &Stylesheet,
-    stylesheet: &Stylesheet,
-    inherited_property: &Style,
-    appeared_elements: &Vec<SimpleSelector>,
+    state: ElementState,
+    default_style: &SelectorMap,
+    user_style: &SelectorMap,
+    stylesheet: &SelectorMap,
+    ancestors: &Ancestors,
+    table: Option<&ElementData>,
 ) -> Style {
-    let mut values = HashMap::with_capacity(16);
-
-    let mut rules = matching_rules(elem, &default_style, appeared_elements);
-    rules.append(&mut matching_rules(elem, stylesheet, appeared_elements));
-
-    // Insert inherited properties
-    inherited_property
-        .property
+    let user_agent = sorted_declarations(default_style.matching_rules(elem, state, ancestors));
+    let user = sorted_declarations(user_style.matching_rules(elem, state, ancestors));
+    // Presentational hints come first in the author origin, as if their specificity was zero,
+    // and the `style` attribute last.
+    let hints = presentational_hints(elem, table);
+    let attr_style = elem
+        .attrs
+        .get("style")
+        .map_or(vec![], |attr_style| parse_attr_style(attr_style.clone()));
+    let author: Vec<&Declaration> = hints
         .iter()
-        .for_each(|(name, value)| {
-            values.insert(name.clone(), value.clone());
-        });
-
-    // Go through the rules from lowest to highest specificity.
-    rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
-    rules.iter().for_each(|&(_, rule)| {
-        rule.declarations.iter().for_each(|declaration| {
-            values.insert(declaration.name.clone(), declaration.values.clone());
-        })
-    });
-
-    if let Some(attr_style) = elem.attrs.get("style") {
-        let decls = parse_attr_style(attr_style.clone());
-        for Declaration { name, values: vals } in decls {
-            values.insert(name, vals);
-        }
-    }
+        .chain(sorted_declarations(stylesheet.matching_rules(elem, state, ancestors)))
+        .chain(attr_style.iter())
+        .collect();
+
+    // Author declarations override the user's, which override the user agent's, whatever
+    // their specificity, so the origins are cascaded one after the other. `!important`
+    // declarations override all the normal ones, in the reverse order of the origins.
+    // ref. https://www.w3.org/TR/css-cascade-4/#cascade-origin
+    let mut user_agent_values = HashMap::with_capacity(16);
+    cascade(&mut user_agent_values, &user_agent, false, &HashMap::new());
+
+    let mut user_values = user_agent_values.clone();
+    cascade(&mut user_values, &user, false, &user_agent_values);
+
+    let mut values = user_values.clone();
+    cascade(&mut values, &author, false, &user_values);
+    cascade(&mut values, &author, true, &user_values);
+    cascade(&mut values, &user, true, &user_agent_values);
+    cascade(&mut values, &user_agent, true, &HashMap::new());
 
     Style::new_with(values)
 }
 
-type MatchedRule<'a> = (Specificity, &'a Rule);
-
-fn matching_rules<'a>(
-    elem: &ElementData,
-    stylesheet: &'a Stylesheet,
-    appeared_elements: &Vec<SimpleSelector>,
-) -> Vec<MatchedRule<'a>> {
-    // For now, we just do a linear scan of all the rules.  For large
-    // documents, it would be more efficient to store the rules in hash tables
-    // based on tag name, id, class, etc.
-    stylesheet
-        .rules
-        .iter()
-        .filter_map(|rule| match_rule(elem, rule, appeared_elements))
+/// The declarations of `rules` from lowest to highest specificity.
+fn sorted_declarations<'a>(mut rules: Vec<MatchedRule<'a>>) -> Vec<&'a Declaration> {
+    rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+    rules
+        .into_iter()
+        .flat_map(|(_, rule)| rule.declarations.iter())
         .collect()
 }
 
-fn match_rule<'a>(
-    elem: &ElementData,
-    rule: &'a Rule,
-    appeared_elements: &Vec<SimpleSelector>,
-) -> Option<MatchedRule<'a>> {
-    // Find the first (most specific) matching selector.
-    rule.selectors
-        .iter()
-        .find(|selector| matches(elem, *selector, appeared_elements))
-        .map(|selector| (selector.specificity(), rule))
-}
-
-fn matches(
-    elem: &ElementData,
-    selector: &Selector,
-    appeared_elements: &Vec<SimpleSelector>,
-) -> bool {
-    match *selector {
-        Selector::Simple(ref simple_selector) => matches_simple_selector(elem, simple_selector),
-        Selector::Descendant(ref a, ref b) => {
-            matches_descendant_combinator(elem, &*a, &**b, appeared_elements)
+/// Applies those of `declarations` that are `!important` or not, as `important` says, to
+/// `values`. `revert` rolls a property back to its value in `previous_origins`, or leaves it
+/// unspecified if none of them set it.
+fn cascade(
+    values: &mut HashMap<String, Vec<Value>>,
+    declarations: &[&Declaration],
+    important: bool,
+    previous_origins: &HashMap<String, Vec<Value>>,
+) {
+    let revert = vec![Value::Keyword("revert".to_string())];
+    for declaration in declarations {
+        if declaration.important != important {
+            continue;
         }
-        Selector::Child(ref a, ref b) => {
-            matches_child_combinator(elem, &*a, &**b, appeared_elements)
+        if declaration.values != revert {
+            values.insert(declaration.name.clone(), declaration.values.clone());
+            continue;
         }
+        match previous_origins.get(&declaration.name) {
+            Some(previous) => values.insert(declaration.name.clone(), previous.clone()),
+            None => values.remove(&declaration.name),
+        };
     }
 }
 
-fn matches_descendant_combinator(
-    elem: &ElementData,
-    simple: &SimpleSelector,
-    selector_b: &Selector,
-    appeared_elements: &Vec<SimpleSelector>,
-) -> bool {
-    appeared_elements.iter().any(|e| {
-        !((simple.tag_name.is_some() && e.tag_name != simple.tag_name)
-            || (simple.id.is_some() && e.id != simple.id)
-            || (!simple.class.iter().all(|class| e.class.contains(class))))
-    }) && matches(elem, selector_b, appeared_elements)
-}
-
-fn matches_child_combinator(
-    elem: &ElementData,
-    simple: &SimpleSelector,
-    selector_b: &Selector,
-    appeared_elements: &Vec<SimpleSelector>,
-) -> bool {
-    if let Some(ref last_elem) = appeared_elements.last() {
-        !((simple.tag_name.is_some() && last_elem.tag_name != simple.tag_name)
-            || (simple.id.is_some() && last_elem.id != simple.id)
-            || (!simple
-                .class
-                .iter()
-                .all(|class| last_elem.class.contains(class))))
-            && matches(elem, selector_b, appeared_elements)
-    } else {
-        false
-    }
-}
-
-fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
-    // Universal selector
-    if selector.tag_name.is_none() && selector.id.is_none() && selector.class.is_empty() {
-        return true;
-    }
-
-    // Check type selector
-    if selector.tag_name.iter().any(|name| elem.tag_name != *name) {
-        return false;
-    }
-
-    // Check ID selector
-    if selector.id.iter().any(|id| elem.id() != Some(id)) {
-        return false;
-    }
-
-    // Check class selectors
-    let elem_classes = elem.classes();
-    if selector
-        .class
-        .iter()
-        .any(|class| !elem_classes.contains(&**class))
-    {
-        return false;
-    }
-
-    // We didn't find any non-matching selector components.
-    true
-}
-
-use std::cell::RefCell;
-thread_local!(pub static LAYOUTBOX: RefCell<Option<LayoutBox>> = { RefCell::new(None) };);
+use std::cell::{Cell, RefCell};
+thread_local!(
+    pub static LAYOUTBOX: RefCell<Option<LayoutBox>> = { RefCell::new(None) };
+    // The viewport size LAYOUTBOX was built for, if it contains viewport-relative lengths.
+    static LAYOUTBOX_VIEWPORT: Cell<Option<(f64, f64)>> = { Cell::new(None) };
+);
 
 /// Transform a style tree into a layout tree.
 pub fn layout_tree(
     root: &Node,
-    stylesheet: &Stylesheet,
+    stylesheet: &SelectorMap,
     mut containing_block: Dimensions,
 ) -> LayoutBox {
-    let mut first_construction_of_layout_tree = false;
+    let viewport_size = (
+        containing_block.content.width.to_f64_px(),
+        containing_block.content.height.to_f64_px(),
+    );
+    // Lengths like `vw` have to be resolved again when the viewport is resized.
+    if LAYOUTBOX_VIEWPORT.with(|v| v.get().map_or(false, |size| size != viewport_size)) {
+        LAYOUTBOX.with(|layoutbox| *layoutbox.borrow_mut() = None);
+    }
+
     let mut root_box = LAYOUTBOX.with(|layoutbox| {
         layoutbox
             .borrow_mut()
             .get_or_insert_with(|| {
-                first_construction_of_layout_tree = true;
-                // let mut id = 0;
-                let default_style = default_style::default_style();
+                LAYOUTBOX_VIEWPORT.with(|v| v.set(None));
                 build_layout_tree(
                     root,
-                    &stylesheet,
-                    &default_style,
-                    &style::Style::new(),
-                    &style::Style::new(),
-                    &vec![],
-                    // &mut id,
+                    stylesheet,
+                    &default_style::default_style(),
+                    &default_style::user_style(),
+                    &ComputedStyle::default(),
+                    &mut Ancestors::new(),
+                    &mut StyleSharingCache::new(),
+                    &mut ListCounter::default(),
+                    None,
+                    &LengthContext {
+                        viewport_width: viewport_size.0,
+                        viewport_height: viewport_size.1,
+                        ..Default::default()
+                    },
+                    &mut 0,
                 )
             })
             .clone()
@@ -470,31 +573,220 @@ pub fn layout_tree(
 
     root_box.layout(
         &mut Floats::new(),
-        Au(0),
+        None,
         containing_block,
         saved_block,
         viewport,
     );
 
-    if first_construction_of_layout_tree {
-        LAYOUTBOX.with(|layoutbox| {
-            if let Some(ref mut layoutbox) = *layoutbox.borrow_mut() {
-                assign_style_properties(&root_box, layoutbox);
-                fn assign_style_properties(root_box: &LayoutBox, layoutbox: &mut LayoutBox) {
-                    if root_box.box_type != BoxType::AnonymousBlock {
-                        layoutbox.property = root_box.property.clone();
-                        for (child, layoutbox_child) in
-                            root_box.children.iter().zip(&mut layoutbox.children)
-                        {
-                            assign_style_properties(child, layoutbox_child);
-                        }
+    // The initial containing block is the viewport at the top of the page, and the containing
+    // block of the absolutely positioned boxes without a positioned ancestor.
+    let initial_containing_block = Rect {
+        x: -root_box.dimensions.content.x,
+        y: -root_box.dimensions.content.y,
+        ..viewport.content
+    };
+    if root_box.property.position == Position::Static {
+        root_box.layout_positioned_descendants(initial_containing_block, false, viewport);
+    }
+    root_box.layout_positioned_descendants(initial_containing_block, true, viewport);
+
+    root_box
+}
+
+/// Builds the boxes of the elements numbered `node_ids` in `root`, whose state changed, and of
+/// their descendants again in the tree kept for the next layout, as well as those of their
+/// following siblings if a selector like `a:hover + p` can match these. Returns false if the
+/// whole tree has to be built again instead, e.g. when one of them isn't laid out as before in
+/// its parent.
+pub fn restyle(
+    root: &Node,
+    stylesheet: &SelectorMap,
+    node_ids: &[usize],
+    viewport_size: (f64, f64),
+) -> bool {
+    let default_style = default_style::default_style();
+    let user_style = default_style::user_style();
+
+    let mut node_ids = node_ids.to_vec();
+    if [&*default_style, &*user_style, stylesheet]
+        .iter()
+        .any(|map| map.state_affects_siblings())
+    {
+        for node_id in node_ids.clone() {
+            node_ids.extend(following_siblings(root, node_id));
+        }
+        node_ids.sort();
+        node_ids.dedup();
+    }
+
+    LAYOUTBOX.with(|layoutbox| {
+        let mut layoutbox = layoutbox.borrow_mut();
+        let root_box = match *layoutbox {
+            Some(ref mut root_box) => root_box,
+            None => return false,
+        };
+        for &node_id in &node_ids {
+            let path = match root.path_to(node_id) {
+                Some(path) => path,
+                None => return false,
+            };
+            let (&(_, node), ancestor_path) = path.split_last().unwrap();
+            // Descendants are built again with their ancestors.
+            if ancestor_path.iter().any(|&(id, _)| node_ids.contains(&id)) {
+                continue;
+            }
+
+            let mut ancestors = Ancestors::new();
+            let mut table = None;
+            for pair in path.windows(2) {
+                let ((parent_id, parent), (child_id, _)) = (pair[0], pair[1]);
+                if let NodeType::Element(ref elem) = parent.data {
+                    ancestors.push(elem, state_of(parent_id));
+                    if elem.tag_name == "table" {
+                        table = Some(elem);
                     }
                 }
+                push_preceding_siblings(&mut ancestors, parent, parent_id, child_id);
+            }
+            let mut length_ctx = LengthContext {
+                viewport_width: viewport_size.0,
+                viewport_height: viewport_size.1,
+                ..Default::default()
+            };
+            let (parent_style, mut list_counter) = match ancestor_path.last() {
+                Some(&(parent_id, parent)) => match find_box(root_box, parent_id) {
+                    Some(parent_box) => (
+                        parent_box.property.clone(),
+                        list_counter_at(parent, parent_id, parent_box, node_id),
+                    ),
+                    None => return false,
+                },
+                None => (ComputedStyle::default(), ListCounter::default()),
+            };
+            if !ancestor_path.is_empty() {
+                length_ctx.font_size = parent_style.font_size.to_f64_px();
+                length_ctx.root_font_size = root_box.property.font_size.to_f64_px();
+            }
+
+            let new_box = build_layout_tree(
+                node,
+                stylesheet,
+                &default_style,
+                &user_style,
+                &parent_style,
+                &mut ancestors,
+                &mut StyleSharingCache::new(),
+                &mut list_counter,
+                table,
+                &length_ctx,
+                &mut node_id.clone(),
+            );
+            let old_box = match find_box(root_box, node_id) {
+                Some(old_box) => old_box,
+                None => return false,
+            };
+            if (old_box.property.display, old_box.property.float)
+                != (new_box.property.display, new_box.property.float)
+            {
+                return false;
+            }
+            *old_box = new_box;
+        }
+        true
+    })
+}
+
+/// The elements after the node numbered `node_id` in `root` among the children of its parent.
+fn following_siblings(root: &Node, node_id: usize) -> Vec<usize> {
+    let path = root.path_to(node_id).unwrap_or_default();
+    let (parent_id, parent) = match path.len().checked_sub(2) {
+        Some(parent_index) => path[parent_index],
+        None => return vec![],
+    };
+    let mut siblings = vec![];
+    let mut child_id = parent_id + 1;
+    for child in &parent.children {
+        if let NodeType::Element(_) = child.data {
+            if child_id > node_id {
+                siblings.push(child_id);
             }
-        });
+        }
+        child_id += child.node_count();
     }
+    siblings
+}
 
-    root_box
+/// Adds the elements among the children of `parent`, numbered `parent_id`, that come before
+/// the child numbered `node_id` to `ancestors`, as the preceding siblings of that child.
+fn push_preceding_siblings(
+    ancestors: &mut Ancestors,
+    parent: &Node,
+    parent_id: usize,
+    node_id: usize,
+) {
+    let mut child_id = parent_id + 1;
+    for child in &parent.children {
+        if child_id == node_id {
+            break;
+        }
+        if let NodeType::Element(ref elem) = child.data {
+            ancestors.push_sibling(elem, state_of(child_id));
+        }
+        child_id += child.node_count();
+    }
+}
+
+/// The counter of the list items among the children of `parent`, numbered `parent_id`, as it is
+/// when the child numbered `node_id` is reached. List items are block-level, so their boxes are
+/// children of `parent_box`.
+fn list_counter_at(
+    parent: &Node,
+    parent_id: usize,
+    parent_box: &LayoutBox,
+    node_id: usize,
+) -> ListCounter {
+    let mut list_counter = ListCounter::new(parent);
+    let mut child_id = parent_id + 1;
+    for child in &parent.children {
+        if child_id == node_id {
+            break;
+        }
+        if let NodeType::Element(ref elem) = child.data {
+            let is_list_item = parent_box.children.iter().any(|child_box| {
+                child_box.node_id == Some(child_id)
+                    && child_box.property.display == Display::ListItem
+            });
+            if is_list_item {
+                list_counter.next(elem);
+            }
+        }
+        child_id += child.node_count();
+    }
+    list_counter
+}
+
+/// The box built for the node numbered `node_id`, if it has one.
+fn find_box(layout_box: &mut LayoutBox, node_id: usize) -> Option<&mut LayoutBox> {
+    if layout_box.node_id == Some(node_id) {
+        return Some(layout_box);
+    }
+    layout_box
+        .children
+        .iter_mut()
+        .filter_map(|child| find_box(child, node_id))
+        .next()
+}
+
+/// A block styled by the declarations in `style`, which may make it another type of box with
+/// `display` or `float`, with `children`. Tests build the boxes they lay out with it.
+#[cfg(test)]
+pub fn block(style: &str, children: Vec<LayoutBox>) -> LayoutBox {
+    let style = style::compute(&format!("display: block; {}", style), &ComputedStyle::default());
+    let node = Node::elem("div".to_string(), HashMap::new(), vec![]);
+    let mut layout_box = LayoutBox::new(box_type(&style, &node), node, style, LayoutInfo::Generic);
+    layout_box.children = children;
+    layout_box
 }
 
 impl LayoutBox {
@@ -504,7 +796,7 @@ impl LayoutBox {
     pub fn layout(
         &mut self,
         floats: &mut Floats,
-        last_margin_bottom: Au,
+        margins: Option<CollapsibleMargins>,
         containing_block: Dimensions,
         saved_block: Dimensions,
         viewport: Dimensions,
@@ -512,21 +804,21 @@ impl LayoutBox {
         match self.box_type {
             BoxType::BlockNode => self.layout_block(
                 floats,
-                last_margin_bottom,
+                margins,
                 containing_block,
                 saved_block,
                 viewport,
             ),
             BoxType::InlineBlockNode => self.layout_inline_block(
                 floats,
-                last_margin_bottom,
+                margins,
                 containing_block,
                 saved_block,
                 viewport,
             ),
             BoxType::Float => self.layout_float(
                 floats,
-                last_margin_bottom,
+                margins,
                 containing_block,
                 saved_block,
                 viewport,
@@ -544,9 +836,105 @@ impl LayoutBox {
                 self.dimensions.content.height = linemaker.cur_height;
                 self.children = linemaker.new_boxes;
             }
+            BoxType::Table => self.layout_table(
+                floats,
+                margins,
+                containing_block,
+                saved_block,
+                viewport,
+            ),
+            BoxType::Flex => self.layout_flex(
+                floats,
+                margins,
+                containing_block,
+                saved_block,
+                viewport,
+            ),
+            BoxType::Grid => self.layout_grid(
+                floats,
+                margins,
+                containing_block,
+                saved_block,
+                viewport,
+            ),
             // InlineNode and TextNode is contained in AnonymousBlock.
             BoxType::InlineNode | BoxType::TextNode(_) => unreachable!(),
-            BoxType::None => {}
+            // The parts of a table are laid out with it.
+            BoxType::TableRowGroup | BoxType::TableRow | BoxType::TableCell => unreachable!(),
+            BoxType::TableColumn | BoxType::None => {}
+        }
+
+        // A positioned box is the containing block of the absolutely positioned boxes in it.
+        if self.property.position != Position::Static {
+            let d = self.dimensions;
+            let padding_box = Rect {
+                x: Au(0),
+                y: Au(0),
+                ..d.content
+            }.expanded_by(d.padding);
+            self.layout_positioned_descendants(padding_box, false, viewport);
+        }
+
+        if let Some(ref mut marker) = self.marker {
+            marker.layout_outside_marker();
+        }
+    }
+
+    /// Lays out the marker of an `outside` list item, which hangs to the left of its first line.
+    fn layout_outside_marker(&mut self) {
+        let mut containing_block: Dimensions = Default::default();
+        // The line of the marker shrinks to fit it.
+        containing_block.content.width = Au(-1);
+        self.layout(
+            &mut Floats::new(),
+            None,
+            containing_block,
+            containing_block,
+            containing_block,
+        );
+        self.dimensions.content.x = -self.dimensions.content.width;
+    }
+
+    /// Adds the box of a child, in the anonymous boxes it needs.
+    pub fn push_child(&mut self, child: LayoutBox) {
+        match self.box_type {
+            BoxType::Table | BoxType::TableRowGroup | BoxType::TableRow | BoxType::TableColumn => {
+                return self.push_table_child(child);
+            }
+            BoxType::Flex | BoxType::Grid if !child.is_absolutely_positioned() => {
+                return self.push_flex_item(child);
+            }
+            _ => {}
+        }
+        // Absolutely positioned boxes stay in the line they're in, which their static position
+        // is at.
+        if child.is_absolutely_positioned() {
+            match self.children.last_mut() {
+                Some(last) if last.box_type == BoxType::AnonymousBlock => {
+                    last.children.push(child)
+                }
+                _ => self.children.push(child),
+            }
+            return;
+        }
+        if child.is_table_internal() {
+            return self.get_table_container().push_table_child(child);
+        }
+
+        match (child.property.display, child.property.float) {
+            (_, style::FloatType::Left) | (_, style::FloatType::Right) => {
+                self.children.push(child);
+            }
+            (Display::None, _) => {} // Don't lay out nodes with `display: none;`
+            (Display::Inline, _)
+            | (Display::InlineBlock, _)
+            | (Display::InlineTable, _)
+            | (Display::InlineFlex, _)
+            | (Display::InlineGrid, _) => {
+                self.get_inline_container().children.push(child);
+            }
+            // Block-level boxes
+            _ => self.children.push(child),
         }
     }
 
@@ -554,7 +942,7 @@ impl LayoutBox {
     fn get_inline_container(&mut self) -> &mut LayoutBox {
         match self.box_type {
             BoxType::InlineNode | BoxType::AnonymousBlock => self,
-            BoxType::Float | BoxType::BlockNode | BoxType::InlineBlockNode => {
+            BoxType::Float | BoxType::BlockNode | BoxType::InlineBlockNode | BoxType::TableCell => {
                 match self.children.last() {
                     Some(&LayoutBox {
                         box_type: BoxType::AnonymousBlock,
@@ -563,45 +951,57 @@ impl LayoutBox {
                     _ => self.children.push(LayoutBox::new(
                         BoxType::AnonymousBlock,
                         Node::text("".to_string()),
-                        Style::new(),
+                        self.property.inherited(),
                         LayoutInfo::Generic,
                     )),
                 }
                 self.children.last_mut().unwrap()
             }
             BoxType::TextNode(_) => panic!(),
-            BoxType::None => unreachable!(),
+            // Table parts get their inline children in anonymous cells, and flex and grid
+            // containers in anonymous items.
+            BoxType::Table
+            | BoxType::Flex
+            | BoxType::Grid
+            | BoxType::TableRowGroup
+            | BoxType::TableRow
+            | BoxType::TableColumn
+            | BoxType::None => unreachable!(),
         }
     }
 
-    pub fn assign_padding(&mut self) {
-        let (padding_top, padding_right, padding_bottom, padding_left) = self.property.padding();
+    /// Percentages refer to the width of the containing block.
+    pub fn assign_padding(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
+        let padding = self.property.padding;
 
         let d = &mut self.dimensions;
-        d.padding.left = Au::from_f64_px(padding_left.to_px().unwrap());
-        d.padding.top = Au::from_f64_px(padding_top.to_px().unwrap());
-        d.padding.bottom = Au::from_f64_px(padding_bottom.to_px().unwrap());
-        d.padding.right = Au::from_f64_px(padding_right.to_px().unwrap());
+        d.padding.left = padding.left.to_au(cb_width);
+        d.padding.top = padding.top.to_au(cb_width);
+        d.padding.bottom = padding.bottom.to_au(cb_width);
+        d.padding.right = padding.right.to_au(cb_width);
     }
 
-    pub fn assign_margin(&mut self) {
-        let (margin_top, margin_right, margin_bottom, margin_left) = self.property.margin();
+    /// Percentages refer to the width of the containing block, and `auto` margins are 0.
+    pub fn assign_margin(&mut self, containing_block: Dimensions) {
+        let cb_width = containing_block.content.width;
+        let margin = self.property.margin;
 
         let d = &mut self.dimensions;
-        d.margin.left = Au::from_f64_px(margin_left.to_px().unwrap());
-        d.margin.top = Au::from_f64_px(margin_top.to_px().unwrap());
-        d.margin.bottom = Au::from_f64_px(margin_bottom.to_px().unwrap());
-        d.margin.right = Au::from_f64_px(margin_right.to_px().unwrap());
+        d.margin.left = margin.left.to_au(cb_width).unwrap_or(Au(0));
+        d.margin.top = margin.top.to_au(cb_width).unwrap_or(Au(0));
+        d.margin.bottom = margin.bottom.to_au(cb_width).unwrap_or(Au(0));
+        d.margin.right = margin.right.to_au(cb_width).unwrap_or(Au(0));
     }
 
     pub fn assign_border_width(&mut self) {
-        let (border_top, border_right, border_bottom, border_left) = self.property.border_width();
+        let border = self.property.border_width;
 
         let d = &mut self.dimensions;
-        d.border.left = Au::from_f64_px(border_left.to_px().unwrap());
-        d.border.top = Au::from_f64_px(border_top.to_px().unwrap());
-        d.border.bottom = Au::from_f64_px(border_bottom.to_px().unwrap());
-        d.border.right = Au::from_f64_px(border_right.to_px().unwrap());
+        d.border.left = border.left;
+        d.border.top = border.top;
+        d.border.bottom = border.bottom;
+        d.border.right = border.right;
     }
 }
 
@@ -610,7 +1010,7 @@ impl LayoutBox {
         use inline;
         match &mut self.info {
             &mut LayoutInfo::Image(ref mut imgdata) => {
-                inline::get_image(&self.node, imgdata, containing_block);
+                inline::get_image(&self.node, &self.property, imgdata, containing_block);
             }
             _ => {}
         }
@@ -652,15 +1052,27 @@ impl LayoutBox {
 
 impl FontWeight {
     pub fn to_cairo_font_weight(&self) -> cairo::FontWeight {
-        match self {
-            &FontWeight::Normal => cairo::FontWeight::Normal,
-            &FontWeight::Bold => cairo::FontWeight::Bold,
+        if self.0 >= 600.0 {
+            cairo::FontWeight::Bold
+        } else {
+            cairo::FontWeight::Normal
         }
     }
+    /// The closest of the weights Pango names.
     pub fn to_pango_font_weight(&self) -> pango::Weight {
-        match self {
-            &FontWeight::Normal => pango::Weight::Normal,
-            &FontWeight::Bold => pango::Weight::Bold,
+        match self.0 {
+            w if w < 150.0 => pango::Weight::Thin,
+            w if w < 250.0 => pango::Weight::Ultralight,
+            w if w < 325.0 => pango::Weight::Light,
+            w if w < 365.0 => pango::Weight::Semilight,
+            w if w < 390.0 => pango::Weight::Book,
+            w if w < 450.0 => pango::Weight::Normal,
+            w if w < 550.0 => pango::Weight::Medium,
+            w if w < 650.0 => pango::Weight::Semibold,
+            w if w < 750.0 => pango::Weight::Bold,
+            w if w < 850.0 => pango::Weight::Ultrabold,
+            w if w < 950.0 => pango::Weight::Heavy,
+            _ => pango::Weight::Ultraheavy,
         }
     }
 }
@@ -670,12 +1082,39 @@ impl FontSlant {
         match self {
             &FontSlant::Normal => cairo::FontSlant::Normal,
             &FontSlant::Italic => cairo::FontSlant::Italic,
+            &FontSlant::Oblique => cairo::FontSlant::Oblique,
         }
     }
     pub fn to_pango_font_slant(&self) -> pango::Style {
         match self {
             &FontSlant::Normal => pango::Style::Normal,
             &FontSlant::Italic => pango::Style::Italic,
+            &FontSlant::Oblique => pango::Style::Oblique,
+        }
+    }
+}
+
+impl FontVariant {
+    pub fn to_pango_font_variant(&self) -> pango::Variant {
+        match self {
+            &FontVariant::Normal => pango::Variant::Normal,
+            &FontVariant::SmallCaps => pango::Variant::SmallCaps,
+        }
+    }
+}
+
+impl FontStretch {
+    pub fn to_pango_font_stretch(&self) -> pango::Stretch {
+        match self {
+            &FontStretch::UltraCondensed => pango::Stretch::UltraCondensed,
+            &FontStretch::ExtraCondensed => pango::Stretch::ExtraCondensed,
+            &FontStretch::Condensed => pango::Stretch::Condensed,
+            &FontStretch::SemiCondensed => pango::Stretch::SemiCondensed,
+            &FontStretch::Normal => pango::Stretch::Normal,
+            &FontStretch::SemiExpanded => pango::Stretch::SemiExpanded,
+            &FontStretch::Expanded => pango::Stretch::Expanded,
+            &FontStretch::ExtraExpanded => pango::Stretch::ExtraExpanded,
+            &FontStretch::UltraExpanded => pango::Stretch::UltraExpanded,
         }
     }
 }
@@ -697,6 +1136,25 @@ impl Rect {
             height: self.height,
         }
     }
+    // The smallest rectangle containing both.
+    pub fn union(self, other: Rect) -> Rect {
+        let x = min(self.x, other.x);
+        let y = min(self.y, other.y);
+        Rect {
+            x: x,
+            y: y,
+            width: max(self.x + self.width, other.x + other.width) - x,
+            height: max(self.y + self.height, other.y + other.height) - y,
+        }
+    }
+    pub fn intersects(self, other: Rect) -> bool {
+        self.x < other.x + other.width && other.x < self.x + self.width
+            && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+    pub fn contains_point(self, x: f64, y: f64) -> bool {
+        self.x.to_f64_px() <= x && x <= (self.x + self.width).to_f64_px()
+            && self.y.to_f64_px() <= y && y <= (self.y + self.height).to_f64_px()
+    }
 }
 
 impl Dimensions {
@@ -756,3 +1214,100 @@ impl fmt::Display for LayoutBox {
         Ok(())
     }
 }
+
+#[test]
+fn test_restyle_siblings() {
+    use css;
+    use element_state;
+    use html;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    let document = html::parse(
+        "<html><body><a class=\"a\">link</a><p class=\"tip\">tip</p><p>text</p></body></html>"
+            .to_string(),
+        PathBuf::from("test.html"),
+    );
+    // html 0, body 1, a 2, "link" 3, p 4, "tip" 5, p 6, "text" 7
+    let stylesheet = SelectorMap::new(Rc::new(css::parse(
+        ".a:hover + .tip { color: red } .a:hover ~ p { background-color: red }".to_string(),
+    )));
+    element_state::reset();
+    let root_box = build_layout_tree(
+        &document,
+        &stylesheet,
+        &default_style::default_style(),
+        &default_style::user_style(),
+        &ComputedStyle::default(),
+        &mut Ancestors::new(),
+        &mut StyleSharingCache::new(),
+        &mut ListCounter::default(),
+        None,
+        &LengthContext::default(),
+        &mut 0,
+    );
+    LAYOUTBOX.with(|layoutbox| *layoutbox.borrow_mut() = Some(root_box));
+
+    let style_of = |node_id: usize| {
+        LAYOUTBOX.with(|layoutbox| {
+            let mut layoutbox = layoutbox.borrow_mut();
+            find_box(layoutbox.as_mut().unwrap(), node_id)
+                .unwrap()
+                .property
+                .clone()
+        })
+    };
+    assert_eq!(style_of(4).color, css::BLACK);
+
+    // Only the link and the last paragraph change state, not their parent.
+    element_state::set_hovered(&document, Some(7));
+    let changed = element_state::set_hovered(&document, Some(3));
+    assert_eq!(changed, vec![2, 6]);
+    assert!(restyle(&document, &stylesheet, &changed, (800.0, 600.0)));
+    assert_eq!(style_of(4).color, css::RED);
+    assert_eq!(style_of(4).background_color, css::RED);
+    assert_eq!(style_of(6).color, css::BLACK);
+    assert_eq!(style_of(6).background_color, css::RED);
+
+    let changed = element_state::set_hovered(&document, Some(7));
+    assert!(restyle(&document, &stylesheet, &changed, (800.0, 600.0)));
+    assert_eq!(style_of(4).color, css::BLACK);
+    assert_eq!(style_of(6).background_color, css::Color::default());
+}
+
+#[test]
+fn test_important_declarations() {
+    use css::{self, Unit};
+    use std::rc::Rc;
+
+    let map = |source: &str| SelectorMap::new(Rc::new(css::parse(source.to_string())));
+    let stylesheet = map(
+        "p { color: red !important; margin-top: 1px !important; margin-left: 1px !important } \
+         #a { color: blue; margin-top: 2px; margin-left: 2px }",
+    );
+    let user_style = map("p { margin-top: 3px !important; margin-left: 3px }");
+    let mut attrs = HashMap::new();
+    attrs.insert("id".to_string(), "a".to_string());
+    attrs.insert("style".to_string(), "color: green".to_string());
+    let node = Node::elem("p".to_string(), attrs, vec![]);
+    let elem = match node.data {
+        NodeType::Element(ref elem) => elem,
+        _ => unreachable!(),
+    };
+
+    let values = specified_values(
+        elem,
+        ElementState::default(),
+        &default_style::default_style(),
+        &user_style,
+        &stylesheet,
+        &Ancestors::new(),
+        None,
+    );
+    let value = |name: &str| values.property.get(name).cloned();
+    // `!important` beats specificity and the `style` attribute, and the user's `!important`
+    // beats the author's.
+    assert_eq!(value("color"), Some(vec![Value::Keyword("red".to_string())]));
+    assert_eq!(value("margin-top"), Some(vec![Value::Length(3.0, Unit::Px)]));
+    assert_eq!(value("margin-left"), Some(vec![Value::Length(1.0, Unit::Px)]));
+}
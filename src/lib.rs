@@ -1,12 +1,30 @@
 pub mod css;
+pub mod bloom;
+pub mod selector_map;
+pub mod style_sharing;
+pub mod element_state;
+pub mod history;
+pub mod property;
 pub mod style;
 pub mod default_style;
+pub mod presentational_hints;
+pub mod list;
 pub mod html;
 pub mod dom;
 pub mod font;
+pub mod font_face;
+pub mod line_break;
 pub mod inline;
 pub mod block;
+pub mod margin;
 pub mod float;
+pub mod table;
+pub mod flex;
+pub mod grid;
+pub mod position;
+pub mod overflow;
+pub mod sizing;
+pub mod intrinsic;
 pub mod layout;
 pub mod painter;
 pub mod window;
@@ -20,3 +38,4 @@ extern crate glib;
 extern crate gtk;
 extern crate pango;
 extern crate pangocairo;
+extern crate reqwest;
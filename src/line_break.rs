@@ -0,0 +1,428 @@
+//! Line breaking: where text can be broken into lines, by the Unicode line breaking algorithm as
+//! tailored by `word-break` and `line-break`, and where `overflow-wrap` lets words break.
+//! ref. https://www.unicode.org/reports/tr14/
+//! ref. https://www.w3.org/TR/css-text-3/#line-breaking
+
+use style::{ComputedStyle, LineBreak, OverflowWrap, WordBreak};
+
+/// The line breaking classes of characters. Ambiguous, complex-context and unknown characters
+/// are `Alphabetic`, as the algorithm resolves them without more data, and Hebrew letters too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BreakClass {
+    /// BK
+    Mandatory,
+    /// CR
+    CarriageReturn,
+    /// LF
+    LineFeed,
+    /// NL
+    NextLine,
+    /// SP
+    Space,
+    /// ZW
+    ZeroWidthSpace,
+    /// WJ
+    WordJoiner,
+    /// GL
+    Glue,
+    /// CM and ZWJ
+    CombiningMark,
+    /// BA
+    BreakAfter,
+    /// BB
+    BreakBefore,
+    /// B2
+    BreakBoth,
+    /// HY
+    Hyphen,
+    /// CB
+    Contingent,
+    /// OP
+    OpenPunctuation,
+    /// CL
+    ClosePunctuation,
+    /// CP
+    CloseParenthesis,
+    /// QU
+    Quotation,
+    /// EX
+    Exclamation,
+    /// IS
+    InfixSeparator,
+    /// SY
+    Symbol,
+    /// NS
+    Nonstarter,
+    /// CJ, small kana and the prolonged sound mark, which `line-break` decides on.
+    ConditionalJapaneseStarter,
+    /// IN
+    Inseparable,
+    /// PR
+    Prefix,
+    /// PO
+    Postfix,
+    /// NU
+    Numeric,
+    /// AL
+    Alphabetic,
+    /// ID
+    Ideographic,
+    /// JL
+    HangulLJamo,
+    /// JV
+    HangulVJamo,
+    /// JT
+    HangulTJamo,
+    /// H2
+    HangulLvSyllable,
+    /// H3
+    HangulLvtSyllable,
+    /// RI
+    RegionalIndicator,
+}
+
+use self::BreakClass::*;
+
+fn break_class(c: char) -> BreakClass {
+    match c {
+        '\u{0B}' | '\u{0C}' | '\u{2028}' | '\u{2029}' => Mandatory,
+        '\r' => CarriageReturn,
+        '\n' => LineFeed,
+        '\u{85}' => NextLine,
+        ' ' => Space,
+        '\u{200B}' => ZeroWidthSpace,
+        '\u{2060}' | '\u{FEFF}' => WordJoiner,
+        '\u{A0}' | '\u{34F}' | '\u{2007}' | '\u{2011}' | '\u{202F}' => Glue,
+        '\u{200D}' | '\u{300}'...'\u{36F}' | '\u{483}'...'\u{489}' | '\u{591}'...'\u{5BD}'
+        | '\u{1AB0}'...'\u{1AFF}' | '\u{1DC0}'...'\u{1DFF}' | '\u{20D0}'...'\u{20FF}'
+        | '\u{3099}' | '\u{309A}' | '\u{FE00}'...'\u{FE0F}' | '\u{FE20}'...'\u{FE2F}' => {
+            CombiningMark
+        }
+        '\t' | '|' | '\u{AD}' | '\u{2000}'...'\u{2006}' | '\u{2008}'...'\u{200A}'
+        | '\u{2010}' | '\u{2012}' | '\u{2013}' | '\u{3000}' => BreakAfter,
+        '\u{B4}' => BreakBefore,
+        '\u{2014}' => BreakBoth,
+        '-' => Hyphen,
+        '\u{FFFC}' => Contingent,
+        '(' | '[' | '{' | '\u{A1}' | '\u{BF}' | '\u{3008}' | '\u{300A}' | '\u{300C}'
+        | '\u{300E}' | '\u{3010}' | '\u{3014}' | '\u{3016}' | '\u{3018}' | '\u{301A}'
+        | '\u{301D}' | '\u{FF08}' | '\u{FF3B}' | '\u{FF5B}' | '\u{FF5F}' | '\u{FF62}' => {
+            OpenPunctuation
+        }
+        '}' | '\u{3001}' | '\u{3002}' | '\u{3009}' | '\u{300B}' | '\u{300D}' | '\u{300F}'
+        | '\u{3011}' | '\u{3015}' | '\u{3017}' | '\u{3019}' | '\u{301B}' | '\u{301E}'
+        | '\u{301F}' | '\u{FF09}' | '\u{FF0C}' | '\u{FF0E}' | '\u{FF3D}' | '\u{FF5D}'
+        | '\u{FF60}' | '\u{FF61}' | '\u{FF63}' | '\u{FF64}' => ClosePunctuation,
+        ')' | ']' => CloseParenthesis,
+        '"' | '\'' | '\u{AB}' | '\u{BB}' | '\u{2018}' | '\u{2019}' | '\u{201C}' | '\u{201D}'
+        | '\u{2039}' | '\u{203A}' => Quotation,
+        '!' | '?' | '\u{FF01}' | '\u{FF1F}' => Exclamation,
+        ',' | '.' | ':' | ';' | '\u{2044}' => InfixSeparator,
+        '/' => Symbol,
+        '\u{203C}' | '\u{203D}' | '\u{2047}'...'\u{2049}' | '\u{3005}' | '\u{301C}'
+        | '\u{303B}' | '\u{303C}' | '\u{309B}'...'\u{309E}' | '\u{30A0}' | '\u{30FB}'
+        | '\u{30FD}' | '\u{30FE}' | '\u{FF1A}' | '\u{FF1B}' | '\u{FF65}' | '\u{FF9E}'
+        | '\u{FF9F}' => Nonstarter,
+        '\u{3041}' | '\u{3043}' | '\u{3045}' | '\u{3047}' | '\u{3049}' | '\u{3063}'
+        | '\u{3083}' | '\u{3085}' | '\u{3087}' | '\u{308E}' | '\u{3095}' | '\u{3096}'
+        | '\u{30A1}' | '\u{30A3}' | '\u{30A5}' | '\u{30A7}' | '\u{30A9}' | '\u{30C3}'
+        | '\u{30E3}' | '\u{30E5}' | '\u{30E7}' | '\u{30EE}' | '\u{30F5}' | '\u{30F6}'
+        | '\u{30FC}' | '\u{31F0}'...'\u{31FF}' | '\u{FF67}'...'\u{FF70}' => {
+            ConditionalJapaneseStarter
+        }
+        '\u{2024}'...'\u{2026}' => Inseparable,
+        '$' | '+' | '\\' | '\u{A3}' | '\u{A5}' | '\u{B1}' | '\u{20A0}'...'\u{20CF}'
+        | '\u{FF04}' | '\u{FFE1}' | '\u{FFE5}' | '\u{FFE6}' => Prefix,
+        '%' | '\u{A2}' | '\u{B0}' | '\u{2030}'...'\u{2037}' | '\u{FF05}' | '\u{FFE0}' => {
+            Postfix
+        }
+        '0'...'9' => Numeric,
+        '\u{1100}'...'\u{115F}' | '\u{A960}'...'\u{A97C}' => HangulLJamo,
+        '\u{1160}'...'\u{11A7}' | '\u{D7B0}'...'\u{D7C6}' => HangulVJamo,
+        '\u{11A8}'...'\u{11FF}' | '\u{D7CB}'...'\u{D7FB}' => HangulTJamo,
+        '\u{AC00}'...'\u{D7A3}' => if (c as u32 - 0xAC00) % 28 == 0 {
+            HangulLvSyllable
+        } else {
+            HangulLvtSyllable
+        },
+        '\u{1F1E6}'...'\u{1F1FF}' => RegionalIndicator,
+        '\u{2E80}'...'\u{2FFF}' | '\u{3003}' | '\u{3004}' | '\u{3006}' | '\u{3007}'
+        | '\u{3012}' | '\u{3013}' | '\u{3020}'...'\u{303A}' | '\u{303D}'...'\u{30FF}'
+        | '\u{3100}'...'\u{33FF}' | '\u{3400}'...'\u{4DBF}' | '\u{4E00}'...'\u{9FFF}'
+        | '\u{A000}'...'\u{A4CF}' | '\u{F900}'...'\u{FAFF}' | '\u{FE30}'...'\u{FE4F}'
+        | '\u{FF00}'...'\u{FF60}' | '\u{FF66}'...'\u{FF9D}' | '\u{1F000}'...'\u{1FAFF}'
+        | '\u{20000}'...'\u{3FFFD}' => Ideographic,
+        _ => Alphabetic,
+    }
+}
+
+/// The class of `c` as `word-break` and `line-break` tailor it.
+fn tailored_break_class(c: char, word_break: WordBreak, line_break: LineBreak) -> BreakClass {
+    let class = match (break_class(c), line_break) {
+        // Small kana and the prolonged sound mark only start lines when it's not `strict`.
+        (ConditionalJapaneseStarter, LineBreak::Strict) => Nonstarter,
+        (ConditionalJapaneseStarter, _) => Ideographic,
+        // `loose` also breaks before iteration marks, centered punctuation and hyphens.
+        (Nonstarter, LineBreak::Loose) | (BreakAfter, LineBreak::Loose) => match c {
+            '\u{3005}' | '\u{303B}' | '\u{309D}' | '\u{309E}' | '\u{30FD}' | '\u{30FE}'
+            | '\u{30FB}' | '\u{FF1A}' | '\u{FF1B}' | '\u{FF65}' | '\u{2010}' | '\u{2013}'
+            | '\u{301C}' | '\u{30A0}' => Ideographic,
+            _ => break_class(c),
+        },
+        (class, _) => class,
+    };
+    match (class, word_break) {
+        (Alphabetic, WordBreak::BreakAll) | (Numeric, WordBreak::BreakAll) => Ideographic,
+        (Ideographic, WordBreak::KeepAll)
+        | (HangulLJamo, WordBreak::KeepAll)
+        | (HangulVJamo, WordBreak::KeepAll)
+        | (HangulTJamo, WordBreak::KeepAll)
+        | (HangulLvSyllable, WordBreak::KeepAll)
+        | (HangulLvtSyllable, WordBreak::KeepAll) => Alphabetic,
+        (class, _) => class,
+    }
+}
+
+/// Whether East Asian punctuation is wide, which rule LB30 doesn't keep together with letters.
+fn is_wide(c: char) -> bool {
+    c >= '\u{1100}'
+}
+
+/// Whether a line can break between a character of class `before` and one of class `after`.
+/// `before_spaces` is the class of the character before the spaces `before` ends, if it's one,
+/// `after_is_wide` whether the character after is wide, and `regional_indicators` how many
+/// regional indicators are right before it.
+fn is_break(
+    before: BreakClass,
+    before_spaces: BreakClass,
+    after: BreakClass,
+    after_is_wide: bool,
+    regional_indicators: usize,
+) -> bool {
+    match (before, after) {
+        // LB4, LB5: after hard line breaks, but not inside CR LF.
+        (Mandatory, _) | (LineFeed, _) | (NextLine, _) => return true,
+        (CarriageReturn, LineFeed) => return false,
+        (CarriageReturn, _) => return true,
+        // LB6, LB7: not before hard line breaks, spaces or zero width spaces.
+        (_, Mandatory) | (_, CarriageReturn) | (_, LineFeed) | (_, NextLine) | (_, Space)
+        | (_, ZeroWidthSpace) => return false,
+        _ => {}
+    }
+    // LB8: after a zero width space, even before spaces.
+    if before_spaces == ZeroWidthSpace {
+        return true;
+    }
+    match (before, after) {
+        // LB11, LB12: not around word joiners, nor after glue.
+        (WordJoiner, _) | (_, WordJoiner) | (Glue, _) => false,
+        // LB12a: nor before glue, except after spaces and hyphens.
+        (Space, Glue) | (BreakAfter, Glue) | (Hyphen, Glue) => true,
+        (_, Glue) => false,
+        // LB13: not before closing punctuation, `!`, `?`, separators or slashes.
+        (_, ClosePunctuation)
+        | (_, CloseParenthesis)
+        | (_, Exclamation)
+        | (_, InfixSeparator)
+        | (_, Symbol) => false,
+        // LB14 - LB17: not after opening punctuation, even with spaces after it, and so on.
+        _ if before_spaces == OpenPunctuation => false,
+        (_, OpenPunctuation) if before_spaces == Quotation => false,
+        (_, Nonstarter)
+            if before_spaces == ClosePunctuation || before_spaces == CloseParenthesis =>
+        {
+            false
+        }
+        (_, BreakBoth) if before_spaces == BreakBoth => false,
+        // LB18: after spaces.
+        (Space, _) => true,
+        // LB19, LB20: not around quotation marks, but around contingent breaks.
+        (Quotation, _) | (_, Quotation) => false,
+        (Contingent, _) | (_, Contingent) => true,
+        // LB21, LB22: not before hyphens, small kana and the like, nor after `´`.
+        (_, BreakAfter) | (_, Hyphen) | (_, Nonstarter) | (BreakBefore, _) => false,
+        (_, Inseparable) => false,
+        // LB23 - LB25: not inside words, numbers, and prices and percentages.
+        (Alphabetic, Numeric) | (Numeric, Alphabetic) => false,
+        (Prefix, Ideographic) | (Ideographic, Postfix) => false,
+        (Prefix, Alphabetic) | (Postfix, Alphabetic) => false,
+        (Alphabetic, Prefix) | (Alphabetic, Postfix) => false,
+        (ClosePunctuation, Postfix)
+        | (CloseParenthesis, Postfix)
+        | (ClosePunctuation, Prefix)
+        | (CloseParenthesis, Prefix)
+        | (Numeric, Postfix)
+        | (Numeric, Prefix)
+        | (Postfix, OpenPunctuation)
+        | (Postfix, Numeric)
+        | (Prefix, OpenPunctuation)
+        | (Prefix, Numeric)
+        | (Hyphen, Numeric)
+        | (InfixSeparator, Numeric)
+        | (Numeric, Numeric)
+        | (Symbol, Numeric) => false,
+        // LB26, LB27: not inside Korean syllables.
+        (HangulLJamo, HangulLJamo)
+        | (HangulLJamo, HangulVJamo)
+        | (HangulLJamo, HangulLvSyllable)
+        | (HangulLJamo, HangulLvtSyllable)
+        | (HangulVJamo, HangulVJamo)
+        | (HangulVJamo, HangulTJamo)
+        | (HangulLvSyllable, HangulVJamo)
+        | (HangulLvSyllable, HangulTJamo)
+        | (HangulTJamo, HangulTJamo)
+        | (HangulLvtSyllable, HangulTJamo) => false,
+        (HangulLJamo, Postfix)
+        | (HangulVJamo, Postfix)
+        | (HangulTJamo, Postfix)
+        | (HangulLvSyllable, Postfix)
+        | (HangulLvtSyllable, Postfix) => false,
+        (Prefix, HangulLJamo)
+        | (Prefix, HangulVJamo)
+        | (Prefix, HangulTJamo)
+        | (Prefix, HangulLvSyllable)
+        | (Prefix, HangulLvtSyllable) => false,
+        // LB28 - LB30: not between letters, nor between them and narrow parentheses.
+        (Alphabetic, Alphabetic) | (InfixSeparator, Alphabetic) => false,
+        (Alphabetic, OpenPunctuation) | (Numeric, OpenPunctuation) => after_is_wide,
+        (CloseParenthesis, Alphabetic) | (CloseParenthesis, Numeric) => false,
+        // LB30a: not inside a flag, a pair of regional indicators.
+        (RegionalIndicator, RegionalIndicator) => regional_indicators % 2 == 0,
+        // LB31: everywhere else.
+        _ => true,
+    }
+}
+
+/// The byte offsets in `text` where `style` lets a line start, in order.
+pub fn break_opportunities(text: &str, style: &ComputedStyle) -> Vec<usize> {
+    if style.line_break == LineBreak::Anywhere {
+        return character_boundaries(text);
+    }
+    let mut opportunities = vec![];
+    let mut chars = text.char_indices();
+    let mut before = match chars.next() {
+        // LB10: a combining mark with nothing to attach to is a letter.
+        Some((_, c)) => match tailored_break_class(c, style.word_break, style.line_break) {
+            CombiningMark => Alphabetic,
+            class => class,
+        },
+        None => return opportunities,
+    };
+    let mut before_spaces = before;
+    let mut regional_indicators = if before == RegionalIndicator { 1 } else { 0 };
+    for (pos, c) in chars {
+        let after = tailored_break_class(c, style.word_break, style.line_break);
+        let after = match (before, after) {
+            // LB9: combining marks take the class of the character they attach to.
+            (Space, CombiningMark)
+            | (ZeroWidthSpace, CombiningMark)
+            | (Mandatory, CombiningMark)
+            | (CarriageReturn, CombiningMark)
+            | (LineFeed, CombiningMark)
+            | (NextLine, CombiningMark) => Alphabetic,
+            (_, CombiningMark) => continue,
+            (_, after) => after,
+        };
+        if is_break(before, before_spaces, after, is_wide(c), regional_indicators) {
+            opportunities.push(pos);
+        }
+        regional_indicators = if after == RegionalIndicator {
+            regional_indicators + 1
+        } else {
+            0
+        };
+        if after != Space {
+            before_spaces = after;
+        }
+        before = after;
+    }
+    opportunities
+}
+
+/// The byte offsets in `text` where any character but a combining mark starts, which lines
+/// start at with `line-break: anywhere`, or to break a word with `overflow-wrap`.
+pub fn character_boundaries(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .skip(1)
+        .filter(|&(_, c)| break_class(c) != CombiningMark)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// Whether `overflow-wrap` or `word-break: break-word` lets words that don't fit in a line
+/// break anywhere.
+pub fn breaks_overflowing_words(style: &ComputedStyle) -> bool {
+    style.overflow_wrap != OverflowWrap::Normal || style.word_break == WordBreak::BreakWord
+}
+
+/// Whether the words broken anywhere are taken into account for the min-content width, which
+/// they are with `anywhere`, but not with `break-word`.
+pub fn min_content_breaks_anywhere(style: &ComputedStyle) -> bool {
+    style.overflow_wrap == OverflowWrap::Anywhere || style.word_break == WordBreak::BreakWord
+}
+
+#[test]
+fn test_break_opportunities() {
+    let style = ComputedStyle::default();
+    let lines = |text: &str, style: &ComputedStyle| {
+        let mut start = 0;
+        let mut lines = vec![];
+        for end in break_opportunities(text, style) {
+            lines.push(&text[start..end]);
+            start = end;
+        }
+        lines.push(&text[start..]);
+        lines.into_iter().map(|line| line.to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        lines("Hello, world! (\"a-b\") 10% $5", &style),
+        vec!["Hello, ", "world! ", "(\"a-", "b\") ", "10% ", "$5"]
+    );
+    // Ideographs break anywhere, but not before closing punctuation and small kana, or after
+    // opening punctuation.
+    assert_eq!(
+        lines("「日本語」です。ちょっと", &style),
+        vec!["「日", "本", "語」", "で", "す。", "ち", "ょ", "っ", "と"]
+    );
+    let strict = ComputedStyle {
+        line_break: LineBreak::Strict,
+        ..ComputedStyle::default()
+    };
+    assert_eq!(lines("ちょっと", &strict), vec!["ちょっ", "と"]);
+    let keep_all = ComputedStyle {
+        word_break: WordBreak::KeepAll,
+        ..ComputedStyle::default()
+    };
+    assert_eq!(
+        lines("日本語です。 한국어", &keep_all),
+        vec!["日本語です。 ", "한국어"]
+    );
+    let break_all = ComputedStyle {
+        word_break: WordBreak::BreakAll,
+        ..ComputedStyle::default()
+    };
+    assert_eq!(lines("ab, c", &break_all), vec!["a", "b, ", "c"]);
+    let anywhere = ComputedStyle {
+        line_break: LineBreak::Anywhere,
+        ..ComputedStyle::default()
+    };
+    assert_eq!(lines("ab, c", &anywhere), vec!["a", "b", ",", " ", "c"]);
+    // Zero width spaces break, even when spaces follow them.
+    assert_eq!(lines("a\u{200B}b", &style), vec!["a\u{200B}", "b"]);
+    assert_eq!(lines("a\u{200B} b", &style), vec!["a\u{200B} ", "b"]);
+    // No-break spaces glue what's around them, but not to the spaces before them.
+    assert_eq!(lines("a\u{A0}b c", &style), vec!["a\u{A0}b ", "c"]);
+    assert_eq!(lines("a \u{A0}b", &style), vec!["a ", "\u{A0}b"]);
+    // Numbers stay with their signs, separators, parentheses and units.
+    assert_eq!(
+        lines("$5.00 (12) -3 10%", &style),
+        vec!["$5.00 ", "(12) ", "-3 ", "10%"]
+    );
+    // Flags are pairs of regional indicators.
+    assert_eq!(
+        lines("\u{1F1EF}\u{1F1F5}\u{1F1EB}\u{1F1F7}\u{1F1E9}", &style),
+        vec!["\u{1F1EF}\u{1F1F5}", "\u{1F1EB}\u{1F1F7}", "\u{1F1E9}"]
+    );
+    // Combining marks stay with the character they're on.
+    assert_eq!(character_boundaries("e\u{301}a"), vec![3]);
+}
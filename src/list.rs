@@ -0,0 +1,191 @@
+//! The numbering of list items and the text of their markers.
+
+use dom::{ElementData, Node, NodeType};
+use style::ListStyleType;
+
+/// Numbers the list items among the children of an element. In an `ol`, they count from its
+/// `start` attribute, down if it's `reversed`, and an item's `value` attribute sets its number.
+/// ref. https://html.spec.whatwg.org/multipage/grouping-content.html#ordinal-value
+#[derive(Clone, Debug)]
+pub struct ListCounter {
+    next: i32,
+    step: i32,
+}
+
+impl Default for ListCounter {
+    /// Counts from 1 up, as outside an `ol`.
+    fn default() -> ListCounter {
+        ListCounter { next: 1, step: 1 }
+    }
+}
+
+impl ListCounter {
+    pub fn new(list: &Node) -> ListCounter {
+        let elem = match list.data {
+            NodeType::Element(ref elem) if elem.tag_name == "ol" => elem,
+            _ => return ListCounter::default(),
+        };
+        let start = elem.attrs.get("start").and_then(|start| parse_integer(start));
+        if !elem.attrs.contains_key("reversed") {
+            return ListCounter {
+                next: start.unwrap_or(1),
+                step: 1,
+            };
+        }
+        let items = list.children
+            .iter()
+            .filter(|child| match child.data {
+                NodeType::Element(ref elem) => elem.tag_name == "li",
+                NodeType::Text(_) => false,
+            })
+            .count();
+        ListCounter {
+            next: start.unwrap_or(items as i32),
+            step: -1,
+        }
+    }
+
+    /// The number of the next list item `item`.
+    pub fn next(&mut self, item: &ElementData) -> i32 {
+        let ordinal = match item.attrs.get("value") {
+            Some(value) if item.tag_name == "li" => parse_integer(value).unwrap_or(self.next),
+            _ => self.next,
+        };
+        self.next = ordinal.saturating_add(self.step);
+        ordinal
+    }
+}
+
+/// The leading integer of `s`, like `parseInt()`.
+/// ref. https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-integers
+fn parse_integer(s: &str) -> Option<i32> {
+    let s = s.trim();
+    let (sign, digits) = if s.starts_with('-') {
+        (-1, &s[1..])
+    } else if s.starts_with('+') {
+        (1, &s[1..])
+    } else {
+        (1, s)
+    };
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    digits[..end].parse::<i32>().ok().map(|n| sign * n)
+}
+
+/// The text of the marker of the list item numbered `ordinal`, followed by its suffix, or None
+/// for `none`. Numbers that a counter style can't represent are written in `decimal`.
+/// ref. https://www.w3.org/TR/css-counter-styles-3/#predefined-counters
+pub fn marker_text(list_style_type: ListStyleType, ordinal: i32) -> Option<String> {
+    let bullet = |bullet: &str| Some(format!("{} ", bullet));
+    let counter = match list_style_type {
+        ListStyleType::None => return None,
+        ListStyleType::Disc => return bullet("\u{2022}"),
+        ListStyleType::Circle => return bullet("\u{25e6}"),
+        ListStyleType::Square => return bullet("\u{25aa}"),
+        ListStyleType::DisclosureOpen => return bullet("\u{25be}"),
+        ListStyleType::DisclosureClosed => return bullet("\u{25b8}"),
+        ListStyleType::Decimal => None,
+        ListStyleType::LowerAlpha => alphabetic(ordinal, 'a'),
+        ListStyleType::UpperAlpha => alphabetic(ordinal, 'A'),
+        ListStyleType::LowerRoman => roman(ordinal).map(|roman| roman.to_lowercase()),
+        ListStyleType::UpperRoman => roman(ordinal),
+        ListStyleType::CjkDecimal => {
+            let digits = "\u{3007}\u{4e00}\u{4e8c}\u{4e09}\u{56db}\u{4e94}\u{516d}\u{4e03}\u{516b}\
+                          \u{4e5d}";
+            let number: String = ordinal
+                .to_string()
+                .chars()
+                .map(|c| c.to_digit(10).map_or(c, |d| digits.chars().nth(d as usize).unwrap()))
+                .collect();
+            return Some(format!("{}\u{3001}", number));
+        }
+    };
+    Some(format!("{}. ", counter.unwrap_or_else(|| ordinal.to_string())))
+}
+
+/// a, b, ..., z, aa, ab, ... from `first`, for positive numbers.
+fn alphabetic(ordinal: i32, first: char) -> Option<String> {
+    if ordinal < 1 {
+        return None;
+    }
+    let mut letters = vec![];
+    let mut n = ordinal;
+    while n > 0 {
+        n -= 1;
+        letters.push((first as u8 + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    Some(letters.into_iter().rev().collect())
+}
+
+/// Upper-case roman numerals, from 1 to 3999.
+fn roman(ordinal: i32) -> Option<String> {
+    if ordinal < 1 || ordinal > 3999 {
+        return None;
+    }
+    let symbols = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut numeral = String::new();
+    let mut n = ordinal;
+    for &(value, symbol) in symbols.iter() {
+        while n >= value {
+            numeral.push_str(symbol);
+            n -= value;
+        }
+    }
+    Some(numeral)
+}
+
+#[test]
+fn test_marker_text() {
+    let text = |list_style_type, ordinal| marker_text(list_style_type, ordinal).unwrap();
+    assert_eq!(text(ListStyleType::Disc, 3), "\u{2022} ");
+    assert_eq!(text(ListStyleType::Decimal, -2), "-2. ");
+    assert_eq!(text(ListStyleType::LowerAlpha, 1), "a. ");
+    assert_eq!(text(ListStyleType::UpperAlpha, 28), "AB. ");
+    assert_eq!(text(ListStyleType::LowerAlpha, 0), "0. ");
+    assert_eq!(text(ListStyleType::LowerRoman, 1994), "mcmxciv. ");
+    assert_eq!(text(ListStyleType::UpperRoman, 4000), "4000. ");
+    assert_eq!(text(ListStyleType::CjkDecimal, 105), "\u{4e00}\u{3007}\u{4e94}\u{3001}");
+    assert_eq!(marker_text(ListStyleType::None, 1), None);
+}
+
+#[test]
+fn test_list_counter() {
+    use html;
+    use std::path::PathBuf;
+
+    let ordinals = |src: &str| {
+        let list = html::parse(src.to_string(), PathBuf::from("test.html"));
+        let mut counter = ListCounter::new(&list);
+        list.children
+            .iter()
+            .filter_map(|item| match item.data {
+                NodeType::Element(ref item) => Some(counter.next(item)),
+                NodeType::Text(_) => None,
+            })
+            .collect::<Vec<i32>>()
+    };
+    assert_eq!(ordinals("<ul><li>a</li><li>b</li></ul>"), vec![1, 2]);
+    assert_eq!(ordinals("<ol start=3><li>a</li><li value=\"10\">b</li><li>c</li></ol>"), vec![
+        3, 10, 11,
+    ]);
+    assert_eq!(ordinals("<ol reversed><li>a</li><li>b</li><li>c</li></ol>"), vec![3, 2, 1]);
+    assert_eq!(ordinals("<ol start=\"5\" reversed><li>a</li><li value=\"x\">b</li></ol>"), vec![
+        5, 4,
+    ]);
+}
@@ -15,15 +15,27 @@ fn main() {
             Arg::with_name("URL")
                 .help("Set URL (starts with http(s):// or file://)")
                 .index(1),
+        )
+        .arg(
+            Arg::with_name("USER_STYLESHEET")
+                .help("Set a user stylesheet (path, or URL starting with file:// or http(s)://)")
+                .long("user-stylesheet")
+                .takes_value(true)
+                .value_name("FILE"),
         );
     let app_matches = app.clone().get_matches();
 
-    interface::run_with_url(if let Some(url) = app_matches.value_of("URL") {
-        url.to_string()
-    } else {
-        let mut cur_dir = std::env::current_dir().unwrap();
-        cur_dir.push("example");
-        cur_dir.push("top.html");
-        format!("file://{}", cur_dir.to_str().unwrap())
-    });
+    interface::run_with_url(
+        if let Some(url) = app_matches.value_of("URL") {
+            url.to_string()
+        } else {
+            let mut cur_dir = std::env::current_dir().unwrap();
+            cur_dir.push("example");
+            cur_dir.push("top.html");
+            format!("file://{}", cur_dir.to_str().unwrap())
+        },
+        app_matches
+            .value_of("USER_STYLESHEET")
+            .map(|path| path.to_string()),
+    );
 }
@@ -0,0 +1,329 @@
+//! Margin collapsing: the adjoining vertical margins of boxes in normal flow combine into a
+//! single margin, as wide as the largest positive one plus the most negative one.
+//! ref. https://www.w3.org/TR/CSS22/box.html#collapsing-margins
+
+use layout::{BoxType, LayoutBox};
+#[cfg(test)]
+use layout::block;
+use style::Overflow;
+
+use std::cmp::{max, min};
+
+use app_units::Au;
+
+/// Adjoining margins, which collapse into one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CollapsibleMargins {
+    /// The largest positive margin.
+    positive: Au,
+    /// The most negative margin.
+    negative: Au,
+}
+
+impl CollapsibleMargins {
+    pub fn new(margin: Au) -> CollapsibleMargins {
+        CollapsibleMargins::default().adjoin(margin)
+    }
+
+    /// These margins and `margin`, which adjoins them.
+    pub fn adjoin(self, margin: Au) -> CollapsibleMargins {
+        CollapsibleMargins {
+            positive: max(self.positive, margin),
+            negative: min(self.negative, margin),
+        }
+    }
+
+    /// These margins and `other`, which adjoin them.
+    pub fn adjoin_all(self, other: CollapsibleMargins) -> CollapsibleMargins {
+        self.adjoin(other.positive).adjoin(other.negative)
+    }
+
+    /// The width of the collapsed margin.
+    pub fn collapse(self) -> Au {
+        self.positive + self.negative
+    }
+}
+
+impl LayoutBox {
+    /// Collapses the top margin of a block-level box with `margins`, those adjoining it before
+    /// the box, or None for a box in a formatting context of its own. Sets the used top margin
+    /// to the part of the collapsed margin they don't reach. Returns the margins the top margin
+    /// of the first child collapses with, which are all above the content box.
+    pub fn collapse_top_margin(
+        &mut self,
+        margins: Option<CollapsibleMargins>,
+        cb_width: Au,
+    ) -> CollapsibleMargins {
+        let margins = match margins {
+            Some(margins) => margins,
+            None => return CollapsibleMargins::default(),
+        };
+        let collapsed = margins.adjoin_all(self.top_margins(cb_width));
+        self.dimensions.margin.top = collapsed.collapse() - margins.collapse();
+        if self.top_margin_adjoins_children(cb_width) {
+            collapsed
+        } else {
+            CollapsibleMargins::default()
+        }
+    }
+
+    /// Collapses the bottom margin of a block-level box with the margins `trailing` after its
+    /// last child, of which `included` is in the content height, if they adjoin. Sets the used
+    /// bottom margin to the part of the collapsed margin that isn't above it.
+    pub fn collapse_bottom_margin(
+        &mut self,
+        margins: Option<CollapsibleMargins>,
+        (trailing, included): (CollapsibleMargins, Au),
+        cb_width: Au,
+    ) {
+        if margins.is_none() || !self.bottom_margin_adjoins_children(cb_width) {
+            return;
+        }
+        let collapsed = trailing.adjoin(self.dimensions.margin.bottom);
+        self.dimensions.content.height -= included;
+        self.dimensions.margin.bottom = collapsed.collapse() - (trailing.collapse() - included);
+    }
+
+    /// The margins collapsing with the top margin of the box, its own included.
+    pub fn top_margins(&self, cb_width: Au) -> CollapsibleMargins {
+        let margin = self.property.margin.top.to_au(cb_width).unwrap_or(Au(0));
+        let mut margins = CollapsibleMargins::new(margin);
+        if !self.top_margin_adjoins_children(cb_width) {
+            return margins;
+        }
+        let width = self.estimated_width(cb_width);
+        for child in self.children.iter().filter(|child| child.in_normal_flow()) {
+            margins = margins.adjoin_all(child.top_margins(width));
+            if !child.collapses_through(width) {
+                break;
+            }
+            margins = margins.adjoin_all(child.bottom_margins(width));
+        }
+        margins
+    }
+
+    /// The margins collapsing with the bottom margin of the box, its own included.
+    pub fn bottom_margins(&self, cb_width: Au) -> CollapsibleMargins {
+        let margin = self.property.margin.bottom.to_au(cb_width).unwrap_or(Au(0));
+        let mut margins = CollapsibleMargins::new(margin);
+        if !self.bottom_margin_adjoins_children(cb_width) {
+            return margins;
+        }
+        let width = self.estimated_width(cb_width);
+        for child in self.children.iter().rev().filter(|child| child.in_normal_flow()) {
+            margins = margins.adjoin_all(child.bottom_margins(width));
+            if !child.collapses_through(width) {
+                break;
+            }
+            margins = margins.adjoin_all(child.top_margins(width));
+        }
+        margins
+    }
+
+    /// Whether the top and bottom margins of the box adjoin, since it has neither a height nor
+    /// in-flow content.
+    pub fn collapses_through(&self, cb_width: Au) -> bool {
+        let style = &self.property;
+        let width = self.estimated_width(cb_width);
+        let in_flow_children_collapse_through = || {
+            self.children
+                .iter()
+                .filter(|child| child.in_normal_flow())
+                .all(|child| child.collapses_through(width))
+        };
+        match self.box_type {
+            BoxType::AnonymousBlock => in_flow_children_collapse_through(),
+            BoxType::BlockNode => {
+                self.top_margin_adjoins_children(cb_width)
+                    && style.border_width.bottom == Au(0)
+                    && style.padding.bottom.to_au(cb_width) == Au(0)
+                    && style
+                        .specified_height(cb_width, None)
+                        .map_or(true, |height| height == Au(0))
+                    && style.height_limits(cb_width, None).0 == Au(0)
+                    && in_flow_children_collapse_through()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the top margin adjoins that of the first in-flow child.
+    fn top_margin_adjoins_children(&self, cb_width: Au) -> bool {
+        let style = &self.property;
+        self.box_type == BoxType::BlockNode && !self.is_replaced()
+            && !self.establishes_formatting_context()
+            && style.border_width.top == Au(0)
+            && style.padding.top.to_au(cb_width) == Au(0)
+    }
+
+    /// Whether the bottom margin adjoins that of the last in-flow child. When all the children
+    /// collapse through, their margins only adjoin the top margin, unless the box does too.
+    fn bottom_margin_adjoins_children(&self, cb_width: Au) -> bool {
+        let style = &self.property;
+        let width = self.estimated_width(cb_width);
+        self.box_type == BoxType::BlockNode && !self.is_replaced()
+            && !self.establishes_formatting_context()
+            && style.border_width.bottom == Au(0)
+            && style.padding.bottom.to_au(cb_width) == Au(0)
+            && style.specified_height(cb_width, None).is_none()
+            && (self.children
+                .iter()
+                .any(|child| child.in_normal_flow() && !child.collapses_through(width))
+                || self.collapses_through(cb_width))
+    }
+
+    /// Whether a block box establishes a block formatting context, which the margins of its
+    /// children don't collapse out of. Floats and absolutely positioned boxes are laid out in
+    /// one of their own.
+    fn establishes_formatting_context(&self) -> bool {
+        let hides_overflow = |overflow: Overflow| match overflow {
+            Overflow::Visible | Overflow::Clip => false,
+            Overflow::Hidden | Overflow::Scroll | Overflow::Auto => true,
+        };
+        hides_overflow(self.property.overflow_x) || hides_overflow(self.property.overflow_y)
+    }
+
+    /// The width of the content box of a block in normal flow, which the margins of its
+    /// children refer to, before it's laid out.
+    fn estimated_width(&self, cb_width: Au) -> Au {
+        let style = &self.property;
+        let width = style.specified_width(cb_width).unwrap_or_else(|| {
+            let margin = |margin: ::style::LengthOrPercentageOrAuto| {
+                margin.to_au(cb_width).unwrap_or(Au(0))
+            };
+            cb_width - margin(style.margin.left) - margin(style.margin.right)
+                - style.horizontal_box_edges(cb_width)
+        });
+        max(Au(0), style.clamp_width(width, Some(cb_width)))
+    }
+}
+
+/// Lays out `root` 800px wide, in a formatting context of its own.
+#[cfg(test)]
+fn layout(mut root: LayoutBox) -> LayoutBox {
+    use float::Floats;
+    use layout::Dimensions;
+
+    let mut viewport: Dimensions = Default::default();
+    viewport.content.width = Au::from_px(800);
+    root.layout(&mut Floats::new(), None, viewport, viewport, viewport);
+    root
+}
+
+#[test]
+fn test_sibling_margins() {
+    let px = Au::from_px;
+    // The distance between the border boxes of two siblings, whose margins collapse.
+    let gap = |margin_bottom: i32, margin_top: i32| {
+        let root = layout(block(
+            "",
+            vec![
+                block(&format!("height: 10px; margin-bottom: {}px", margin_bottom), vec![]),
+                block(&format!("height: 10px; margin-top: {}px", margin_top), vec![]),
+            ],
+        ));
+        let (first, second) = (&root.children[0], &root.children[1]);
+        assert_eq!(root.dimensions.content.height, second.dimensions.border_box().y + px(10));
+        second.dimensions.border_box().y - first.dimensions.border_box().y - px(10)
+    };
+    assert_eq!(gap(20, 30), px(30));
+    assert_eq!(gap(30, 20), px(30));
+    assert_eq!(gap(20, -5), px(15));
+    assert_eq!(gap(-10, -5), px(-10));
+    assert_eq!(gap(20, -30), px(-10));
+}
+
+#[test]
+fn test_parent_and_child_margins() {
+    let px = Au::from_px;
+    let root = layout(block(
+        "",
+        vec![
+            block(
+                "margin-top: 10px",
+                vec![block("height: 10px; margin: 20px 0 5px", vec![])],
+            ),
+            block("height: 10px", vec![]),
+        ],
+    ));
+    // The margins of the first child are those of its parent.
+    let parent = &root.children[0];
+    assert_eq!(parent.dimensions.content.y, px(20));
+    assert_eq!(parent.children[0].dimensions.content.y, px(0));
+    assert_eq!(parent.dimensions.content.height, px(10));
+    assert_eq!(root.children[1].dimensions.content.y, px(35));
+    assert_eq!(root.dimensions.content.height, px(45));
+
+    // Borders and paddings separate them, and so do boxes with a formatting context of their
+    // own.
+    for style in &["margin-top: 10px; padding-top: 1px", "margin-top: 10px; overflow: hidden"] {
+        let root = layout(block(
+            "",
+            vec![block(style, vec![block("height: 10px; margin-top: 20px", vec![])])],
+        ));
+        let parent = &root.children[0];
+        assert_eq!(parent.dimensions.border_box().y, px(10));
+        assert_eq!(parent.children[0].dimensions.border_box().y, px(20));
+    }
+
+    // A height keeps the bottom margins apart.
+    let root = layout(block(
+        "",
+        vec![block("height: 30px", vec![block("height: 10px; margin-bottom: 40px", vec![])])],
+    ));
+    assert_eq!(root.children[0].dimensions.margin.bottom, px(0));
+    assert_eq!(root.dimensions.content.height, px(30));
+}
+
+#[test]
+fn test_empty_block_margins() {
+    let px = Au::from_px;
+    let root = layout(block(
+        "",
+        vec![
+            block("height: 10px; margin-bottom: 10px", vec![]),
+            block("margin: 20px 0 30px", vec![block("margin-top: -15px", vec![])]),
+            block("height: 10px; margin-top: 5px", vec![]),
+        ],
+    ));
+    // All the margins between the boxes around the empty ones collapse.
+    assert_eq!(root.children[2].dimensions.content.y, px(25));
+    assert_eq!(root.dimensions.content.height, px(35));
+
+    // A min-height keeps the box from being empty.
+    let root = layout(block(
+        "",
+        vec![
+            block("margin-top: 10px; margin-bottom: 10px; min-height: 1px", vec![]),
+            block("height: 10px", vec![]),
+        ],
+    ));
+    assert_eq!(root.children[1].dimensions.content.y, px(21));
+}
+
+#[test]
+fn test_clearance_margins() {
+    let px = Au::from_px;
+    let root = layout(block(
+        "",
+        vec![
+            block("float: left; width: 100px; height: 50px", vec![]),
+            block("margin-top: 10px", vec![]),
+            block("clear: left; height: 10px; margin-top: 10px", vec![]),
+        ],
+    ));
+    // The cleared box is below the float, whatever its margin.
+    assert_eq!(root.children[2].dimensions.border_box().y, px(50));
+    assert_eq!(root.dimensions.content.height, px(60));
+
+    // A box already below the floats gets no clearance, and its margins still collapse.
+    let root = layout(block(
+        "",
+        vec![
+            block("float: left; width: 100px; height: 50px", vec![]),
+            block("height: 60px; margin-bottom: 20px", vec![]),
+            block("clear: left; height: 10px; margin-top: 10px", vec![]),
+        ],
+    ));
+    assert_eq!(root.children[2].dimensions.border_box().y, px(80));
+}
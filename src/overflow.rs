@@ -0,0 +1,114 @@
+//! Overflow: boxes that clip the content overflowing their padding box, and scroll containers,
+//! which can be scrolled to it.
+//! ref. https://www.w3.org/TR/css-overflow-3/
+
+use layout::{BoxType, LayoutBox, Rect};
+use style::Overflow;
+
+use std::cmp::max;
+
+use app_units::Au;
+
+impl LayoutBox {
+    /// Whether the content overflowing the padding box isn't painted. Inline boxes don't clip.
+    pub fn clips_overflow(&self) -> bool {
+        let style = &self.property;
+        (style.overflow_x.is_clipped() || style.overflow_y.is_clipped()) && self.is_container()
+    }
+
+    /// Whether the box can be scrolled by the user, with a scrollbar or the mouse wheel.
+    pub fn is_scroll_container(&self) -> bool {
+        let user_scrollable = |overflow: Overflow| match overflow {
+            Overflow::Scroll | Overflow::Auto => true,
+            _ => false,
+        };
+        (user_scrollable(self.property.overflow_x) || user_scrollable(self.property.overflow_y))
+            && self.is_container()
+    }
+
+    fn is_container(&self) -> bool {
+        match self.box_type {
+            BoxType::InlineNode | BoxType::TextNode(_) | BoxType::AnonymousBlock => false,
+            _ => true,
+        }
+    }
+
+    /// How far the box can be scrolled right and down to show all its content, on the axes
+    /// it can be scrolled on.
+    pub fn scroll_range(&self) -> (Au, Au) {
+        let d = self.dimensions;
+        let padding_box = Rect {
+            x: Au(0),
+            y: Au(0),
+            ..d.content
+        }.expanded_by(d.padding);
+        let overflow = self.children
+            .iter()
+            .fold(padding_box, |area, child| area.union(child.overflow_area()));
+        let range = |overflow: Overflow, end: Au, padding_box_end: Au| {
+            if overflow.is_scrollable() {
+                max(Au(0), end - padding_box_end)
+            } else {
+                Au(0)
+            }
+        };
+        (
+            range(
+                self.property.overflow_x,
+                overflow.x + overflow.width,
+                padding_box.x + padding_box.width,
+            ),
+            range(
+                self.property.overflow_y,
+                overflow.y + overflow.height,
+                padding_box.y + padding_box.height,
+            ),
+        )
+    }
+
+    /// The area covered by the border box and the content that isn't clipped, relative to the
+    /// content box of the parent.
+    fn overflow_area(&self) -> Rect {
+        let d = self.dimensions;
+        let border_box = d.border_box();
+        if self.clips_overflow() {
+            return border_box;
+        }
+        self.children.iter().fold(border_box, |area, child| {
+            area.union(child.overflow_area().add_parent_coordinate(d.content.x, d.content.y))
+        })
+    }
+}
+
+#[test]
+fn test_scroll_range() {
+    use layout::block;
+
+    let px = Au::from_px;
+    let sized_block = |overflow: &str, x: i32, y: i32, width: i32, height: i32| {
+        let mut layout_box = block(&format!("overflow: {}", overflow), vec![]);
+        layout_box.dimensions.content = Rect {
+            x: px(x),
+            y: px(y),
+            width: px(width),
+            height: px(height),
+        };
+        layout_box
+    };
+
+    let mut scroller = sized_block("auto", 0, 0, 100, 100);
+    scroller.dimensions.padding.right = px(10);
+    // The content of a child that doesn't clip overflows the scroll container too.
+    let mut child = sized_block("visible", 0, 50, 100, 100);
+    child.children.push(sized_block("visible", 30, 0, 150, 10));
+    scroller.children.push(child);
+    assert_eq!(scroller.scroll_range(), (px(70), px(50)));
+
+    // That of a child that clips doesn't.
+    scroller.children[0].property.overflow_x = Overflow::Hidden;
+    assert_eq!(scroller.scroll_range(), (px(0), px(50)));
+
+    // Nor is it scrolled to on an axis that isn't clipped.
+    scroller.property.overflow_y = Overflow::Clip;
+    assert_eq!(scroller.scroll_range(), (px(0), px(0)));
+}
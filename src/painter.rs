@@ -1,23 +1,58 @@
 use layout::{BoxType, ImageMetaData, LayoutBox, LayoutInfo, Rect};
+use style::{Overflow, Position, TextOverflow};
 use font::Font;
 use dom::{ElementData, LayoutType, NodeType};
-use css::{Color, TextDecoration, BLACK};
+use css::{Color, TextDecoration, WHITE};
 use app_units::Au;
 
+use std::cmp::{max, min};
+
 use gdk_pixbuf;
 use gtk;
 
-use window::{AnkerKind, ANKERS, URL_FRAGMENTS};
+use window::{AnkerKind, ANKERS, NODE_RECTS, SCROLL_CONTAINERS, SCROLL_OFFSETS, URL_FRAGMENTS};
+
+const SCROLLBAR_WIDTH: i32 = 8;
+const SCROLLBAR_TRACK: Color = Color {
+    r: 0x00,
+    g: 0x00,
+    b: 0x00,
+    a: 0x10,
+};
+const SCROLLBAR_THUMB: Color = Color {
+    r: 0x00,
+    g: 0x00,
+    b: 0x00,
+    a: 0x60,
+};
+const ELLIPSIS: &str = "\u{2026}";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DisplayCommand {
     SolidColor(Color, Rect),
     Image(gdk_pixbuf::Pixbuf, ImageMetaData, Rect),
     Text(String, Rect, Color, Vec<TextDecoration>, Font),
     Button(gtk::Button, Rect),
+    /// The commands up to the matching `PopClip` only paint inside the rectangle.
+    PushClip(Rect),
+    PopClip(Rect),
+}
+
+impl DisplayCommand {
+    /// The area the command paints.
+    pub fn rect(&self) -> Rect {
+        match *self {
+            DisplayCommand::SolidColor(_, rect)
+            | DisplayCommand::Image(_, _, rect)
+            | DisplayCommand::Text(_, rect, _, _, _)
+            | DisplayCommand::Button(_, rect)
+            | DisplayCommand::PushClip(rect)
+            | DisplayCommand::PopClip(rect) => rect,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DisplayCommandInfo {
     pub command: DisplayCommand,
 }
@@ -30,18 +65,96 @@ impl DisplayCommandInfo {
 
 pub type DisplayList = Vec<DisplayCommandInfo>;
 
-pub fn build_display_list(layout_root: &mut LayoutBox) -> DisplayList {
-    let mut list = Vec::new();
-    render_layout_box(
-        &mut list,
-        Au::from_f64_px(0.0),
-        Au::from_f64_px(0.0),
-        layout_root,
+/// Paints the page laid out in `layout_root`, of which `viewport` is visible.
+pub fn build_display_list(layout_root: &LayoutBox, viewport: Rect) -> DisplayList {
+    NODE_RECTS.with(|node_rects| node_rects.borrow_mut().clear());
+    SCROLL_CONTAINERS.with(|containers| containers.borrow_mut().clear());
+    // The canvas is painted white first, under the root element's own background.
+    let mut list = vec![DisplayCommandInfo::new(DisplayCommand::SolidColor(
+        WHITE,
+        layout_root.dimensions.border_box(),
+    ))];
+    let (x, y) = layout_root.positioned_offset(
+        Rect {
+            x: Au(0),
+            y: Au(0),
+            ..viewport
+        },
+        viewport,
     );
+    // The root element is the root stacking context.
+    render_layout_box(&mut list, x, y, layout_root, viewport, true, None);
     list
 }
 
-fn render_layout_box(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutBox) {
+/// A positioned box, which is painted after the rest of its stacking context, in the order of
+/// its `z-index`. (`x`, `y`) is where the content box of its parent is painted.
+struct Layer<'a> {
+    z_index: i32,
+    x: Au,
+    y: Au,
+    viewport: Rect,
+    layout_box: &'a LayoutBox,
+}
+
+/// Collects the positioned descendants of `layout_box` that belong to the stacking context it
+/// is in, in tree order. The descendants of those that don't form stacking contexts of their
+/// own belong to it too.
+fn collect_layers<'a>(
+    layers: &mut Vec<Layer<'a>>,
+    x: Au,
+    y: Au,
+    layout_box: &'a LayoutBox,
+    viewport: Rect,
+) {
+    let parent = layout_box.dimensions.content.add_parent_coordinate(x, y);
+    for child in &layout_box.children {
+        if child.property.position == Position::Static {
+            if !child.clips_overflow() {
+                collect_layers(layers, parent.x, parent.y, child, viewport);
+            }
+            continue;
+        }
+        let (offset_x, offset_y) = child.positioned_offset(parent, viewport);
+        // The page doesn't scroll under a `fixed` box, so its descendants are placed as if it
+        // weren't scrolled.
+        let viewport = if child.property.position == Position::Fixed {
+            Rect {
+                x: Au(0),
+                y: Au(0),
+                ..viewport
+            }
+        } else {
+            viewport
+        };
+        let (child_x, child_y) = (parent.x + offset_x, parent.y + offset_y);
+        layers.push(Layer {
+            z_index: child.z_index,
+            x: child_x,
+            y: child_y,
+            viewport: viewport,
+            layout_box: child,
+        });
+        if !child.is_stacking_context() && !child.clips_overflow() {
+            collect_layers(layers, child_x, child_y, child, viewport);
+        }
+    }
+}
+
+/// Paints `layout_box`, whose parent's content box is at (`x`, `y`), and the descendants that
+/// aren't positioned. When it is a `stacking_context`, the positioned descendants are painted
+/// too: those with a negative `z-index` above its background, and the others above the rest.
+/// Text that goes past `ellipsis` is cut off there by an ellipsis.
+/// ref. https://www.w3.org/TR/CSS22/zindex.html
+fn render_layout_box(
+    list: &mut DisplayList,
+    x: Au,
+    y: Au,
+    layout_box: &LayoutBox,
+    viewport: Rect,
+    stacking_context: bool,
+    ellipsis: Option<Au>,
+) {
     let is_input_elem = match layout_box.info {
         LayoutInfo::Button(_, _) => true,
         _ => false,
@@ -51,39 +164,100 @@ fn render_layout_box(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut Layo
 
     render_background(&mut buf, x, y, layout_box);
     render_borders(&mut buf, x, y, layout_box);
+    register_node_rect(x, y, layout_box);
+
+    // The content of a box that clips is painted inside its padding box, moved by how far the
+    // box is scrolled. The positioned descendants are clipped too, so it paints them itself.
+    let clip = if layout_box.clips_overflow() {
+        Some(clip_rect(x, y, layout_box))
+    } else {
+        None
+    };
+    let scroll_offset = match clip {
+        Some(clip) => scroll_offset(clip, layout_box),
+        None => (Au(0), Au(0)),
+    };
+    if let Some(clip) = clip {
+        buf.push(DisplayCommandInfo::new(DisplayCommand::PushClip(clip)));
+    }
+    let (scrolled_x, scrolled_y) = (x - scroll_offset.0, y - scroll_offset.1);
 
-    let mut children = layout_box.children.clone();
-    children.sort_by(|&LayoutBox { z_index: a, .. }, &LayoutBox { z_index: b, .. }| a.cmp(&b));
+    let mut layers = vec![];
+    if stacking_context || clip.is_some() {
+        collect_layers(&mut layers, scrolled_x, scrolled_y, layout_box, viewport);
+        layers.sort_by_key(|layer| layer.z_index);
+    }
+    let render_layers = |buf: &mut DisplayList, layers: &[Layer]| {
+        for layer in layers {
+            let layout_box = layer.layout_box;
+            let stacking_context = layout_box.is_stacking_context();
+            render_layout_box(
+                buf,
+                layer.x,
+                layer.y,
+                layout_box,
+                layer.viewport,
+                stacking_context,
+                None,
+            );
+        }
+    };
+    let negative = layers
+        .iter()
+        .position(|layer| layer.z_index >= 0)
+        .unwrap_or(layers.len());
+    render_layers(&mut buf, &layers[..negative]);
 
-    for mut child in children
-        .iter_mut()
+    let (content_x, content_y) = (
+        scrolled_x + layout_box.dimensions.content.x,
+        scrolled_y + layout_box.dimensions.content.y,
+    );
+    if let Some(ref marker) = layout_box.marker {
+        render_layout_box(&mut buf, content_x, content_y, marker, viewport, false, None);
+    }
+
+    // The lines of a block container that clips its overflow horizontally can end with an
+    // ellipsis at the end of its content box.
+    let ellipsis = match layout_box.box_type {
+        BoxType::AnonymousBlock | BoxType::InlineNode => ellipsis,
+        _ if layout_box.property.text_overflow == TextOverflow::Ellipsis
+            && layout_box.property.overflow_x.is_clipped() =>
+        {
+            let d = &layout_box.dimensions;
+            Some(x + d.content.x + d.content.width)
+        }
+        _ => None,
+    };
+
+    let children = layout_box
+        .children
+        .iter()
+        .filter(|child| child.property.position == Position::Static);
+    for child in children
+        .clone()
         .filter(|child| child.box_type != BoxType::Float)
     {
-        render_layout_box(
-            &mut buf,
-            x + layout_box.dimensions.content.x,
-            y + layout_box.dimensions.content.y,
-            &mut child,
-        );
+        render_layout_box(&mut buf, content_x, content_y, child, viewport, false, ellipsis);
     }
-    for mut child in children
-        .iter_mut()
-        .filter(|child| child.box_type == BoxType::Float)
-    {
-        render_layout_box(
-            &mut buf,
-            x + layout_box.dimensions.content.x,
-            y + layout_box.dimensions.content.y,
-            &mut child,
-        );
+    for child in children.filter(|child| child.box_type == BoxType::Float) {
+        render_layout_box(&mut buf, content_x, content_y, child, viewport, false, ellipsis);
     }
 
-    render_text(&mut buf, x, y, layout_box);
+    render_text(&mut buf, x, y, layout_box, ellipsis);
     render_image(&mut buf, x, y, layout_box);
 
     register_anker(x, y, layout_box);
     register_url_fragment(x, y, layout_box);
 
+    render_layers(&mut buf, &layers[negative..]);
+
+    if let Some(clip) = clip {
+        buf.push(DisplayCommandInfo::new(DisplayCommand::PopClip(clip)));
+        if layout_box.is_scroll_container() {
+            render_scrollbars(&mut buf, clip, scroll_offset, layout_box);
+        }
+    }
+
     if is_input_elem {
         render_button(list, &mut buf, x, y, layout_box);
     } else {
@@ -91,12 +265,117 @@ fn render_layout_box(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut Layo
     }
 }
 
+/// The area the content of `layout_box` is clipped to: its padding box on the axes it clips
+/// on.
+fn clip_rect(x: Au, y: Au, layout_box: &LayoutBox) -> Rect {
+    // Far enough to never clip.
+    let unclipped = Au::from_px(1_000_000);
+    let padding_box = layout_box.dimensions.padding_box().add_parent_coordinate(x, y);
+    let style = &layout_box.property;
+    let (x, width) = if style.overflow_x.is_clipped() {
+        (padding_box.x, padding_box.width)
+    } else {
+        (-unclipped, unclipped * 2)
+    };
+    let (y, height) = if style.overflow_y.is_clipped() {
+        (padding_box.y, padding_box.height)
+    } else {
+        (-unclipped, unclipped * 2)
+    };
+    Rect {
+        x: x,
+        y: y,
+        width: width,
+        height: height,
+    }
+}
+
+/// How far `layout_box`, whose content is clipped to `clip`, is scrolled. Scroll containers are
+/// registered to be scrolled with the mouse wheel.
+fn scroll_offset(clip: Rect, layout_box: &LayoutBox) -> (Au, Au) {
+    let node_id = match layout_box.node_id {
+        Some(node_id) => node_id,
+        None => return (Au(0), Au(0)),
+    };
+    let (range_x, range_y) = layout_box.scroll_range();
+    if layout_box.is_scroll_container() {
+        SCROLL_CONTAINERS.with(|containers| {
+            containers
+                .borrow_mut()
+                .push((clip, node_id, (range_x, range_y)))
+        });
+    }
+    // The content may have become smaller since the box was scrolled.
+    SCROLL_OFFSETS.with(|offsets| {
+        offsets
+            .borrow()
+            .get(&node_id)
+            .map_or((Au(0), Au(0)), |&(x, y)| (min(x, range_x), min(y, range_y)))
+    })
+}
+
+/// Paints the scrollbars of a scroll container along the right and bottom edges of `clip`,
+/// over its content. `overflow: auto` only shows them when there is something to scroll to.
+fn render_scrollbars(list: &mut DisplayList, clip: Rect, offset: (Au, Au), layout_box: &LayoutBox) {
+    let width = Au::from_px(SCROLLBAR_WIDTH);
+    let (range_x, range_y) = layout_box.scroll_range();
+    let shows = |overflow: Overflow, range: Au| match overflow {
+        Overflow::Scroll => true,
+        Overflow::Auto => range > Au(0),
+        _ => false,
+    };
+    // The thumb is as much shorter than the track as the content is longer than the box.
+    let thumb = |track: Au, range: Au, offset: Au| {
+        let length = max(width, track.scale_by(track.to_f32_px() / (track + range).to_f32_px()));
+        let position = if range > Au(0) {
+            (track - length).scale_by(offset.to_f32_px() / range.to_f32_px())
+        } else {
+            Au(0)
+        };
+        (position, length)
+    };
+    if shows(layout_box.property.overflow_y, range_y) {
+        let track = Rect {
+            x: clip.x + clip.width - width,
+            width: width,
+            ..clip
+        };
+        let (position, length) = thumb(track.height, range_y, offset.1);
+        list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(SCROLLBAR_TRACK, track)));
+        list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
+            SCROLLBAR_THUMB,
+            Rect {
+                y: track.y + position,
+                height: length,
+                ..track
+            },
+        )));
+    }
+    if shows(layout_box.property.overflow_x, range_x) {
+        let track = Rect {
+            y: clip.y + clip.height - width,
+            height: width,
+            ..clip
+        };
+        let (position, length) = thumb(track.width, range_x, offset.0);
+        list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(SCROLLBAR_TRACK, track)));
+        list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
+            SCROLLBAR_THUMB,
+            Rect {
+                x: track.x + position,
+                width: length,
+                ..track
+            },
+        )));
+    }
+}
+
 fn render_button(
     list: &mut DisplayList,
     _children: &mut DisplayList,
     x: Au,
     y: Au,
-    layout_box: &mut LayoutBox,
+    layout_box: &LayoutBox,
 ) {
     if let &LayoutInfo::Button(ref btn, _) = &layout_box.info {
         list.push(DisplayCommandInfo::new(DisplayCommand::Button(
@@ -106,27 +385,47 @@ fn render_button(
     }
 }
 
-fn render_text(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutBox) {
-    if let &mut BoxType::TextNode(ref text_info) = &mut layout_box.box_type {
+fn render_text(list: &mut DisplayList, x: Au, y: Au, layout_box: &LayoutBox, ellipsis: Option<Au>) {
+    if let BoxType::TextNode(ref text_info) = layout_box.box_type {
         let text = if let NodeType::Text(ref text) = layout_box.node.data {
             &text.as_str()[text_info.range.clone()]
         } else {
             unreachable!()
         };
+        let mut rect = layout_box.dimensions.content.add_parent_coordinate(x, y);
+        let text = match ellipsis {
+            Some(edge) if rect.x + rect.width > edge => {
+                if rect.x >= edge {
+                    return;
+                }
+                let text = ellipsized(text, &text_info.font, edge - rect.x);
+                rect.width = Au::from_f64_px(text_info.font.text_width(&text));
+                text
+            }
+            _ => text.to_string(),
+        };
         list.push(DisplayCommandInfo::new(DisplayCommand::Text(
-            text.to_string(),
-            layout_box.dimensions.content.add_parent_coordinate(x, y),
-            match layout_box.property.value("color") {
-                Some(maybe_color) => maybe_color[0].to_color(),
-                _ => None,
-            }.unwrap_or(BLACK),
-            layout_box.property.text_decoration(),
-            text_info.font,
+            text,
+            rect,
+            layout_box.property.color,
+            layout_box.property.text_decoration.clone(),
+            text_info.font.clone(),
         )));
     }
 }
 
-fn render_image(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutBox) {
+/// The longest start of `text` that fits in `width` with an ellipsis after it.
+fn ellipsized(text: &str, font: &Font, width: Au) -> String {
+    let width = width.to_f64_px() - font.text_width(ELLIPSIS);
+    let end = text.char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take_while(|&end| font.text_width(&text[..end]) <= width)
+        .last()
+        .unwrap_or(0);
+    format!("{}{}", &text[..end], ELLIPSIS)
+}
+
+fn render_image(list: &mut DisplayList, x: Au, y: Au, layout_box: &LayoutBox) {
     if let NodeType::Element(ElementData {
         ref layout_type, ..
     }) = layout_box.node.data
@@ -143,7 +442,7 @@ fn render_image(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutBox
     }
 }
 
-fn register_anker(x: Au, y: Au, layout_box: &mut LayoutBox) {
+fn register_anker(x: Au, y: Au, layout_box: &LayoutBox) {
     match layout_box.info {
         LayoutInfo::Anker => {
             if let Some(url) = layout_box.node.anker_url() {
@@ -163,7 +462,17 @@ fn register_anker(x: Au, y: Au, layout_box: &mut LayoutBox) {
     }
 }
 
-fn register_url_fragment(x: Au, y: Au, layout_box: &mut LayoutBox) {
+fn register_node_rect(x: Au, y: Au, layout_box: &LayoutBox) {
+    if let Some(node_id) = layout_box.node_id {
+        let rect = layout_box
+            .dimensions
+            .border_box()
+            .add_parent_coordinate(x, y);
+        NODE_RECTS.with(|node_rects| node_rects.borrow_mut().push((rect, node_id)));
+    }
+}
+
+fn register_url_fragment(x: Au, y: Au, layout_box: &LayoutBox) {
     if let NodeType::Element(ref e) = layout_box.node.data {
         if let Some(id) = e.id() {
             URL_FRAGMENTS.with(|url_fragments| {
@@ -181,28 +490,30 @@ fn register_url_fragment(x: Au, y: Au, layout_box: &mut LayoutBox) {
     }
 }
 
-fn render_background(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutBox) {
-    lookup_color(layout_box, "background-color", "background").map(|color| {
-        list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
-            color,
-            layout_box
-                .dimensions
-                .border_box()
-                .add_parent_coordinate(x, y),
-        )))
-    });
+fn render_background(list: &mut DisplayList, x: Au, y: Au, layout_box: &LayoutBox) {
+    let color = layout_box.property.background_color;
+    if color.a == 0 {
+        return;
+    }
+    list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
+        color,
+        layout_box
+            .dimensions
+            .border_box()
+            .add_parent_coordinate(x, y),
+    )));
 }
 
-fn render_borders(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutBox) {
+fn render_borders(list: &mut DisplayList, x: Au, y: Au, layout_box: &LayoutBox) {
     let d = &layout_box.dimensions;
     let border_box = d.border_box().add_parent_coordinate(x, y);
 
-    let (top_color, right_color, bottom_color, left_color) = layout_box.property.border_color();
+    let color = layout_box.property.border_color;
 
     // Left border
-    if let Some(left_color) = left_color {
+    if d.border.left > Au(0) {
         list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
-            left_color,
+            color.left,
             Rect {
                 x: border_box.x,
                 y: border_box.y,
@@ -213,9 +524,9 @@ fn render_borders(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutB
     }
 
     // Right border
-    if let Some(right_color) = right_color {
+    if d.border.right > Au(0) {
         list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
-            right_color,
+            color.right,
             Rect {
                 x: border_box.x + border_box.width - d.border.right,
                 y: border_box.y,
@@ -226,9 +537,9 @@ fn render_borders(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutB
     }
 
     // Top border
-    if let Some(top_color) = top_color {
+    if d.border.top > Au(0) {
         list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
-            top_color,
+            color.top,
             Rect {
                 x: border_box.x,
                 y: border_box.y,
@@ -239,9 +550,9 @@ fn render_borders(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutB
     }
 
     // Bottom border
-    if let Some(bottom_color) = bottom_color {
+    if d.border.bottom > Au(0) {
         list.push(DisplayCommandInfo::new(DisplayCommand::SolidColor(
-            bottom_color,
+            color.bottom,
             Rect {
                 x: border_box.x,
                 y: border_box.y + border_box.height - d.border.bottom,
@@ -251,14 +562,3 @@ fn render_borders(list: &mut DisplayList, x: Au, y: Au, layout_box: &mut LayoutB
         )));
     }
 }
-
-/// Return the specified color for CSS property `name` or `fallback_name`, or None if no color was specified.
-fn lookup_color(layout_box: &mut LayoutBox, name: &str, fallback_name: &str) -> Option<Color> {
-    match layout_box
-        .property
-        .lookup_without_default(name, fallback_name)
-    {
-        Some(maybe_color) => maybe_color[0].to_color(),
-        _ => None,
-    }
-}
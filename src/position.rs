@@ -0,0 +1,398 @@
+//! Positioned layout: boxes offset from their place in normal flow, and boxes taken out of it
+//! and placed against their containing block.
+//! ref. https://www.w3.org/TR/css-position-3/
+
+use layout::{Dimensions, EdgeSizes, LayoutBox, Rect};
+use style::Position;
+
+use std::cmp::{max, min};
+
+use app_units::Au;
+
+/// The specified values of an absolutely positioned box on one axis, which are `None` for
+/// `auto`.
+#[derive(Clone, Copy, Debug)]
+struct Axis {
+    start: Option<Au>,
+    end: Option<Au>,
+    size: Option<Au>,
+    margin_start: Option<Au>,
+    margin_end: Option<Au>,
+    /// The borders and paddings on both sides.
+    edges: Au,
+}
+
+impl Axis {
+    /// Solves the constraint that the inset, the margin box and the other inset add up to
+    /// `cb_size`, and returns the used start inset, content size, start margin and end margin.
+    /// `static_start` is where the box would start in normal flow, and `fit` gives the size of
+    /// the content for the space available to it.
+    /// ref. https://www.w3.org/TR/CSS22/visudet.html#abs-non-replaced-width
+    /// ref. https://www.w3.org/TR/CSS22/visudet.html#abs-non-replaced-height
+    fn solve(
+        &self,
+        cb_size: Au,
+        static_start: Au,
+        horizontal: bool,
+        fit: &Fn(Au) -> Au,
+    ) -> (Au, Au, Au, Au) {
+        let edges = self.edges;
+        if let (Some(start), Some(end), Some(size)) = (self.start, self.end, self.size) {
+            let remaining = cb_size - start - end - size - edges;
+            let (margin_start, margin_end) = match (self.margin_start, self.margin_end) {
+                (None, None) => {
+                    let half = remaining / 2;
+                    if horizontal && half < Au(0) {
+                        (Au(0), remaining)
+                    } else {
+                        (half, remaining - half)
+                    }
+                }
+                (None, Some(margin_end)) => (remaining - margin_end, margin_end),
+                (Some(margin_start), None) => (margin_start, remaining - margin_start),
+                // Over-constrained: the end inset is ignored.
+                (Some(margin_start), Some(margin_end)) => (margin_start, margin_end),
+            };
+            return (start, size, margin_start, margin_end);
+        }
+
+        let margin_start = self.margin_start.unwrap_or(Au(0));
+        let margin_end = self.margin_end.unwrap_or(Au(0));
+        let margins = margin_start + margin_end + edges;
+        let (start, size) = match (self.start, self.end, self.size) {
+            (None, None, None) | (Some(_), None, None) => {
+                let start = self.start.unwrap_or(static_start);
+                (start, fit(cb_size - start - margins))
+            }
+            (None, Some(end), None) => {
+                let size = fit(cb_size - end - margins);
+                (cb_size - end - margins - size, size)
+            }
+            (None, None, Some(size)) => (static_start, size),
+            (None, Some(end), Some(size)) => (cb_size - end - margins - size, size),
+            (Some(start), Some(end), None) => (start, max(Au(0), cb_size - start - end - margins)),
+            (Some(start), None, Some(size)) | (Some(start), Some(_), Some(size)) => (start, size),
+        };
+        (start, size, margin_start, margin_end)
+    }
+}
+
+impl LayoutBox {
+    /// Whether the box is out of flow and placed against its containing block.
+    pub fn is_absolutely_positioned(&self) -> bool {
+        match self.property.position {
+            Position::Absolute | Position::Fixed => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the box paints its positioned descendants itself, in the order of their
+    /// `z-index`, instead of leaving them to the stacking context it is in.
+    pub fn is_stacking_context(&self) -> bool {
+        match self.property.position {
+            Position::Static => false,
+            Position::Fixed | Position::Sticky => true,
+            Position::Relative | Position::Absolute => self.property.z_index.is_some(),
+        }
+    }
+
+    /// Whether the box or one of its descendants is painted at a place that depends on how far
+    /// the page or a scroll container is scrolled.
+    pub fn has_scroll_dependent_boxes(&self) -> bool {
+        match self.property.position {
+            Position::Fixed | Position::Sticky => true,
+            _ if self.is_scroll_container() => true,
+            _ => self.children
+                .iter()
+                .any(|child| child.has_scroll_dependent_boxes()),
+        }
+    }
+
+    /// Lays out the absolutely positioned descendants whose containing block is
+    /// `containing_block`, which is relative to the content box of this box. With `fixed`,
+    /// those are the `fixed` ones, and the containing block is the initial one.
+    pub fn layout_positioned_descendants(
+        &mut self,
+        containing_block: Rect,
+        fixed: bool,
+        viewport: Dimensions,
+    ) {
+        let target = if fixed {
+            Position::Fixed
+        } else {
+            Position::Absolute
+        };
+        for child in &mut self.children {
+            let position = child.property.position;
+            if position == target {
+                child.layout_absolute(containing_block, viewport);
+            }
+            // Positioned boxes lay out the absolutely positioned boxes in them themselves.
+            if fixed || position == Position::Static {
+                let containing_block = Rect {
+                    x: containing_block.x - child.dimensions.content.x,
+                    y: containing_block.y - child.dimensions.content.y,
+                    ..containing_block
+                };
+                child.layout_positioned_descendants(containing_block, fixed, viewport);
+            }
+        }
+    }
+
+    /// Lays out an absolutely positioned box against `containing_block`, from the static
+    /// position its content box was left at in normal flow.
+    fn layout_absolute(&mut self, containing_block: Rect, viewport: Dimensions) {
+        let static_x = self.dimensions.content.x - containing_block.x;
+        let static_y = self.dimensions.content.y - containing_block.y;
+        let (cb_width, cb_height) = (containing_block.width, containing_block.height);
+
+        let mut container: Dimensions = Default::default();
+        container.content = containing_block;
+        self.assign_padding(container);
+        self.assign_border_width();
+
+        let property = self.property.clone();
+        let (inset, margin) = (property.inset, property.margin);
+        let d = self.dimensions;
+
+        let horizontal = Axis {
+            start: inset.left.to_au(cb_width),
+            end: inset.right.to_au(cb_width),
+            size: property.specified_width(cb_width),
+            margin_start: margin.left.to_au(cb_width),
+            margin_end: margin.right.to_au(cb_width),
+            edges: d.border.left + d.padding.left + d.padding.right + d.border.right,
+        };
+        let widths = self.content_intrinsic_widths();
+        let fit = |available| widths.shrink_to_fit(available);
+        let (mut x, mut used_width, mut margin_left, mut margin_right) =
+            horizontal.solve(cb_width, static_x, true, &fit);
+        // The width is solved again with the limit it violates as the specified width.
+        let clamped_width = property.clamp_width(used_width, Some(cb_width));
+        if clamped_width != used_width {
+            let horizontal = Axis {
+                size: Some(clamped_width),
+                ..horizontal
+            };
+            let solved = horizontal.solve(cb_width, static_x, true, &fit);
+            x = solved.0;
+            used_width = solved.1;
+            margin_left = solved.2;
+            margin_right = solved.3;
+        }
+
+        let specified_height = property.specified_height(cb_width, Some(cb_height));
+        self.layout_item(used_width, specified_height, container, viewport);
+
+        let vertical = Axis {
+            start: inset.top.to_au(cb_height),
+            end: inset.bottom.to_au(cb_height),
+            size: specified_height,
+            margin_start: margin.top.to_au(cb_width),
+            margin_end: margin.bottom.to_au(cb_width),
+            edges: d.border.top + d.padding.top + d.padding.bottom + d.border.bottom,
+        };
+        let content_height = self.dimensions.content.height;
+        let fit = |_| content_height;
+        let (mut y, mut used_height, mut margin_top, mut margin_bottom) =
+            vertical.solve(cb_height, static_y, false, &fit);
+        let clamped_height = property.clamp_height(used_height, cb_width, Some(cb_height));
+        if clamped_height != used_height {
+            let vertical = Axis {
+                size: Some(clamped_height),
+                ..vertical
+            };
+            let solved = vertical.solve(cb_height, static_y, false, &fit);
+            y = solved.0;
+            used_height = solved.1;
+            margin_top = solved.2;
+            margin_bottom = solved.3;
+        }
+        if used_height != content_height {
+            self.layout_item(used_width, Some(used_height), container, viewport);
+        }
+
+        // The used sizes only replaced the specified ones for the layout.
+        self.property = property;
+
+        let d = &mut self.dimensions;
+        d.margin = EdgeSizes {
+            left: margin_left,
+            right: margin_right,
+            top: margin_top,
+            bottom: margin_bottom,
+        };
+        d.content.x = containing_block.x + x + margin_left + d.border.left + d.padding.left;
+        d.content.y = containing_block.y + y + margin_top + d.border.top + d.padding.top;
+        d.content.width = used_width;
+        d.content.height = used_height;
+    }
+
+    /// How far the box is painted from where it is laid out, for `relative`, `sticky` and
+    /// `fixed` boxes. `parent` is the content box of the parent, and `viewport` the visible
+    /// area of the page, in page coordinates.
+    pub fn positioned_offset(&self, parent: Rect, viewport: Rect) -> (Au, Au) {
+        match self.property.position {
+            Position::Relative => self.relative_offset(parent),
+            Position::Sticky => self.sticky_offset(parent, viewport),
+            // `fixed` boxes are laid out against the top of the page, and move with the view.
+            Position::Fixed => (viewport.x, viewport.y),
+            Position::Static | Position::Absolute => (Au(0), Au(0)),
+        }
+    }
+
+    /// `left` wins over `right`, and `top` over `bottom`.
+    /// ref. https://www.w3.org/TR/CSS22/visuren.html#relative-positioning
+    fn relative_offset(&self, parent: Rect) -> (Au, Au) {
+        let inset = self.property.inset;
+        let x = match (inset.left.to_au(parent.width), inset.right.to_au(parent.width)) {
+            (Some(left), _) => left,
+            (None, Some(right)) => -right,
+            (None, None) => Au(0),
+        };
+        let y = match (inset.top.to_au(parent.height), inset.bottom.to_au(parent.height)) {
+            (Some(top), _) => top,
+            (None, Some(bottom)) => -bottom,
+            (None, None) => Au(0),
+        };
+        (x, y)
+    }
+
+    /// A sticky box is shifted to stay its insets away from the edges of the view, but not out
+    /// of the content box of its parent.
+    /// ref. https://www.w3.org/TR/css-position-3/#stickypos-insets
+    fn sticky_offset(&self, parent: Rect, viewport: Rect) -> (Au, Au) {
+        let inset = self.property.inset;
+        let margin_box = self.dimensions
+            .margin_box()
+            .add_parent_coordinate(parent.x, parent.y);
+        let x = sticky_shift(
+            (margin_box.x, margin_box.x + margin_box.width),
+            (parent.x, parent.x + parent.width),
+            (viewport.x, viewport.x + viewport.width),
+            (
+                inset.left.to_au(viewport.width),
+                inset.right.to_au(viewport.width),
+            ),
+        );
+        let y = sticky_shift(
+            (margin_box.y, margin_box.y + margin_box.height),
+            (parent.y, parent.y + parent.height),
+            (viewport.y, viewport.y + viewport.height),
+            (
+                inset.top.to_au(viewport.height),
+                inset.bottom.to_au(viewport.height),
+            ),
+        );
+        (x, y)
+    }
+}
+
+/// The shift of a sticky box spanning `range` on an axis, in a containing block spanning
+/// `limit`, to keep it `insets` away from the edges of `view`. The start inset wins when both
+/// can't be honored.
+fn sticky_shift(
+    range: (Au, Au),
+    limit: (Au, Au),
+    view: (Au, Au),
+    insets: (Option<Au>, Option<Au>),
+) -> Au {
+    let mut shift = Au(0);
+    if let Some(end) = insets.1 {
+        let overflow = range.1 - (view.1 - end);
+        if overflow > Au(0) {
+            shift = -max(Au(0), min(overflow, range.0 - limit.0));
+        }
+    }
+    if let Some(start) = insets.0 {
+        let underflow = view.0 + start - range.0;
+        if underflow > Au(0) {
+            shift = max(Au(0), min(underflow, limit.1 - range.1));
+        }
+    }
+    shift
+}
+
+#[test]
+fn test_solve_absolute_axis() {
+    let px = Au::from_px;
+    let axis = |start, end, size, margins: (Option<Au>, Option<Au>)| Axis {
+        start: start,
+        end: end,
+        size: size,
+        margin_start: margins.0,
+        margin_end: margins.1,
+        edges: px(10),
+    };
+    let fit = |available: Au| min(available, px(50));
+    let none = (Some(px(0)), Some(px(0)));
+
+    // Everything auto: the box is at its static position and shrinks to fit.
+    assert_eq!(
+        axis(None, None, None, none).solve(px(200), px(30), true, &fit),
+        (px(30), px(50), px(0), px(0))
+    );
+    // Only the end inset: the box shrinks to fit against the end.
+    assert_eq!(
+        axis(None, Some(px(20)), None, none).solve(px(200), px(30), true, &fit),
+        (px(120), px(50), px(0), px(0))
+    );
+    // Both insets: the box stretches between them.
+    assert_eq!(
+        axis(Some(px(20)), Some(px(30)), None, none).solve(px(200), px(0), true, &fit),
+        (px(20), px(140), px(0), px(0))
+    );
+    // Auto margins center the box.
+    assert_eq!(
+        axis(Some(px(0)), Some(px(0)), Some(px(100)), (None, None))
+            .solve(px(200), px(0), true, &fit),
+        (px(0), px(100), px(45), px(45))
+    );
+    // Negative auto margins only go to the end horizontally.
+    assert_eq!(
+        axis(Some(px(0)), Some(px(0)), Some(px(300)), (None, None))
+            .solve(px(200), px(0), true, &fit),
+        (px(0), px(300), px(0), px(-110))
+    );
+    assert_eq!(
+        axis(Some(px(0)), Some(px(0)), Some(px(300)), (None, None))
+            .solve(px(200), px(0), false, &fit),
+        (px(0), px(300), px(-55), px(-55))
+    );
+    // Over-constrained: the end inset is ignored.
+    assert_eq!(
+        axis(Some(px(10)), Some(px(10)), Some(px(20)), none).solve(px(200), px(0), true, &fit),
+        (px(10), px(20), px(0), px(0))
+    );
+}
+
+#[test]
+fn test_sticky_shift() {
+    let px = Au::from_px;
+    let top = (Some(px(10)), None);
+    // Not scrolled past: no shift.
+    assert_eq!(
+        sticky_shift((px(100), px(150)), (px(0), px(1000)), (px(0), px(500)), top),
+        px(0)
+    );
+    // Scrolled past: the box sticks 10px below the top of the view.
+    assert_eq!(
+        sticky_shift((px(100), px(150)), (px(0), px(1000)), (px(300), px(800)), top),
+        px(210)
+    );
+    // But it doesn't leave its containing block.
+    assert_eq!(
+        sticky_shift((px(100), px(150)), (px(0), px(400)), (px(400), px(900)), top),
+        px(250)
+    );
+    // The bottom inset keeps it above the end of the view.
+    assert_eq!(
+        sticky_shift(
+            (px(600), px(650)),
+            (px(0), px(1000)),
+            (px(0), px(500)),
+            (None, Some(px(0)))
+        ),
+        px(-150)
+    );
+}
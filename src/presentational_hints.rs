@@ -0,0 +1,433 @@
+//! Presentational hints: the styling asked for by legacy HTML attributes such as `bgcolor` or
+//! `align`, following the rendering section of the HTML standard.
+//!
+//! The hints are cascaded as author declarations with zero specificity, so any author rule
+//! overrides them.
+
+use css::{named_color, Color, Declaration, Unit, Value};
+use dom::ElementData;
+use property::{self, Token};
+
+/// The declarations the attributes of `elem` map to. `table` is the nearest `table` ancestor,
+/// whose `border` and `cellpadding` attributes style its cells.
+pub fn presentational_hints(elem: &ElementData, table: Option<&ElementData>) -> Vec<Declaration> {
+    let mut hints = Hints(vec![]);
+    let attr = |name: &str| elem.attrs.get(name).map(|value| value.as_str());
+
+    match elem.tag_name.as_str() {
+        "body" => {
+            hints.color("background-color", attr("bgcolor"));
+            hints.color("color", attr("text"));
+            for &(name, property) in &[
+                ("marginheight", "margin-top"),
+                ("marginheight", "margin-bottom"),
+                ("topmargin", "margin-top"),
+                ("bottommargin", "margin-bottom"),
+                ("marginwidth", "margin-left"),
+                ("marginwidth", "margin-right"),
+                ("leftmargin", "margin-left"),
+                ("rightmargin", "margin-right"),
+            ] {
+                hints.pixels(property, attr(name));
+            }
+        }
+        "font" => {
+            hints.color("color", attr("color"));
+            if let Some(face) = attr("face") {
+                hints.font_family(face);
+            }
+            if let Some(size) = attr("size").and_then(legacy_font_size) {
+                hints.add("font-size", vec![Value::Keyword(size.to_string())]);
+            }
+        }
+        "div" | "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "caption" | "legend" => {
+            hints.text_align(attr("align"))
+        }
+        "table" => {
+            hints.dimension("width", attr("width"), false);
+            hints.dimension("height", attr("height"), true);
+            hints.color("background-color", attr("bgcolor"));
+            if let Some(border) = attr("border").map(|b| parse_non_negative_integer(b).unwrap_or(1))
+            {
+                hints.add("border-width", vec![px(border as f64)]);
+                hints.add("border-style", vec![keyword("outset")]);
+            }
+            hints.pixels("border-spacing", attr("cellspacing"));
+            match attr("align").map(|align| align.to_ascii_lowercase()) {
+                Some(ref align) if align == "left" || align == "right" => {
+                    hints.add("float", vec![keyword(align)])
+                }
+                Some(ref align) if align == "center" => {
+                    hints.add("margin-left", vec![keyword("auto")]);
+                    hints.add("margin-right", vec![keyword("auto")]);
+                }
+                _ => {}
+            }
+        }
+        "col" | "colgroup" => hints.dimension("width", attr("width"), false),
+        "td" | "th" => {
+            hints.dimension("width", attr("width"), false);
+            hints.dimension("height", attr("height"), false);
+            hints.color("background-color", attr("bgcolor"));
+            hints.text_align(attr("align"));
+            hints.vertical_align(attr("valign"));
+            if attr("nowrap").is_some() && attr("width").is_none() {
+                hints.add("white-space", vec![keyword("nowrap")]);
+            }
+            if let Some(table) = table {
+                let table_attr = |name: &str| table.attrs.get(name).map(|value| value.as_str());
+                if table_attr("border").map_or(false, |b| parse_non_negative_integer(b) != Some(0))
+                {
+                    hints.add("border-width", vec![px(1.0)]);
+                    hints.add("border-style", vec![keyword("inset")]);
+                }
+                hints.pixels("padding", table_attr("cellpadding"));
+            }
+        }
+        "tr" | "thead" | "tbody" | "tfoot" => {
+            if elem.tag_name == "tr" {
+                hints.dimension("height", attr("height"), true);
+            }
+            hints.color("background-color", attr("bgcolor"));
+            hints.text_align(attr("align"));
+            hints.vertical_align(attr("valign"));
+        }
+        "img" => {
+            hints.dimension("width", attr("width"), true);
+            hints.dimension("height", attr("height"), true);
+            for &(name, property) in &[
+                ("hspace", "margin-left"),
+                ("hspace", "margin-right"),
+                ("vspace", "margin-top"),
+                ("vspace", "margin-bottom"),
+            ] {
+                hints.dimension(property, attr(name), true);
+            }
+            if let Some(border) = attr("border").and_then(parse_non_negative_integer) {
+                hints.add("border-width", vec![px(border as f64)]);
+                hints.add("border-style", vec![keyword("solid")]);
+            }
+            match attr("align").map(|align| align.to_ascii_lowercase()) {
+                Some(ref align) if align == "left" || align == "right" => {
+                    hints.add("float", vec![keyword(align)])
+                }
+                align => hints.vertical_align(align.as_ref().map(|align| align.as_str())),
+            }
+        }
+        "hr" => {
+            match attr("align").map(|align| align.to_ascii_lowercase()) {
+                Some(ref align) if align == "left" => {
+                    hints.add("margin-left", vec![px(0.0)]);
+                    hints.add("margin-right", vec![keyword("auto")]);
+                }
+                Some(ref align) if align == "right" => {
+                    hints.add("margin-left", vec![keyword("auto")]);
+                    hints.add("margin-right", vec![px(0.0)]);
+                }
+                Some(ref align) if align == "center" => {
+                    hints.add("margin-left", vec![keyword("auto")]);
+                    hints.add("margin-right", vec![keyword("auto")]);
+                }
+                _ => {}
+            }
+            if let Some(color) = attr("color").and_then(parse_legacy_color) {
+                hints.add("color", vec![color.clone()]);
+                hints.add("background-color", vec![color]);
+                hints.add("border-style", vec![keyword("solid")]);
+            }
+            if attr("noshade").is_some() {
+                hints.add("border-style", vec![keyword("solid")]);
+                hints.add("background-color", vec![keyword("gray")]);
+            }
+            if let Some(size) = attr("size").and_then(parse_non_negative_integer) {
+                if size > 1 {
+                    hints.add("height", vec![px((size - 2) as f64)]);
+                } else {
+                    hints.add("border-bottom-width", vec![px(0.0)]);
+                }
+            }
+            hints.dimension("width", attr("width"), true);
+        }
+        "ol" | "ul" | "li" => match attr("type").and_then(list_style_type) {
+            Some(list_style_type) => hints.add("list-style-type", vec![keyword(list_style_type)]),
+            None => {}
+        },
+        _ => {}
+    }
+
+    hints.0
+}
+
+struct Hints(Vec<Declaration>);
+
+impl Hints {
+    /// Adds a declaration the way it would be parsed from a stylesheet.
+    fn add(&mut self, name: &str, values: Vec<Value>) {
+        let tokens = values.into_iter().map(Token::Value).collect();
+        self.0
+            .append(&mut property::expand(name, tokens).unwrap_or(vec![]));
+    }
+
+    fn color(&mut self, name: &str, attr: Option<&str>) {
+        if let Some(color) = attr.and_then(parse_legacy_color) {
+            self.add(name, vec![color]);
+        }
+    }
+
+    fn pixels(&mut self, name: &str, attr: Option<&str>) {
+        if let Some(n) = attr.and_then(parse_non_negative_integer) {
+            self.add(name, vec![px(n as f64)]);
+        }
+    }
+
+    fn dimension(&mut self, name: &str, attr: Option<&str>, allow_zero: bool) {
+        match attr.and_then(parse_dimension) {
+            Some(Value::Length(n, _)) if n == 0.0 && !allow_zero => {}
+            Some(dimension) => self.add(name, vec![dimension]),
+            None => {}
+        }
+    }
+
+    fn text_align(&mut self, attr: Option<&str>) {
+        let align = attr.map(|align| align.to_ascii_lowercase());
+        match align.as_ref().map(|align| align.as_str()) {
+            Some(align @ "left") | Some(align @ "right") | Some(align @ "center")
+            | Some(align @ "justify") => self.add("text-align", vec![keyword(align)]),
+            Some("middle") => self.add("text-align", vec![keyword("center")]),
+            _ => {}
+        }
+    }
+
+    fn vertical_align(&mut self, attr: Option<&str>) {
+        let align = attr.map(|align| align.to_ascii_lowercase());
+        match align.as_ref().map(|align| align.as_str()) {
+            Some(align @ "top") | Some(align @ "middle") | Some(align @ "bottom")
+            | Some(align @ "baseline") => self.add("vertical-align", vec![keyword(align)]),
+            Some("center") => self.add("vertical-align", vec![keyword("middle")]),
+            _ => {}
+        }
+    }
+
+    fn font_family(&mut self, face: &str) {
+        let mut tokens = vec![];
+        for name in face.split(',').map(|name| name.trim()).filter(|n| !n.is_empty()) {
+            if !tokens.is_empty() {
+                tokens.push(Token::Comma);
+            }
+            tokens.push(Token::Value(Value::Str(name.to_string())));
+        }
+        if !tokens.is_empty() {
+            self.0
+                .append(&mut property::expand("font-family", tokens).unwrap_or(vec![]));
+        }
+    }
+}
+
+fn keyword(name: &str) -> Value {
+    Value::Keyword(name.to_string())
+}
+
+fn px(n: f64) -> Value {
+    Value::Length(n, Unit::Px)
+}
+
+/// The rules for parsing non-negative integers: leading digits after optional whitespace.
+fn parse_non_negative_integer(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let s = if s.starts_with('+') { &s[1..] } else { s };
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// The rules for parsing dimension values: a number of pixels, or a percentage if the number
+/// is followed by `%`. Trailing garbage is ignored.
+fn parse_dimension(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let integer: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if integer.is_empty() {
+        return None;
+    }
+    let mut rest = &s[integer.len()..];
+    let mut number = integer;
+    if rest.starts_with('.') {
+        let fraction: String = rest[1..].chars().take_while(|c| c.is_ascii_digit()).collect();
+        rest = &rest[1 + fraction.len()..];
+        if !fraction.is_empty() {
+            number = format!("{}.{}", number, fraction);
+        }
+    }
+    let n: f64 = number.parse().ok()?;
+    Some(if rest.starts_with('%') {
+        Value::Length(n, Unit::Percent)
+    } else {
+        Value::Length(n, Unit::Px)
+    })
+}
+
+/// The rules for parsing a legacy color value, which make something of any string, e.g.
+/// `bgcolor="ff0000"` is red.
+fn parse_legacy_color(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if s.is_empty() || s.eq_ignore_ascii_case("transparent") {
+        return None;
+    }
+    if named_color(s.to_ascii_lowercase().as_str()).is_some() {
+        return Some(Value::Keyword(s.to_ascii_lowercase()));
+    }
+    let hex = |s: &str| u8::from_str_radix(s, 16).ok();
+    if s.len() == 4 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+        let digit = |i: usize| hex(&s[i..i + 1]).map(|d| d * 0x11);
+        return Some(Value::Color(Color {
+            r: digit(1)?,
+            g: digit(2)?,
+            b: digit(3)?,
+            a: 0xff,
+        }));
+    }
+
+    let mut digits: Vec<char> = s.chars()
+        .map(|c| if c.is_ascii_hexdigit() { c } else { '0' })
+        .take(128)
+        .collect();
+    if s.starts_with('#') {
+        digits.remove(0);
+    }
+    while digits.is_empty() || digits.len() % 3 != 0 {
+        digits.push('0');
+    }
+    let mut components: Vec<Vec<char>> = digits
+        .chunks(digits.len() / 3)
+        .map(|chunk| chunk.to_vec())
+        .collect();
+    let mut length = components[0].len();
+    if length > 8 {
+        for component in components.iter_mut() {
+            component.drain(0..length - 8);
+        }
+        length = 8;
+    }
+    while length > 2 && components.iter().all(|component| component[0] == '0') {
+        for component in components.iter_mut() {
+            component.remove(0);
+        }
+        length -= 1;
+    }
+    let component = |i: usize| {
+        let digits: String = components[i].iter().take(2).collect();
+        hex(digits.as_str())
+    };
+    Some(Value::Color(Color {
+        r: component(0)?,
+        g: component(1)?,
+        b: component(2)?,
+        a: 0xff,
+    }))
+}
+
+/// The `font-size` keyword for the `size` attribute of `font`, from 1 to 7 or relative to 3.
+fn legacy_font_size(s: &str) -> Option<&'static str> {
+    let s = s.trim();
+    let (sign, digits) = match s.chars().next()? {
+        '+' => (1, &s[1..]),
+        '-' => (-1, &s[1..]),
+        _ => (0, s),
+    };
+    let n = parse_non_negative_integer(digits)? as i64;
+    let size = match sign {
+        0 => n,
+        sign => 3 + sign * n,
+    };
+    Some(match size {
+        size if size <= 1 => "x-small",
+        2 => "small",
+        3 => "medium",
+        4 => "large",
+        5 => "x-large",
+        6 => "xx-large",
+        _ => "xxx-large",
+    })
+}
+
+fn list_style_type(type_: &str) -> Option<&'static str> {
+    Some(match type_ {
+        "1" => "decimal",
+        "a" => "lower-alpha",
+        "A" => "upper-alpha",
+        "i" => "lower-roman",
+        "I" => "upper-roman",
+        _ => match type_.to_ascii_lowercase().as_str() {
+            "disc" => "disc",
+            "circle" => "circle",
+            "square" | "round" => "square",
+            "none" => "none",
+            _ => return None,
+        },
+    })
+}
+
+#[test]
+fn test_presentational_hints() {
+    use css::RED;
+    use std::collections::HashMap;
+
+    let elem = |tag_name: &str, attrs: &[(&str, &str)]| ElementData {
+        tag_name: tag_name.to_string(),
+        layout_type: ::dom::LayoutType::Generic,
+        attrs: attrs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>(),
+    };
+    let hints = |elem: &ElementData, table: Option<&ElementData>| {
+        presentational_hints(elem, table)
+            .into_iter()
+            .map(|decl| (decl.name, decl.values))
+            .collect::<HashMap<_, _>>()
+    };
+
+    let body = hints(&elem("body", &[("bgcolor", "ff0000"), ("text", "white")]), None);
+    assert_eq!(body["background-color"], vec![Value::Color(RED)]);
+    assert_eq!(body["color"], vec![keyword("white")]);
+
+    let font = hints(&elem("font", &[("size", "+2"), ("face", "Arial, Helvetica")]), None);
+    assert_eq!(font["font-size"], vec![keyword("x-large")]);
+    assert_eq!(
+        font["font-family"],
+        vec![Value::Str("Arial".to_string()), Value::Str("Helvetica".to_string())]
+    );
+
+    let table = elem(
+        "table",
+        &[("width", "80%"), ("border", ""), ("cellpadding", "4"), ("align", "center")],
+    );
+    let table_hints = hints(&table, None);
+    assert_eq!(table_hints["width"], vec![Value::Length(80.0, Unit::Percent)]);
+    assert_eq!(table_hints["border-top-width"], vec![px(1.0)]);
+    assert_eq!(table_hints["border-left-style"], vec![keyword("outset")]);
+    assert_eq!(table_hints["margin-left"], vec![keyword("auto")]);
+
+    let td = elem("td", &[("align", "RIGHT"), ("valign", "top"), ("width", "0")]);
+    let td = hints(&td, Some(&table));
+    assert_eq!(td["text-align"], vec![keyword("right")]);
+    assert_eq!(td["vertical-align"], vec![keyword("top")]);
+    assert_eq!(td["padding-right"], vec![px(4.0)]);
+    assert_eq!(td["border-bottom-style"], vec![keyword("inset")]);
+    assert!(!td.contains_key("width"));
+
+    let img = hints(&elem("img", &[("width", "120px"), ("hspace", "5"), ("align", "left")]), None);
+    assert_eq!(img["width"], vec![px(120.0)]);
+    assert_eq!(img["margin-right"], vec![px(5.0)]);
+    assert_eq!(img["float"], vec![keyword("left")]);
+
+    assert_eq!(parse_dimension(" 12.5%"), Some(Value::Length(12.5, Unit::Percent)));
+    assert_eq!(parse_dimension("px"), None);
+    assert_eq!(
+        parse_legacy_color("#0f8"),
+        Some(Value::Color(Color { r: 0, g: 0xff, b: 0x88, a: 0xff }))
+    );
+    // Each third of the digits is a component; only its first two significant digits count.
+    assert_eq!(
+        parse_legacy_color("chucknorris"),
+        Some(Value::Color(Color { r: 0xc0, g: 0, b: 0, a: 0xff }))
+    );
+}
@@ -0,0 +1,1506 @@
+//! The properties naglfar understands: which longhands each shorthand sets and which values the
+//! longhands accept. Declarations are expanded and checked while the stylesheet is parsed, so a
+//! style only ever contains longhands. Properties not listed here are kept as written.
+
+use css::{named_color, Declaration, LengthContext, Unit, Value};
+use style::{GridAreas, GridAutoFlow, GridLine, GridTemplate};
+
+/// A value in a declaration, or one of the separators between values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Value(Value),
+    Comma,
+    Slash,
+}
+
+/// The longhands set by `shorthand`, or None if it is not a shorthand.
+pub fn longhands(shorthand: &str) -> Option<&'static [&'static str]> {
+    Some(match shorthand {
+        "margin" => &["margin-top", "margin-right", "margin-bottom", "margin-left"],
+        "padding" => &["padding-top", "padding-right", "padding-bottom", "padding-left"],
+        "inset" => &["top", "right", "bottom", "left"],
+        "border-width" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+        ],
+        "border-style" => &[
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+        ],
+        "border-color" => &[
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "border-top" => &["border-top-width", "border-top-style", "border-top-color"],
+        "border-right" => &["border-right-width", "border-right-style", "border-right-color"],
+        "border-bottom" => &["border-bottom-width", "border-bottom-style", "border-bottom-color"],
+        "border-left" => &["border-left-width", "border-left-style", "border-left-color"],
+        "border" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "font" => &[
+            "font-style",
+            "font-variant",
+            "font-weight",
+            "font-stretch",
+            "font-size",
+            "line-height",
+            "font-family",
+        ],
+        "background" => &[
+            "background-image",
+            "background-position",
+            "background-size",
+            "background-repeat",
+            "background-attachment",
+            "background-origin",
+            "background-clip",
+            "background-color",
+        ],
+        "list-style" => &["list-style-position", "list-style-type", "list-style-image"],
+        "outline" => &["outline-width", "outline-style", "outline-color"],
+        "flex" => &["flex-grow", "flex-shrink", "flex-basis"],
+        "flex-flow" => &["flex-direction", "flex-wrap"],
+        "gap" | "grid-gap" => &["row-gap", "column-gap"],
+        "overflow" => &["overflow-x", "overflow-y"],
+        "grid-row" => &["grid-row-start", "grid-row-end"],
+        "grid-column" => &["grid-column-start", "grid-column-end"],
+        "grid-area" => &[
+            "grid-row-start",
+            "grid-column-start",
+            "grid-row-end",
+            "grid-column-end",
+        ],
+        "grid-template" => &[
+            "grid-template-rows",
+            "grid-template-columns",
+            "grid-template-areas",
+        ],
+        "place-content" => &["align-content", "justify-content"],
+        "place-items" => &["align-items", "justify-items"],
+        "place-self" => &["align-self", "justify-self"],
+        // Every longhand naglfar knows. `direction` and `unicode-bidi` would be excluded.
+        "all" => &[
+            "display",
+            "float",
+            "clear",
+            "width",
+            "height",
+            "min-width",
+            "min-height",
+            "max-width",
+            "max-height",
+            "box-sizing",
+            "z-index",
+            "position",
+            "top",
+            "right",
+            "bottom",
+            "left",
+            "overflow-x",
+            "overflow-y",
+            "text-overflow",
+            "margin-top",
+            "margin-right",
+            "margin-bottom",
+            "margin-left",
+            "padding-top",
+            "padding-right",
+            "padding-bottom",
+            "padding-left",
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+            "outline-width",
+            "outline-style",
+            "outline-color",
+            "color",
+            "font-style",
+            "font-variant",
+            "font-weight",
+            "font-stretch",
+            "font-size",
+            "line-height",
+            "font-family",
+            "text-align",
+            "word-break",
+            "overflow-wrap",
+            "line-break",
+            "text-decoration",
+            "background-image",
+            "background-position",
+            "background-size",
+            "background-repeat",
+            "background-attachment",
+            "background-origin",
+            "background-clip",
+            "background-color",
+            "list-style-position",
+            "list-style-type",
+            "list-style-image",
+            "flex-direction",
+            "flex-wrap",
+            "justify-content",
+            "align-content",
+            "align-items",
+            "align-self",
+            "row-gap",
+            "column-gap",
+            "order",
+            "flex-grow",
+            "flex-shrink",
+            "flex-basis",
+            "grid-template-columns",
+            "grid-template-rows",
+            "grid-template-areas",
+            "grid-auto-columns",
+            "grid-auto-rows",
+            "grid-auto-flow",
+            "grid-row-start",
+            "grid-row-end",
+            "grid-column-start",
+            "grid-column-end",
+            "justify-items",
+            "justify-self",
+        ],
+        _ => return None,
+    })
+}
+
+/// The initial value of a longhand that belongs to one of the shorthands above.
+pub fn initial_value(longhand: &str) -> Option<Vec<Value>> {
+    let keyword = |k: &str| vec![Value::Keyword(k.to_string())];
+    Some(match longhand {
+        "margin-top" | "margin-right" | "margin-bottom" | "margin-left" | "padding-top"
+        | "padding-right" | "padding-bottom" | "padding-left" => vec![Value::Length(0.0, Unit::Px)],
+        "border-top-width" | "border-right-width" | "border-bottom-width"
+        | "border-left-width" | "outline-width" => keyword("medium"),
+        "border-top-style" | "border-right-style" | "border-bottom-style"
+        | "border-left-style" | "outline-style" => keyword("none"),
+        "border-top-color" | "border-right-color" | "border-bottom-color"
+        | "border-left-color" | "outline-color" => keyword("currentcolor"),
+        "font-style" | "font-variant" | "font-weight" | "font-stretch" | "line-height" => {
+            keyword("normal")
+        }
+        "font-size" => keyword("medium"),
+        "font-family" => keyword("sans-serif"),
+        "background-image" | "list-style-image" => keyword("none"),
+        "background-position" => vec![
+            Value::Length(0.0, Unit::Percent),
+            Value::Length(0.0, Unit::Percent),
+        ],
+        "background-size" => keyword("auto"),
+        "background-repeat" => keyword("repeat"),
+        "background-attachment" => keyword("scroll"),
+        "background-origin" => keyword("padding-box"),
+        "background-clip" => keyword("border-box"),
+        "background-color" => keyword("transparent"),
+        "list-style-position" => keyword("outside"),
+        "list-style-type" => keyword("disc"),
+        "flex-grow" => vec![Value::Num(0.0)],
+        "flex-shrink" => vec![Value::Num(1.0)],
+        "flex-basis" => keyword("auto"),
+        "flex-direction" => keyword("row"),
+        "flex-wrap" => keyword("nowrap"),
+        "row-gap" | "column-gap" => keyword("normal"),
+        "grid-template-rows" | "grid-template-columns" | "grid-template-areas" => {
+            keyword("none")
+        }
+        "grid-row-start" | "grid-row-end" | "grid-column-start" | "grid-column-end" => {
+            keyword("auto")
+        }
+        "top" | "right" | "bottom" | "left" => keyword("auto"),
+        "overflow-x" | "overflow-y" => keyword("visible"),
+        _ => return None,
+    })
+}
+
+/// Expands a declaration into longhand declarations. Returns Err(()) if the value doesn't match
+/// the property's grammar, in which case the declaration must be ignored.
+pub fn expand(name: &str, tokens: Vec<Token>) -> Result<Vec<Declaration>, ()> {
+    if let Some(keyword) = css_wide_keyword(&tokens) {
+        return Ok(match longhands(name) {
+            Some(longhands) => longhands
+                .iter()
+                .map(|longhand| declaration(longhand, vec![keyword.clone()]))
+                .collect(),
+            None => vec![declaration(name, vec![keyword])],
+        });
+    }
+
+    if tokens.is_empty() {
+        return Err(());
+    }
+
+    match name {
+        "margin" | "padding" | "border-width" | "border-style" | "border-color" | "inset" => {
+            expand_sides(longhands(name).unwrap(), &values(tokens)?)
+        }
+        "border-top" | "border-right" | "border-bottom" | "border-left" | "outline" => {
+            expand_any_order(longhands(name).unwrap(), &values(tokens)?)
+        }
+        "border" => expand_border(&values(tokens)?),
+        "font" => expand_font(tokens),
+        "background" => expand_background(tokens),
+        "list-style" => expand_any_order(longhands(name).unwrap(), &values(tokens)?),
+        "flex" => expand_flex(&values(tokens)?),
+        "flex-flow" => expand_any_order(longhands(name).unwrap(), &values(tokens)?),
+        "gap" | "grid-gap" | "overflow" => {
+            expand_pair(longhands(name).unwrap(), &values(tokens)?)
+        }
+        // The old names of the gap properties.
+        "grid-row-gap" => expand_longhand("row-gap", values(tokens)?),
+        "grid-column-gap" => expand_longhand("column-gap", values(tokens)?),
+        // The old name of `overflow-wrap`.
+        "word-wrap" => expand_longhand("overflow-wrap", values(tokens)?),
+        "grid-row" | "grid-column" | "grid-area" => {
+            expand_grid_lines(longhands(name).unwrap(), tokens)
+        }
+        "grid-template" => expand_grid_template(tokens),
+        "place-content" | "place-items" | "place-self" => {
+            expand_place(longhands(name).unwrap(), tokens)
+        }
+        // `all` only accepts the CSS-wide keywords, which are handled above.
+        "all" => Err(()),
+        "font-family" => Ok(vec![declaration(name, parse_font_family(tokens)?)]),
+        _ if name.starts_with("background-") => {
+            // Only the topmost layer is kept, as that is the only one painted.
+            let first_layer = tokens.into_iter().take_while(|t| t != &Token::Comma).collect();
+            expand_longhand(name, values(first_layer)?)
+        }
+        _ => expand_longhand(name, values_without_separators(tokens)),
+    }
+}
+
+/// Expands each of `declarations`, dropping the invalid ones.
+pub fn expand_declarations(declarations: &Vec<Declaration>) -> Vec<Declaration> {
+    let mut expanded = vec![];
+    for decl in declarations {
+        let tokens = decl.values.iter().cloned().map(Token::Value).collect();
+        if let Ok(longhands) = expand(decl.name.as_str(), tokens) {
+            expanded.extend(longhands.into_iter().map(|longhand| Declaration {
+                important: decl.important,
+                ..longhand
+            }));
+        }
+    }
+    expanded
+}
+
+/// Whether `longhand` is inherited when it isn't specified, or is `unset`.
+/// ref. https://www.w3.org/TR/CSS22/propidx.html
+pub fn is_inherited(longhand: &str) -> bool {
+    match longhand {
+        "color" | "font-style" | "font-variant" | "font-weight" | "font-stretch" | "font-size"
+        | "line-height" | "font-family" | "text-align" | "text-indent" | "text-transform"
+        | "letter-spacing" | "word-spacing" | "white-space" | "word-break" | "overflow-wrap"
+        | "line-break" | "list-style-position"
+        | "list-style-type" | "list-style-image" | "visibility" | "cursor" | "quotes"
+        | "direction" | "border-collapse" | "border-spacing" | "caption-side"
+        | "empty-cells" => true,
+        _ => false,
+    }
+}
+
+pub fn is_css_wide_keyword(value: &Value) -> bool {
+    match *value {
+        Value::Keyword(ref k) => match k.as_str() {
+            "inherit" | "initial" | "unset" | "revert" => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn css_wide_keyword(tokens: &Vec<Token>) -> Option<Value> {
+    match tokens.as_slice() {
+        &[Token::Value(ref value)] if is_css_wide_keyword(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn declaration(name: &str, values: Vec<Value>) -> Declaration {
+    Declaration {
+        name: name.to_string(),
+        values: values,
+        important: false,
+    }
+}
+
+fn values(tokens: Vec<Token>) -> Result<Vec<Value>, ()> {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            Token::Value(value) => Ok(value),
+            Token::Comma | Token::Slash => Err(()),
+        })
+        .collect()
+}
+
+fn values_without_separators(tokens: Vec<Token>) -> Vec<Value> {
+    tokens
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Value(value) => Some(value),
+            Token::Comma | Token::Slash => None,
+        })
+        .collect()
+}
+
+fn expand_longhand(name: &str, values: Vec<Value>) -> Result<Vec<Declaration>, ()> {
+    match accepts(name, &values) {
+        Some(false) => Err(()),
+        Some(true) | None if values.is_empty() => Err(()),
+        Some(true) | None => Ok(vec![declaration(name, values)]),
+    }
+}
+
+/// Whether `longhand` accepts `values`, or None if naglfar doesn't know the property.
+fn accepts(longhand: &str, values: &[Value]) -> Option<bool> {
+    if values.iter().any(is_css_wide_keyword) {
+        return Some(values.len() == 1);
+    }
+    let one = |pred: &Fn(&Value) -> bool| values.len() == 1 && pred(&values[0]);
+    let up_to = |n: usize, pred: &Fn(&Value) -> bool| {
+        values.len() >= 1 && values.len() <= n && values.iter().all(|v| pred(v))
+    };
+    Some(match longhand {
+        "display" => one(&|v| {
+            is_keyword(
+                v,
+                &[
+                    "none",
+                    "block",
+                    "inline",
+                    "inline-block",
+                    "list-item",
+                    "flow-root",
+                    "table",
+                    "inline-table",
+                    "table-row-group",
+                    "table-header-group",
+                    "table-footer-group",
+                    "table-row",
+                    "table-cell",
+                    "table-caption",
+                    "table-column",
+                    "table-column-group",
+                    "flex",
+                    "inline-flex",
+                    "grid",
+                    "inline-grid",
+                ],
+            )
+        }),
+        "float" => one(&|v| is_keyword(v, &["left", "right", "none"])),
+        "clear" => one(&|v| is_keyword(v, &["left", "right", "both", "none"])),
+        "width" | "height" => {
+            one(&|v| (is_length_percentage(v) && is_non_negative(v)) || is_keyword(v, &["auto"]))
+        }
+        "margin-top" | "margin-right" | "margin-bottom" | "margin-left" | "top" | "right"
+        | "bottom" | "left" => one(&|v| is_length_percentage(v) || is_keyword(v, &["auto"])),
+        "padding-top" | "padding-right" | "padding-bottom" | "padding-left" => {
+            one(&|v| is_length_percentage(v) && is_non_negative(v))
+        }
+        "min-width" | "min-height" => {
+            one(&|v| (is_length_percentage(v) && is_non_negative(v)) || is_keyword(v, &["auto"]))
+        }
+        "max-width" | "max-height" => {
+            one(&|v| (is_length_percentage(v) && is_non_negative(v)) || is_keyword(v, &["none"]))
+        }
+        "box-sizing" => one(&|v| is_keyword(v, &["content-box", "border-box"])),
+        "border-top-width" | "border-right-width" | "border-bottom-width"
+        | "border-left-width" | "outline-width" => one(&is_line_width),
+        "border-top-style" | "border-right-style" | "border-bottom-style"
+        | "border-left-style" => one(&|v| is_line_style(v)),
+        "outline-style" => one(&|v| {
+            (is_line_style(v) && !is_keyword(v, &["hidden"])) || is_keyword(v, &["auto"])
+        }),
+        "border-top-color" | "border-right-color" | "border-bottom-color"
+        | "border-left-color" | "color" | "background-color" => one(&is_color),
+        "outline-color" => one(&|v| is_color(v) || is_keyword(v, &["invert", "auto"])),
+        "font-style" => one(&|v| is_keyword(v, &["normal", "italic", "oblique"])),
+        "font-variant" => one(&|v| {
+            is_keyword(
+                v,
+                &[
+                    "normal",
+                    "small-caps",
+                    "all-small-caps",
+                    "petite-caps",
+                    "all-petite-caps",
+                    "unicase",
+                    "titling-caps",
+                ],
+            )
+        }),
+        "font-weight" => one(&is_font_weight),
+        "text-align" => one(&|v| {
+            is_keyword(v, &["left", "right", "center", "justify", "start", "end"])
+        }),
+        // The line, its style and its color in any order.
+        "text-decoration" => {
+            one(&|v| is_keyword(v, &["none"])) || up_to(5, &|v| {
+                is_keyword(
+                    v,
+                    &[
+                        "underline",
+                        "overline",
+                        "line-through",
+                        "solid",
+                        "double",
+                        "dotted",
+                        "dashed",
+                        "wavy",
+                    ],
+                ) || is_color(v)
+            })
+        }
+        "font-stretch" => one(&is_font_stretch),
+        "font-size" => one(&is_font_size),
+        "line-height" => one(&is_line_height),
+        "background-image" | "list-style-image" => one(&is_image),
+        "background-position" => up_to(4, &|v| {
+            is_length_percentage(v) || is_keyword(v, &["left", "right", "top", "bottom", "center"])
+        }),
+        "background-size" => {
+            one(&|v| is_keyword(v, &["cover", "contain"]))
+                || up_to(2, &|v| is_length_percentage(v) || is_keyword(v, &["auto"]))
+        }
+        "background-repeat" => {
+            one(&|v| is_keyword(v, &["repeat-x", "repeat-y"]))
+                || up_to(2, &|v| is_keyword(v, &["repeat", "space", "round", "no-repeat"]))
+        }
+        "background-attachment" => one(&|v| is_keyword(v, &["scroll", "fixed", "local"])),
+        "background-origin" => one(&is_box),
+        "background-clip" => one(&|v| is_box(v) || is_keyword(v, &["text"])),
+        "list-style-position" => one(&|v| is_keyword(v, &["inside", "outside"])),
+        "list-style-type" => one(&|v| match *v {
+            Value::Keyword(_) | Value::Str(_) => true,
+            _ => false,
+        }),
+        "border-collapse" => one(&|v| is_keyword(v, &["separate", "collapse"])),
+        "border-spacing" => up_to(2, &|v| is_length(v) && is_non_negative(v)),
+        "table-layout" => one(&|v| is_keyword(v, &["auto", "fixed"])),
+        "vertical-align" => one(&|v| {
+            is_length_percentage(v)
+                || is_keyword(
+                    v,
+                    &[
+                        "baseline",
+                        "sub",
+                        "super",
+                        "text-top",
+                        "text-bottom",
+                        "middle",
+                        "top",
+                        "bottom",
+                    ],
+                )
+        }),
+        "flex-grow" | "flex-shrink" => one(&|v| match *v {
+            Value::Num(n) => n >= 0.0,
+            _ => false,
+        }),
+        "flex-basis" => one(&|v| {
+            (is_length_percentage(v) && is_non_negative(v)) || is_keyword(v, &["auto", "content"])
+        }),
+        "flex-direction" => {
+            one(&|v| is_keyword(v, &["row", "row-reverse", "column", "column-reverse"]))
+        }
+        "flex-wrap" => one(&|v| is_keyword(v, &["nowrap", "wrap", "wrap-reverse"])),
+        "justify-content" | "align-content" => one(&|v| {
+            is_keyword(
+                v,
+                &[
+                    "normal",
+                    "start",
+                    "end",
+                    "flex-start",
+                    "flex-end",
+                    "center",
+                    "left",
+                    "right",
+                    "space-between",
+                    "space-around",
+                    "space-evenly",
+                    "stretch",
+                ],
+            )
+        }),
+        "align-items" | "align-self" => {
+            let is_alignment = |v: &Value| {
+                is_keyword(
+                    v,
+                    &[
+                        "normal",
+                        "stretch",
+                        "start",
+                        "end",
+                        "self-start",
+                        "self-end",
+                        "flex-start",
+                        "flex-end",
+                        "center",
+                        "baseline",
+                    ],
+                ) || (longhand == "align-self" && is_keyword(v, &["auto"]))
+            };
+            one(&is_alignment) || match values {
+                &[ref first, ref baseline] => {
+                    is_keyword(first, &["first", "last"]) && is_keyword(baseline, &["baseline"])
+                }
+                _ => false,
+            }
+        }
+        "row-gap" | "column-gap" => {
+            one(&|v| (is_length_percentage(v) && is_non_negative(v)) || is_keyword(v, &["normal"]))
+        }
+        "order" => one(&|v| match *v {
+            Value::Num(n) => n == n.trunc(),
+            _ => false,
+        }),
+        "z-index" => one(&|v| match *v {
+            Value::Num(n) => n == n.trunc(),
+            _ => is_keyword(v, &["auto"]),
+        }),
+        "position" => one(&|v| {
+            is_keyword(v, &["static", "relative", "absolute", "fixed", "sticky"])
+        }),
+        "overflow-x" | "overflow-y" => {
+            one(&|v| is_keyword(v, &["visible", "hidden", "clip", "scroll", "auto"]))
+        }
+        "text-overflow" => one(&|v| is_keyword(v, &["clip", "ellipsis"])),
+        "word-break" => one(&|v| is_keyword(v, &["normal", "break-all", "keep-all", "break-word"])),
+        "overflow-wrap" => one(&|v| is_keyword(v, &["normal", "break-word", "anywhere"])),
+        "line-break" => one(&|v| {
+            is_keyword(v, &["auto", "loose", "normal", "strict", "anywhere"])
+        }),
+        "grid-template-columns" | "grid-template-rows" => {
+            GridTemplate::from_values(values, &LengthContext::default()).is_some()
+        }
+        "grid-template-areas" => GridAreas::from_values(values).is_some(),
+        "grid-auto-columns" | "grid-auto-rows" => values
+            .iter()
+            .all(|v| v.to_track_size(&LengthContext::default()).is_some()),
+        "grid-auto-flow" => GridAutoFlow::from_values(values).is_some(),
+        "grid-row-start" | "grid-row-end" | "grid-column-start" | "grid-column-end" => {
+            GridLine::from_values(values).is_some()
+        }
+        "justify-items" | "justify-self" => {
+            let is_alignment = |v: &Value| {
+                is_keyword(
+                    v,
+                    &[
+                        "normal",
+                        "stretch",
+                        "start",
+                        "end",
+                        "self-start",
+                        "self-end",
+                        "flex-start",
+                        "flex-end",
+                        "center",
+                        "left",
+                        "right",
+                        "baseline",
+                    ],
+                ) || (longhand == "justify-items" && is_keyword(v, &["legacy"]))
+                    || (longhand == "justify-self" && is_keyword(v, &["auto"]))
+            };
+            one(&is_alignment) || match values {
+                &[ref first, ref baseline] => {
+                    is_keyword(first, &["first", "last"]) && is_keyword(baseline, &["baseline"])
+                }
+                _ => false,
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// `margin: 1px 2px` and the like: one to four values for the top, right, bottom and left sides.
+fn expand_sides(longhands: &[&str], values: &Vec<Value>) -> Result<Vec<Declaration>, ()> {
+    let sides = match values.len() {
+        1 => [0, 0, 0, 0],
+        2 => [0, 1, 0, 1],
+        3 => [0, 1, 2, 1],
+        4 => [0, 1, 2, 3],
+        _ => return Err(()),
+    };
+    let mut decls = vec![];
+    for (longhand, &i) in longhands.iter().zip(sides.iter()) {
+        decls.append(&mut expand_longhand(longhand, vec![values[i].clone()])?);
+    }
+    Ok(decls)
+}
+
+/// `a || b || c`: each value sets the first of `longhands` that accepts it and hasn't been set
+/// yet. Longhands without a value are reset to their initial values.
+fn expand_any_order(longhands: &[&str], values: &Vec<Value>) -> Result<Vec<Declaration>, ()> {
+    let mut set: Vec<Option<Value>> = vec![None; longhands.len()];
+    for value in values {
+        let i = (0..longhands.len())
+            .find(|&i| set[i].is_none() && accepts(longhands[i], &[value.clone()]) == Some(true))
+            .ok_or(())?;
+        set[i] = Some(value.clone());
+    }
+    Ok(longhands
+        .iter()
+        .zip(set.into_iter())
+        .map(|(longhand, value)| {
+            declaration(
+                longhand,
+                value.map_or_else(|| initial_value(longhand).unwrap(), |value| vec![value]),
+            )
+        })
+        .collect())
+}
+
+fn expand_border(values: &Vec<Value>) -> Result<Vec<Declaration>, ()> {
+    let side = expand_any_order(longhands("border-top").unwrap(), values)?;
+    let (width, style, color) = (&side[0].values, &side[1].values, &side[2].values);
+    Ok(longhands("border")
+        .unwrap()
+        .iter()
+        .map(|longhand| {
+            declaration(
+                longhand,
+                if longhand.ends_with("-width") {
+                    width.clone()
+                } else if longhand.ends_with("-style") {
+                    style.clone()
+                } else {
+                    color.clone()
+                },
+            )
+        })
+        .collect())
+}
+
+/// `font: [style || variant || weight || stretch]? size [/ line-height]? family`, or a system
+/// font keyword.
+fn expand_font(tokens: Vec<Token>) -> Result<Vec<Declaration>, ()> {
+    let longhands = longhands("font").unwrap();
+    let initial = |longhand: &str| initial_value(longhand).unwrap();
+
+    if let &[Token::Value(Value::Keyword(ref k))] = tokens.as_slice() {
+        match k.as_str() {
+            "caption" | "icon" | "menu" | "message-box" | "small-caption" | "status-bar" => {
+                return Ok(longhands
+                    .iter()
+                    .map(|longhand| match *longhand {
+                        "font-family" => {
+                            declaration(longhand, vec![Value::Keyword("system-ui".to_string())])
+                        }
+                        _ => declaration(longhand, initial(longhand)),
+                    })
+                    .collect())
+            }
+            _ => {}
+        }
+    }
+
+    let mut tokens = tokens.into_iter().peekable();
+
+    // The first four longhands may come in any order before the font size. `normal` can be any
+    // of them, so it just leaves one of them at its initial value.
+    let mut prefix = vec![];
+    let size = loop {
+        match tokens.next() {
+            Some(Token::Value(value)) => {
+                if prefix.len() < 4 && !is_font_size(&value) {
+                    prefix.push(value);
+                } else {
+                    break value;
+                }
+            }
+            _ => return Err(()),
+        }
+    };
+    let prefix: Vec<Value> = prefix
+        .into_iter()
+        .filter(|v| !is_keyword(v, &["normal"]))
+        .collect();
+    let mut decls = expand_any_order(&longhands[0..4], &prefix)?;
+    decls.append(&mut expand_longhand("font-size", vec![size])?);
+
+    let line_height = if tokens.peek() == Some(&Token::Slash) {
+        tokens.next();
+        match tokens.next() {
+            Some(Token::Value(value)) => value,
+            _ => return Err(()),
+        }
+    } else {
+        Value::Keyword("normal".to_string())
+    };
+    decls.append(&mut expand_longhand("line-height", vec![line_height])?);
+
+    decls.push(declaration("font-family", parse_font_family(tokens.collect())?));
+    Ok(decls)
+}
+
+/// A comma-separated list of family names. Unquoted names may consist of several identifiers.
+/// Generic families are kept as keywords and other names become strings.
+fn parse_font_family(tokens: Vec<Token>) -> Result<Vec<Value>, ()> {
+    let mut families = vec![];
+    for family in tokens.split(|token| token == &Token::Comma) {
+        let family = values(family.to_vec())?;
+        families.push(match family.as_slice() {
+            &[Value::Str(ref name)] => Value::Str(name.clone()),
+            &[Value::Keyword(ref name)] if is_generic_family(name) => Value::Keyword(name.clone()),
+            _ => {
+                let mut names = vec![];
+                for value in &family {
+                    match *value {
+                        Value::Keyword(ref name) if !is_css_wide_keyword(value) => {
+                            names.push(name.clone())
+                        }
+                        _ => return Err(()),
+                    }
+                }
+                if names.is_empty() {
+                    return Err(());
+                }
+                Value::Str(names.join(" "))
+            }
+        });
+    }
+    Ok(families)
+}
+
+pub fn is_generic_family(name: &str) -> bool {
+    match name {
+        "serif" | "sans-serif" | "monospace" | "cursive" | "fantasy" | "system-ui" | "math"
+        | "emoji" | "fangsong" | "ui-serif" | "ui-sans-serif" | "ui-monospace"
+        | "ui-rounded" => true,
+        _ => false,
+    }
+}
+
+/// `background: [layer,]* final-layer`, where only the final layer may have a color. Only the
+/// topmost (first) layer is kept, as that is the only one painted.
+fn expand_background(tokens: Vec<Token>) -> Result<Vec<Declaration>, ()> {
+    let layers: Vec<&[Token]> = tokens.split(|token| token == &Token::Comma).collect();
+    let last = layers.len() - 1;
+    let mut first_layer = None;
+    let mut color = None;
+    for (i, layer) in layers.iter().enumerate() {
+        let (decls, layer_color) = parse_background_layer(layer)?;
+        if layer_color.is_some() && i != last {
+            return Err(());
+        }
+        color = layer_color;
+        first_layer.get_or_insert(decls);
+    }
+    let mut decls = first_layer.unwrap();
+    decls.push(declaration(
+        "background-color",
+        color.map_or_else(|| initial_value("background-color").unwrap(), |color| vec![color]),
+    ));
+    Ok(decls)
+}
+
+/// Returns the declarations for the longhands but `background-color`, and the color if any.
+fn parse_background_layer(layer: &[Token]) -> Result<(Vec<Declaration>, Option<Value>), ()> {
+    let is_position = |v: &Value| {
+        is_length_percentage(v) || is_keyword(v, &["left", "right", "top", "bottom", "center"])
+    };
+    let is_size = |v: &Value| is_length_percentage(v) || is_keyword(v, &["auto"]);
+    let is_repeat = |v: &Value| is_keyword(v, &["repeat", "space", "round", "no-repeat"]);
+
+    let (mut image, mut position, mut size, mut repeat) = (None, None, None, None);
+    let (mut attachment, mut boxes, mut color) = (None, vec![], None);
+
+    // Takes up to `max` values from `i` on that satisfy `pred`.
+    let take_while = |i: &mut usize, max: usize, pred: &Fn(&Value) -> bool| {
+        let mut values = vec![];
+        while let Some(&Token::Value(ref value)) = layer.get(*i) {
+            if values.len() == max || !pred(value) {
+                break;
+            }
+            values.push(value.clone());
+            *i += 1;
+        }
+        values
+    };
+
+    let mut i = 0;
+    while i < layer.len() {
+        let value = match layer[i] {
+            Token::Value(ref value) => value.clone(),
+            _ => return Err(()),
+        };
+
+        if image.is_none() && is_image(&value) {
+            image = Some(vec![value]);
+            i += 1;
+        } else if position.is_none() && is_position(&value) {
+            position = Some(take_while(&mut i, 4, &is_position));
+            if layer.get(i) == Some(&Token::Slash) {
+                i += 1;
+                let sizes = match layer.get(i) {
+                    Some(&Token::Value(ref v)) if is_keyword(v, &["cover", "contain"]) => {
+                        i += 1;
+                        vec![v.clone()]
+                    }
+                    _ => take_while(&mut i, 2, &is_size),
+                };
+                if sizes.is_empty() {
+                    return Err(());
+                }
+                size = Some(sizes);
+            }
+        } else if repeat.is_none() && is_keyword(&value, &["repeat-x", "repeat-y"]) {
+            repeat = Some(vec![value]);
+            i += 1;
+        } else if repeat.is_none() && is_repeat(&value) {
+            repeat = Some(take_while(&mut i, 2, &is_repeat));
+        } else if attachment.is_none() && is_keyword(&value, &["scroll", "fixed", "local"]) {
+            attachment = Some(vec![value]);
+            i += 1;
+        } else if boxes.len() < 2 && is_box(&value) {
+            boxes.push(value);
+            i += 1;
+        } else if color.is_none() && is_color(&value) {
+            color = Some(value);
+            i += 1;
+        } else {
+            return Err(());
+        }
+    }
+
+    // A single box sets both the origin and the clip.
+    let origin = boxes.get(0).map(|b| vec![b.clone()]);
+    let clip = boxes.get(1).or(boxes.get(0)).map(|b| vec![b.clone()]);
+    let decls = [
+        ("background-image", image),
+        ("background-position", position),
+        ("background-size", size),
+        ("background-repeat", repeat),
+        ("background-attachment", attachment),
+        ("background-origin", origin),
+        ("background-clip", clip),
+    ].iter()
+        .map(|&(longhand, ref values)| {
+            declaration(
+                longhand,
+                values
+                    .clone()
+                    .unwrap_or_else(|| initial_value(longhand).unwrap()),
+            )
+        })
+        .collect();
+    Ok((decls, color))
+}
+
+/// `flex: none | auto | [grow shrink?] || basis`
+fn expand_flex(values: &Vec<Value>) -> Result<Vec<Declaration>, ()> {
+    let auto = || Value::Keyword("auto".to_string());
+    let (grow, shrink, basis) = match values.as_slice() {
+        &[ref v] if is_keyword(v, &["none"]) => (Value::Num(0.0), Value::Num(0.0), auto()),
+        &[ref v] if is_keyword(v, &["auto"]) => (Value::Num(1.0), Value::Num(1.0), auto()),
+        _ => {
+            let (mut grow, mut shrink, mut basis) = (None, None, None);
+            let mut follows_grow = false;
+            for value in values {
+                // A unitless zero is a flex factor unless both factors are already given.
+                follows_grow = match *value {
+                    Value::Num(_) if grow.is_none() => {
+                        grow = Some(value.clone());
+                        true
+                    }
+                    Value::Num(_) if follows_grow => {
+                        shrink = Some(value.clone());
+                        false
+                    }
+                    _ if basis.is_none() => {
+                        basis = Some(value.clone());
+                        false
+                    }
+                    _ => return Err(()),
+                };
+            }
+            (
+                grow.unwrap_or(Value::Num(1.0)),
+                shrink.unwrap_or(Value::Num(1.0)),
+                basis.unwrap_or(Value::Length(0.0, Unit::Percent)),
+            )
+        }
+    };
+    let mut decls = expand_longhand("flex-grow", vec![grow])?;
+    decls.append(&mut expand_longhand("flex-shrink", vec![shrink])?);
+    decls.append(&mut expand_longhand("flex-basis", vec![basis])?);
+    Ok(decls)
+}
+
+/// `gap: row-gap column-gap?` and `overflow: overflow-x overflow-y?`: the first value sets both
+/// longhands unless there is a second one for the other.
+fn expand_pair(longhands: &[&str], values: &Vec<Value>) -> Result<Vec<Declaration>, ()> {
+    let (first, second) = match values.as_slice() {
+        &[ref both] => (both, both),
+        &[ref first, ref second] => (first, second),
+        _ => return Err(()),
+    };
+    let mut decls = expand_longhand(longhands[0], vec![first.clone()])?;
+    decls.append(&mut expand_longhand(longhands[1], vec![second.clone()])?);
+    Ok(decls)
+}
+
+/// `grid-row` and `grid-column`: `start [/ end]?`, and `grid-area`: up to four lines split by
+/// slashes. A missing line is the name of the line it comes after in the same direction if that
+/// is a name, otherwise `auto`.
+fn expand_grid_lines(longhands: &[&str], tokens: Vec<Token>) -> Result<Vec<Declaration>, ()> {
+    let lines: Vec<Vec<Value>> = tokens
+        .split(|token| token == &Token::Slash)
+        .map(|line| values(line.to_vec()))
+        .collect::<Result<_, ()>>()?;
+    if lines.len() > longhands.len() {
+        return Err(());
+    }
+    let mut decls: Vec<Declaration> = vec![];
+    for (i, longhand) in longhands.iter().enumerate() {
+        let line = match lines.get(i) {
+            Some(line) => line.clone(),
+            None => {
+                // The line across the item in `grid-area`, or else the item's other end.
+                let other = if i >= 2 { i - 2 } else { 0 };
+                match decls[other].values.as_slice() {
+                    &[Value::Keyword(ref name)] if name != "auto" && name != "span" => {
+                        decls[other].values.clone()
+                    }
+                    _ => initial_value(longhand).unwrap(),
+                }
+            }
+        };
+        decls.append(&mut expand_longhand(longhand, line)?);
+    }
+    Ok(decls)
+}
+
+/// `grid-template: none | rows / columns | [names? "row" size? names?]+ [/ columns]?`
+fn expand_grid_template(tokens: Vec<Token>) -> Result<Vec<Declaration>, ()> {
+    let none = || vec![Value::Keyword("none".to_string())];
+    let parts: Vec<Vec<Value>> = tokens
+        .split(|token| token == &Token::Slash)
+        .map(|part| values(part.to_vec()))
+        .collect::<Result<_, ()>>()?;
+    let (rows, columns) = match parts.as_slice() {
+        &[ref rows] if rows == &none() => (none(), none()),
+        &[ref rows] => (rows.clone(), none()),
+        &[ref rows, ref columns] => (rows.clone(), columns.clone()),
+        _ => return Err(()),
+    };
+
+    let has_areas = rows.iter().any(|v| match *v {
+        Value::Str(_) => true,
+        _ => false,
+    });
+    if !has_areas {
+        if parts.len() == 1 && rows != none() {
+            return Err(());
+        }
+        let mut decls = expand_longhand("grid-template-rows", rows)?;
+        decls.append(&mut expand_longhand("grid-template-columns", columns)?);
+        decls.push(declaration("grid-template-areas", none()));
+        return Ok(decls);
+    }
+
+    // Each string is a row of areas, followed by the row's size, `auto` if missing.
+    let (mut row_sizes, mut areas) = (vec![], vec![]);
+    let mut missing_size = false;
+    for value in rows {
+        match value {
+            Value::Str(_) => {
+                if missing_size {
+                    row_sizes.push(Value::Keyword("auto".to_string()));
+                }
+                missing_size = true;
+                areas.push(value);
+            }
+            Value::LineNames(_) => {
+                if missing_size {
+                    row_sizes.push(Value::Keyword("auto".to_string()));
+                    missing_size = false;
+                }
+                row_sizes.push(value);
+            }
+            _ if missing_size => {
+                row_sizes.push(value);
+                missing_size = false;
+            }
+            _ => return Err(()),
+        }
+    }
+    if missing_size {
+        row_sizes.push(Value::Keyword("auto".to_string()));
+    }
+    let mut decls = expand_longhand("grid-template-rows", row_sizes)?;
+    decls.append(&mut expand_longhand("grid-template-columns", columns)?);
+    decls.append(&mut expand_longhand("grid-template-areas", areas)?);
+    Ok(decls)
+}
+
+/// `place-content`, `place-items` and `place-self`: the alignment in the block direction, then
+/// the one in the inline direction, which is the same if missing.
+fn expand_place(longhands: &[&str], tokens: Vec<Token>) -> Result<Vec<Declaration>, ()> {
+    let values = values(tokens)?;
+    // `first baseline` and `last baseline` are one value.
+    let mut alignments: Vec<Vec<Value>> = vec![];
+    for value in values {
+        match alignments.last_mut() {
+            Some(ref mut last) if last.len() == 1 && is_keyword(&last[0], &["first", "last"])
+                && is_keyword(&value, &["baseline"]) =>
+            {
+                last.push(value);
+                continue;
+            }
+            _ => {}
+        }
+        alignments.push(vec![value]);
+    }
+    let (block, inline) = match alignments.as_slice() {
+        &[ref both] => (both.clone(), both.clone()),
+        &[ref block, ref inline] => (block.clone(), inline.clone()),
+        _ => return Err(()),
+    };
+    let mut decls = expand_longhand(longhands[0], block)?;
+    decls.append(&mut expand_longhand(longhands[1], inline)?);
+    Ok(decls)
+}
+
+fn is_keyword(value: &Value, keywords: &[&str]) -> bool {
+    match *value {
+        Value::Keyword(ref k) => keywords.contains(&k.as_str()),
+        _ => false,
+    }
+}
+
+fn is_length(value: &Value) -> bool {
+    match *value {
+        Value::Length(_, Unit::Percent) | Value::Length(_, Unit::Fr) => false,
+        Value::Length(_, _) => true,
+        Value::Num(n) => n == 0.0,
+        _ => false,
+    }
+}
+
+fn is_length_percentage(value: &Value) -> bool {
+    match *value {
+        Value::Length(_, Unit::Percent) => true,
+        _ => is_length(value),
+    }
+}
+
+fn is_non_negative(value: &Value) -> bool {
+    match *value {
+        Value::Length(n, _) | Value::Num(n) => n >= 0.0,
+        _ => true,
+    }
+}
+
+fn is_color(value: &Value) -> bool {
+    match *value {
+        Value::Color(_) => true,
+        Value::Keyword(ref k) => k == "currentcolor" || named_color(k).is_some(),
+        _ => false,
+    }
+}
+
+fn is_line_width(value: &Value) -> bool {
+    (is_length(value) && is_non_negative(value)) || is_keyword(value, &["thin", "medium", "thick"])
+}
+
+fn is_line_style(value: &Value) -> bool {
+    is_keyword(
+        value,
+        &[
+            "none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset",
+            "outset",
+        ],
+    )
+}
+
+fn is_box(value: &Value) -> bool {
+    is_keyword(value, &["border-box", "padding-box", "content-box"])
+}
+
+fn is_image(value: &Value) -> bool {
+    match *value {
+        Value::Url(_) => true,
+        // Gradients are not drawn but still replace the image they override.
+        _ => is_keyword(
+            value,
+            &[
+                "none",
+                "linear-gradient",
+                "radial-gradient",
+                "conic-gradient",
+                "repeating-linear-gradient",
+                "repeating-radial-gradient",
+                "repeating-conic-gradient",
+            ],
+        ),
+    }
+}
+
+fn is_font_weight(value: &Value) -> bool {
+    match *value {
+        Value::Num(n) => 1.0 <= n && n <= 1000.0,
+        _ => is_keyword(value, &["normal", "bold", "bolder", "lighter"]),
+    }
+}
+
+fn is_font_stretch(value: &Value) -> bool {
+    match *value {
+        Value::Length(n, Unit::Percent) => n >= 0.0,
+        _ => is_keyword(
+            value,
+            &[
+                "normal",
+                "ultra-condensed",
+                "extra-condensed",
+                "condensed",
+                "semi-condensed",
+                "semi-expanded",
+                "expanded",
+                "extra-expanded",
+                "ultra-expanded",
+            ],
+        ),
+    }
+}
+
+fn is_font_size(value: &Value) -> bool {
+    (is_length_percentage(value) && is_non_negative(value)) || is_keyword(
+        value,
+        &[
+            "xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large", "xxx-large",
+            "larger", "smaller",
+        ],
+    )
+}
+
+fn is_line_height(value: &Value) -> bool {
+    match *value {
+        Value::Num(n) => n >= 0.0,
+        _ => {
+            (is_length_percentage(value) && is_non_negative(value))
+                || is_keyword(value, &["normal"])
+        }
+    }
+}
+
+#[test]
+fn test_box_shorthands() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style("margin: 1px auto 3px; padding: 1px 2px 3px 4px 5px".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("margin-top", vec![Value::Length(1.0, Unit::Px)]),
+            declaration("margin-right", vec![Value::Keyword("auto".to_string())]),
+            declaration("margin-bottom", vec![Value::Length(3.0, Unit::Px)]),
+            declaration("margin-left", vec![Value::Keyword("auto".to_string())]),
+        ]
+    );
+
+    let decls = parse_attr_style("border-left: red 2px dashed".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("border-left-width", vec![Value::Length(2.0, Unit::Px)]),
+            declaration("border-left-style", vec![Value::Keyword("dashed".to_string())]),
+            declaration("border-left-color", vec![Value::Keyword("red".to_string())]),
+        ]
+    );
+
+    let decls = parse_attr_style("border: solid; border: 1px 2px".to_string());
+    assert_eq!(decls.len(), 12);
+    assert!(decls.iter().all(|decl| !decl.name.ends_with("-width")
+        || decl.values == vec![Value::Keyword("medium".to_string())]));
+}
+
+#[test]
+fn test_font_shorthand() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style(
+        "font: italic bold 12px/1.5 \"Helvetica Neue\", Times New Roman, serif".to_string(),
+    );
+    let values: Vec<(&str, Vec<Value>)> = decls
+        .iter()
+        .map(|decl| (decl.name.as_str(), decl.values.clone()))
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            ("font-style", vec![Value::Keyword("italic".to_string())]),
+            ("font-variant", vec![Value::Keyword("normal".to_string())]),
+            ("font-weight", vec![Value::Keyword("bold".to_string())]),
+            ("font-stretch", vec![Value::Keyword("normal".to_string())]),
+            ("font-size", vec![Value::Length(12.0, Unit::Px)]),
+            ("line-height", vec![Value::Num(1.5)]),
+            (
+                "font-family",
+                vec![
+                    Value::Str("Helvetica Neue".to_string()),
+                    Value::Str("times new roman".to_string()),
+                    Value::Keyword("serif".to_string()),
+                ],
+            ),
+        ]
+    );
+
+    // The font size and family are required.
+    assert_eq!(parse_attr_style("font: bold serif".to_string()), vec![]);
+    assert_eq!(parse_attr_style("font: 12px".to_string()), vec![]);
+}
+
+#[test]
+fn test_longhand_values() {
+    use css::parse_attr_style;
+
+    // An invalid declaration doesn't override a valid one before it.
+    let decls = parse_attr_style(
+        "width: 10px; width: -5px; display: flex; display: blocky; float: center; \
+         clear: up; text-align: middle; text-decoration: underline red; text-decoration: 1px"
+            .to_string(),
+    );
+    assert_eq!(
+        decls,
+        vec![
+            declaration("width", vec![Value::Length(10.0, Unit::Px)]),
+            declaration("display", vec![Value::Keyword("flex".to_string())]),
+            declaration(
+                "text-decoration",
+                vec![
+                    Value::Keyword("underline".to_string()),
+                    Value::Keyword("red".to_string()),
+                ],
+            ),
+        ]
+    );
+    assert_eq!(parse_attr_style("height: auto 1px".to_string()), vec![]);
+    assert_eq!(
+        parse_attr_style("min-width: -1px; max-width: auto; box-sizing: padding-box".to_string()),
+        vec![]
+    );
+}
+
+#[test]
+fn test_background_shorthand() {
+    use css::parse_attr_style;
+
+    let src = "background: url(a.png) no-repeat center / cover, white";
+    let decls = parse_attr_style(src.to_string());
+    let values: Vec<Vec<Value>> = decls.into_iter().map(|decl| decl.values).collect();
+    assert_eq!(values[0], vec![Value::Url("a.png".to_string())]);
+    assert_eq!(values[1], vec![Value::Keyword("center".to_string())]);
+    assert_eq!(values[2], vec![Value::Keyword("cover".to_string())]);
+    assert_eq!(values[3], vec![Value::Keyword("no-repeat".to_string())]);
+    assert_eq!(values[7], vec![Value::Keyword("white".to_string())]);
+    // Only the final layer can have a color.
+    assert_eq!(parse_attr_style("background: red, url(a.png)".to_string()), vec![]);
+}
+
+#[test]
+fn test_list_style_shorthand() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style("list-style: none inside".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("list-style-position", vec![Value::Keyword("inside".to_string())]),
+            declaration("list-style-type", vec![Value::Keyword("none".to_string())]),
+            declaration("list-style-image", vec![Value::Keyword("none".to_string())]),
+        ]
+    );
+}
+
+#[test]
+fn test_flex_shorthands() {
+    use css::parse_attr_style;
+
+    let flex = |src: &str| {
+        parse_attr_style(format!("flex: {}", src))
+            .into_iter()
+            .map(|decl| decl.values[0].clone())
+            .collect::<Vec<Value>>()
+    };
+    assert_eq!(
+        flex("2"),
+        vec![Value::Num(2.0), Value::Num(1.0), Value::Length(0.0, Unit::Percent)]
+    );
+    assert_eq!(
+        flex("1 0 10px"),
+        vec![Value::Num(1.0), Value::Num(0.0), Value::Length(10.0, Unit::Px)]
+    );
+    assert_eq!(
+        flex("none"),
+        vec![Value::Num(0.0), Value::Num(0.0), Value::Keyword("auto".to_string())]
+    );
+    assert_eq!(flex("1 2 3 4"), vec![]);
+
+    let decls = parse_attr_style("flex-flow: wrap column; gap: 1px 10%".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("flex-direction", vec![Value::Keyword("column".to_string())]),
+            declaration("flex-wrap", vec![Value::Keyword("wrap".to_string())]),
+            declaration("row-gap", vec![Value::Length(1.0, Unit::Px)]),
+            declaration("column-gap", vec![Value::Length(10.0, Unit::Percent)]),
+        ]
+    );
+    assert_eq!(parse_attr_style("gap: -1px; flex-flow: row column".to_string()), vec![]);
+}
+
+#[test]
+fn test_position_shorthands() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style("inset: 1px auto; position: sticky".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("top", vec![Value::Length(1.0, Unit::Px)]),
+            declaration("right", vec![Value::Keyword("auto".to_string())]),
+            declaration("bottom", vec![Value::Length(1.0, Unit::Px)]),
+            declaration("left", vec![Value::Keyword("auto".to_string())]),
+            declaration("position", vec![Value::Keyword("sticky".to_string())]),
+        ]
+    );
+    assert_eq!(parse_attr_style("position: float; z-index: 1.5".to_string()), vec![]);
+}
+
+#[test]
+fn test_overflow_shorthand() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style("overflow: hidden auto; text-overflow: ellipsis".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("overflow-x", vec![Value::Keyword("hidden".to_string())]),
+            declaration("overflow-y", vec![Value::Keyword("auto".to_string())]),
+            declaration("text-overflow", vec![Value::Keyword("ellipsis".to_string())]),
+        ]
+    );
+    assert_eq!(parse_attr_style("overflow: auto auto auto".to_string()), vec![]);
+}
+
+#[test]
+fn test_text_wrapping_properties() {
+    use css::parse_attr_style;
+
+    // `word-wrap` is the old name of `overflow-wrap`.
+    let decls = parse_attr_style("word-wrap: break-word; line-break: strict".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("overflow-wrap", vec![Value::Keyword("break-word".to_string())]),
+            declaration("line-break", vec![Value::Keyword("strict".to_string())]),
+        ]
+    );
+    assert_eq!(parse_attr_style("word-break: break-word anywhere".to_string()), vec![]);
+}
+
+#[test]
+fn test_outline_shorthand() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style("outline: inherit".to_string());
+    assert_eq!(decls.len(), 3);
+    assert!(
+        decls
+            .iter()
+            .all(|decl| decl.values == vec![Value::Keyword("inherit".to_string())])
+    );
+}
+
+#[test]
+fn test_all_shorthand() {
+    use css::parse_attr_style;
+
+    let decls = parse_attr_style("all: unset; all: none".to_string());
+    assert_eq!(decls.len(), longhands("all").unwrap().len());
+    assert!(decls.iter().any(|decl| decl.name == "display"));
+    assert!(
+        decls
+            .iter()
+            .all(|decl| decl.values == vec![Value::Keyword("unset".to_string())])
+    );
+}
+
+#[test]
+fn test_grid_shorthands() {
+    use css::parse_attr_style;
+
+    let keyword = |keyword: &str| Value::Keyword(keyword.to_string());
+    let decls = parse_attr_style("grid-area: main / 2".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("grid-row-start", vec![keyword("main")]),
+            declaration("grid-column-start", vec![Value::Num(2.0)]),
+            declaration("grid-row-end", vec![keyword("main")]),
+            declaration("grid-column-end", vec![keyword("auto")]),
+        ]
+    );
+
+    let decls = parse_attr_style("grid-template: \"a b\" 10px / 1fr auto".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("grid-template-rows", vec![Value::Length(10.0, Unit::Px)]),
+            declaration(
+                "grid-template-columns",
+                vec![Value::Length(1.0, Unit::Fr), keyword("auto")],
+            ),
+            declaration("grid-template-areas", vec![Value::Str("a b".to_string())]),
+        ]
+    );
+    // The areas must be rectangles.
+    let src = "grid-template-areas: \"a b\" \"b a\"";
+    assert_eq!(parse_attr_style(src.to_string()), vec![]);
+    assert_eq!(parse_attr_style("grid-auto-rows: 1fr 1px".to_string()).len(), 1);
+    assert_eq!(parse_attr_style("grid-template-columns: 1fr 1fr 1px".to_string()).len(), 1);
+    assert_eq!(parse_attr_style("grid-template-columns: -1px".to_string()), vec![]);
+
+    let decls = parse_attr_style("place-items: center start".to_string());
+    assert_eq!(
+        decls,
+        vec![
+            declaration("align-items", vec![keyword("center")]),
+            declaration("justify-items", vec![keyword("start")]),
+        ]
+    );
+}
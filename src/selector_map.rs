@@ -0,0 +1,549 @@
+//! Selector matching.
+//!
+//! Instead of trying every rule on every element, the selectors of a stylesheet are bucketed
+//! in a `SelectorMap` by the id, class or tag name their subject needs, so only the buckets
+//! the element could match are looked at. The ancestors of the element are summarized in a
+//! Bloom filter (`Ancestors`), which rejects most selectors whose combinators need ancestors
+//! the element doesn't have without walking up the tree. `Ancestors` also keeps the preceding
+//! siblings of the element and of its ancestors, which `+` and `~` match.
+
+use bloom::{hash_str, BloomFilter};
+use css::{PseudoClass, Rule, Selector, SimpleSelector, Specificity, Stylesheet};
+use dom::ElementData;
+use element_state::ElementState;
+use history;
+
+use std::cmp::max;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type MatchedRule<'a> = (Specificity, &'a Rule);
+
+const TAG_NAME_HASH: u8 = 0;
+const ID_HASH: u8 = 1;
+const CLASS_HASH: u8 = 2;
+
+/// A selector of a rule.
+struct Entry {
+    /// The index of the rule in the stylesheet.
+    source_order: usize,
+    /// The index of the selector in the rule.
+    selector_index: usize,
+    specificity: Specificity,
+    /// The tag names, ids and classes that the ancestors of a matching element must have.
+    ancestor_hashes: Vec<u32>,
+    /// Whether the subject has to be matched against the siblings or children of the element.
+    structural: bool,
+    /// Whether a compound with a dynamic pseudo-class is followed by `+` or `~`.
+    state_affects_siblings: bool,
+}
+
+/// The selectors of a stylesheet, built once for it and kept as long as it is used.
+pub struct SelectorMap {
+    stylesheet: Rc<Stylesheet>,
+    by_id: HashMap<String, Vec<Entry>>,
+    by_class: HashMap<String, Vec<Entry>>,
+    by_tag_name: HashMap<String, Vec<Entry>>,
+    universal: Vec<Entry>,
+}
+
+impl SelectorMap {
+    pub fn new(stylesheet: Rc<Stylesheet>) -> SelectorMap {
+        let mut map = SelectorMap {
+            stylesheet: stylesheet.clone(),
+            by_id: HashMap::new(),
+            by_class: HashMap::new(),
+            by_tag_name: HashMap::new(),
+            universal: vec![],
+        };
+        for (source_order, rule) in stylesheet.rules.iter().enumerate() {
+            for (selector_index, selector) in rule.selectors.iter().enumerate() {
+                map.insert(
+                    subject(selector),
+                    Entry {
+                        source_order: source_order,
+                        selector_index: selector_index,
+                        specificity: selector.specificity(),
+                        ancestor_hashes: ancestor_hashes(selector),
+                        structural: subject(selector).structural,
+                        state_affects_siblings: state_affects_siblings(selector),
+                    },
+                );
+            }
+        }
+        map
+    }
+
+    pub fn stylesheet(&self) -> &Stylesheet {
+        &self.stylesheet
+    }
+
+    /// Puts `entry` in the most selective bucket its `subject` allows. Any of them is correct,
+    /// as the subject has to match in full anyway.
+    fn insert(&mut self, subject: &SimpleSelector, entry: Entry) {
+        if let Some(ref id) = subject.id {
+            self.by_id.entry(id.clone()).or_insert_with(Vec::new).push(entry);
+        } else if let Some(class) = subject.class.iter().next() {
+            self.by_class.entry(class.clone()).or_insert_with(Vec::new).push(entry);
+        } else if let Some(ref tag_name) = subject.tag_name {
+            self.by_tag_name.entry(tag_name.clone()).or_insert_with(Vec::new).push(entry);
+        } else {
+            self.universal.push(entry);
+        }
+    }
+
+    /// The rules matching `elem`, in the order they appear in the stylesheet, with the
+    /// specificity of their most specific selector that matches.
+    pub fn matching_rules<'a>(
+        &'a self,
+        elem: &ElementData,
+        state: ElementState,
+        ancestors: &Ancestors,
+    ) -> Vec<MatchedRule<'a>> {
+        let mut matched: Vec<(usize, Specificity, &'a Rule)> = vec![];
+        for entry in self.buckets(elem).into_iter().flat_map(|entries| entries.iter()) {
+            let rule = &self.stylesheet.rules[entry.source_order];
+            if !entry
+                .ancestor_hashes
+                .iter()
+                .all(|&hash| ancestors.filter.might_contain_hash(hash))
+                || !matches(elem, state, &rule.selectors[entry.selector_index], ancestors)
+            {
+                continue;
+            }
+            // A rule counts once even if several of its selectors match.
+            match matched
+                .iter_mut()
+                .find(|&&mut (source_order, _, _)| source_order == entry.source_order)
+            {
+                Some(&mut (_, ref mut specificity, _)) => {
+                    *specificity = max(*specificity, entry.specificity)
+                }
+                None => matched.push((entry.source_order, entry.specificity, rule)),
+            }
+        }
+
+        matched.sort_by_key(|&(source_order, _, _)| source_order);
+        matched
+            .into_iter()
+            .map(|(_, specificity, rule)| (specificity, rule))
+            .collect()
+    }
+
+    /// Whether some selector that could match `elem` depends on its siblings or children, so
+    /// that elements equal to it may not match the same rules even among its siblings.
+    pub fn has_structural_selectors_for(&self, elem: &ElementData) -> bool {
+        self.buckets(elem)
+            .into_iter()
+            .any(|entries| entries.iter().any(|entry| entry.structural))
+    }
+
+    /// Whether the state of an element can change the rules its following siblings match, as
+    /// with `a:hover + p`.
+    pub fn state_affects_siblings(&self) -> bool {
+        self.by_id
+            .values()
+            .chain(self.by_class.values())
+            .chain(self.by_tag_name.values())
+            .chain(Some(&self.universal))
+            .any(|entries| entries.iter().any(|entry| entry.state_affects_siblings))
+    }
+
+    /// The buckets holding the selectors that could match `elem`.
+    fn buckets(&self, elem: &ElementData) -> Vec<&Vec<Entry>> {
+        let mut buckets: Vec<&Vec<Entry>> = elem.id()
+            .and_then(|id| self.by_id.get(id))
+            .into_iter()
+            .collect();
+        for class in elem.classes() {
+            buckets.extend(self.by_class.get(class));
+        }
+        buckets.extend(self.by_tag_name.get(&elem.tag_name));
+        buckets.push(&self.universal);
+        buckets
+    }
+}
+
+/// The elements around the one being styled: its ancestors, from the root down to its parent,
+/// and the preceding siblings of each of them and of the element. Their `pseudo_classes` are
+/// the ones they match.
+pub struct Ancestors {
+    pub elements: Vec<SimpleSelector>,
+    /// The preceding siblings at each depth, the nearest last. There is one more level than
+    /// `elements`, the last one for the siblings of the element being styled.
+    siblings: Vec<Vec<SimpleSelector>>,
+    filter: BloomFilter,
+}
+
+impl Ancestors {
+    pub fn new() -> Ancestors {
+        Ancestors {
+            elements: vec![],
+            siblings: vec![vec![]],
+            filter: BloomFilter::new(),
+        }
+    }
+
+    /// Enters `elem`, whose children are styled next.
+    pub fn push(&mut self, elem: &ElementData, state: ElementState) {
+        let element = element(elem, state);
+        for hash in hashes(&element) {
+            self.filter.insert_hash(hash);
+        }
+        self.elements.push(element);
+        self.siblings.push(vec![]);
+    }
+
+    /// Leaves the last element pushed, which becomes a preceding sibling of the next one.
+    pub fn pop(&mut self) {
+        if let Some(element) = self.elements.pop() {
+            for hash in hashes(&element) {
+                self.filter.remove_hash(hash);
+            }
+            self.siblings.pop();
+            self.siblings.last_mut().unwrap().push(element);
+        }
+    }
+
+    /// Adds `elem` as a preceding sibling of the next element, without entering it.
+    pub fn push_sibling(&mut self, elem: &ElementData, state: ElementState) {
+        self.siblings.last_mut().unwrap().push(element(elem, state));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// The element at `depth` that is preceded by `index` siblings.
+    fn at(&self, depth: usize, index: usize) -> &SimpleSelector {
+        match self.siblings[depth].get(index) {
+            Some(sibling) => sibling,
+            None => &self.elements[depth],
+        }
+    }
+}
+
+/// `elem` in `state` as a compound selector it matches.
+fn element(elem: &ElementData, state: ElementState) -> SimpleSelector {
+    SimpleSelector {
+            tag_name: Some(elem.tag_name.clone()),
+            id: elem.id().cloned(),
+            class: elem.classes()
+                .iter()
+                .filter(|class| !class.is_empty())
+                .map(|class| class.to_string())
+                .collect(),
+            structural: false,
+            pseudo_classes: [
+                PseudoClass::Hover,
+                PseudoClass::Active,
+                PseudoClass::Focus,
+                PseudoClass::FocusWithin,
+                PseudoClass::Link,
+                PseudoClass::Visited,
+            ].iter()
+                .cloned()
+                .filter(|&pseudo_class| matches_pseudo_class(elem, state, pseudo_class))
+                .collect(),
+    }
+}
+
+fn hashes(selector: &SimpleSelector) -> Vec<u32> {
+    selector
+        .tag_name
+        .iter()
+        .map(|tag_name| hash_str(TAG_NAME_HASH, tag_name))
+        .chain(selector.id.iter().map(|id| hash_str(ID_HASH, id)))
+        .chain(selector.class.iter().map(|class| hash_str(CLASS_HASH, class)))
+        .collect()
+}
+
+fn subject(selector: &Selector) -> &SimpleSelector {
+    match *selector {
+        Selector::Simple(ref simple) => simple,
+        Selector::Descendant(_, ref rest)
+        | Selector::Child(_, ref rest)
+        | Selector::Adjacent(_, ref rest)
+        | Selector::Sibling(_, ref rest) => subject(rest),
+    }
+}
+
+/// The hashes of the compounds that match ancestors of the subject: those followed by a
+/// descendant or child combinator. A sibling of an ancestor isn't one itself.
+fn ancestor_hashes(selector: &Selector) -> Vec<u32> {
+    match *selector {
+        Selector::Simple(_) => vec![],
+        Selector::Descendant(ref ancestor, ref rest) | Selector::Child(ref ancestor, ref rest) => {
+            let mut hashes = hashes(ancestor);
+            hashes.append(&mut ancestor_hashes(rest));
+            hashes
+        }
+        Selector::Adjacent(_, ref rest) | Selector::Sibling(_, ref rest) => ancestor_hashes(rest),
+    }
+}
+
+fn state_affects_siblings(selector: &Selector) -> bool {
+    match *selector {
+        Selector::Simple(_) => false,
+        Selector::Descendant(_, ref rest) | Selector::Child(_, ref rest) => {
+            state_affects_siblings(rest)
+        }
+        Selector::Adjacent(ref sibling, ref rest) | Selector::Sibling(ref sibling, ref rest) => {
+            !sibling.pseudo_classes.is_empty() || state_affects_siblings(rest)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Combinator {
+    Descendant,
+    Child,
+    Adjacent,
+    Sibling,
+}
+
+/// Whether `selector` matches `elem`, which comes after `ancestors`.
+pub fn matches(
+    elem: &ElementData,
+    state: ElementState,
+    selector: &Selector,
+    ancestors: &Ancestors,
+) -> bool {
+    // The compounds left of the subject, with the combinator on their right.
+    let mut compounds = vec![];
+    let mut selector = selector;
+    loop {
+        match *selector {
+            Selector::Simple(ref subject) => {
+                if !matches_simple_selector(elem, state, subject) {
+                    return false;
+                }
+                break;
+            }
+            Selector::Descendant(ref ancestor, ref rest) => {
+                compounds.push((Combinator::Descendant, ancestor));
+                selector = rest;
+            }
+            Selector::Child(ref ancestor, ref rest) => {
+                compounds.push((Combinator::Child, ancestor));
+                selector = rest;
+            }
+            Selector::Adjacent(ref sibling, ref rest) => {
+                compounds.push((Combinator::Adjacent, sibling));
+                selector = rest;
+            }
+            Selector::Sibling(ref sibling, ref rest) => {
+                compounds.push((Combinator::Sibling, sibling));
+                selector = rest;
+            }
+        }
+    }
+    compounds.reverse();
+    let depth = ancestors.elements.len();
+    matches_ancestors(&compounds, ancestors, depth, ancestors.siblings[depth].len())
+}
+
+/// Matches `compounds`, nearest to the subject first, from right to left against the elements
+/// before the one at `depth` preceded by `index` siblings.
+fn matches_ancestors(
+    compounds: &[(Combinator, &SimpleSelector)],
+    ancestors: &Ancestors,
+    depth: usize,
+    index: usize,
+) -> bool {
+    let (&(combinator, compound), rest) = match compounds.split_first() {
+        Some(first) => first,
+        None => return true,
+    };
+    let matches_at = |depth: usize, index: usize| {
+        matches_ancestor(ancestors.at(depth, index), compound)
+            && matches_ancestors(rest, ancestors, depth, index)
+    };
+    let parent_at = |depth: usize| (depth, ancestors.siblings[depth].len());
+    match combinator {
+        Combinator::Child => depth > 0 && {
+            let (depth, index) = parent_at(depth - 1);
+            matches_at(depth, index)
+        },
+        Combinator::Descendant => (0..depth).rev().any(|depth| {
+            let (depth, index) = parent_at(depth);
+            matches_at(depth, index)
+        }),
+        Combinator::Adjacent => index > 0 && matches_at(depth, index - 1),
+        Combinator::Sibling => (0..index).rev().any(|index| matches_at(depth, index)),
+    }
+}
+
+fn matches_ancestor(ancestor: &SimpleSelector, compound: &SimpleSelector) -> bool {
+    !((compound.tag_name.is_some() && ancestor.tag_name != compound.tag_name)
+        || (compound.id.is_some() && ancestor.id != compound.id)
+        || (!compound.class.iter().all(|class| ancestor.class.contains(class)))
+        || (!compound
+            .pseudo_classes
+            .iter()
+            .all(|pseudo_class| ancestor.pseudo_classes.contains(pseudo_class))))
+}
+
+fn matches_simple_selector(
+    elem: &ElementData,
+    state: ElementState,
+    selector: &SimpleSelector,
+) -> bool {
+    // Universal selector
+    if selector.tag_name.is_none() && selector.id.is_none() && selector.class.is_empty()
+        && selector.pseudo_classes.is_empty()
+    {
+        return true;
+    }
+
+    // Check type selector
+    if selector.tag_name.iter().any(|name| elem.tag_name != *name) {
+        return false;
+    }
+
+    // Check ID selector
+    if selector.id.iter().any(|id| elem.id() != Some(id)) {
+        return false;
+    }
+
+    // Check class selectors
+    let elem_classes = elem.classes();
+    if selector
+        .class
+        .iter()
+        .any(|class| !elem_classes.contains(&**class))
+    {
+        return false;
+    }
+
+    if !selector
+        .pseudo_classes
+        .iter()
+        .all(|&pseudo_class| matches_pseudo_class(elem, state, pseudo_class))
+    {
+        return false;
+    }
+
+    // We didn't find any non-matching selector components.
+    true
+}
+
+fn matches_pseudo_class(
+    elem: &ElementData,
+    state: ElementState,
+    pseudo_class: PseudoClass,
+) -> bool {
+    match pseudo_class {
+        PseudoClass::Hover => state.hover,
+        PseudoClass::Active => state.active,
+        PseudoClass::Focus => state.focus,
+        PseudoClass::FocusWithin => state.focus_within,
+        PseudoClass::Link => elem.is_link() && !history::is_visited(&elem.attrs["href"]),
+        PseudoClass::Visited => elem.is_link() && history::is_visited(&elem.attrs["href"]),
+    }
+}
+
+#[test]
+fn test_selector_map() {
+    use css;
+    use dom::LayoutType;
+
+    let no_state = ElementState::default();
+    let elem = |tag_name: &str, id: Option<&str>, class: &str| ElementData {
+        tag_name: tag_name.to_string(),
+        layout_type: LayoutType::Generic,
+        attrs: id.map(|id| ("id".to_string(), id.to_string()))
+            .into_iter()
+            .chain(Some(("class".to_string(), class.to_string())))
+            .collect(),
+    };
+
+    let stylesheet = css::parse(
+        "
+        p { a: 0 }
+        .note, #main p.note { a: 1 }
+        * { a: 2 }
+        div > p { a: 3 }
+        nav p { a: 4 }
+        body div p { a: 5 }
+        body > p { a: 6 }
+        "
+            .to_string(),
+    );
+    let map = SelectorMap::new(Rc::new(stylesheet));
+
+    let mut ancestors = Ancestors::new();
+    ancestors.push(&elem("body", None, ""), no_state);
+    ancestors.push(&elem("div", Some("main"), "wide"), no_state);
+
+    let p = elem("p", None, "note");
+    let matched: Vec<(Specificity, &str)> = map.matching_rules(&p, no_state, &ancestors)
+        .into_iter()
+        .map(|(specificity, rule)| match rule.declarations[0].values[0] {
+            css::Value::Num(n) if n == 0.0 => (specificity, "p"),
+            css::Value::Num(n) if n == 1.0 => (specificity, ".note"),
+            css::Value::Num(n) if n == 2.0 => (specificity, "*"),
+            css::Value::Num(n) if n == 3.0 => (specificity, "div > p"),
+            css::Value::Num(n) if n == 5.0 => (specificity, "body div p"),
+            _ => (specificity, "unexpected"),
+        })
+        .collect();
+    assert_eq!(
+        matched,
+        vec![
+            ((0, 0, 1), "p"),
+            ((1, 1, 1), ".note"),
+            ((0, 0, 0), "*"),
+            ((0, 0, 2), "div > p"),
+            ((0, 0, 3), "body div p"),
+        ]
+    );
+
+    // Ancestors must be in the right order, and a child combinator needs the parent.
+    let a = elem("a", None, "");
+    let selector = |src: &str| css::parse(format!("{} {{}}", src)).rules[0].selectors[0].clone();
+    assert!(matches(&a, no_state, &selector("body div a"), &ancestors));
+    assert!(!matches(&a, no_state, &selector("div body a"), &ancestors));
+    assert!(!matches(&a, no_state, &selector("body > a"), &ancestors));
+
+    ancestors.pop();
+    assert_eq!(map.matching_rules(&p, no_state, &ancestors).len(), 4);
+
+    // Dynamic pseudo-classes match the state of the element and its ancestors.
+    let hover = ElementState {
+        hover: true,
+        ..ElementState::default()
+    };
+    assert!(matches(&a, hover, &selector("a:hover"), &ancestors));
+    assert!(!matches(&a, no_state, &selector("a:hover"), &ancestors));
+    assert!(!matches(&a, hover, &selector("body:hover a"), &ancestors));
+    ancestors.pop();
+    ancestors.push(&elem("body", None, ""), hover);
+    assert!(matches(&a, hover, &selector("body:hover a"), &ancestors));
+    assert_eq!(selector("a:hover").specificity(), (0, 1, 1));
+
+    let mut link = elem("a", None, "");
+    assert!(!matches(&link, no_state, &selector(":link"), &ancestors));
+    link.attrs.insert("href".to_string(), "https://example.com/".to_string());
+    assert!(matches(&link, no_state, &selector(":link"), &ancestors));
+    assert!(!matches(&link, no_state, &selector(":visited"), &ancestors));
+
+    // Sibling combinators match the elements popped before, and their ancestors.
+    let mut ancestors = Ancestors::new();
+    ancestors.push(&elem("body", None, ""), no_state);
+    ancestors.push(&elem("div", None, "menu"), no_state);
+    ancestors.pop();
+    ancestors.push_sibling(&elem("h1", None, ""), hover);
+    ancestors.push(&elem("div", None, "main"), no_state);
+    ancestors.push_sibling(&elem("h2", None, ""), no_state);
+    assert!(matches(&a, no_state, &selector("h2 + a"), &ancestors));
+    assert!(!matches(&a, no_state, &selector("body + a"), &ancestors));
+    assert!(matches(&a, no_state, &selector(".menu ~ h1:hover + .main > a"), &ancestors));
+    assert!(matches(&a, no_state, &selector(".menu ~ .main a"), &ancestors));
+    assert!(!matches(&a, no_state, &selector(".menu + .main a"), &ancestors));
+    assert!(!matches(&a, no_state, &selector(".main ~ .menu a"), &ancestors));
+    assert!(!SelectorMap::new(Rc::new(css::parse("h1 + .main a:hover {}".to_string())))
+        .state_affects_siblings());
+    assert!(SelectorMap::new(Rc::new(css::parse("h1:hover + .main a {}".to_string())))
+        .state_affects_siblings());
+}
@@ -0,0 +1,238 @@
+//! The used `width` and `height` of boxes: which box they size with `box-sizing`, and how
+//! `min-width`, `max-width`, `min-height` and `max-height` limit them.
+//! ref. https://www.w3.org/TR/CSS22/visudet.html#min-max-widths
+//! ref. https://www.w3.org/TR/css-sizing-3/#box-sizing
+
+use style::{BoxSizing, ComputedStyle, LengthOrPercentage, LengthOrPercentageOrAuto};
+
+use std::cmp::{max, min};
+
+use app_units::Au;
+
+impl ComputedStyle {
+    /// The size of the content box for a `size` of the box `box-sizing` refers to, which has
+    /// `edges` of paddings and borders.
+    fn content_size(&self, size: Au, edges: Au) -> Au {
+        match self.box_sizing {
+            BoxSizing::ContentBox => size,
+            BoxSizing::BorderBox => max(Au(0), size - edges),
+        }
+    }
+
+    /// The paddings and borders on the left and right. Percentages refer to `cb_width`.
+    pub fn horizontal_box_edges(&self, cb_width: Au) -> Au {
+        self.padding.left.to_au(cb_width) + self.padding.right.to_au(cb_width)
+            + self.border_width.left + self.border_width.right
+    }
+
+    /// The paddings and borders on the top and bottom. Percentages refer to `cb_width` too.
+    pub fn vertical_box_edges(&self, cb_width: Au) -> Au {
+        self.padding.top.to_au(cb_width) + self.padding.bottom.to_au(cb_width)
+            + self.border_width.top + self.border_width.bottom
+    }
+
+    /// The width of the content box for a `width` given in the box `box-sizing` refers to.
+    pub fn content_width(&self, width: Au, cb_width: Au) -> Au {
+        self.content_size(width, self.horizontal_box_edges(cb_width))
+    }
+
+    /// The height of the content box for a `height` given in the box `box-sizing` refers to.
+    pub fn content_height(&self, height: Au, cb_width: Au) -> Au {
+        self.content_size(height, self.vertical_box_edges(cb_width))
+    }
+
+    /// The specified `width` as that of the content box, or None for `auto`.
+    pub fn specified_width(&self, cb_width: Au) -> Option<Au> {
+        self.width
+            .to_au(cb_width)
+            .map(|width| self.content_width(width, cb_width))
+    }
+
+    /// The specified `height` as that of the content box, or None for `auto`. Percentages of the
+    /// height of a containing block that depends on its content are `auto` too.
+    pub fn specified_height(&self, cb_width: Au, cb_height: Option<Au>) -> Option<Au> {
+        let height = match (self.height, cb_height) {
+            (LengthOrPercentageOrAuto::Length(height), _) => height,
+            (LengthOrPercentageOrAuto::Percentage(_), Some(cb_height)) => {
+                self.height.to_au(cb_height)?
+            }
+            _ => return None,
+        };
+        Some(self.content_size(height, self.vertical_box_edges(cb_width)))
+    }
+
+    /// The limits `min-width` and `max-width` set on the width of the content box. Percentages
+    /// of an unknown `cb_width` are left out.
+    pub fn width_limits(&self, cb_width: Option<Au>) -> (Au, Option<Au>) {
+        let edges = self.horizontal_box_edges(cb_width.unwrap_or(Au(0)));
+        self.limits(self.min_width, self.max_width, cb_width, edges)
+    }
+
+    /// The limits `min-height` and `max-height` set on the height of the content box.
+    pub fn height_limits(&self, cb_width: Au, cb_height: Option<Au>) -> (Au, Option<Au>) {
+        let edges = self.vertical_box_edges(cb_width);
+        self.limits(self.min_height, self.max_height, cb_height, edges)
+    }
+
+    fn limits(
+        &self,
+        min_size: LengthOrPercentageOrAuto,
+        max_size: Option<LengthOrPercentage>,
+        cb_size: Option<Au>,
+        edges: Au,
+    ) -> (Au, Option<Au>) {
+        let min_size = match (min_size, cb_size) {
+            (LengthOrPercentageOrAuto::Length(size), _) => self.content_size(size, edges),
+            (LengthOrPercentageOrAuto::Percentage(_), Some(cb_size)) => {
+                self.content_size(min_size.to_au(cb_size).unwrap(), edges)
+            }
+            _ => Au(0),
+        };
+        let max_size = match (max_size, cb_size) {
+            (Some(LengthOrPercentage::Length(size)), _) => Some(self.content_size(size, edges)),
+            (Some(size @ LengthOrPercentage::Percentage(_)), Some(cb_size)) => {
+                Some(self.content_size(size.to_au(cb_size), edges))
+            }
+            _ => None,
+        };
+        (min_size, max_size)
+    }
+
+    /// `width` limited by `max-width`, then by `min-width`, which wins over it.
+    pub fn clamp_width(&self, width: Au, cb_width: Option<Au>) -> Au {
+        clamp(width, self.width_limits(cb_width))
+    }
+
+    /// `height` limited by `max-height`, then by `min-height`.
+    pub fn clamp_height(&self, height: Au, cb_width: Au, cb_height: Option<Au>) -> Au {
+        clamp(height, self.height_limits(cb_width, cb_height))
+    }
+
+    /// The used size of a replaced element with an intrinsic ratio, from `size`, which is
+    /// either specified or intrinsic, limited by the minimum and maximum sizes. When neither
+    /// `width` nor `height` are specified, the ratio is kept as much as the limits allow.
+    pub fn constrain_replaced_size(
+        &self,
+        size: (Au, Au),
+        cb_width: Au,
+        cb_height: Option<Au>,
+    ) -> (Au, Au) {
+        let width_limits = self.width_limits(Some(cb_width));
+        let height_limits = self.height_limits(cb_width, cb_height);
+        match (self.width, self.height) {
+            (LengthOrPercentageOrAuto::Auto, LengthOrPercentageOrAuto::Auto) => {
+                constrain_ratio(size, width_limits, height_limits)
+            }
+            _ => (clamp(size.0, width_limits), clamp(size.1, height_limits)),
+        }
+    }
+}
+
+/// `size` limited by `max_size`, then by `min_size`.
+pub fn clamp(size: Au, (min_size, max_size): (Au, Option<Au>)) -> Au {
+    max(min_size, max_size.map_or(size, |max_size| min(size, max_size)))
+}
+
+/// Limits `(width, height)` keeping its ratio as much as possible.
+/// ref. https://www.w3.org/TR/CSS22/visudet.html#min-max-widths (the table for replaced elements)
+fn constrain_ratio(
+    (width, height): (Au, Au),
+    (min_width, max_width): (Au, Option<Au>),
+    (min_height, max_height): (Au, Option<Au>),
+) -> (Au, Au) {
+    if width == Au(0) || height == Au(0) {
+        return (
+            clamp(width, (min_width, max_width)),
+            clamp(height, (min_height, max_height)),
+        );
+    }
+    // A maximum below the minimum is the minimum.
+    let max_width = max_width.map_or(Au(i32::max_value()), |max_width| max(min_width, max_width));
+    let max_height = max_height.map_or(Au(i32::max_value()), |max_height| {
+        max(min_height, max_height)
+    });
+    let scale = |size: Au, to: Au, from: Au| size.scale_by(to.to_f32_px() / from.to_f32_px());
+    let ratio = |a: Au, b: Au| a.to_f64_px() / b.to_f64_px();
+
+    if width > max_width && height > max_height {
+        if ratio(max_width, width) <= ratio(max_height, height) {
+            (max_width, max(min_height, scale(height, max_width, width)))
+        } else {
+            (max(min_width, scale(width, max_height, height)), max_height)
+        }
+    } else if width < min_width && height < min_height {
+        if ratio(min_width, width) <= ratio(min_height, height) {
+            (min(max_width, scale(width, min_height, height)), min_height)
+        } else {
+            (min_width, min(max_height, scale(height, min_width, width)))
+        }
+    } else if width < min_width && height > max_height {
+        (min_width, max_height)
+    } else if width > max_width && height < min_height {
+        (max_width, min_height)
+    } else if width > max_width {
+        (max_width, max(min_height, scale(height, max_width, width)))
+    } else if width < min_width {
+        (min_width, min(max_height, scale(height, min_width, width)))
+    } else if height > max_height {
+        (max(min_width, scale(width, max_height, height)), max_height)
+    } else if height < min_height {
+        (min(max_width, scale(width, min_height, height)), min_height)
+    } else {
+        (width, height)
+    }
+}
+
+#[test]
+fn test_width_limits() {
+    let px = Au::from_px;
+    let style = ComputedStyle {
+        min_width: LengthOrPercentageOrAuto::Length(px(100)),
+        max_width: Some(LengthOrPercentage::Percentage(50.0)),
+        padding: ::style::Sides::all(LengthOrPercentage::Length(px(10))),
+        ..ComputedStyle::default()
+    };
+    assert_eq!(style.clamp_width(px(300), Some(px(400))), px(200));
+    assert_eq!(style.clamp_width(px(50), Some(px(400))), px(100));
+    // `min-width` wins.
+    assert_eq!(style.clamp_width(px(300), Some(px(100))), px(100));
+    // Percentages of an unknown width are left out.
+    assert_eq!(style.clamp_width(px(300), None), px(300));
+
+    // With `border-box`, the limits include the paddings.
+    let style = ComputedStyle {
+        box_sizing: BoxSizing::BorderBox,
+        width: LengthOrPercentageOrAuto::Length(px(150)),
+        ..style
+    };
+    assert_eq!(style.specified_width(px(400)), Some(px(130)));
+    assert_eq!(style.clamp_width(px(300), Some(px(400))), px(180));
+    assert_eq!(style.clamp_width(px(50), Some(px(400))), px(80));
+}
+
+#[test]
+fn test_constrain_ratio() {
+    let px = Au::from_px;
+    let none = (Au(0), None);
+    // Too wide: the height is scaled down with the width.
+    assert_eq!(
+        constrain_ratio((px(400), px(200)), (Au(0), Some(px(100))), none),
+        (px(100), px(50))
+    );
+    // Too wide and too tall: the one that has to shrink more wins.
+    assert_eq!(
+        constrain_ratio((px(400), px(200)), (Au(0), Some(px(200))), (Au(0), Some(px(50)))),
+        (px(100), px(50))
+    );
+    // Too narrow: the height is scaled up, but not past its maximum.
+    assert_eq!(
+        constrain_ratio((px(50), px(50)), (px(100), None), (Au(0), Some(px(80)))),
+        (px(100), px(80))
+    );
+    // Too narrow and too tall: the ratio can't be kept.
+    assert_eq!(
+        constrain_ratio((px(50), px(300)), (px(100), None), (Au(0), Some(px(200)))),
+        (px(100), px(200))
+    );
+    assert_eq!(constrain_ratio((px(50), px(30)), none, none), (px(50), px(30)));
+}
@@ -1,67 +1,99 @@
-use css::{Color, TextDecoration, Unit, Value, pt2px};
-use font::{FontSlant, FontWeight};
+use css::{Color, LengthContext, TextDecoration, Unit, Value, BLACK};
+use font::{Font, FontFamily, FontSlant, FontStretch, FontVariant, FontWeight};
+use property::{is_css_wide_keyword, is_inherited};
 
 use std::collections::HashMap;
 
 use app_units::Au;
 
+/// The declared values of an element, after the cascade.
 #[derive(Clone, Debug)]
 pub struct Style {
     pub property: HashMap<String, Vec<Value>>,
-    pub cached: CachedProperties,
 }
 
-// pub struct Style(pub HashMap<String, Vec<Value>>);
-
 impl Style {
     pub fn new() -> Style {
         Style {
             property: HashMap::new(),
-            cached: CachedProperties::new(),
         }
     }
 
     pub fn new_with(hashmap: HashMap<String, Vec<Value>>) -> Style {
-        Style {
-            property: hashmap,
-            cached: CachedProperties::new(),
-        }
+        Style { property: hashmap }
     }
-}
 
-#[derive(Clone, Debug)]
-pub struct CachedProperties {
-    // Option<(top, right, bottom, left)>
-    margin: (Option<Value>, Option<Value>, Option<Value>, Option<Value>),
-    padding: (Option<Value>, Option<Value>, Option<Value>, Option<Value>),
-    border_width: (Option<Value>, Option<Value>, Option<Value>, Option<Value>),
-    border_color: (Option<Color>, Option<Color>, Option<Color>, Option<Color>),
+    pub fn value(&self, name: &str) -> Option<Vec<Value>> {
+        self.property.get(name).cloned()
+    }
 
-    font_size: Option<Value>,
-    line_height: Option<Value>,
-}
+    pub fn value_with_default(&self, name: &str, default: &Vec<Value>) -> Vec<Value> {
+        self.value(name).unwrap_or(default.clone())
+    }
 
-impl CachedProperties {
-    pub fn new() -> CachedProperties {
-        CachedProperties {
-            margin: (None, None, None, None),
-            padding: (None, None, None, None),
-            border_width: (None, None, None, None),
-            border_color: (None, None, None, None),
-            font_size: None,
-            line_height: None,
-        }
+    pub fn lookup(&self, name: &str, fallback_name: &str, default: &Vec<Value>) -> Vec<Value> {
+        self.value(name)
+            .unwrap_or_else(|| self.value(fallback_name).unwrap_or_else(|| default.clone()))
+    }
+
+    pub fn lookup_without_default(&self, name: &str, fallback_name: &str) -> Option<Vec<Value>> {
+        self.value(name).or_else(|| self.value(fallback_name))
+    }
+
+    pub fn uses_viewport_relative_lengths(&self) -> bool {
+        self.property
+            .values()
+            .any(|values| values.iter().any(|value| value.is_viewport_relative()))
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Display {
     Inline,
     Block,
     InlineBlock,
+    /// A block with a marker box.
+    ListItem,
+    Table,
+    InlineTable,
+    TableRowGroup,
+    TableHeaderGroup,
+    TableFooterGroup,
+    TableRow,
+    TableCell,
+    TableCaption,
+    TableColumn,
+    TableColumnGroup,
+    Flex,
+    InlineFlex,
+    Grid,
+    InlineGrid,
+    None,
+}
+
+/// The predefined counter styles, and the bullets that don't count.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ListStyleType {
+    Disc,
+    Circle,
+    Square,
+    DisclosureOpen,
+    DisclosureClosed,
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    CjkDecimal,
     None,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ListStylePosition {
+    Inside,
+    Outside,
+}
+
 #[derive(Clone, PartialEq, Debug, Copy)]
 pub enum FloatType {
     Left,
@@ -76,523 +108,1680 @@ pub enum ClearType {
     Both,
 }
 
-pub const DEFAULT_FONT_SIZE: f64 = 16.0f64;
-pub const DEFAULT_LINE_HEIGHT_SCALE: f64 = 1.2f64;
+/// How a box is positioned: in normal flow, moved from there (`relative` and `sticky`), or taken
+/// out of flow and placed against its containing block (`absolute` and `fixed`).
+/// ref. https://www.w3.org/TR/css-position-3/#position-property
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum Position {
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+    Sticky,
+}
 
-impl Style {
-    pub fn value(&self, name: &str) -> Option<Vec<Value>> {
-        self.property.get(name).cloned()
+/// Which box `width` and `height` and their limits size.
+/// ref. https://www.w3.org/TR/css-sizing-3/#box-sizing
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum BoxSizing {
+    ContentBox,
+    BorderBox,
+}
+
+/// What is done with the content of a box that overflows its padding box on an axis.
+/// ref. https://www.w3.org/TR/css-overflow-3/#overflow-properties
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum Overflow {
+    Visible,
+    Hidden,
+    Clip,
+    Scroll,
+    Auto,
+}
+
+impl Overflow {
+    /// Whether the overflowing content is cut off at the padding box.
+    pub fn is_clipped(&self) -> bool {
+        *self != Overflow::Visible
     }
 
-    pub fn value_with_default(&self, name: &str, default: &Vec<Value>) -> Vec<Value> {
-        self.value(name).unwrap_or(default.clone())
+    /// Whether the box can be scrolled to the overflowing content, by the user or not.
+    pub fn is_scrollable(&self) -> bool {
+        match *self {
+            Overflow::Hidden | Overflow::Scroll | Overflow::Auto => true,
+            Overflow::Visible | Overflow::Clip => false,
+        }
     }
 
-    pub fn lookup(&self, name: &str, fallback_name: &str, default: &Vec<Value>) -> Vec<Value> {
-        self.value(name)
-            .unwrap_or_else(|| self.value(fallback_name).unwrap_or_else(|| default.clone()))
+    /// The value used beside a scrollable value on the other axis.
+    fn scrollable(self) -> Overflow {
+        match self {
+            Overflow::Visible => Overflow::Auto,
+            Overflow::Clip => Overflow::Hidden,
+            other => other,
+        }
     }
+}
 
-    pub fn lookup_without_default(&self, name: &str, fallback_name: &str) -> Option<Vec<Value>> {
-        self.value(name).or_else(|| self.value(fallback_name))
+/// How the end of text that overflows a clipped line is shown.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum TextOverflow {
+    Clip,
+    Ellipsis,
+}
+
+/// Whether lines can break between letters, or only between words even in CJK text.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum WordBreak {
+    Normal,
+    BreakAll,
+    KeepAll,
+    /// Deprecated: `normal` with `overflow-wrap: anywhere`.
+    BreakWord,
+}
+
+/// Whether words that don't fit in a line are broken.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum OverflowWrap {
+    Normal,
+    BreakWord,
+    Anywhere,
+}
+
+/// How strictly the rules for punctuation and small kana apply in CJK text.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum LineBreak {
+    Auto,
+    Loose,
+    Normal,
+    Strict,
+    Anywhere,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum BorderStyle {
+    None,
+    Hidden,
+    Dotted,
+    Dashed,
+    Solid,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum BorderCollapse {
+    Separate,
+    Collapse,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum TableLayout {
+    Auto,
+    Fixed,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum VerticalAlign {
+    Baseline,
+    Sub,
+    Super,
+    TextTop,
+    TextBottom,
+    Middle,
+    Top,
+    Bottom,
+    /// Raises the box by the length, percentages referring to the `line-height`.
+    Length(LengthOrPercentage),
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum FlexDirection {
+    Row,
+    RowReverse,
+    Column,
+    ColumnReverse,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum FlexWrap {
+    NoWrap,
+    Wrap,
+    WrapReverse,
+}
+
+/// How the free space is shared around the items of a flex line (`justify-content`) or the
+/// lines of a flex container (`align-content`), or around the tracks of a grid. `start` and `end`
+/// are the same as `flex-start` and `flex-end`, as naglfar only lays out left-to-right text.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum ContentDistribution {
+    Normal,
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+    Stretch,
+}
+
+/// How an item is aligned in the cross axis of its flex line (`align-items` and `align-self`),
+/// or in its grid area (also `justify-items` and `justify-self`).
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum ItemAlignment {
+    Normal,
+    Stretch,
+    FlexStart,
+    FlexEnd,
+    Center,
+    Baseline,
+}
+
+/// One end of the range a grid track's size is in.
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum TrackBreadth {
+    Length(LengthOrPercentage),
+    /// A share of the space left in the grid container, in `fr`.
+    Flex(f64),
+    MinContent,
+    MaxContent,
+    Auto,
+}
+
+/// The size of a grid track. A single breadth is both the minimum and the maximum, except that
+/// a flexible track's minimum is `auto`.
+/// ref. https://www.w3.org/TR/css-grid-1/#track-sizes
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum TrackSize {
+    MinMax(TrackBreadth, TrackBreadth),
+    /// `fit-content(limit)`: like `auto`, but no larger than the limit.
+    FitContent(LengthOrPercentage),
+}
+
+/// Grid tracks, and the names of the line before each track and of the last line.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrackList {
+    pub sizes: Vec<TrackSize>,
+    pub line_names: Vec<Vec<String>>,
+}
+
+/// `grid-template-columns` or `grid-template-rows`, with `repeat()`s of a number of tracks
+/// expanded. `repeat(auto-fill, ...)` and `repeat(auto-fit, ...)` put as many repetitions as
+/// fit in the container between `tracks` and `after`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct GridTemplate {
+    pub tracks: TrackList,
+    pub auto_repeat: Option<AutoRepeat>,
+    pub after: TrackList,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AutoRepeat {
+    /// `auto-fit`, whose repetitions without items collapse.
+    pub fit: bool,
+    pub tracks: TrackList,
+}
+
+/// `grid-template-areas`: the size of the grid the strings draw, and its named areas.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct GridAreas {
+    pub rows: usize,
+    pub columns: usize,
+    pub areas: Vec<GridArea>,
+}
+
+/// A named area, between the lines `rows.0` and `rows.1`, and `columns.0` and `columns.1`,
+/// counted from 0.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GridArea {
+    pub name: String,
+    pub rows: (usize, usize),
+    pub columns: (usize, usize),
+}
+
+/// Where a grid item starts or ends: `grid-row-start`, `grid-column-end`...
+/// ref. https://www.w3.org/TR/css-grid-1/#line-placement
+#[derive(Clone, PartialEq, Debug)]
+pub enum GridLine {
+    Auto,
+    /// The nth line, or the nth line with the name, counting from the end if negative.
+    Line(i32, Option<String>),
+    /// The nth line, or the nth line with the name, away from the item's other end.
+    Span(i32, Option<String>),
+    /// The edge of the area with the name, or else the first line with the name.
+    Name(String),
+}
+
+#[derive(Clone, PartialEq, Debug, Copy, Default)]
+pub struct GridAutoFlow {
+    /// Whether items are placed down the columns rather than across the rows.
+    pub column: bool,
+    /// Whether items fill earlier holes in the grid, out of order.
+    pub dense: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum TextAlign {
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum LineHeight {
+    Normal,
+    Number(f64),
+    Length(Au),
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum LengthOrPercentage {
+    Length(Au),
+    Percentage(f64),
+}
+
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub enum LengthOrPercentageOrAuto {
+    Length(Au),
+    Percentage(f64),
+    Auto,
+}
+
+/// A value for each side of a box, like `margin-top`, `margin-right`...
+#[derive(Clone, PartialEq, Debug, Copy)]
+pub struct Sides<T> {
+    pub top: T,
+    pub right: T,
+    pub bottom: T,
+    pub left: T,
+}
+
+/// The computed values of an element. Relative lengths are resolved into `Au` here, except for
+/// percentages that depend on the layout.
+/// ref. https://www.w3.org/TR/css-cascade-4/#computed
+#[derive(Clone, PartialEq, Debug)]
+pub struct ComputedStyle {
+    pub display: Display,
+    pub float: FloatType,
+    pub clear: Option<ClearType>,
+    pub width: LengthOrPercentageOrAuto,
+    pub height: LengthOrPercentageOrAuto,
+    /// `auto` is 0 outside flex and grid layout.
+    pub min_width: LengthOrPercentageOrAuto,
+    pub min_height: LengthOrPercentageOrAuto,
+    /// None for `none`.
+    pub max_width: Option<LengthOrPercentage>,
+    pub max_height: Option<LengthOrPercentage>,
+    pub box_sizing: BoxSizing,
+    pub margin: Sides<LengthOrPercentageOrAuto>,
+    pub padding: Sides<LengthOrPercentage>,
+    pub border_width: Sides<Au>,
+    pub border_style: Sides<BorderStyle>,
+    pub border_color: Sides<Color>,
+    /// None for `auto`.
+    pub z_index: Option<i32>,
+    pub position: Position,
+    /// `top`, `right`, `bottom` and `left`.
+    pub inset: Sides<LengthOrPercentageOrAuto>,
+    pub overflow_x: Overflow,
+    pub overflow_y: Overflow,
+    pub text_overflow: TextOverflow,
+    pub color: Color,
+    pub background_color: Color,
+    pub font_family: Vec<FontFamily>,
+    pub font_size: Au,
+    pub line_height: LineHeight,
+    pub font_weight: FontWeight,
+    pub font_style: FontSlant,
+    pub font_variant: FontVariant,
+    pub font_stretch: FontStretch,
+    pub text_align: TextAlign,
+    pub word_break: WordBreak,
+    pub overflow_wrap: OverflowWrap,
+    pub line_break: LineBreak,
+    pub text_decoration: Vec<TextDecoration>,
+    pub list_style_type: ListStyleType,
+    pub list_style_position: ListStylePosition,
+    /// The URL of the image, or None for `none`.
+    pub list_style_image: Option<String>,
+    pub border_collapse: BorderCollapse,
+    /// The horizontal and the vertical spacing.
+    pub border_spacing: (Au, Au),
+    pub table_layout: TableLayout,
+    pub vertical_align: VerticalAlign,
+    pub flex_direction: FlexDirection,
+    pub flex_wrap: FlexWrap,
+    pub justify_content: ContentDistribution,
+    pub align_content: ContentDistribution,
+    pub align_items: ItemAlignment,
+    /// None for `auto`, which takes the parent's `align-items`.
+    pub align_self: Option<ItemAlignment>,
+    /// `normal` is 0.
+    pub row_gap: LengthOrPercentage,
+    pub column_gap: LengthOrPercentage,
+    pub order: i32,
+    pub flex_grow: f64,
+    pub flex_shrink: f64,
+    /// `content` is the same as `auto` with an `auto` size.
+    pub flex_basis: LengthOrPercentageOrAuto,
+    pub grid_template_columns: GridTemplate,
+    pub grid_template_rows: GridTemplate,
+    pub grid_template_areas: GridAreas,
+    pub grid_auto_columns: Vec<TrackSize>,
+    pub grid_auto_rows: Vec<TrackSize>,
+    pub grid_auto_flow: GridAutoFlow,
+    pub grid_row_start: GridLine,
+    pub grid_row_end: GridLine,
+    pub grid_column_start: GridLine,
+    pub grid_column_end: GridLine,
+    /// `legacy` is `normal`.
+    pub justify_items: ItemAlignment,
+    /// None for `auto`, which takes the parent's `justify-items`.
+    pub justify_self: Option<ItemAlignment>,
+}
+
+pub const DEFAULT_FONT_SIZE: f64 = 16.0f64;
+pub const DEFAULT_LINE_HEIGHT_SCALE: f64 = 1.2f64;
+/// How much `larger` and `smaller` scale the parent's font size by.
+pub const FONT_SIZE_SCALE: f64 = 1.2f64;
+
+impl<T: Clone> Sides<T> {
+    pub fn all(value: T) -> Sides<T> {
+        Sides {
+            top: value.clone(),
+            right: value.clone(),
+            bottom: value.clone(),
+            left: value,
+        }
     }
+}
 
-    pub fn display(&self) -> Display {
-        match self.value("display") {
-            Some(x) => match x[0] {
-                Value::Keyword(ref s) => match &**s {
-                    "block" => Display::Block,
-                    "inline-block" => Display::InlineBlock,
-                    "none" => Display::None,
-                    "inline" | _ => Display::Inline,
-                },
-                _ => Display::Inline,
-            },
-            _ => Display::Inline,
+impl LengthOrPercentage {
+    pub fn to_au(&self, containing_length: Au) -> Au {
+        match *self {
+            LengthOrPercentage::Length(au) => au,
+            LengthOrPercentage::Percentage(f) => {
+                Au::from_f64_px(containing_length.to_f64_px() * f / 100.0)
+            }
         }
     }
 
-    pub fn float(&self) -> FloatType {
-        match self.value("float") {
-            Some(x) => match x[0] {
-                Value::Keyword(ref s) => match &**s {
-                    "left" => FloatType::Left,
-                    "right" => FloatType::Right,
-                    "none" => FloatType::None,
-                    _ => FloatType::None,
-                },
-                _ => FloatType::None,
-            },
-            _ => FloatType::None,
+    fn from_value(value: &Value, ctx: &LengthContext) -> Option<LengthOrPercentage> {
+        match *value {
+            Value::Length(f, Unit::Percent) => Some(LengthOrPercentage::Percentage(f)),
+            _ => value
+                .to_px_in(ctx)
+                .map(|px| LengthOrPercentage::Length(Au::from_f64_px(px))),
         }
     }
 
-    pub fn clear(&self) -> Option<ClearType> {
-        match self.value("clear") {
-            Some(x) => match x[0] {
-                Value::Keyword(ref s) => match &**s {
-                    "left" => Some(ClearType::Left),
-                    "right" => Some(ClearType::Right),
-                    "both" => Some(ClearType::Both),
-                    _ => None,
-                },
-                _ => None,
-            },
-            _ => None,
+    /// Returns Some(None) for `none`.
+    fn from_value_or_none(
+        value: &Value,
+        ctx: &LengthContext,
+    ) -> Option<Option<LengthOrPercentage>> {
+        match *value {
+            Value::Keyword(ref k) if k == "none" => Some(None),
+            _ => LengthOrPercentage::from_value(value, ctx).map(Some),
         }
     }
+}
 
-    pub fn padding(&mut self) -> (Value, Value, Value, Value) {
-        match (
-            self.cached.padding.0.clone(),
-            self.cached.padding.1.clone(),
-            self.cached.padding.2.clone(),
-            self.cached.padding.3.clone(),
-        ) {
-            (Some(top), Some(right), Some(bottom), Some(left)) => return (top, right, bottom, left),
-            _ => {}
+impl LengthOrPercentageOrAuto {
+    /// Returns None for `auto`.
+    pub fn to_au(&self, containing_length: Au) -> Option<Au> {
+        match *self {
+            LengthOrPercentageOrAuto::Length(au) => Some(au),
+            LengthOrPercentageOrAuto::Percentage(f) => {
+                Some(LengthOrPercentage::Percentage(f).to_au(containing_length))
+            }
+            LengthOrPercentageOrAuto::Auto => None,
+        }
+    }
+
+    fn from_value(value: &Value, ctx: &LengthContext) -> Option<LengthOrPercentageOrAuto> {
+        match *value {
+            Value::Keyword(ref k) if k == "auto" => Some(LengthOrPercentageOrAuto::Auto),
+            _ => LengthOrPercentage::from_value(value, ctx).map(|length| match length {
+                LengthOrPercentage::Length(au) => LengthOrPercentageOrAuto::Length(au),
+                LengthOrPercentage::Percentage(f) => LengthOrPercentageOrAuto::Percentage(f),
+            }),
+        }
+    }
+}
+
+impl Default for ComputedStyle {
+    /// The initial values of all the properties.
+    fn default() -> ComputedStyle {
+        ComputedStyle {
+            display: Display::Inline,
+            float: FloatType::None,
+            clear: None,
+            width: LengthOrPercentageOrAuto::Auto,
+            height: LengthOrPercentageOrAuto::Auto,
+            min_width: LengthOrPercentageOrAuto::Auto,
+            min_height: LengthOrPercentageOrAuto::Auto,
+            max_width: None,
+            max_height: None,
+            box_sizing: BoxSizing::ContentBox,
+            margin: Sides::all(LengthOrPercentageOrAuto::Length(Au(0))),
+            padding: Sides::all(LengthOrPercentage::Length(Au(0))),
+            // `medium`, but `border-style` is `none`.
+            border_width: Sides::all(Au(0)),
+            border_style: Sides::all(BorderStyle::None),
+            border_color: Sides::all(BLACK),
+            z_index: None,
+            position: Position::Static,
+            inset: Sides::all(LengthOrPercentageOrAuto::Auto),
+            overflow_x: Overflow::Visible,
+            overflow_y: Overflow::Visible,
+            text_overflow: TextOverflow::Clip,
+            color: BLACK,
+            background_color: Color::default(),
+            font_family: vec![FontFamily::SansSerif],
+            font_size: Au::from_f64_px(DEFAULT_FONT_SIZE),
+            line_height: LineHeight::Normal,
+            font_weight: FontWeight::NORMAL,
+            font_style: FontSlant::Normal,
+            font_variant: FontVariant::Normal,
+            font_stretch: FontStretch::Normal,
+            text_align: TextAlign::Left,
+            word_break: WordBreak::Normal,
+            overflow_wrap: OverflowWrap::Normal,
+            line_break: LineBreak::Auto,
+            text_decoration: vec![],
+            list_style_type: ListStyleType::Disc,
+            list_style_position: ListStylePosition::Outside,
+            list_style_image: None,
+            border_collapse: BorderCollapse::Separate,
+            border_spacing: (Au(0), Au(0)),
+            table_layout: TableLayout::Auto,
+            vertical_align: VerticalAlign::Baseline,
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::NoWrap,
+            justify_content: ContentDistribution::Normal,
+            align_content: ContentDistribution::Normal,
+            align_items: ItemAlignment::Normal,
+            align_self: None,
+            row_gap: LengthOrPercentage::Length(Au(0)),
+            column_gap: LengthOrPercentage::Length(Au(0)),
+            order: 0,
+            flex_grow: 0.0,
+            flex_shrink: 1.0,
+            flex_basis: LengthOrPercentageOrAuto::Auto,
+            grid_template_columns: GridTemplate::default(),
+            grid_template_rows: GridTemplate::default(),
+            grid_template_areas: GridAreas::default(),
+            grid_auto_columns: vec![TrackSize::MinMax(TrackBreadth::Auto, TrackBreadth::Auto)],
+            grid_auto_rows: vec![TrackSize::MinMax(TrackBreadth::Auto, TrackBreadth::Auto)],
+            grid_auto_flow: GridAutoFlow::default(),
+            grid_row_start: GridLine::Auto,
+            grid_row_end: GridLine::Auto,
+            grid_column_start: GridLine::Auto,
+            grid_column_end: GridLine::Auto,
+            justify_items: ItemAlignment::Normal,
+            justify_self: None,
         }
+    }
+}
 
-        // padding has initial value 0.
-        let zero = Value::Length(0.0, Unit::Px);
+impl ComputedStyle {
+    /// The style a child gets before its own declarations apply: the inherited properties are
+    /// taken from `self` and the others are reset to their initial values.
+    pub fn inherited(&self) -> ComputedStyle {
+        ComputedStyle {
+            color: self.color,
+            border_color: Sides::all(self.color),
+            font_family: self.font_family.clone(),
+            font_size: self.font_size,
+            line_height: self.line_height,
+            font_weight: self.font_weight,
+            font_style: self.font_style,
+            font_variant: self.font_variant,
+            font_stretch: self.font_stretch,
+            text_align: self.text_align,
+            word_break: self.word_break,
+            overflow_wrap: self.overflow_wrap,
+            line_break: self.line_break,
+            // Text decorations aren't inherited but propagate to the descendants' text.
+            text_decoration: self.text_decoration.clone(),
+            list_style_type: self.list_style_type,
+            list_style_position: self.list_style_position,
+            list_style_image: self.list_style_image.clone(),
+            border_collapse: self.border_collapse,
+            border_spacing: self.border_spacing,
+            ..Default::default()
+        }
+    }
 
-        let mut padding_top = self.value("padding-top").and_then(|x| Some(x[0].clone()));
-        let mut padding_bottom = self.value("padding-bottom")
-            .and_then(|x| Some(x[0].clone()));
-        let mut padding_left = self.value("padding-left").and_then(|x| Some(x[0].clone()));
-        let mut padding_right = self.value("padding-right").and_then(|x| Some(x[0].clone()));
+    /// Computes the style of an element from its declared values and its parent's style.
+    /// Values that are invalid or unsupported for the property are ignored.
+    pub fn compute(
+        specified: &Style,
+        parent: &ComputedStyle,
+        ctx: &LengthContext,
+    ) -> ComputedStyle {
+        let mut style = parent.inherited();
+
+        // `em`s in `font-size` refer to the parent's font size, and everywhere else to the
+        // element's own, so `font-size` is computed first. Likewise `color` is needed to resolve
+        // `currentcolor` in the other properties.
+        if let Some(font_size) = specified.property.get("font-size") {
+            let parent_ctx = LengthContext {
+                font_size: parent.font_size.to_f64_px(),
+                ..*ctx
+            };
+            style.apply_property("font-size", font_size, parent, &parent_ctx);
+        }
+        let ctx = LengthContext {
+            font_size: style.font_size.to_f64_px(),
+            ..*ctx
+        };
+        if let Some(color) = specified.property.get("color") {
+            style.apply_property("color", color, parent, &ctx);
+        }
+        style.border_color = Sides::all(style.color);
 
-        if let Some(padding) = self.value("padding") {
-            match padding.len() {
-                1 => {
-                    padding_top.get_or_insert_with(|| padding[0].clone());
-                    padding_bottom.get_or_insert_with(|| padding[0].clone());
-                    padding_left.get_or_insert_with(|| padding[0].clone());
-                    padding_right.get_or_insert_with(|| padding[0].clone());
-                }
-                2 => {
-                    padding_top.get_or_insert_with(|| padding[0].clone());
-                    padding_bottom.get_or_insert_with(|| padding[0].clone());
-                    padding_left.get_or_insert_with(|| padding[1].clone());
-                    padding_right.get_or_insert_with(|| padding[1].clone());
-                }
-                3 => {
-                    padding_top.get_or_insert_with(|| padding[0].clone());
-                    padding_left.get_or_insert_with(|| padding[1].clone());
-                    padding_right.get_or_insert_with(|| padding[1].clone());
-                    padding_bottom.get_or_insert_with(|| padding[2].clone());
-                }
-                4 => {
-                    padding_top.get_or_insert_with(|| padding[0].clone());
-                    padding_right.get_or_insert_with(|| padding[1].clone());
-                    padding_bottom.get_or_insert_with(|| padding[2].clone());
-                    padding_left.get_or_insert_with(|| padding[3].clone());
-                }
-                0 | _ => unreachable!(),
+        for (name, values) in &specified.property {
+            if name != "font-size" && name != "color" {
+                style.apply_property(name, values, parent, &ctx);
             }
         }
 
-        padding_top.get_or_insert_with(|| zero.clone());
-        padding_right.get_or_insert_with(|| zero.clone());
-        padding_bottom.get_or_insert_with(|| zero.clone());
-        padding_left.get_or_insert_with(|| zero.clone());
+        // A border without a style takes no space.
+        if !style.border_style.top.is_visible() {
+            style.border_width.top = Au(0);
+        }
+        if !style.border_style.right.is_visible() {
+            style.border_width.right = Au(0);
+        }
+        if !style.border_style.bottom.is_visible() {
+            style.border_width.bottom = Au(0);
+        }
+        if !style.border_style.left.is_visible() {
+            style.border_width.left = Au(0);
+        }
 
-        self.cached.padding.0 = padding_top.clone();
-        self.cached.padding.1 = padding_right.clone();
-        self.cached.padding.2 = padding_bottom.clone();
-        self.cached.padding.3 = padding_left.clone();
+        // A box that scrolls on one axis can't let its content overflow visibly on the other.
+        if style.overflow_x.is_scrollable() || style.overflow_y.is_scrollable() {
+            style.overflow_x = style.overflow_x.scrollable();
+            style.overflow_y = style.overflow_y.scrollable();
+        }
 
-        (
-            padding_top.unwrap(),
-            padding_right.unwrap(),
-            padding_bottom.unwrap(),
-            padding_left.unwrap(),
-        )
+        style
     }
 
-    pub fn margin(&mut self) -> (Value, Value, Value, Value) {
-        match (
-            self.cached.margin.0.clone(),
-            self.cached.margin.1.clone(),
-            self.cached.margin.2.clone(),
-            self.cached.margin.3.clone(),
-        ) {
-            (Some(top), Some(right), Some(bottom), Some(left)) => return (top, right, bottom, left),
-            _ => {}
+    fn apply_property(
+        &mut self,
+        name: &str,
+        values: &Vec<Value>,
+        parent: &ComputedStyle,
+        ctx: &LengthContext,
+    ) {
+        match values.as_slice() {
+            &[Value::Keyword(ref keyword)] if is_css_wide_keyword(&values[0]) => {
+                self.apply_css_wide_keyword(name, keyword.as_str(), parent)
+            }
+            _ => self.set_property(name, values, parent, ctx),
         }
+    }
 
-        // margin has initial value 0.
-        let zero = Value::Length(0.0, Unit::Px);
+    /// `inherit`, `initial` and `unset`. `revert` is resolved by the cascade, and only remains
+    /// when no earlier origin sets the property, in which case it acts as `unset`.
+    /// ref. https://www.w3.org/TR/css-cascade-4/#defaulting-keywords
+    fn apply_css_wide_keyword(&mut self, name: &str, keyword: &str, parent: &ComputedStyle) {
+        let inherit = match keyword {
+            "inherit" => true,
+            "initial" => false,
+            _ => is_inherited(name),
+        };
+        if inherit {
+            self.copy_property(name, parent);
+        } else {
+            // The initial border colors are `currentcolor`.
+            let initial = ComputedStyle {
+                border_color: Sides::all(self.color),
+                ..Default::default()
+            };
+            self.copy_property(name, &initial);
+        }
+    }
 
-        let mut margin_top = self.value("margin-top").and_then(|x| Some(x[0].clone()));
-        let mut margin_bottom = self.value("margin-bottom").and_then(|x| Some(x[0].clone()));
-        let mut margin_left = self.value("margin-left").and_then(|x| Some(x[0].clone()));
-        let mut margin_right = self.value("margin-right").and_then(|x| Some(x[0].clone()));
+    /// Sets the property `name` to its computed value in `from`.
+    fn copy_property(&mut self, name: &str, from: &ComputedStyle) {
+        match name {
+            "display" => self.display = from.display,
+            "float" => self.float = from.float,
+            "clear" => self.clear = from.clear,
+            "width" => self.width = from.width,
+            "height" => self.height = from.height,
+            "min-width" => self.min_width = from.min_width,
+            "min-height" => self.min_height = from.min_height,
+            "max-width" => self.max_width = from.max_width,
+            "max-height" => self.max_height = from.max_height,
+            "box-sizing" => self.box_sizing = from.box_sizing,
+            "margin-top" => self.margin.top = from.margin.top,
+            "margin-right" => self.margin.right = from.margin.right,
+            "margin-bottom" => self.margin.bottom = from.margin.bottom,
+            "margin-left" => self.margin.left = from.margin.left,
+            "padding-top" => self.padding.top = from.padding.top,
+            "padding-right" => self.padding.right = from.padding.right,
+            "padding-bottom" => self.padding.bottom = from.padding.bottom,
+            "padding-left" => self.padding.left = from.padding.left,
+            "border-top-width" => self.border_width.top = from.border_width.top,
+            "border-right-width" => self.border_width.right = from.border_width.right,
+            "border-bottom-width" => self.border_width.bottom = from.border_width.bottom,
+            "border-left-width" => self.border_width.left = from.border_width.left,
+            "border-top-style" => self.border_style.top = from.border_style.top,
+            "border-right-style" => self.border_style.right = from.border_style.right,
+            "border-bottom-style" => self.border_style.bottom = from.border_style.bottom,
+            "border-left-style" => self.border_style.left = from.border_style.left,
+            "border-top-color" => self.border_color.top = from.border_color.top,
+            "border-right-color" => self.border_color.right = from.border_color.right,
+            "border-bottom-color" => self.border_color.bottom = from.border_color.bottom,
+            "border-left-color" => self.border_color.left = from.border_color.left,
+            "z-index" => self.z_index = from.z_index,
+            "position" => self.position = from.position,
+            "top" => self.inset.top = from.inset.top,
+            "right" => self.inset.right = from.inset.right,
+            "bottom" => self.inset.bottom = from.inset.bottom,
+            "left" => self.inset.left = from.inset.left,
+            "overflow-x" => self.overflow_x = from.overflow_x,
+            "overflow-y" => self.overflow_y = from.overflow_y,
+            "text-overflow" => self.text_overflow = from.text_overflow,
+            "color" => self.color = from.color,
+            "background-color" => self.background_color = from.background_color,
+            "font-family" => self.font_family = from.font_family.clone(),
+            "font-size" => self.font_size = from.font_size,
+            "line-height" => self.line_height = from.line_height,
+            "font-weight" => self.font_weight = from.font_weight,
+            "font-style" => self.font_style = from.font_style,
+            "font-variant" => self.font_variant = from.font_variant,
+            "font-stretch" => self.font_stretch = from.font_stretch,
+            "text-align" => self.text_align = from.text_align,
+            "word-break" => self.word_break = from.word_break,
+            "overflow-wrap" => self.overflow_wrap = from.overflow_wrap,
+            "line-break" => self.line_break = from.line_break,
+            // The decorations propagated from the ancestors apply whatever the element sets.
+            "text-decoration" => {}
+            "list-style-type" => self.list_style_type = from.list_style_type,
+            "list-style-position" => self.list_style_position = from.list_style_position,
+            "list-style-image" => self.list_style_image = from.list_style_image.clone(),
+            "border-collapse" => self.border_collapse = from.border_collapse,
+            "border-spacing" => self.border_spacing = from.border_spacing,
+            "table-layout" => self.table_layout = from.table_layout,
+            "vertical-align" => self.vertical_align = from.vertical_align,
+            "flex-direction" => self.flex_direction = from.flex_direction,
+            "flex-wrap" => self.flex_wrap = from.flex_wrap,
+            "justify-content" => self.justify_content = from.justify_content,
+            "align-content" => self.align_content = from.align_content,
+            "align-items" => self.align_items = from.align_items,
+            "align-self" => self.align_self = from.align_self,
+            "row-gap" => self.row_gap = from.row_gap,
+            "column-gap" => self.column_gap = from.column_gap,
+            "order" => self.order = from.order,
+            "flex-grow" => self.flex_grow = from.flex_grow,
+            "flex-shrink" => self.flex_shrink = from.flex_shrink,
+            "flex-basis" => self.flex_basis = from.flex_basis,
+            "grid-template-columns" => {
+                self.grid_template_columns = from.grid_template_columns.clone()
+            }
+            "grid-template-rows" => self.grid_template_rows = from.grid_template_rows.clone(),
+            "grid-template-areas" => self.grid_template_areas = from.grid_template_areas.clone(),
+            "grid-auto-columns" => self.grid_auto_columns = from.grid_auto_columns.clone(),
+            "grid-auto-rows" => self.grid_auto_rows = from.grid_auto_rows.clone(),
+            "grid-auto-flow" => self.grid_auto_flow = from.grid_auto_flow,
+            "grid-row-start" => self.grid_row_start = from.grid_row_start.clone(),
+            "grid-row-end" => self.grid_row_end = from.grid_row_end.clone(),
+            "grid-column-start" => self.grid_column_start = from.grid_column_start.clone(),
+            "grid-column-end" => self.grid_column_end = from.grid_column_end.clone(),
+            "justify-items" => self.justify_items = from.justify_items,
+            "justify-self" => self.justify_self = from.justify_self,
+            _ => {}
+        }
+    }
 
-        if let Some(margin) = self.value("margin") {
-            match margin.len() {
-                1 => {
-                    margin_top.get_or_insert_with(|| margin[0].clone());
-                    margin_bottom.get_or_insert_with(|| margin[0].clone());
-                    margin_left.get_or_insert_with(|| margin[0].clone());
-                    margin_right.get_or_insert_with(|| margin[0].clone());
+    fn set_property(
+        &mut self,
+        name: &str,
+        values: &Vec<Value>,
+        parent: &ComputedStyle,
+        ctx: &LengthContext,
+    ) {
+        let value = match values.first() {
+            Some(value) => value,
+            None => return,
+        };
+
+        macro_rules! set { ($field:expr, $computed:expr) => {
+            if let Some(computed) = $computed {
+                $field = computed;
+            }
+        }}
+
+        match name {
+            "display" => set!(self.display, value.to_display()),
+            "float" => set!(self.float, value.to_float_type()),
+            "clear" => set!(self.clear, value.to_clear_type()),
+            "width" => set!(self.width, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "height" => set!(self.height, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "min-width" => set!(self.min_width, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "min-height" => {
+                set!(self.min_height, LengthOrPercentageOrAuto::from_value(value, ctx))
+            }
+            "max-width" => set!(self.max_width, LengthOrPercentage::from_value_or_none(value, ctx)),
+            "max-height" => {
+                set!(self.max_height, LengthOrPercentage::from_value_or_none(value, ctx))
+            }
+            "box-sizing" => set!(self.box_sizing, value.to_box_sizing()),
+            "margin-top" => set!(self.margin.top, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "margin-right" => {
+                set!(self.margin.right, LengthOrPercentageOrAuto::from_value(value, ctx))
+            }
+            "margin-bottom" => {
+                set!(self.margin.bottom, LengthOrPercentageOrAuto::from_value(value, ctx))
+            }
+            "margin-left" => {
+                set!(self.margin.left, LengthOrPercentageOrAuto::from_value(value, ctx))
+            }
+            "padding-top" => set!(self.padding.top, LengthOrPercentage::from_value(value, ctx)),
+            "padding-right" => {
+                set!(self.padding.right, LengthOrPercentage::from_value(value, ctx))
+            }
+            "padding-bottom" => {
+                set!(self.padding.bottom, LengthOrPercentage::from_value(value, ctx))
+            }
+            "padding-left" => set!(self.padding.left, LengthOrPercentage::from_value(value, ctx)),
+            "border-top-width" => set!(self.border_width.top, value.to_line_width(ctx)),
+            "border-right-width" => set!(self.border_width.right, value.to_line_width(ctx)),
+            "border-bottom-width" => set!(self.border_width.bottom, value.to_line_width(ctx)),
+            "border-left-width" => set!(self.border_width.left, value.to_line_width(ctx)),
+            "border-top-style" => set!(self.border_style.top, value.to_border_style()),
+            "border-right-style" => set!(self.border_style.right, value.to_border_style()),
+            "border-bottom-style" => set!(self.border_style.bottom, value.to_border_style()),
+            "border-left-style" => set!(self.border_style.left, value.to_border_style()),
+            "border-top-color" => {
+                set!(self.border_color.top, value.to_color_or_current(self.color))
+            }
+            "border-right-color" => {
+                set!(self.border_color.right, value.to_color_or_current(self.color))
+            }
+            "border-bottom-color" => {
+                set!(self.border_color.bottom, value.to_color_or_current(self.color))
+            }
+            "border-left-color" => {
+                set!(self.border_color.left, value.to_color_or_current(self.color))
+            }
+            "z-index" => set!(self.z_index, value.to_z_index()),
+            "position" => set!(self.position, value.to_position()),
+            "top" => set!(self.inset.top, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "right" => set!(self.inset.right, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "bottom" => {
+                set!(self.inset.bottom, LengthOrPercentageOrAuto::from_value(value, ctx))
+            }
+            "left" => set!(self.inset.left, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            "overflow-x" => set!(self.overflow_x, value.to_overflow()),
+            "overflow-y" => set!(self.overflow_y, value.to_overflow()),
+            "text-overflow" => set!(self.text_overflow, value.to_text_overflow()),
+            // `color: currentcolor` is the same as `color: inherit`.
+            "color" => set!(self.color, value.to_color_or_current(parent.color)),
+            "background-color" => {
+                set!(self.background_color, value.to_color_or_current(self.color))
+            }
+            "font-family" => {
+                let families: Vec<FontFamily> =
+                    values.iter().filter_map(|value| value.to_font_family()).collect();
+                if !families.is_empty() {
+                    self.font_family = families;
                 }
-                2 => {
-                    margin_top.get_or_insert_with(|| margin[0].clone());
-                    margin_bottom.get_or_insert_with(|| margin[0].clone());
-                    margin_left.get_or_insert_with(|| margin[1].clone());
-                    margin_right.get_or_insert_with(|| margin[1].clone());
+            }
+            "font-size" => set!(self.font_size, value.to_font_size(parent.font_size, ctx)),
+            "line-height" => set!(self.line_height, value.to_line_height(self.font_size, ctx)),
+            "font-weight" => set!(self.font_weight, value.to_font_weight(parent.font_weight)),
+            "font-style" => set!(self.font_style, value.to_font_slant()),
+            "font-variant" => set!(self.font_variant, value.to_font_variant()),
+            "font-stretch" => set!(self.font_stretch, value.to_font_stretch()),
+            "text-align" => set!(self.text_align, value.to_text_align()),
+            "word-break" => set!(self.word_break, value.to_word_break()),
+            "overflow-wrap" => set!(self.overflow_wrap, value.to_overflow_wrap()),
+            "line-break" => set!(self.line_break, value.to_line_break()),
+            "text-decoration" => for value in values {
+                match value.to_text_decoration() {
+                    Some(TextDecoration::None) | None => {}
+                    Some(decoration) => if !self.text_decoration.contains(&decoration) {
+                        self.text_decoration.push(decoration)
+                    },
                 }
-                3 => {
-                    margin_top.get_or_insert_with(|| margin[0].clone());
-                    margin_left.get_or_insert_with(|| margin[1].clone());
-                    margin_right.get_or_insert_with(|| margin[1].clone());
-                    margin_bottom.get_or_insert_with(|| margin[2].clone());
+            },
+            "list-style-type" => set!(self.list_style_type, value.to_list_style_type()),
+            "list-style-position" => {
+                set!(self.list_style_position, value.to_list_style_position())
+            }
+            "list-style-image" => set!(self.list_style_image, value.to_list_style_image()),
+            "border-collapse" => set!(self.border_collapse, value.to_border_collapse()),
+            // One length for both directions, or the horizontal then the vertical spacing.
+            "border-spacing" => {
+                let lengths: Option<Vec<Au>> = values
+                    .iter()
+                    .map(|value| value.to_px_in(ctx).map(Au::from_f64_px))
+                    .collect();
+                match lengths.as_ref().map(|lengths| lengths.as_slice()) {
+                    Some(&[spacing]) => self.border_spacing = (spacing, spacing),
+                    Some(&[horizontal, vertical]) => self.border_spacing = (horizontal, vertical),
+                    _ => {}
                 }
-                4 => {
-                    margin_top.get_or_insert_with(|| margin[0].clone());
-                    margin_right.get_or_insert_with(|| margin[1].clone());
-                    margin_bottom.get_or_insert_with(|| margin[2].clone());
-                    margin_left.get_or_insert_with(|| margin[3].clone());
+            }
+            "table-layout" => set!(self.table_layout, value.to_table_layout()),
+            "vertical-align" => set!(self.vertical_align, value.to_vertical_align(ctx)),
+            "flex-direction" => set!(self.flex_direction, value.to_flex_direction()),
+            "flex-wrap" => set!(self.flex_wrap, value.to_flex_wrap()),
+            "justify-content" => set!(self.justify_content, value.to_content_distribution()),
+            "align-content" => set!(self.align_content, value.to_content_distribution()),
+            "align-items" => set!(self.align_items, value.to_item_alignment()),
+            "align-self" => match *value {
+                Value::Keyword(ref k) if k == "auto" => self.align_self = None,
+                _ => set!(self.align_self, value.to_item_alignment().map(Some)),
+            },
+            "row-gap" => set!(self.row_gap, value.to_gap(ctx)),
+            "column-gap" => set!(self.column_gap, value.to_gap(ctx)),
+            "order" => if let Value::Num(n) = *value {
+                self.order = n as i32
+            },
+            "flex-grow" => if let Value::Num(n) = *value {
+                self.flex_grow = n
+            },
+            "flex-shrink" => if let Value::Num(n) = *value {
+                self.flex_shrink = n
+            },
+            "flex-basis" => match *value {
+                Value::Keyword(ref k) if k == "content" => {
+                    self.flex_basis = LengthOrPercentageOrAuto::Auto
                 }
-                0 | _ => unreachable!(),
+                _ => set!(self.flex_basis, LengthOrPercentageOrAuto::from_value(value, ctx)),
+            },
+            "grid-template-columns" => {
+                set!(self.grid_template_columns, GridTemplate::from_values(values, ctx))
+            }
+            "grid-template-rows" => {
+                set!(self.grid_template_rows, GridTemplate::from_values(values, ctx))
             }
+            "grid-template-areas" => set!(self.grid_template_areas, GridAreas::from_values(values)),
+            "grid-auto-columns" => set!(self.grid_auto_columns, track_sizes(values, ctx)),
+            "grid-auto-rows" => set!(self.grid_auto_rows, track_sizes(values, ctx)),
+            "grid-auto-flow" => set!(self.grid_auto_flow, GridAutoFlow::from_values(values)),
+            "grid-row-start" => set!(self.grid_row_start, GridLine::from_values(values)),
+            "grid-row-end" => set!(self.grid_row_end, GridLine::from_values(values)),
+            "grid-column-start" => set!(self.grid_column_start, GridLine::from_values(values)),
+            "grid-column-end" => set!(self.grid_column_end, GridLine::from_values(values)),
+            "justify-items" => match *value {
+                Value::Keyword(ref k) if k == "legacy" => {
+                    self.justify_items = ItemAlignment::Normal
+                }
+                _ => set!(self.justify_items, value.to_item_alignment()),
+            },
+            "justify-self" => match *value {
+                Value::Keyword(ref k) if k == "auto" => self.justify_self = None,
+                _ => set!(self.justify_self, value.to_item_alignment().map(Some)),
+            },
+            _ => {}
         }
+    }
 
-        margin_top.get_or_insert_with(|| zero.clone());
-        margin_right.get_or_insert_with(|| zero.clone());
-        margin_bottom.get_or_insert_with(|| zero.clone());
-        margin_left.get_or_insert_with(|| zero.clone());
-
-        self.cached.margin.0 = margin_top.clone();
-        self.cached.margin.1 = margin_right.clone();
-        self.cached.margin.2 = margin_bottom.clone();
-        self.cached.margin.3 = margin_left.clone();
-
-        (
-            margin_top.unwrap(),
-            margin_right.unwrap(),
-            margin_bottom.unwrap(),
-            margin_left.unwrap(),
+    /// The font text in this style is drawn with.
+    pub fn font(&self) -> Font {
+        Font::new(
+            self.font_family.clone(),
+            self.font_size,
+            self.font_weight,
+            self.font_style,
+            self.font_variant,
+            self.font_stretch,
         )
     }
 
-    pub fn border_width(&mut self) -> (Value, Value, Value, Value) {
-        match (
-            self.cached.border_width.0.clone(),
-            self.cached.border_width.1.clone(),
-            self.cached.border_width.2.clone(),
-            self.cached.border_width.3.clone(),
-        ) {
-            (Some(top), Some(right), Some(bottom), Some(left)) => return (top, right, bottom, left),
-            _ => {}
+    /// The used `line-height`.
+    pub fn line_height(&self) -> Au {
+        let font_size = self.font_size.to_f64_px();
+        match self.line_height {
+            LineHeight::Normal => Au::from_f64_px(font_size * DEFAULT_LINE_HEIGHT_SCALE),
+            LineHeight::Number(f) => Au::from_f64_px(font_size * f),
+            LineHeight::Length(au) => au,
         }
+    }
+}
 
-        // border has initial value 0.
-        let zero = Value::Length(0.0, Unit::Px);
-
-        let mut border_top = self.value("border-top-width")
-            .and_then(|x| Some(x[0].clone()));
-        let mut border_bottom = self.value("border-bottom-width")
-            .and_then(|x| Some(x[0].clone()));
-        let mut border_left = self.value("border-left-width")
-            .and_then(|x| Some(x[0].clone()));
-        let mut border_right = self.value("border-right-width")
-            .and_then(|x| Some(x[0].clone()));
-
-        macro_rules! return_if_possible {
-            () => {
-                if border_top.is_some() && border_bottom.is_some()
-                    && border_left.is_some() && border_right.is_some() {
-                    return (
-                        border_top.unwrap(), border_right.unwrap(),
-                        border_bottom.unwrap(), border_left.unwrap(),
-                    );
-                }
-            }
+impl Display {
+    /// The block-level display of a box that has to be a block, like a flex item.
+    /// ref. https://www.w3.org/TR/css-display-3/#blockify
+    pub fn blockified(self) -> Display {
+        match self {
+            Display::InlineTable => Display::Table,
+            Display::InlineFlex => Display::Flex,
+            Display::InlineGrid => Display::Grid,
+            Display::Block
+            | Display::ListItem
+            | Display::Table
+            | Display::Flex
+            | Display::Grid
+            | Display::None => self,
+            _ => Display::Block,
         }
+    }
+}
 
-        return_if_possible!();
+impl Default for TrackList {
+    /// No tracks, and a single line without names.
+    fn default() -> TrackList {
+        TrackList {
+            sizes: vec![],
+            line_names: vec![vec![]],
+        }
+    }
+}
 
-        if let Some(border) = self.value("border-width") {
-            match border.len() {
-                1 => {
-                    border_top.get_or_insert_with(|| border[0].clone());
-                    border_bottom.get_or_insert_with(|| border[0].clone());
-                    border_left.get_or_insert_with(|| border[0].clone());
-                    border_right.get_or_insert_with(|| border[0].clone());
-                }
-                2 => {
-                    border_top.get_or_insert_with(|| border[0].clone());
-                    border_bottom.get_or_insert_with(|| border[0].clone());
-                    border_left.get_or_insert_with(|| border[1].clone());
-                    border_right.get_or_insert_with(|| border[1].clone());
-                }
-                3 => {
-                    border_top.get_or_insert_with(|| border[0].clone());
-                    border_left.get_or_insert_with(|| border[1].clone());
-                    border_right.get_or_insert_with(|| border[1].clone());
-                    border_bottom.get_or_insert_with(|| border[2].clone());
-                }
-                4 => {
-                    border_top.get_or_insert_with(|| border[0].clone());
-                    border_right.get_or_insert_with(|| border[1].clone());
-                    border_bottom.get_or_insert_with(|| border[2].clone());
-                    border_left.get_or_insert_with(|| border[3].clone());
-                }
-                0 | _ => unreachable!(),
+impl TrackList {
+    /// Track sizes and bracketed line names, without `repeat()`.
+    fn from_values(values: &[Value], ctx: &LengthContext) -> Option<TrackList> {
+        let mut list = TrackList::default();
+        for value in values {
+            list.push(value, ctx)?;
+        }
+        if list.sizes.is_empty() {
+            return None;
+        }
+        Some(list)
+    }
+
+    fn push(&mut self, value: &Value, ctx: &LengthContext) -> Option<()> {
+        match *value {
+            Value::LineNames(ref names) => {
+                self.line_names.last_mut().unwrap().extend(names.iter().cloned())
+            }
+            _ => {
+                self.sizes.push(value.to_track_size(ctx)?);
+                self.line_names.push(vec![]);
             }
         }
+        Some(())
+    }
 
-        return_if_possible!();
+    /// Adds the tracks of `other` after these. The last line of these and the first line of
+    /// `other` become the same line, with the names of both.
+    pub fn append(&mut self, other: &TrackList) {
+        self.line_names
+            .last_mut()
+            .unwrap()
+            .extend(other.line_names[0].iter().cloned());
+        self.sizes.extend(other.sizes.iter().cloned());
+        self.line_names
+            .extend(other.line_names[1..].iter().cloned());
+    }
+}
 
-        macro_rules! f { ($name:expr, $var:expr) => {
-            if let Some(border_info) = self.value($name) {
-                for border in border_info {
-                    if let &Value::Length(_, _) = &border {
-                        $var.get_or_insert_with(|| border.clone());
-                        break;
+impl GridTemplate {
+    /// `none`, or a track list. Each `repeat()` repeats one or more tracks, and there may be a
+    /// single `repeat(auto-fill, ...)` or `repeat(auto-fit, ...)` of tracks with a fixed size.
+    /// ref. https://www.w3.org/TR/css-grid-1/#track-sizing
+    pub fn from_values(values: &[Value], ctx: &LengthContext) -> Option<GridTemplate> {
+        let mut template = GridTemplate::default();
+        if let &[Value::Keyword(ref k)] = values {
+            return if k == "none" { Some(template) } else { None };
+        }
+        for value in values {
+            match *value {
+                Value::Function(ref name, ref args) if name == "repeat" => {
+                    let (count, tracks) = match args.as_slice() {
+                        &[ref count, ref tracks] if count.len() == 1 => (&count[0], tracks),
+                        _ => return None,
+                    };
+                    let tracks = TrackList::from_values(tracks, ctx)?;
+                    match *count {
+                        Value::Num(n) if n >= 1.0 && n == n.trunc() => for _ in 0..n as usize {
+                            template.current().append(&tracks)
+                        },
+                        Value::Keyword(ref k)
+                            if (k == "auto-fill" || k == "auto-fit")
+                                && template.auto_repeat.is_none()
+                                && tracks.sizes.iter().all(|size| size.is_fixed()) =>
+                        {
+                            template.auto_repeat = Some(AutoRepeat {
+                                fit: k == "auto-fit",
+                                tracks: tracks,
+                            })
+                        }
+                        _ => return None,
                     }
                 }
+                _ => template.current().push(value, ctx)?,
             }
-            return_if_possible!();
-        } }
+        }
+        if template.tracks.sizes.is_empty() && template.auto_repeat.is_none()
+            && template.after.sizes.is_empty()
+        {
+            return None;
+        }
+        Some(template)
+    }
 
-        f!("border-top", border_top);
-        f!("border-bottom", border_bottom);
-        f!("border-left", border_left);
-        f!("border-right", border_right);
+    /// The list the next tracks are added to.
+    fn current(&mut self) -> &mut TrackList {
+        if self.auto_repeat.is_some() {
+            &mut self.after
+        } else {
+            &mut self.tracks
+        }
+    }
 
-        if let Some(border_info) = self.value("border") {
-            let mut border_width = None;
-            for border in border_info {
-                if let &Value::Length(_, _) = &border {
-                    border_width = Some(border);
-                    break;
-                }
-            }
-            if let Some(border_width) = border_width {
-                border_top.get_or_insert_with(|| border_width.clone());
-                border_right.get_or_insert_with(|| border_width.clone());
-                border_bottom.get_or_insert_with(|| border_width.clone());
-                border_left.get_or_insert_with(|| border_width.clone());
+    /// The tracks with `repeats` repetitions of the automatic repetition.
+    pub fn expand(&self, repeats: usize) -> TrackList {
+        let mut list = self.tracks.clone();
+        if let Some(ref auto_repeat) = self.auto_repeat {
+            for _ in 0..repeats {
+                list.append(&auto_repeat.tracks);
             }
         }
+        list.append(&self.after);
+        list
+    }
+}
 
-        border_top.get_or_insert_with(|| zero.clone());
-        border_right.get_or_insert_with(|| zero.clone());
-        border_bottom.get_or_insert_with(|| zero.clone());
-        border_left.get_or_insert_with(|| zero.clone());
-
-        self.cached.border_width.0 = border_top.clone();
-        self.cached.border_width.1 = border_right.clone();
-        self.cached.border_width.2 = border_bottom.clone();
-        self.cached.border_width.3 = border_left.clone();
-        (
-            border_top.unwrap(),
-            border_right.unwrap(),
-            border_bottom.unwrap(),
-            border_left.unwrap(),
-        )
+impl TrackSize {
+    /// Whether the size has a minimum or a maximum that is a length or a percentage.
+    pub fn is_fixed(&self) -> bool {
+        match *self {
+            TrackSize::MinMax(TrackBreadth::Length(_), _)
+            | TrackSize::MinMax(_, TrackBreadth::Length(_)) => true,
+            _ => false,
+        }
     }
+}
 
-    pub fn border_color(&mut self) -> (Option<Color>, Option<Color>, Option<Color>, Option<Color>) {
-        if self.cached.border_color.0.is_some() || self.cached.border_color.1.is_some()
-            || self.cached.border_color.2.is_some()
-            || self.cached.border_color.3.is_some()
-        {
-            return (
-                self.cached.border_color.0.clone(),
-                self.cached.border_color.1.clone(),
-                self.cached.border_color.2.clone(),
-                self.cached.border_color.3.clone(),
-            );
-        }
-
-        let mut border_top = self.value("border-top-color").and_then(|x| x[0].to_color());
-        let mut border_bottom = self.value("border-bottom-color")
-            .and_then(|x| x[0].to_color());
-        let mut border_left = self.value("border-left-color")
-            .and_then(|x| x[0].to_color());
-        let mut border_right = self.value("border-right-color")
-            .and_then(|x| x[0].to_color());
-
-        macro_rules! return_if_possible {
-            () => {
-                if border_top.is_some() && border_bottom.is_some()
-                    && border_left.is_some() && border_right.is_some() {
-                    return (
-                        border_top, border_right,
-                        border_bottom, border_left,
-                    );
+/// `grid-auto-columns` and `grid-auto-rows`: one or more track sizes, repeated as needed.
+fn track_sizes(values: &[Value], ctx: &LengthContext) -> Option<Vec<TrackSize>> {
+    let sizes: Option<Vec<TrackSize>> = values.iter().map(|v| v.to_track_size(ctx)).collect();
+    sizes.and_then(|sizes| if sizes.is_empty() { None } else { Some(sizes) })
+}
+
+impl GridAreas {
+    /// `none`, or a string for each row, of names or `.`s for cells that aren't in an area. Each
+    /// name has to make up a rectangle, and each row has to have as many cells.
+    /// ref. https://www.w3.org/TR/css-grid-1/#grid-template-areas-property
+    pub fn from_values(values: &[Value]) -> Option<GridAreas> {
+        if let &[Value::Keyword(ref k)] = values {
+            return if k == "none" { Some(GridAreas::default()) } else { None };
+        }
+        let mut cells: Vec<Vec<String>> = vec![];
+        for value in values {
+            match *value {
+                Value::Str(ref row) => {
+                    cells.push(row.split_whitespace().map(|cell| cell.to_lowercase()).collect())
                 }
+                _ => return None,
             }
         }
+        let columns = cells.first().map_or(0, |row| row.len());
+        if columns == 0 || cells.iter().any(|row| row.len() != columns) {
+            return None;
+        }
 
-        if let Some(border) = self.value("border-color") {
-            match border.len() {
-                1 => {
-                    border_top.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_bottom.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_left.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_right.get_or_insert_with(|| border[0].to_color().unwrap());
-                }
-                2 => {
-                    border_top.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_bottom.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_left.get_or_insert_with(|| border[1].to_color().unwrap());
-                    border_right.get_or_insert_with(|| border[1].to_color().unwrap());
+        let mut areas: Vec<GridArea> = vec![];
+        for (row, names) in cells.iter().enumerate() {
+            for (column, name) in names.iter().enumerate() {
+                if name.chars().all(|c| c == '.') {
+                    continue;
                 }
-                3 => {
-                    border_top.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_left.get_or_insert_with(|| border[1].to_color().unwrap());
-                    border_right.get_or_insert_with(|| border[1].to_color().unwrap());
-                    border_bottom.get_or_insert_with(|| border[2].to_color().unwrap());
+                if areas.iter().any(|area| &area.name == name) {
+                    continue;
                 }
-                4 => {
-                    border_top.get_or_insert_with(|| border[0].to_color().unwrap());
-                    border_right.get_or_insert_with(|| border[1].to_color().unwrap());
-                    border_bottom.get_or_insert_with(|| border[2].to_color().unwrap());
-                    border_left.get_or_insert_with(|| border[3].to_color().unwrap());
+                let width = names[column..].iter().take_while(|n| n == &name).count();
+                let height = cells[row..]
+                    .iter()
+                    .take_while(|names| &names[column] == name)
+                    .count();
+                areas.push(GridArea {
+                    name: name.clone(),
+                    rows: (row, row + height),
+                    columns: (column, column + width),
+                });
+            }
+        }
+        // Each cell with a name has to be in the rectangle found for it.
+        for (row, names) in cells.iter().enumerate() {
+            for (column, name) in names.iter().enumerate() {
+                let inside = |area: &GridArea| {
+                    area.rows.0 <= row && row < area.rows.1 && area.columns.0 <= column
+                        && column < area.columns.1
+                };
+                match areas.iter().find(|area| inside(area)) {
+                    Some(area) if &area.name == name => {}
+                    None if name.chars().all(|c| c == '.') => {}
+                    _ => return None,
                 }
-                0 | _ => unreachable!(),
             }
         }
 
-        return_if_possible!();
+        Some(GridAreas {
+            rows: cells.len(),
+            columns: columns,
+            areas: areas,
+        })
+    }
+}
 
-        macro_rules! f { ($name:expr, $var:expr) => {
-            if let Some(border_info) = self.value($name) {
-                if let Some(border_color) = (|| {
-                    for border in border_info {
-                        let color = border.to_color();
-                        if color.is_some() { return color; }
-                    }
-                    None
-                })()
-                {
-                    $var.get_or_insert_with(|| border_color.clone());
+impl GridLine {
+    /// `auto`, a name, `integer && name?`, or `span && [integer || name]`.
+    pub fn from_values(values: &[Value]) -> Option<GridLine> {
+        let (mut span, mut number, mut name) = (false, None, None);
+        for value in values {
+            match *value {
+                Value::Keyword(ref k) if k == "auto" => {
+                    return if values.len() == 1 { Some(GridLine::Auto) } else { None }
+                }
+                Value::Keyword(ref k) if k == "span" && !span => span = true,
+                Value::Keyword(ref k) if name.is_none() && k != "span" => name = Some(k.clone()),
+                Value::Num(n) if number.is_none() && n == n.trunc() && n != 0.0 => {
+                    number = Some(n as i32)
                 }
+                _ => return None,
             }
-            return_if_possible!();
-        } }
-
-        f!("border-top", border_top);
-        f!("border-bottom", border_bottom);
-        f!("border-left", border_left);
-        f!("border-right", border_right);
+        }
+        match (span, number, name) {
+            (true, Some(n), _) if n < 0 => None,
+            (true, n, name) => Some(GridLine::Span(n.unwrap_or(1), name)),
+            (false, Some(n), name) => Some(GridLine::Line(n, name)),
+            (false, None, Some(name)) => Some(GridLine::Name(name)),
+            (false, None, None) => None,
+        }
+    }
+}
 
-        if let Some(border_info) = self.value("border") {
-            if let Some(border_color) = (|| {
-                for border in border_info {
-                    let color = border.to_color();
-                    if color.is_some() {
-                        return color;
-                    }
+impl GridAutoFlow {
+    /// `[row | column] || dense`
+    pub fn from_values(values: &[Value]) -> Option<GridAutoFlow> {
+        let mut flow = GridAutoFlow::default();
+        let (mut direction, mut dense) = (false, false);
+        for value in values {
+            match value.keyword()? {
+                "row" | "column" if !direction => {
+                    direction = true;
+                    flow.column = value.keyword() == Some("column");
                 }
-                None
-            })()
-            {
-                border_top.get_or_insert_with(|| border_color.clone());
-                border_right.get_or_insert_with(|| border_color.clone());
-                border_bottom.get_or_insert_with(|| border_color.clone());
-                border_left.get_or_insert_with(|| border_color.clone());
+                "dense" if !dense => {
+                    dense = true;
+                    flow.dense = true;
+                }
+                _ => return None,
             }
         }
+        if values.is_empty() {
+            return None;
+        }
+        Some(flow)
+    }
+}
+
+impl BorderStyle {
+    /// Whether a border in this style is drawn and takes space.
+    pub fn is_visible(&self) -> bool {
+        match *self {
+            BorderStyle::None | BorderStyle::Hidden => false,
+            _ => true,
+        }
+    }
+}
+
+impl Value {
+    pub fn keyword(&self) -> Option<&str> {
+        match *self {
+            Value::Keyword(ref k) => Some(k.as_str()),
+            _ => None,
+        }
+    }
 
-        self.cached.border_color.0 = border_top.clone();
-        self.cached.border_color.1 = border_right.clone();
-        self.cached.border_color.2 = border_bottom.clone();
-        self.cached.border_color.3 = border_left.clone();
+    /// Display types without a layout of their own yet are laid out as the closest one we have.
+    pub fn to_display(&self) -> Option<Display> {
+        Some(match self.keyword()? {
+            "block" => Display::Block,
+            "inline-block" => Display::InlineBlock,
+            "none" => Display::None,
+            "inline" => Display::Inline,
+            "list-item" => Display::ListItem,
+            "table" => Display::Table,
+            "inline-table" => Display::InlineTable,
+            "table-row-group" => Display::TableRowGroup,
+            "table-header-group" => Display::TableHeaderGroup,
+            "table-footer-group" => Display::TableFooterGroup,
+            "table-row" => Display::TableRow,
+            "table-cell" => Display::TableCell,
+            "table-caption" => Display::TableCaption,
+            "table-column" => Display::TableColumn,
+            "table-column-group" => Display::TableColumnGroup,
+            "flex" => Display::Flex,
+            "inline-flex" => Display::InlineFlex,
+            "grid" => Display::Grid,
+            "inline-grid" => Display::InlineGrid,
+            "flow-root" => Display::Block,
+            _ => return None,
+        })
+    }
 
-        (border_top, border_right, border_bottom, border_left)
+    /// Counter styles that aren't predefined are ignored, as are strings.
+    pub fn to_list_style_type(&self) -> Option<ListStyleType> {
+        Some(match self.keyword()? {
+            "disc" => ListStyleType::Disc,
+            "circle" => ListStyleType::Circle,
+            "square" => ListStyleType::Square,
+            "disclosure-open" => ListStyleType::DisclosureOpen,
+            "disclosure-closed" => ListStyleType::DisclosureClosed,
+            "decimal" => ListStyleType::Decimal,
+            "lower-alpha" | "lower-latin" => ListStyleType::LowerAlpha,
+            "upper-alpha" | "upper-latin" => ListStyleType::UpperAlpha,
+            "lower-roman" => ListStyleType::LowerRoman,
+            "upper-roman" => ListStyleType::UpperRoman,
+            "cjk-decimal" => ListStyleType::CjkDecimal,
+            "none" => ListStyleType::None,
+            _ => return None,
+        })
     }
 
-    pub fn text_decoration(&self) -> Vec<TextDecoration> {
-        if let Some(text_decorations) = self.value("text-decoration") {
-            let mut decorations = vec![];
-            for text_decoration in text_decorations {
-                if let Some(decoration) = text_decoration.to_text_decoration() {
-                    decorations.push(decoration);
-                }
+    pub fn to_list_style_position(&self) -> Option<ListStylePosition> {
+        Some(match self.keyword()? {
+            "inside" => ListStylePosition::Inside,
+            "outside" => ListStylePosition::Outside,
+            _ => return None,
+        })
+    }
+
+    /// Only `url()` images are supported.
+    pub fn to_list_style_image(&self) -> Option<Option<String>> {
+        match *self {
+            Value::Url(ref url) => Some(Some(url.clone())),
+            Value::Keyword(ref k) if k == "none" => Some(None),
+            _ => None,
+        }
+    }
+
+    pub fn to_border_collapse(&self) -> Option<BorderCollapse> {
+        Some(match self.keyword()? {
+            "separate" => BorderCollapse::Separate,
+            "collapse" => BorderCollapse::Collapse,
+            _ => return None,
+        })
+    }
+
+    pub fn to_table_layout(&self) -> Option<TableLayout> {
+        Some(match self.keyword()? {
+            "auto" => TableLayout::Auto,
+            "fixed" => TableLayout::Fixed,
+            _ => return None,
+        })
+    }
+
+    pub fn to_vertical_align(&self, ctx: &LengthContext) -> Option<VerticalAlign> {
+        let keyword = match *self {
+            Value::Keyword(ref keyword) => keyword,
+            _ => return LengthOrPercentage::from_value(self, ctx).map(VerticalAlign::Length),
+        };
+        Some(match keyword.as_str() {
+            "baseline" => VerticalAlign::Baseline,
+            "sub" => VerticalAlign::Sub,
+            "super" => VerticalAlign::Super,
+            "text-top" => VerticalAlign::TextTop,
+            "text-bottom" => VerticalAlign::TextBottom,
+            "middle" => VerticalAlign::Middle,
+            "top" => VerticalAlign::Top,
+            "bottom" => VerticalAlign::Bottom,
+            _ => return None,
+        })
+    }
+
+    pub fn to_flex_direction(&self) -> Option<FlexDirection> {
+        Some(match self.keyword()? {
+            "row" => FlexDirection::Row,
+            "row-reverse" => FlexDirection::RowReverse,
+            "column" => FlexDirection::Column,
+            "column-reverse" => FlexDirection::ColumnReverse,
+            _ => return None,
+        })
+    }
+
+    pub fn to_flex_wrap(&self) -> Option<FlexWrap> {
+        Some(match self.keyword()? {
+            "nowrap" => FlexWrap::NoWrap,
+            "wrap" => FlexWrap::Wrap,
+            "wrap-reverse" => FlexWrap::WrapReverse,
+            _ => return None,
+        })
+    }
+
+    pub fn to_content_distribution(&self) -> Option<ContentDistribution> {
+        Some(match self.keyword()? {
+            "normal" => ContentDistribution::Normal,
+            "flex-start" | "start" | "left" => ContentDistribution::FlexStart,
+            "flex-end" | "end" | "right" => ContentDistribution::FlexEnd,
+            "center" => ContentDistribution::Center,
+            "space-between" => ContentDistribution::SpaceBetween,
+            "space-around" => ContentDistribution::SpaceAround,
+            "space-evenly" => ContentDistribution::SpaceEvenly,
+            "stretch" => ContentDistribution::Stretch,
+            _ => return None,
+        })
+    }
+
+    pub fn to_item_alignment(&self) -> Option<ItemAlignment> {
+        Some(match self.keyword()? {
+            "normal" => ItemAlignment::Normal,
+            "stretch" => ItemAlignment::Stretch,
+            "flex-start" | "start" | "self-start" | "left" => ItemAlignment::FlexStart,
+            "flex-end" | "end" | "self-end" | "right" => ItemAlignment::FlexEnd,
+            "center" => ItemAlignment::Center,
+            "baseline" | "first" => ItemAlignment::Baseline,
+            _ => return None,
+        })
+    }
+
+    /// `normal` gaps are 0 outside of multi-column containers.
+    pub fn to_gap(&self, ctx: &LengthContext) -> Option<LengthOrPercentage> {
+        match *self {
+            Value::Keyword(ref k) if k == "normal" => Some(LengthOrPercentage::Length(Au(0))),
+            _ => LengthOrPercentage::from_value(self, ctx),
+        }
+    }
+
+    /// A grid track breadth. Negative lengths and flex factors are invalid.
+    pub fn to_track_breadth(&self, ctx: &LengthContext) -> Option<TrackBreadth> {
+        match *self {
+            Value::Length(f, Unit::Fr) if f >= 0.0 => Some(TrackBreadth::Flex(f)),
+            Value::Length(f, _) if f >= 0.0 => {
+                LengthOrPercentage::from_value(self, ctx).map(TrackBreadth::Length)
             }
-            decorations
-        } else {
-            vec![]
+            Value::Num(f) if f == 0.0 => {
+                Some(TrackBreadth::Length(LengthOrPercentage::Length(Au(0))))
+            }
+            Value::Keyword(ref k) => match k.as_str() {
+                "min-content" => Some(TrackBreadth::MinContent),
+                "max-content" => Some(TrackBreadth::MaxContent),
+                "auto" => Some(TrackBreadth::Auto),
+                _ => None,
+            },
+            _ => None,
         }
     }
 
-    pub fn font_size(&mut self) -> Au {
-        if let Some(ref font_size) = self.cached.font_size {
-            return Au::from_f64_px(
-                font_size
-                    .clone()
-                    .maybe_percent_to_px(DEFAULT_LINE_HEIGHT_SCALE)
-                    .unwrap(),
-            );
+    /// A breadth, `minmax(min, max)` whose minimum isn't flexible, or `fit-content(limit)`.
+    pub fn to_track_size(&self, ctx: &LengthContext) -> Option<TrackSize> {
+        match *self {
+            Value::Function(ref name, ref args) => match (name.as_str(), args.as_slice()) {
+                ("minmax", &[ref min, ref max]) if min.len() == 1 && max.len() == 1 => {
+                    match min[0].to_track_breadth(ctx)? {
+                        TrackBreadth::Flex(_) => None,
+                        min => Some(TrackSize::MinMax(min, max[0].to_track_breadth(ctx)?)),
+                    }
+                }
+                ("fit-content", &[ref limit]) if limit.len() == 1 => match limit[0]
+                    .to_track_breadth(ctx)?
+                {
+                    TrackBreadth::Length(limit) => Some(TrackSize::FitContent(limit)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => match self.to_track_breadth(ctx)? {
+                TrackBreadth::Flex(f) => Some(TrackSize::MinMax(
+                    TrackBreadth::Auto,
+                    TrackBreadth::Flex(f),
+                )),
+                breadth => Some(TrackSize::MinMax(breadth, breadth)),
+            },
         }
+    }
 
-        let default_font_size = Value::Length(DEFAULT_FONT_SIZE, Unit::Px);
-        let font_size = &self.value_with_default("font-size", &vec![default_font_size])[0];
-        self.cached.font_size = Some(font_size.clone());
-        Au::from_f64_px(font_size.maybe_percent_to_px(DEFAULT_FONT_SIZE).unwrap())
+    pub fn to_float_type(&self) -> Option<FloatType> {
+        Some(match self.keyword()? {
+            "left" => FloatType::Left,
+            "right" => FloatType::Right,
+            "none" => FloatType::None,
+            _ => return None,
+        })
+    }
+
+    pub fn to_position(&self) -> Option<Position> {
+        Some(match self.keyword()? {
+            "static" => Position::Static,
+            "relative" => Position::Relative,
+            "absolute" => Position::Absolute,
+            "fixed" => Position::Fixed,
+            "sticky" => Position::Sticky,
+            _ => return None,
+        })
+    }
+
+    pub fn to_box_sizing(&self) -> Option<BoxSizing> {
+        Some(match self.keyword()? {
+            "content-box" => BoxSizing::ContentBox,
+            "border-box" => BoxSizing::BorderBox,
+            _ => return None,
+        })
+    }
+
+    pub fn to_overflow(&self) -> Option<Overflow> {
+        Some(match self.keyword()? {
+            "visible" => Overflow::Visible,
+            "hidden" => Overflow::Hidden,
+            "clip" => Overflow::Clip,
+            "scroll" => Overflow::Scroll,
+            "auto" => Overflow::Auto,
+            _ => return None,
+        })
+    }
+
+    pub fn to_text_overflow(&self) -> Option<TextOverflow> {
+        Some(match self.keyword()? {
+            "clip" => TextOverflow::Clip,
+            "ellipsis" => TextOverflow::Ellipsis,
+            _ => return None,
+        })
+    }
+
+    pub fn to_clear_type(&self) -> Option<Option<ClearType>> {
+        Some(match self.keyword()? {
+            "left" => Some(ClearType::Left),
+            "right" => Some(ClearType::Right),
+            "both" => Some(ClearType::Both),
+            "none" => None,
+            _ => return None,
+        })
+    }
+
+    pub fn to_border_style(&self) -> Option<BorderStyle> {
+        Some(match self.keyword()? {
+            "none" => BorderStyle::None,
+            "hidden" => BorderStyle::Hidden,
+            "dotted" => BorderStyle::Dotted,
+            "dashed" => BorderStyle::Dashed,
+            "solid" => BorderStyle::Solid,
+            "double" => BorderStyle::Double,
+            "groove" => BorderStyle::Groove,
+            "ridge" => BorderStyle::Ridge,
+            "inset" => BorderStyle::Inset,
+            "outset" => BorderStyle::Outset,
+            _ => return None,
+        })
+    }
+
+    /// A border width: a length or one of `thin`, `medium` and `thick`.
+    pub fn to_line_width(&self, ctx: &LengthContext) -> Option<Au> {
+        match *self {
+            Value::Keyword(ref k) if k == "thin" => Some(Au::from_px(1)),
+            Value::Keyword(ref k) if k == "medium" => Some(Au::from_px(3)),
+            Value::Keyword(ref k) if k == "thick" => Some(Au::from_px(5)),
+            _ => self.to_px_in(ctx).map(Au::from_f64_px),
+        }
     }
 
-    pub fn font_weight(&self) -> FontWeight {
-        let default_font_weight = Value::Keyword("normal".to_string());
-        self.value_with_default("font-weight", &vec![default_font_weight])[0].to_font_weight()
+    pub fn to_z_index(&self) -> Option<Option<i32>> {
+        match *self {
+            Value::Keyword(ref k) if k == "auto" => Some(None),
+            Value::Num(f) if f == f.trunc() => Some(Some(f as i32)),
+            _ => None,
+        }
     }
 
-    pub fn font_style(&self) -> FontSlant {
-        let default_font_slant = Value::Keyword("normal".to_string());
-        self.lookup("font-style", "font-style", &vec![default_font_slant])[0].to_font_slant()
+    /// `%`, `em`, `larger` and `smaller` are relative to the parent's font size, and the absolute
+    /// keywords to `medium`, which is `DEFAULT_FONT_SIZE`.
+    /// ref. https://www.w3.org/TR/css-fonts-4/#font-size-prop
+    pub fn to_font_size(&self, parent_font_size: Au, ctx: &LengthContext) -> Option<Au> {
+        match *self {
+            Value::Length(f, Unit::Percent) => {
+                Some(Au::from_f64_px(parent_font_size.to_f64_px() * f / 100.0))
+            }
+            Value::Length(_, _) => self.to_px_in(ctx).map(Au::from_f64_px),
+            Value::Keyword(ref k) => {
+                let parent_font_size = parent_font_size.to_f64_px();
+                let (base, scale) = match k.as_str() {
+                    "larger" => (parent_font_size, FONT_SIZE_SCALE),
+                    "smaller" => (parent_font_size, 1.0 / FONT_SIZE_SCALE),
+                    "xx-small" => (DEFAULT_FONT_SIZE, 3.0 / 5.0),
+                    "x-small" => (DEFAULT_FONT_SIZE, 3.0 / 4.0),
+                    "small" => (DEFAULT_FONT_SIZE, 8.0 / 9.0),
+                    "medium" => (DEFAULT_FONT_SIZE, 1.0),
+                    "large" => (DEFAULT_FONT_SIZE, 6.0 / 5.0),
+                    "x-large" => (DEFAULT_FONT_SIZE, 3.0 / 2.0),
+                    "xx-large" => (DEFAULT_FONT_SIZE, 2.0),
+                    "xxx-large" => (DEFAULT_FONT_SIZE, 3.0),
+                    _ => return None,
+                };
+                Some(Au::from_f64_px(base * scale))
+            }
+            _ => None,
+        }
     }
 
-    pub fn line_height(&mut self) -> Au {
-        let font_size = self.font_size().to_f64_px();
-        let default_line_height = Value::Length(font_size * DEFAULT_LINE_HEIGHT_SCALE, Unit::Px);
+    /// A family name, which is a string, or a generic family keyword.
+    pub fn to_font_family(&self) -> Option<FontFamily> {
+        match *self {
+            Value::Str(ref name) => Some(FontFamily::Named(name.clone())),
+            Value::Keyword(ref name) => Some(match name.as_str() {
+                "serif" | "ui-serif" => FontFamily::Serif,
+                "sans-serif" | "ui-sans-serif" | "ui-rounded" => FontFamily::SansSerif,
+                "monospace" | "ui-monospace" => FontFamily::Monospace,
+                "cursive" => FontFamily::Cursive,
+                "fantasy" => FontFamily::Fantasy,
+                "system-ui" => FontFamily::SystemUi,
+                // `emoji`, `math` and `fangsong` are fontconfig aliases too.
+                _ => FontFamily::Named(name.clone()),
+            }),
+            _ => None,
+        }
+    }
 
-        if let Some(ref line_height) = self.cached.line_height {
-            return Au::from_f64_px(match line_height {
-                &Value::Keyword(ref k) if k == "normal" => font_size * DEFAULT_LINE_HEIGHT_SCALE,
-                &Value::Length(f, Unit::Px) => f,
-                &Value::Length(f, Unit::Pt) => pt2px(f),
-                &Value::Length(_, _) => unimplemented!(),
-                &Value::Num(f) => font_size * f,
-                _ => panic!(),
-            });
+    pub fn to_line_height(&self, font_size: Au, ctx: &LengthContext) -> Option<LineHeight> {
+        match *self {
+            Value::Keyword(ref k) if k == "normal" => Some(LineHeight::Normal),
+            Value::Num(f) => Some(LineHeight::Number(f)),
+            Value::Length(f, Unit::Percent) => Some(LineHeight::Length(Au::from_f64_px(
+                font_size.to_f64_px() * f / 100.0,
+            ))),
+            Value::Length(_, _) => self.to_px_in(ctx)
+                .map(|px| LineHeight::Length(Au::from_f64_px(px))),
+            _ => None,
         }
+    }
 
-        let line_height = &self.value_with_default("line-height", &vec![default_line_height])[0];
-        self.cached.line_height = Some(line_height.clone());
-        Au::from_f64_px(match line_height {
-            &Value::Keyword(ref k) if k == "normal" => font_size * DEFAULT_LINE_HEIGHT_SCALE,
-            &Value::Length(f, Unit::Px) => f,
-            &Value::Length(f, Unit::Pt) => pt2px(f),
-            &Value::Length(_, _) => unimplemented!(),
-            &Value::Num(f) => font_size * f,
-            _ => panic!(),
+    pub fn to_text_align(&self) -> Option<TextAlign> {
+        Some(match self.keyword()? {
+            "left" | "start" => TextAlign::Left,
+            "right" | "end" => TextAlign::Right,
+            "center" => TextAlign::Center,
+            "justify" => TextAlign::Justify,
+            _ => return None,
         })
     }
 
-    pub fn text_align(&self) -> Value {
-        self.value_with_default("text-align", &vec![Value::Keyword("left".to_string())])[0].clone()
+    pub fn to_word_break(&self) -> Option<WordBreak> {
+        Some(match self.keyword()? {
+            "normal" => WordBreak::Normal,
+            "break-all" => WordBreak::BreakAll,
+            "keep-all" => WordBreak::KeepAll,
+            "break-word" => WordBreak::BreakWord,
+            _ => return None,
+        })
     }
-}
 
-impl Value {
-    pub fn to_font_weight(&self) -> FontWeight {
-        match self {
-            &Value::Keyword(ref k) if k.as_str() == "normal" => FontWeight::Normal,
-            &Value::Keyword(ref k) if k.as_str() == "bold" => FontWeight::Bold,
-            _ => FontWeight::Normal,
+    pub fn to_overflow_wrap(&self) -> Option<OverflowWrap> {
+        Some(match self.keyword()? {
+            "normal" => OverflowWrap::Normal,
+            "break-word" => OverflowWrap::BreakWord,
+            "anywhere" => OverflowWrap::Anywhere,
+            _ => return None,
+        })
+    }
+
+    pub fn to_line_break(&self) -> Option<LineBreak> {
+        Some(match self.keyword()? {
+            "auto" => LineBreak::Auto,
+            "loose" => LineBreak::Loose,
+            "normal" => LineBreak::Normal,
+            "strict" => LineBreak::Strict,
+            "anywhere" => LineBreak::Anywhere,
+            _ => return None,
+        })
+    }
+
+    /// `bolder` and `lighter` are relative to the parent's weight.
+    pub fn to_font_weight(&self, parent_weight: FontWeight) -> Option<FontWeight> {
+        match *self {
+            Value::Num(w) if 1.0 <= w && w <= 1000.0 => Some(FontWeight(w)),
+            Value::Keyword(ref k) => match k.as_str() {
+                "normal" => Some(FontWeight::NORMAL),
+                "bold" => Some(FontWeight::BOLD),
+                "bolder" => Some(parent_weight.bolder()),
+                "lighter" => Some(parent_weight.lighter()),
+                _ => None,
+            },
+            _ => None,
         }
     }
-    pub fn to_font_slant(&self) -> FontSlant {
-        match self {
-            &Value::Keyword(ref k) if k.as_str() == "normal" => FontSlant::Normal,
-            &Value::Keyword(ref k) if k.as_str() == "italic" => FontSlant::Italic,
-            _ => FontSlant::Normal,
+
+    pub fn to_font_slant(&self) -> Option<FontSlant> {
+        Some(match self.keyword()? {
+            "normal" => FontSlant::Normal,
+            "italic" => FontSlant::Italic,
+            "oblique" => FontSlant::Oblique,
+            _ => return None,
+        })
+    }
+
+    /// Pango only has small capitals, which the other caps variants fall back to.
+    pub fn to_font_variant(&self) -> Option<FontVariant> {
+        Some(match self.keyword()? {
+            "small-caps" | "all-small-caps" | "petite-caps" | "all-petite-caps" => {
+                FontVariant::SmallCaps
+            }
+            "normal" | "unicase" | "titling-caps" => FontVariant::Normal,
+            _ => return None,
+        })
+    }
+
+    pub fn to_font_stretch(&self) -> Option<FontStretch> {
+        match *self {
+            Value::Length(p, Unit::Percent) => Some(FontStretch::from_percentage(p)),
+            _ => Some(match self.keyword()? {
+                "ultra-condensed" => FontStretch::UltraCondensed,
+                "extra-condensed" => FontStretch::ExtraCondensed,
+                "condensed" => FontStretch::Condensed,
+                "semi-condensed" => FontStretch::SemiCondensed,
+                "normal" => FontStretch::Normal,
+                "semi-expanded" => FontStretch::SemiExpanded,
+                "expanded" => FontStretch::Expanded,
+                "extra-expanded" => FontStretch::ExtraExpanded,
+                "ultra-expanded" => FontStretch::UltraExpanded,
+                _ => return None,
+            }),
         }
     }
 }
@@ -622,3 +1811,172 @@ fn test1() {
                a { display: inline; text-decoration: underline; }";
     css::parse(src.to_string());
 }
+
+/// The style computed from the declarations in `src` for an element whose parent has `parent`.
+#[cfg(test)]
+pub fn compute(src: &str, parent: &ComputedStyle) -> ComputedStyle {
+    use css::parse_attr_style;
+
+    let specified = Style::new_with(
+        parse_attr_style(src.to_string())
+            .into_iter()
+            .map(|decl| (decl.name, decl.values))
+            .collect(),
+    );
+    ComputedStyle::compute(&specified, parent, &LengthContext::default())
+}
+
+#[test]
+fn test_compute() {
+    use css::RED;
+
+    let parent = compute(
+        "font-size: 20px; color: red; text-decoration: underline; margin: 10px auto; \
+         border: solid; display: list-item; list-style: square inside url(a.png); \
+         word-break: keep-all; word-wrap: anywhere",
+        &ComputedStyle::default(),
+    );
+    assert_eq!(parent.display, Display::ListItem);
+    assert_eq!(parent.margin.top, LengthOrPercentageOrAuto::Length(Au::from_px(10)));
+    assert_eq!(parent.margin.left, LengthOrPercentageOrAuto::Auto);
+    assert_eq!(parent.border_width, Sides::all(Au::from_px(3)));
+    assert_eq!(parent.border_color, Sides::all(RED));
+
+    let child = compute(
+        "font-size: 2em; padding: 1em 10%; border-top-width: 2px; line-height: 150%; \
+         background-color: currentcolor; flex: 2; order: -1; gap: normal 1em; \
+         align-self: first baseline",
+        &parent,
+    );
+    // Inherited properties
+    assert_eq!(child.font_size, Au::from_px(40));
+    assert_eq!(child.color, RED);
+    assert_eq!(child.text_decoration, vec![TextDecoration::Underline]);
+    assert_eq!(child.list_style_type, ListStyleType::Square);
+    assert_eq!(child.list_style_position, ListStylePosition::Inside);
+    assert_eq!(child.list_style_image, Some("a.png".to_string()));
+    assert_eq!(child.word_break, WordBreak::KeepAll);
+    assert_eq!(child.overflow_wrap, OverflowWrap::Anywhere);
+    // Non-inherited properties
+    assert_eq!(child.display, Display::Inline);
+    assert_eq!(child.margin, Sides::all(LengthOrPercentageOrAuto::Length(Au(0))));
+    assert_eq!(child.padding.top, LengthOrPercentage::Length(Au::from_px(40)));
+    assert_eq!(child.padding.right, LengthOrPercentage::Percentage(10.0));
+    assert_eq!(child.border_width.top, Au(0));
+    assert_eq!(child.line_height, LineHeight::Length(Au::from_px(60)));
+    assert_eq!(child.line_height(), Au::from_px(60));
+    assert_eq!(child.background_color, RED);
+    assert_eq!(child.flex_grow, 2.0);
+    assert_eq!(child.flex_basis, LengthOrPercentageOrAuto::Percentage(0.0));
+    assert_eq!(child.order, -1);
+    assert_eq!(child.row_gap, LengthOrPercentage::Length(Au(0)));
+    assert_eq!(child.column_gap, LengthOrPercentage::Length(Au::from_px(40)));
+    assert_eq!(child.align_self, Some(ItemAlignment::Baseline));
+
+    let scroller = compute("overflow: clip scroll", &parent);
+    assert_eq!(scroller.overflow_x, Overflow::Hidden);
+    assert_eq!(scroller.overflow_y, Overflow::Scroll);
+    let clipped = compute("overflow-x: clip", &parent);
+    assert_eq!(clipped.overflow_x, Overflow::Clip);
+    assert_eq!(clipped.overflow_y, Overflow::Visible);
+
+    let sized = compute(
+        "min-width: 10%; max-width: 2em; max-height: none; box-sizing: border-box",
+        &parent,
+    );
+    assert_eq!(sized.min_width, LengthOrPercentageOrAuto::Percentage(10.0));
+    assert_eq!(sized.max_width, Some(LengthOrPercentage::Length(Au::from_px(40))));
+    assert_eq!(sized.min_height, LengthOrPercentageOrAuto::Auto);
+    assert_eq!(sized.max_height, None);
+    assert_eq!(sized.box_sizing, BoxSizing::BorderBox);
+}
+
+#[test]
+fn test_css_wide_keywords() {
+    use css::RED;
+
+    let parent = compute(
+        "font-size: 20px; color: red; margin: 10px; display: block",
+        &ComputedStyle::default(),
+    );
+
+    let child = compute(
+        "margin-top: inherit; font-size: initial; color: unset; margin-left: unset; \
+         border-top-color: initial; display: revert",
+        &parent,
+    );
+    assert_eq!(child.margin.top, LengthOrPercentageOrAuto::Length(Au::from_px(10)));
+    assert_eq!(child.margin.left, LengthOrPercentageOrAuto::Length(Au(0)));
+    assert_eq!(child.font_size, Au::from_f64_px(DEFAULT_FONT_SIZE));
+    assert_eq!(child.color, RED);
+    assert_eq!(child.border_color.top, RED);
+    assert_eq!(child.display, Display::Inline);
+
+    let child = compute("all: initial", &parent);
+    assert_eq!(child, ComputedStyle::default());
+    let child = compute("all: unset", &parent);
+    assert_eq!(child, parent.inherited());
+}
+
+#[test]
+fn test_font_size() {
+    let parent = compute("font-size: 20px", &ComputedStyle::default());
+    let font_size = |src: &str| compute(src, &parent).font_size;
+
+    assert_eq!(font_size("font-size: 50%"), Au::from_px(10));
+    assert_eq!(font_size("font-size: 1.5em"), Au::from_px(30));
+    assert_eq!(font_size("font-size: larger"), Au::from_px(24));
+    assert_eq!(font_size("font-size: x-large"), Au::from_px(24));
+    assert_eq!(font_size("font-size: xx-small"), Au::from_f64_px(9.6));
+    assert_eq!(font_size("font-size: medium"), Au::from_px(16));
+
+    // Relative sizes compound in nested elements.
+    let smaller = compute("font-size: smaller", &parent);
+    let nested = compute("font-size: 2em", &smaller);
+    assert_eq!(nested.font_size, Au::from_f64_px(20.0 / 1.2 * 2.0));
+}
+
+#[test]
+fn test_font_family() {
+    let parent = compute(
+        "font-family: 'Noto Sans', Helvetica  Neue, monospace",
+        &ComputedStyle::default(),
+    );
+    let families = vec![
+        FontFamily::Named("Noto Sans".to_string()),
+        // Unquoted names are lowercased like other identifiers. Fontconfig ignores the case.
+        FontFamily::Named("helvetica neue".to_string()),
+        FontFamily::Monospace,
+    ];
+    assert_eq!(parent.font_family, families);
+    assert_eq!(ComputedStyle::default().font_family, vec![FontFamily::SansSerif]);
+    assert_eq!(compute("", &parent).font_family, families);
+}
+
+#[test]
+fn test_font_weight_and_style() {
+    let parent = compute("font-weight: 300", &ComputedStyle::default());
+    assert_eq!(parent.font_weight, FontWeight(300.0));
+
+    let weight = |src: &str| compute(src, &parent).font_weight;
+    assert_eq!(weight("font-weight: 600"), FontWeight(600.0));
+    assert_eq!(weight("font-weight: bolder"), FontWeight(400.0));
+    assert_eq!(weight("font-weight: lighter"), FontWeight(100.0));
+    assert_eq!(weight("font-weight: bold"), FontWeight::BOLD);
+    assert_eq!(weight("font-weight: 1001"), FontWeight(300.0));
+    assert_eq!(FontWeight::BOLD.bolder(), FontWeight(900.0));
+    assert_eq!(FontWeight(800.0).lighter(), FontWeight(700.0));
+
+    let style = compute(
+        "font-style: oblique; font-variant: small-caps; font-stretch: 80%",
+        &parent,
+    );
+    assert_eq!(style.font_style, FontSlant::Oblique);
+    assert_eq!(style.font_variant, FontVariant::SmallCaps);
+    assert_eq!(style.font_stretch, FontStretch::Condensed);
+    let style = compute("font: italic small-caps 600 condensed 12px serif", &parent);
+    assert_eq!(style.font_style, FontSlant::Italic);
+    assert_eq!(style.font_variant, FontVariant::SmallCaps);
+    assert_eq!(style.font_weight, FontWeight(600.0));
+    assert_eq!(style.font_stretch, FontStretch::Condensed);
+}
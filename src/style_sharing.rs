@@ -0,0 +1,106 @@
+//! Sharing computed styles between siblings.
+//!
+//! Siblings have the same ancestors and parent style, so two of them with the same tag name,
+//! attributes and state match the same rules and get the same style, unless a selector that
+//! could match them depends on their siblings (like `:nth-child()` or `+`) or their children
+//! (like `:empty`). Lists and tables are made of many such elements, so the styles of the last
+//! few siblings are kept to skip the cascade for the next ones.
+
+use dom::ElementData;
+use element_state::ElementState;
+use selector_map::SelectorMap;
+use style::ComputedStyle;
+
+const CACHE_SIZE: usize = 8;
+
+pub struct StyleSharingCache<'a> {
+    /// The most recently used first.
+    entries: Vec<(&'a ElementData, ElementState, ComputedStyle)>,
+}
+
+impl<'a> StyleSharingCache<'a> {
+    /// An empty cache, to be used for the children of one element.
+    pub fn new() -> StyleSharingCache<'a> {
+        StyleSharingCache {
+            entries: Vec::with_capacity(CACHE_SIZE),
+        }
+    }
+
+    /// The style of a sibling that `elem` can share.
+    pub fn lookup(&mut self, elem: &ElementData, state: ElementState) -> Option<ComputedStyle> {
+        let index = self.entries
+            .iter()
+            .position(|&(sibling, sibling_state, _)| sibling == elem && sibling_state == state)?;
+        let entry = self.entries.remove(index);
+        let style = entry.2.clone();
+        self.entries.insert(0, entry);
+        Some(style)
+    }
+
+    pub fn insert(&mut self, elem: &'a ElementData, state: ElementState, style: ComputedStyle) {
+        self.entries.truncate(CACHE_SIZE - 1);
+        self.entries.insert(0, (elem, state, style));
+    }
+}
+
+/// Whether the style of `elem` can be shared with its siblings, given the stylesheets of all
+/// origins.
+pub fn can_share_style(elem: &ElementData, stylesheets: &[&SelectorMap]) -> bool {
+    !stylesheets
+        .iter()
+        .any(|stylesheet| stylesheet.has_structural_selectors_for(elem))
+}
+
+#[test]
+fn test_style_sharing_cache() {
+    use css;
+    use dom::LayoutType;
+    use std::rc::Rc;
+
+    let elem = |tag_name: &str, class: &str| ElementData {
+        tag_name: tag_name.to_string(),
+        layout_type: LayoutType::Generic,
+        attrs: Some(("class".to_string(), class.to_string()))
+            .into_iter()
+            .collect(),
+    };
+    let style = |z_index: i32| ComputedStyle {
+        z_index: Some(z_index),
+        ..ComputedStyle::default()
+    };
+
+    let no_state = ElementState::default();
+    let items: Vec<ElementData> = (0..10).map(|i| elem("li", &format!("item{}", i))).collect();
+    let mut cache = StyleSharingCache::new();
+    for (i, item) in items.iter().enumerate() {
+        cache.insert(item, no_state, style(i as i32));
+    }
+    // Only the last CACHE_SIZE siblings are kept.
+    assert_eq!(cache.lookup(&elem("li", "item0"), no_state), None);
+    assert_eq!(cache.lookup(&elem("li", "item2"), no_state), Some(style(2)));
+    assert_eq!(cache.lookup(&elem("td", "item2"), no_state), None);
+
+    // A lookup makes the entry the most recently used.
+    cache.insert(&items[0], no_state, style(0));
+    assert_eq!(cache.lookup(&elem("li", "item2"), no_state), Some(style(2)));
+    assert_eq!(cache.lookup(&elem("li", "item3"), no_state), None);
+    // Nor can siblings in different states.
+    let hover = ElementState {
+        hover: true,
+        ..ElementState::default()
+    };
+    assert_eq!(cache.lookup(&elem("li", "item2"), hover), None);
+
+    let stylesheet = css::parse(
+        "
+        li { color: red }
+        .item0 + .item1 { color: blue }
+        ul li:nth-child(2n) { color: green }
+        "
+            .to_string(),
+    );
+    let map = SelectorMap::new(Rc::new(stylesheet));
+    assert!(can_share_style(&elem("p", "item0"), &[&map]));
+    assert!(!can_share_style(&elem("p", "item1"), &[&map]));
+    assert!(!can_share_style(&elem("li", "item0"), &[&map]));
+}
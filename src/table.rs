@@ -0,0 +1,939 @@
+//! Table layout.
+//!
+//! A table box holds its captions, column groups, columns and row groups, and row groups hold
+//! rows of cells; the anonymous boxes missing from this structure are generated when the box
+//! tree is built. Cells are placed in a grid following their `colspan` and `rowspan`, columns
+//! are sized with the automatic or the fixed table layout algorithm, and rows are as tall as
+//! their tallest cells.
+//! ref. https://www.w3.org/TR/CSS22/tables.html
+
+use layout::{BoxType, Dimensions, EdgeSizes, LayoutBox, LayoutInfo};
+use intrinsic::IntrinsicWidths;
+use float::Floats;
+use margin::CollapsibleMargins;
+use dom::{Node, NodeType};
+use css::Color;
+use style::{BorderCollapse, BorderStyle, ComputedStyle, Display, LengthOrPercentage,
+            LengthOrPercentageOrAuto, Sides, TableLayout, VerticalAlign};
+
+use std::cmp::{max, min};
+
+use app_units::Au;
+
+/// The cells of a table placed in its grid.
+#[derive(Clone, Debug, PartialEq)]
+struct Grid {
+    /// The indices of the row group and of the row in it of each row, in the order the rows
+    /// are laid out: the header groups first and the footer groups last.
+    rows: Vec<(usize, usize)>,
+    cells: Vec<Slot>,
+    columns: Vec<ColumnBox>,
+    column_count: usize,
+}
+
+/// Where a cell is in the boxes of the table and in its grid.
+#[derive(Clone, Debug, PartialEq)]
+struct Slot {
+    group: usize,
+    row_in_group: usize,
+    cell: usize,
+    row: usize,
+    col: usize,
+    rowspan: usize,
+    colspan: usize,
+}
+
+/// The columns a column or column group box spans.
+#[derive(Clone, Debug, PartialEq)]
+struct ColumnBox {
+    /// The index of the box in the table, and in its column group if it's in one.
+    index: usize,
+    child: Option<usize>,
+    first: usize,
+    span: usize,
+    width: Option<Au>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Border {
+    width: Au,
+    style: BorderStyle,
+    color: Color,
+}
+
+impl LayoutBox {
+    /// Whether this box has to be in a table, a row group or a row.
+    pub fn is_table_internal(&self) -> bool {
+        match self.box_type {
+            BoxType::TableRowGroup
+            | BoxType::TableRow
+            | BoxType::TableCell
+            | BoxType::TableColumn => true,
+            _ => false,
+        }
+    }
+
+    /// Adds a child to a table, row group, row or column box, in the anonymous row group, row
+    /// or cell it needs. Whitespace between the parts of a table is dropped, and so are columns
+    /// outside of tables and anything but columns in column groups.
+    /// ref. https://www.w3.org/TR/CSS22/tables.html#anonymous-boxes
+    pub fn push_table_child(&mut self, child: LayoutBox) {
+        let in_anonymous_cell = self.box_type == BoxType::TableRow
+            && self.children.last().map_or(false, |last| {
+                last.node_id.is_none() && last.box_type == BoxType::TableCell
+            });
+        if child.is_whitespace() && !in_anonymous_cell {
+            return;
+        }
+
+        match self.box_type {
+            BoxType::Table => match child.box_type {
+                BoxType::TableRowGroup | BoxType::TableColumn => self.children.push(child),
+                BoxType::BlockNode if child.property.display == Display::TableCaption => {
+                    self.children.push(child)
+                }
+                _ => self.anonymous_table_child(BoxType::TableRowGroup, Display::TableRowGroup)
+                    .push_table_child(child),
+            },
+            BoxType::TableRowGroup => match child.box_type {
+                BoxType::TableRow => self.children.push(child),
+                BoxType::TableColumn => {}
+                _ => self.anonymous_table_child(BoxType::TableRow, Display::TableRow)
+                    .push_table_child(child),
+            },
+            BoxType::TableRow => match child.box_type {
+                BoxType::TableCell => self.children.push(child),
+                BoxType::TableColumn => {}
+                _ => self.anonymous_table_child(BoxType::TableCell, Display::TableCell)
+                    .push_child(child),
+            },
+            BoxType::TableColumn => {
+                if self.property.display == Display::TableColumnGroup
+                    && child.property.display == Display::TableColumn
+                {
+                    self.children.push(child)
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// The anonymous table to put a row group, row, cell or column outside of a table in.
+    pub fn get_table_container(&mut self) -> &mut LayoutBox {
+        let display = match self.box_type {
+            BoxType::InlineNode => Display::InlineTable,
+            _ => Display::Table,
+        };
+        self.anonymous_table_child(BoxType::Table, display)
+    }
+
+    /// The last child if it's an anonymous `box_type` box, otherwise a new one.
+    fn anonymous_table_child(&mut self, box_type: BoxType, display: Display) -> &mut LayoutBox {
+        let reusable = self.children.last().map_or(false, |last| {
+            last.node_id.is_none() && last.box_type == box_type
+        });
+        if !reusable {
+            let style = ComputedStyle {
+                display: display,
+                ..self.property.inherited()
+            };
+            self.children.push(LayoutBox::new(
+                box_type,
+                Node::text("".to_string()),
+                style,
+                LayoutInfo::Generic,
+            ));
+        }
+        self.children.last_mut().unwrap()
+    }
+}
+
+impl LayoutBox {
+    /// Lay out a table in normal flow, or in a line if it's an inline-level table.
+    pub fn layout_table(
+        &mut self,
+        _floats: &mut Floats,
+        margins: Option<CollapsibleMargins>,
+        containing_block: Dimensions,
+        _saved_block: Dimensions,
+        viewport: Dimensions,
+    ) {
+        self.assign_padding(containing_block);
+        self.assign_border_width();
+        self.assign_margin(containing_block);
+
+        let captions_height = self.layout_table_contents(containing_block, viewport);
+
+        // `auto` margins center a block-level table like a block.
+        let underflow = containing_block.content.width - self.dimensions.margin_box().width;
+        let margin = self.property.margin;
+        let is_block_level = self.property.display == Display::Table;
+        // Margin collapse, as for blocks.
+        self.collapse_top_margin(margins, containing_block.content.width);
+        let d = &mut self.dimensions;
+        match (margin.left, margin.right) {
+            _ if !is_block_level => {}
+            (LengthOrPercentageOrAuto::Auto, LengthOrPercentageOrAuto::Auto) => {
+                d.margin.left = max(Au(0), underflow / 2);
+                d.margin.right = d.margin.left;
+            }
+            (LengthOrPercentageOrAuto::Auto, _) => d.margin.left = max(Au(0), underflow),
+            _ => {}
+        }
+
+        // The captions are above the border box.
+        d.margin.top += captions_height;
+
+        d.content.x = d.margin.left + d.border.left + d.padding.left;
+        d.content.y = containing_block.content.height + d.margin.top + d.border.top
+            + d.padding.top;
+    }
+
+    /// Lays out the captions, columns, rows and cells of a table whose edges are assigned, and
+    /// sets its content size. Returns the height of the captions, which are laid out above the
+    /// border box.
+    pub fn layout_table_contents(
+        &mut self,
+        containing_block: Dimensions,
+        viewport: Dimensions,
+    ) -> Au {
+        let grid = self.table_grid();
+        let (h_spacing, v_spacing) = match self.property.border_collapse {
+            BorderCollapse::Separate => self.property.border_spacing,
+            BorderCollapse::Collapse => {
+                self.collapse_borders(&grid);
+                // The borders of the table are drawn by its cells, and it has no padding.
+                self.dimensions.border = EdgeSizes::default();
+                self.dimensions.padding = EdgeSizes::default();
+                (Au(0), Au(0))
+            }
+        };
+
+        // Column widths
+        let cb_width = containing_block.content.width;
+        let d = self.dimensions;
+        let edges = d.padding.left + d.padding.right + d.border.left + d.border.right;
+        let spacing = if grid.column_count > 0 {
+            h_spacing * (grid.column_count as i32 + 1)
+        } else {
+            Au(0)
+        };
+        let specified_width = match self.property.width {
+            LengthOrPercentageOrAuto::Length(width) => Some(width),
+            // A containing block that shrinks to fit has no width to take a percentage of.
+            LengthOrPercentageOrAuto::Percentage(_) if cb_width >= Au(0) => {
+                self.property.width.to_au(cb_width)
+            }
+            _ => None,
+        };
+        let widths = match (self.property.table_layout, specified_width) {
+            (TableLayout::Fixed, Some(width)) => {
+                self.fixed_column_widths(&grid, max(Au(0), width - spacing), h_spacing)
+            }
+            _ => {
+                let measures = self.column_measures(&grid, h_spacing);
+                let min_width = measures.iter().fold(Au(0), |sum, m| sum + m.min) + spacing;
+                let max_width = measures.iter().fold(Au(0), |sum, m| sum + m.max) + spacing;
+                let width = match specified_width {
+                    Some(width) => width,
+                    None if cb_width < Au(0) => max_width,
+                    None => min(max_width, cb_width - d.margin.left - d.margin.right - edges),
+                };
+                distribute(&measures, max(width, min_width) - spacing)
+            }
+        };
+        let mut column_x = Vec::with_capacity(widths.len());
+        let mut x = h_spacing;
+        for &width in &widths {
+            column_x.push(x);
+            x += width + h_spacing;
+        }
+        let grid_width = if widths.is_empty() { Au(0) } else { x };
+        let span_width = |first: usize, span: usize| {
+            let last = first + span - 1;
+            column_x[last] + widths[last] - column_x[first]
+        };
+
+        // Cells are laid out at the widths of the columns they span to find the heights of
+        // the rows.
+        let mut table_block = self.dimensions;
+        table_block.content.width = grid_width;
+        for slot in &grid.cells {
+            let width = span_width(slot.col, slot.colspan);
+            self.cell_mut(slot)
+                .layout_table_cell(width, table_block, viewport);
+        }
+
+        let mut row_heights: Vec<Au> = grid.rows
+            .iter()
+            .map(|&(group, row)| match self.children[group].children[row].property.height {
+                LengthOrPercentageOrAuto::Length(height) => height,
+                _ => Au(0),
+            })
+            .collect();
+        // Baseline-aligned cells starting in a row line up their first lines.
+        let mut baselines = vec![Au(0); grid.rows.len()];
+        for slot in &grid.cells {
+            let cell = self.cell_mut(slot);
+            if cell.is_baseline_aligned() {
+                baselines[slot.row] = max(baselines[slot.row], cell.cell_baseline());
+            }
+        }
+        for slot in grid.cells.iter().filter(|slot| slot.rowspan == 1) {
+            let cell = self.cell_mut(slot);
+            let mut height = cell.dimensions.border_box().height;
+            if cell.is_baseline_aligned() {
+                height += baselines[slot.row] - cell.cell_baseline();
+            }
+            row_heights[slot.row] = max(row_heights[slot.row], height);
+        }
+        // The last row a cell spans grows if the cell doesn't fit in the rows.
+        for slot in grid.cells.iter().filter(|slot| slot.rowspan > 1) {
+            let last = slot.row + slot.rowspan - 1;
+            let spanned = row_heights[slot.row..last + 1]
+                .iter()
+                .fold(v_spacing * (slot.rowspan as i32 - 1), |sum, &height| sum + height);
+            let height = self.cell(slot).dimensions.border_box().height;
+            if height > spanned {
+                row_heights[last] += height - spanned;
+            }
+        }
+        let mut row_y = Vec::with_capacity(row_heights.len());
+        let mut y = v_spacing;
+        for &height in &row_heights {
+            row_y.push(y);
+            y += height + v_spacing;
+        }
+        let grid_height = if row_heights.is_empty() { Au(0) } else { y };
+
+        // Cells are stretched to the rows they span, and their content is aligned vertically
+        // in the extra space, which goes to their padding.
+        for slot in &grid.cells {
+            let last = slot.row + slot.rowspan - 1;
+            let height = row_y[last] + row_heights[last] - row_y[slot.row];
+            let row_baseline = baselines[slot.row];
+            let cell = self.cell_mut(slot);
+            let extra = max(Au(0), height - cell.dimensions.border_box().height);
+            let shift = match cell.property.vertical_align {
+                VerticalAlign::Top => Au(0),
+                VerticalAlign::Middle => extra / 2,
+                VerticalAlign::Bottom => extra,
+                _ => min(extra, row_baseline - cell.cell_baseline()),
+            };
+            let d = &mut cell.dimensions;
+            d.padding.top += shift;
+            d.padding.bottom += extra - shift;
+            // Rows start at the left of the grid.
+            d.content.x = column_x[slot.col] + d.border.left + d.padding.left;
+            d.content.y = d.border.top + d.padding.top;
+        }
+
+        // Row groups and rows span the grid horizontally.
+        let mut y = v_spacing;
+        for (index, group) in self.children.iter_mut().enumerate() {
+            if group.box_type != BoxType::TableRowGroup {
+                continue;
+            }
+            let rows: Vec<usize> = (0..grid.rows.len())
+                .filter(|&row| grid.rows[row].0 == index)
+                .collect();
+            if let (Some(&first), Some(&last)) = (rows.first(), rows.last()) {
+                y = row_y[first];
+                group.dimensions.content.height = row_y[last] + row_heights[last] - y;
+            } else {
+                group.dimensions.content.height = Au(0);
+            }
+            group.dimensions.content.y = y;
+            group.dimensions.content.width = grid_width;
+            for row in rows {
+                let row_box = &mut group.children[grid.rows[row].1];
+                row_box.dimensions.content.y = row_y[row] - y;
+                row_box.dimensions.content.width = grid_width;
+                row_box.dimensions.content.height = row_heights[row];
+            }
+        }
+
+        for column in &grid.columns {
+            let x = column_x[column.first];
+            let width = span_width(column.first, column.span);
+            let column_box = match column.child {
+                Some(child) => {
+                    // Columns are placed in their group, which starts at the first of them.
+                    let group_x = grid.columns
+                        .iter()
+                        .find(|group| group.index == column.index && group.child.is_none())
+                        .map_or(Au(0), |group| column_x[group.first]);
+                    let column_box = &mut self.children[column.index].children[child];
+                    column_box.dimensions.content.x = x - group_x;
+                    column_box
+                }
+                None => {
+                    let column_box = &mut self.children[column.index];
+                    column_box.dimensions.content.x = x;
+                    column_box
+                }
+            };
+            column_box.dimensions.content.y = if column.child.is_some() {
+                Au(0)
+            } else {
+                v_spacing
+            };
+            column_box.dimensions.content.width = width;
+            column_box.dimensions.content.height = max(Au(0), grid_height - v_spacing * 2);
+        }
+
+        let d = &mut self.dimensions;
+        d.content.width = grid_width;
+        d.content.height = match self.property.height {
+            LengthOrPercentageOrAuto::Length(height) => max(grid_height, height),
+            _ => grid_height,
+        };
+
+        // The captions are as wide as the border box, and stacked above it.
+        let mut caption_block = Dimensions::default();
+        caption_block.content.width = d.border_box().width;
+        let mut captions_height = Au(0);
+        for caption in &mut self.children {
+            if caption.property.display != Display::TableCaption {
+                continue;
+            }
+            caption_block.content.height = captions_height;
+            caption.layout(
+                &mut Floats::new(),
+                None,
+                caption_block,
+                caption_block,
+                viewport,
+            );
+            captions_height += caption.dimensions.margin_box().height;
+        }
+        for caption in &mut self.children {
+            if caption.property.display == Display::TableCaption {
+                caption.dimensions.content.x -= d.border.left + d.padding.left;
+                caption.dimensions.content.y -= captions_height + d.border.top + d.padding.top;
+            }
+        }
+        captions_height
+    }
+
+    /// The intrinsic widths of the content of a table, from those of its columns.
+    pub fn table_intrinsic_widths(&self) -> IntrinsicWidths {
+        let grid = self.table_grid();
+        let h_spacing = match self.property.border_collapse {
+            BorderCollapse::Separate => self.property.border_spacing.0,
+            BorderCollapse::Collapse => Au(0),
+        };
+        let spacing = if grid.column_count > 0 {
+            h_spacing * (grid.column_count as i32 + 1)
+        } else {
+            Au(0)
+        };
+        self.column_measures(&grid, h_spacing)
+            .iter()
+            .fold(IntrinsicWidths::new(spacing, spacing), |sum, m| {
+                IntrinsicWidths::new(sum.min + m.min, sum.max + m.max)
+            })
+    }
+
+    /// Places the cells of a table in its grid.
+    /// ref. https://html.spec.whatwg.org/multipage/tables.html#forming-a-table
+    fn table_grid(&self) -> Grid {
+        let mut columns = vec![];
+        let mut next_column = 0;
+        for (index, child) in self.children.iter().enumerate() {
+            if child.box_type != BoxType::TableColumn {
+                continue;
+            }
+            let group_width = length(child.property.width);
+            if child.children.is_empty() {
+                let span = span(&child.node, "span").map_or(1, |span| max(1, min(span, 1000)));
+                columns.push(ColumnBox {
+                    index: index,
+                    child: None,
+                    first: next_column,
+                    span: span,
+                    width: group_width,
+                });
+                next_column += span;
+                continue;
+            }
+            let first = next_column;
+            for (i, column) in child.children.iter().enumerate() {
+                let span = span(&column.node, "span").map_or(1, |span| max(1, min(span, 1000)));
+                columns.push(ColumnBox {
+                    index: index,
+                    child: Some(i),
+                    first: next_column,
+                    span: span,
+                    width: length(column.property.width).or(group_width),
+                });
+                next_column += span;
+            }
+            columns.push(ColumnBox {
+                index: index,
+                child: None,
+                first: first,
+                span: next_column - first,
+                width: None,
+            });
+        }
+
+        // Header groups are laid out first and footer groups last.
+        let mut groups: Vec<usize> = (0..self.children.len())
+            .filter(|&i| self.children[i].box_type == BoxType::TableRowGroup)
+            .collect();
+        groups.sort_by_key(|&i| match self.children[i].property.display {
+            Display::TableHeaderGroup => 0,
+            Display::TableFooterGroup => 2,
+            _ => 1,
+        });
+
+        let mut rows = vec![];
+        let mut cells = vec![];
+        let mut column_count = next_column;
+        for group in groups {
+            let row_boxes = &self.children[group].children;
+            // How many more rows each column is covered for by a cell from a row above.
+            // Cells don't span rows out of their group.
+            let mut covered: Vec<usize> = vec![];
+            for (row_in_group, row) in row_boxes.iter().enumerate() {
+                let rows_left = row_boxes.len() - row_in_group;
+                let mut col = 0;
+                for (i, cell) in row.children.iter().enumerate() {
+                    while col < covered.len() && covered[col] > 0 {
+                        col += 1;
+                    }
+                    let colspan = span(&cell.node, "colspan").map_or(1, |n| max(1, min(n, 1000)));
+                    let rowspan = match span(&cell.node, "rowspan") {
+                        Some(0) => rows_left,
+                        Some(n) => min(n, min(rows_left, 65534)),
+                        None => 1,
+                    };
+                    cells.push(Slot {
+                        group: group,
+                        row_in_group: row_in_group,
+                        cell: i,
+                        row: rows.len(),
+                        col: col,
+                        rowspan: rowspan,
+                        colspan: colspan,
+                    });
+                    if covered.len() < col + colspan {
+                        covered.resize(col + colspan, 0);
+                    }
+                    for covered in &mut covered[col..col + colspan] {
+                        *covered = rowspan;
+                    }
+                    col += colspan;
+                }
+                column_count = max(column_count, covered.len());
+                for covered in &mut covered {
+                    *covered = covered.saturating_sub(1);
+                }
+                rows.push((group, row_in_group));
+            }
+        }
+
+        Grid {
+            rows: rows,
+            cells: cells,
+            columns: columns,
+            column_count: column_count,
+        }
+    }
+
+    fn cell(&self, slot: &Slot) -> &LayoutBox {
+        &self.children[slot.group].children[slot.row_in_group].children[slot.cell]
+    }
+
+    fn cell_mut(&mut self, slot: &Slot) -> &mut LayoutBox {
+        &mut self.children[slot.group].children[slot.row_in_group].children[slot.cell]
+    }
+
+    /// The min-content and max-content widths of the columns, for the automatic table layout.
+    /// Cells spanning several columns widen them evenly if they don't fit.
+    /// ref. https://www.w3.org/TR/CSS22/tables.html#auto-table-layout
+    fn column_measures(&self, grid: &Grid, h_spacing: Au) -> Vec<IntrinsicWidths> {
+        let mut mins = vec![Au(0); grid.column_count];
+        let mut maxes = vec![Au(0); grid.column_count];
+        for column in &grid.columns {
+            if let Some(width) = column.width {
+                let each = width / column.span as i32;
+                for max_width in &mut maxes[column.first..column.first + column.span] {
+                    *max_width = max(*max_width, each);
+                }
+            }
+        }
+
+        let mut slots: Vec<&Slot> = grid.cells.iter().collect();
+        slots.sort_by_key(|slot| slot.colspan);
+        for slot in slots {
+            let widths = self.cell(slot).intrinsic_widths();
+            let columns = slot.col..slot.col + slot.colspan;
+            let spacing = h_spacing * (slot.colspan as i32 - 1);
+            widen(&mut mins[columns.clone()], widths.min - spacing);
+            widen(&mut maxes[columns], widths.max - spacing);
+        }
+
+        mins.into_iter()
+            .zip(maxes)
+            .map(|(min_width, max_width)| {
+                IntrinsicWidths::new(min_width, max(min_width, max_width))
+            })
+            .collect()
+    }
+
+    /// The widths of the columns with the fixed table layout, sharing `width`: the columns and
+    /// the cells of the first row set the widths, and the other columns get the rest.
+    /// ref. https://www.w3.org/TR/CSS22/tables.html#fixed-table-layout
+    fn fixed_column_widths(&self, grid: &Grid, width: Au, h_spacing: Au) -> Vec<Au> {
+        let mut widths: Vec<Option<Au>> = vec![None; grid.column_count];
+        for column in &grid.columns {
+            if let Some(width) = column.width {
+                for column_width in &mut widths[column.first..column.first + column.span] {
+                    *column_width = Some(width / column.span as i32);
+                }
+            }
+        }
+        for slot in grid.cells.iter().filter(|slot| slot.row == 0) {
+            let columns = slot.col..slot.col + slot.colspan;
+            if widths[columns.clone()].iter().any(|width| width.is_some()) {
+                continue;
+            }
+            let cell = self.cell(slot);
+            if let Some(cell_width) = length(cell.property.width) {
+                let style = &cell.property;
+                let border_box = cell_width + style.border_width.left + style.border_width.right
+                    + length_of(style.padding.left) + length_of(style.padding.right);
+                let each = (border_box - h_spacing * (slot.colspan as i32 - 1))
+                    / slot.colspan as i32;
+                for column_width in &mut widths[columns] {
+                    *column_width = Some(max(Au(0), each));
+                }
+            }
+        }
+
+        let specified = widths
+            .iter()
+            .fold(Au(0), |sum, width| sum + width.unwrap_or(Au(0)));
+        let remaining = max(Au(0), width - specified);
+        let auto_columns = widths.iter().filter(|width| width.is_none()).count();
+        if auto_columns > 0 {
+            let each = remaining / auto_columns as i32;
+            return widths
+                .into_iter()
+                .map(|width| width.unwrap_or(each))
+                .collect();
+        }
+        // The columns all have a width, so they widen evenly to fill the table.
+        let mut widths: Vec<Au> = widths.into_iter().map(|width| width.unwrap()).collect();
+        widen(&mut widths, specified + remaining);
+        widths
+    }
+
+    /// Resolves the borders between cells in the collapsing border model: each edge gets the
+    /// most eye-catching of the borders meeting there, and is drawn by one of the cells, the one
+    /// on its right or below it, except at the right and bottom of the table.
+    /// ref. https://www.w3.org/TR/CSS22/tables.html#border-conflict-resolution
+    fn collapse_borders(&mut self, grid: &Grid) {
+        let rows = grid.rows.len();
+        let mut cell_at = vec![vec![None; grid.column_count]; rows];
+        for (i, slot) in grid.cells.iter().enumerate() {
+            for row in &mut cell_at[slot.row..slot.row + slot.rowspan] {
+                for cell in &mut row[slot.col..slot.col + slot.colspan] {
+                    *cell = Some(i);
+                }
+            }
+        }
+        let table = borders(&self.property);
+        let neighbor = |row: usize, col: usize| -> Option<Sides<Border>> {
+            cell_at[row][col].map(|i| borders(&self.cell(&grid.cells[i]).property))
+        };
+        let none = Border {
+            width: Au(0),
+            style: BorderStyle::None,
+            color: self.property.color,
+        };
+
+        let resolved: Vec<Sides<Border>> = grid.cells
+            .iter()
+            .map(|slot| {
+                let cell = borders(&self.cell(slot).property);
+                let left = if slot.col == 0 {
+                    winner(cell.left, table.left)
+                } else {
+                    neighbor(slot.row, slot.col - 1)
+                        .map_or(cell.left, |neighbor| winner(cell.left, neighbor.right))
+                };
+                let top = if slot.row == 0 {
+                    winner(cell.top, table.top)
+                } else {
+                    neighbor(slot.row - 1, slot.col)
+                        .map_or(cell.top, |neighbor| winner(cell.top, neighbor.bottom))
+                };
+                let right = if slot.col + slot.colspan == grid.column_count {
+                    winner(cell.right, table.right)
+                } else {
+                    none
+                };
+                let bottom = if slot.row + slot.rowspan == rows {
+                    winner(cell.bottom, table.bottom)
+                } else {
+                    none
+                };
+                Sides {
+                    top: top,
+                    right: right,
+                    bottom: bottom,
+                    left: left,
+                }
+            })
+            .collect();
+
+        for (slot, borders) in grid.cells.iter().zip(resolved) {
+            let style = &mut self.cell_mut(slot).property;
+            style.border_width = Sides {
+                top: borders.top.width,
+                right: borders.right.width,
+                bottom: borders.bottom.width,
+                left: borders.left.width,
+            };
+            style.border_style = Sides {
+                top: borders.top.style,
+                right: borders.right.style,
+                bottom: borders.bottom.style,
+                left: borders.left.style,
+            };
+            style.border_color = Sides {
+                top: borders.top.color,
+                right: borders.right.color,
+                bottom: borders.bottom.color,
+                left: borders.left.color,
+            };
+        }
+    }
+
+    /// Lays out the content of a cell `width` wide, including its padding and borders.
+    fn layout_table_cell(&mut self, width: Au, table_block: Dimensions, viewport: Dimensions) {
+        self.assign_padding(table_block);
+        self.assign_border_width();
+        self.dimensions.margin = EdgeSizes::default();
+
+        {
+            let d = &mut self.dimensions;
+            d.content.x = Au(0);
+            d.content.y = Au(0);
+            d.content.width = max(
+                Au(0),
+                width - d.padding.left - d.padding.right - d.border.left - d.border.right,
+            );
+        }
+        self.floats = Floats::new();
+        self.layout_block_children(CollapsibleMargins::default(), viewport);
+
+        // The height of a cell is a minimum.
+        if let LengthOrPercentageOrAuto::Length(height) = self.property.height {
+            self.dimensions.content.height = max(self.dimensions.content.height, height);
+        }
+    }
+
+    /// Other values than `top`, `middle` and `bottom` align cells on their baseline.
+    fn is_baseline_aligned(&self) -> bool {
+        match self.property.vertical_align {
+            VerticalAlign::Top | VerticalAlign::Middle | VerticalAlign::Bottom => false,
+            _ => true,
+        }
+    }
+
+    /// The distance from the top of the border box of a laid out cell to its first baseline.
+    fn cell_baseline(&mut self) -> Au {
+        self.dimensions.border.top + self.dimensions.padding.top + self.content_inline_ascent()
+    }
+}
+
+/// Widens `widths` evenly so that they add up to at least `width`.
+fn widen(widths: &mut [Au], width: Au) {
+    let total = widths.iter().fold(Au(0), |sum, &width| sum + width);
+    if width <= total || widths.is_empty() {
+        return;
+    }
+    let extra = width - total;
+    let each = extra / widths.len() as i32;
+    for width in widths.iter_mut() {
+        *width += each;
+    }
+    // Rounding leftovers go to the last one.
+    let last = widths.len() - 1;
+    widths[last] += extra - each * widths.len() as i32;
+}
+
+/// The widths of columns measuring `measures` sharing `width`: each column gets its
+/// min-content width, then they grow towards their max-content widths at the same pace, then
+/// beyond in proportion to them.
+fn distribute(measures: &[IntrinsicWidths], width: Au) -> Vec<Au> {
+    let min_total = measures.iter().fold(Au(0), |sum, m| sum + m.min);
+    let max_total = measures.iter().fold(Au(0), |sum, m| sum + m.max);
+    let mut widths: Vec<Au> = if width <= min_total {
+        measures.iter().map(|m| m.min).collect()
+    } else if width <= max_total {
+        let ratio = (width - min_total).to_f64_px() / (max_total - min_total).to_f64_px();
+        measures
+            .iter()
+            .map(|m| m.min + Au::from_f64_px((m.max - m.min).to_f64_px() * ratio))
+            .collect()
+    } else if max_total > Au(0) {
+        let ratio = width.to_f64_px() / max_total.to_f64_px();
+        measures
+            .iter()
+            .map(|m| Au::from_f64_px(m.max.to_f64_px() * ratio))
+            .collect()
+    } else {
+        measures.iter().map(|_| Au(0)).collect()
+    };
+    // Rounding leftovers go to the last column.
+    let total = widths.iter().fold(Au(0), |sum, &width| sum + width);
+    if let Some(last) = widths.last_mut() {
+        if total < width {
+            *last += width - total;
+        }
+    }
+    widths
+}
+
+/// The value of the attribute `name` of `node`, like `colspan`, if it's a number.
+fn span(node: &Node, name: &str) -> Option<usize> {
+    match node.data {
+        NodeType::Element(ref elem) => elem.attrs.get(name)?.trim().parse().ok(),
+        NodeType::Text(_) => None,
+    }
+}
+
+fn length(width: LengthOrPercentageOrAuto) -> Option<Au> {
+    match width {
+        LengthOrPercentageOrAuto::Length(width) => Some(width),
+        _ => None,
+    }
+}
+
+/// A padding that isn't a percentage, which the widths of fixed columns can't depend on.
+fn length_of(padding: LengthOrPercentage) -> Au {
+    match padding {
+        LengthOrPercentage::Length(padding) => padding,
+        LengthOrPercentage::Percentage(_) => Au(0),
+    }
+}
+
+fn borders(style: &ComputedStyle) -> Sides<Border> {
+    let border = |width, style, color| Border {
+        width: width,
+        style: style,
+        color: color,
+    };
+    Sides {
+        top: border(style.border_width.top, style.border_style.top, style.border_color.top),
+        right: border(
+            style.border_width.right,
+            style.border_style.right,
+            style.border_color.right,
+        ),
+        bottom: border(
+            style.border_width.bottom,
+            style.border_style.bottom,
+            style.border_color.bottom,
+        ),
+        left: border(style.border_width.left, style.border_style.left, style.border_color.left),
+    }
+}
+
+/// The border that wins a conflict: `hidden` suppresses the others, then the wider border wins,
+/// then the style that comes first in double, solid, dashed, dotted, ridge, outset, groove and
+/// inset. `a` is the border of the cell, which wins ties.
+fn winner(a: Border, b: Border) -> Border {
+    if a.style == BorderStyle::Hidden || b.style == BorderStyle::Hidden {
+        return Border {
+            width: Au(0),
+            style: BorderStyle::Hidden,
+            color: a.color,
+        };
+    }
+    let rank = |border: &Border| {
+        let style = match border.style {
+            BorderStyle::Double => 8,
+            BorderStyle::Solid => 7,
+            BorderStyle::Dashed => 6,
+            BorderStyle::Dotted => 5,
+            BorderStyle::Ridge => 4,
+            BorderStyle::Outset => 3,
+            BorderStyle::Groove => 2,
+            BorderStyle::Inset => 1,
+            BorderStyle::None | BorderStyle::Hidden => 0,
+        };
+        (border.style.is_visible(), border.width, style)
+    };
+    if rank(&b) > rank(&a) {
+        b
+    } else {
+        a
+    }
+}
+
+#[test]
+fn test_table_grid() {
+    use layout::block;
+    use std::collections::HashMap;
+
+    let layout_box = |display: &str, tag_name: &str, attrs: &[(&str, &str)], children| {
+        let attrs: HashMap<String, String> = attrs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let mut layout_box = block(&format!("display: {}", display), children);
+        layout_box.node = Node::elem(tag_name.to_string(), attrs, vec![]);
+        layout_box
+    };
+    let cell = |attrs| layout_box("table-cell", "td", attrs, vec![]);
+    let row = |cells| layout_box("table-row", "tr", &[], cells);
+
+    // +---+---+---+
+    // | a     | b |
+    // +---+---+   +
+    // | c | d |   |
+    // +---+---+---+
+    let body = layout_box("table-row-group", "tbody", &[], vec![
+        row(vec![cell(&[("colspan", "2")]), cell(&[("rowspan", "0")])]),
+        row(vec![cell(&[]), cell(&[("rowspan", "5")])]),
+    ]);
+    let col = layout_box("table-column", "col", &[("span", "2")], vec![]);
+    let table = layout_box("table", "table", &[], vec![col, body]);
+    let grid = table.table_grid();
+    assert_eq!(grid.column_count, 3);
+    assert_eq!(grid.rows, vec![(1, 0), (1, 1)]);
+    let placement: Vec<(usize, usize, usize, usize)> = grid.cells
+        .iter()
+        .map(|slot| (slot.row, slot.col, slot.rowspan, slot.colspan))
+        .collect();
+    assert_eq!(placement, vec![(0, 0, 1, 2), (0, 2, 2, 1), (1, 0, 1, 1), (1, 1, 1, 1)]);
+    assert_eq!(grid.columns, vec![ColumnBox {
+        index: 0,
+        child: None,
+        first: 0,
+        span: 2,
+        width: None,
+    }]);
+}
+
+#[test]
+fn test_distribute() {
+    let px = Au::from_px;
+    let measures = [
+        IntrinsicWidths::new(px(10), px(30)),
+        IntrinsicWidths::new(px(20), px(20)),
+    ];
+    assert_eq!(distribute(&measures, px(20)), vec![px(10), px(20)]);
+    assert_eq!(distribute(&measures, px(40)), vec![px(20), px(20)]);
+    assert_eq!(distribute(&measures, px(100)), vec![px(60), px(40)]);
+
+    let mut widths = vec![Au(1), Au(2)];
+    widen(&mut widths, Au(8));
+    assert_eq!(widths, vec![Au(3), Au(5)]);
+}
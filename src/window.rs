@@ -10,19 +10,24 @@ use gtk::ContainerExt;
 use glib::prelude::*; // or `use gtk::prelude::*;`
 use glib;
 
-use gdk::{ContextExt, Cursor, CursorType, Event, EventButton, EventMask, EventMotion, RGBA};
+use gdk::{ContextExt, Cursor, CursorType, Event, EventButton, EventMask, EventMotion, EventScroll,
+          ScrollDirection, RGBA};
 use gdk_pixbuf::{InterpType, PixbufExt};
 
 use cairo::Context;
 use pango::LayoutExt;
 
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::{Cell, RefCell}, cmp::{max, min}, collections::HashMap};
+
+use app_units::Au;
 
 use layout::Rect;
 use painter::{DisplayCommand, DisplayList};
 use font::FONT_DESC;
-use css::{TextDecoration, px2pt};
-use interface::update_html_source;
+use css::TextDecoration;
+use interface::{invalidate_layout, update_element_state, update_html_source};
+use font_face::register_loaded_fonts;
+use element_state;
 
 
 #[derive(Clone, Debug)]
@@ -31,16 +36,34 @@ pub enum AnkerKind {
     URLFragment(String),
 }
 
+pub type ScrollOffsets = HashMap<usize, (Au, Au)>;
+
 thread_local!(
     pub static ANKERS: RefCell<HashMap<Rect, AnkerKind>> = { RefCell::new(HashMap::with_capacity(8)) };
     // HashMap<URL Fragment(id), y coordinate of the content>
     pub static URL_FRAGMENTS: RefCell<HashMap<String, f64>> = { RefCell::new(HashMap::with_capacity(8)) };
     pub static BUTTONS: RefCell<HashMap<usize, gtk::Button>> = { RefCell::new(HashMap::with_capacity(8)) };
     pub static SURFACE_CACHE: RefCell<Option<cairo::ImageSurface>> = { RefCell::new(None) };
+    // The border boxes of the nodes in painting order, with the numbers of the nodes.
+    pub static NODE_RECTS: RefCell<Vec<(Rect, usize)>> = { RefCell::new(vec![]) };
+    // The padding boxes of the scroll containers in painting order, with the numbers of their
+    // nodes and how far they can be scrolled right and down.
+    pub static SCROLL_CONTAINERS: RefCell<Vec<(Rect, usize, (Au, Au))>> = { RefCell::new(vec![]) };
+    // How far the scroll containers have been scrolled, by the numbers of their nodes.
+    pub static SCROLL_OFFSETS: RefCell<ScrollOffsets> = { RefCell::new(HashMap::new()) };
+    // What SURFACE_CACHE shows.
+    static PAINTED_ITEMS: RefCell<DisplayList> = { RefCell::new(vec![]) };
+    // Whether elements have been styled again since SURFACE_CACHE was painted.
+    static ELEMENT_STATE_CHANGED: Cell<bool> = { Cell::new(false) };
+    // Whether the page has been scrolled since SURFACE_CACHE was painted.
+    static SCROLLED: Cell<bool> = { Cell::new(false) };
 );
 
 static mut RESIZED: bool = false;
 
+/// How far a box is scrolled for each step of the mouse wheel, in px.
+const SCROLL_STEP: f64 = 40.0;
+
 struct RenderingWindow {
     window: gtk::Window,
     drawing_area: gtk::DrawingArea,
@@ -115,6 +138,7 @@ impl RenderingWindow {
 
                 update_html_source(url);
                 ANKERS.with(|ankers| ankers.borrow_mut().clear());
+                SCROLL_OFFSETS.with(|offsets| offsets.borrow_mut().clear());
                 SURFACE_CACHE.with(|sc| *sc.borrow_mut() = None);
 
                 drawing_area.queue_draw();
@@ -125,12 +149,31 @@ impl RenderingWindow {
 
         let scrolled_window = gtk::ScrolledWindow::new(None, None);
         scrolled_window.add(&overlay);
+        {
+            use gtk::{AdjustmentExt, ScrolledWindowExt};
+            // `fixed` and `sticky` boxes are painted again where the page is scrolled to.
+            let adjustments = scrolled_window
+                .get_hadjustment()
+                .into_iter()
+                .chain(scrolled_window.get_vadjustment());
+            for adjustment in adjustments {
+                let drawing_area = drawing_area.clone();
+                adjustment.connect_value_changed(move |_| {
+                    SCROLLED.with(|scrolled| scrolled.set(true));
+                    drawing_area.queue_draw();
+                });
+            }
+        }
         vbox.pack_start(&scrolled_window, true, true, 0);
 
         window.add(&vbox);
         overlay.add_events(
             EventMask::POINTER_MOTION_MASK.bits() as i32
-                | EventMask::BUTTON_PRESS_MASK.bits() as i32,
+                | EventMask::BUTTON_PRESS_MASK.bits() as i32
+                | EventMask::BUTTON_RELEASE_MASK.bits() as i32
+                | EventMask::LEAVE_NOTIFY_MASK.bits() as i32
+                | EventMask::SCROLL_MASK.bits() as i32
+                | EventMask::SMOOTH_SCROLL_MASK.bits() as i32,
         );
 
         overlay
@@ -165,6 +208,77 @@ impl RenderingWindow {
                         window.set_cursor(Some(&Cursor::new(CursorType::LeftPtr)));
                     }
                 });
+
+                let hovered = node_at(x, y);
+                if update_element_state(|document| element_state::set_hovered(document, hovered)) {
+                    repaint_element_state(&overlay);
+                }
+                Some(true.to_value())
+            })
+            .unwrap();
+
+        overlay
+            .connect("scroll-event", false, |args| {
+                let overlay = args[0]
+                    .clone()
+                    .downcast::<gtk::Overlay>()
+                    .unwrap()
+                    .get()
+                    .unwrap();
+                let event = args[1]
+                    .clone()
+                    .downcast::<Event>()
+                    .unwrap()
+                    .get()
+                    .unwrap()
+                    .downcast::<EventScroll>()
+                    .unwrap();
+                let (x, y) = event.get_position();
+                let (dx, dy) = match event.get_direction() {
+                    ScrollDirection::Up => (0.0, -1.0),
+                    ScrollDirection::Down => (0.0, 1.0),
+                    ScrollDirection::Left => (-1.0, 0.0),
+                    ScrollDirection::Right => (1.0, 0.0),
+                    _ => event.get_delta(),
+                };
+                let step = |delta: f64| Au::from_f64_px(delta * SCROLL_STEP);
+                // The page only scrolls when no box under the pointer can.
+                if scroll_container_at(x, y, step(dx), step(dy)) {
+                    SCROLLED.with(|scrolled| scrolled.set(true));
+                    overlay.get_children()[0].queue_draw(); // [0] is DrawingArea
+                    Some(true.to_value())
+                } else {
+                    Some(false.to_value())
+                }
+            })
+            .unwrap();
+
+        overlay
+            .connect("leave-notify-event", false, |args| {
+                let overlay = args[0]
+                    .clone()
+                    .downcast::<gtk::Overlay>()
+                    .unwrap()
+                    .get()
+                    .unwrap();
+                if update_element_state(|document| element_state::set_hovered(document, None)) {
+                    repaint_element_state(&overlay);
+                }
+                Some(false.to_value())
+            })
+            .unwrap();
+
+        overlay
+            .connect("button-release-event", false, |args| {
+                let overlay = args[0]
+                    .clone()
+                    .downcast::<gtk::Overlay>()
+                    .unwrap()
+                    .get()
+                    .unwrap();
+                if update_element_state(|document| element_state::set_active(document, None)) {
+                    repaint_element_state(&overlay);
+                }
                 Some(true.to_value())
             })
             .unwrap();
@@ -187,6 +301,17 @@ impl RenderingWindow {
                     .unwrap()
                     .get_position();
 
+                let pressed = node_at(clicked_x, clicked_y);
+                if update_element_state(|document| {
+                    let mut changed = element_state::set_active(document, pressed);
+                    changed.extend(element_state::set_focused(document, pressed));
+                    changed.sort();
+                    changed.dedup();
+                    changed
+                }) {
+                    repaint_element_state(&overlay);
+                }
+
                 ANKERS.with(|ankers| {
                     let mut jump_to_another_page = false;
 
@@ -224,6 +349,7 @@ impl RenderingWindow {
 
                     if jump_to_another_page {
                         ankers.borrow_mut().clear();
+                        SCROLL_OFFSETS.with(|offsets| offsets.borrow_mut().clear());
                         SURFACE_CACHE.with(|sc| *sc.borrow_mut() = None);
                     }
                 });
@@ -238,6 +364,20 @@ impl RenderingWindow {
             false
         });
 
+        {
+            // Web fonts arrive in the background. Until then text is drawn in a fallback font,
+            // so lay the page out again with every face that gets registered.
+            let drawing_area = drawing_area.clone();
+            gtk::timeout_add(100, move || {
+                if register_loaded_fonts() {
+                    invalidate_layout();
+                    SURFACE_CACHE.with(|sc| *sc.borrow_mut() = None);
+                    drawing_area.queue_draw();
+                }
+                glib::Continue(true)
+            });
+        }
+
         let instance = RenderingWindow {
             window: window,
             drawing_area: drawing_area,
@@ -259,19 +399,26 @@ impl RenderingWindow {
                 //     .unwrap(); // [1] is Layout
 
                 let surface = SURFACE_CACHE.with(|sc| {
+                    let pango_ctx = widget.create_pango_context().unwrap();
+                    let mut pango_layout = pango::Layout::new(&pango_ctx);
+
                     if let Some(ref surface) = *sc.borrow_mut() {
                         unsafe {
                             if RESIZED {
                                 RESIZED = false;
                             } else {
-                                return surface.clone();
+                                let state_changed =
+                                    ELEMENT_STATE_CHANGED.with(|changed| changed.replace(false));
+                                let scrolled = SCROLLED.with(|scrolled| scrolled.replace(false));
+                                if !(state_changed || scrolled)
+                                    || repaint_changes(surface, &mut pango_layout, f(widget))
+                                {
+                                    return surface.clone();
+                                }
                             }
                         }
                     }
 
-                    let pango_ctx = widget.create_pango_context().unwrap();
-                    let mut pango_layout = pango::Layout::new(&pango_ctx);
-
                     let items = f(widget);
                     let content_rect =
                         if let DisplayCommand::SolidColor(_, content_rect) = items[0].command {
@@ -304,6 +451,7 @@ impl RenderingWindow {
                     // radial.add_color_stop_rgba(0.4, 0.0, 0.0, 0.0, 0.0);
                     // ctx.mask(&radial);
 
+                    PAINTED_ITEMS.with(|painted_items| *painted_items.borrow_mut() = items);
                     *sc.borrow_mut() = Some(surface.clone());
                     surface
                 });
@@ -336,6 +484,103 @@ impl RenderingWindow {
     }
 }
 
+/// The node whose box is painted last at (`x`, `y`), which is the one on top.
+fn node_at(x: f64, y: f64) -> Option<usize> {
+    NODE_RECTS.with(|node_rects| {
+        node_rects
+            .borrow()
+            .iter()
+            .rev()
+            .find(|&&(rect, _)| rect.contains_point(x, y))
+            .map(|&(_, node_id)| node_id)
+    })
+}
+
+/// Scrolls the innermost scroll container at (`x`, `y`) that can still be scrolled by (`dx`,
+/// `dy`). Returns false if there is none.
+fn scroll_container_at(x: f64, y: f64, dx: Au, dy: Au) -> bool {
+    let clamp = |offset: Au, range: Au| max(Au(0), min(offset, range));
+    SCROLL_CONTAINERS.with(|containers| {
+        containers
+            .borrow()
+            .iter()
+            .rev()
+            .filter(|&&(rect, _, _)| rect.contains_point(x, y))
+            .any(|&(_, node_id, (range_x, range_y))| {
+                SCROLL_OFFSETS.with(|offsets| {
+                    let mut offsets = offsets.borrow_mut();
+                    let offset = offsets.entry(node_id).or_insert((Au(0), Au(0)));
+                    // The offset may be past the range if the content has become smaller.
+                    let old_offset = (clamp(offset.0, range_x), clamp(offset.1, range_y));
+                    *offset = (
+                        clamp(old_offset.0 + dx, range_x),
+                        clamp(old_offset.1 + dy, range_y),
+                    );
+                    *offset != old_offset
+                })
+            })
+    })
+}
+
+/// Draws the page again after elements have been styled again for their new state.
+fn repaint_element_state(overlay: &gtk::Overlay) {
+    ELEMENT_STATE_CHANGED.with(|changed| changed.set(true));
+    overlay.get_children()[0].queue_draw(); // [0] is DrawingArea
+}
+
+/// Paints over `surface` the area where `items` differ from what it shows. Returns false if
+/// the whole page has to be painted again instead, as when its size changed.
+fn repaint_changes(
+    surface: &cairo::ImageSurface,
+    pango_layout: &mut pango::Layout,
+    items: DisplayList,
+) -> bool {
+    PAINTED_ITEMS.with(|painted_items| {
+        let mut painted_items = painted_items.borrow_mut();
+        // The first item is the canvas, as large as the page.
+        if painted_items.first() != items.first() {
+            return false;
+        }
+        if let Some(damage) = damaged_rect(&painted_items, &items) {
+            let ctx = cairo::Context::new(surface);
+            ctx.rectangle(
+                damage.x.to_f64_px(),
+                damage.y.to_f64_px(),
+                damage.width.to_f64_px(),
+                damage.height.to_f64_px(),
+            );
+            ctx.clip();
+            for item in items
+                .iter()
+                .filter(|item| item.command.rect().intersects(damage))
+            {
+                render_item(&ctx, pango_layout, &item.command);
+            }
+        }
+        *painted_items = items;
+        true
+    })
+}
+
+/// The area covered by the items that differ between `old` and `new`, apart from those they
+/// start and end with.
+fn damaged_rect(old: &DisplayList, new: &DisplayList) -> Option<Rect> {
+    let common_start = old.iter().zip(new).take_while(|&(a, b)| a == b).count();
+    let (old, new) = (&old[common_start..], &new[common_start..]);
+    let common_end = old.iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    old[..old.len() - common_end]
+        .iter()
+        .chain(&new[..new.len() - common_end])
+        .map(|item| item.command.rect())
+        .fold(None, |damage: Option<Rect>, rect| {
+            Some(damage.map_or(rect, |damage| damage.union(rect)))
+        })
+}
+
 fn render_item(
     ctx: &Context,
     pango_layout: &mut pango::Layout,
@@ -375,9 +620,7 @@ fn render_item(
         &DisplayCommand::Text(ref text, rect, ref color, ref decorations, ref font) => {
             FONT_DESC.with(|font_desc| {
                 let mut font_desc = font_desc.borrow_mut();
-                font_desc.set_size(pango::units_from_double(px2pt(font.size.to_f64_px())));
-                font_desc.set_style(font.slant.to_pango_font_slant());
-                font_desc.set_weight(font.weight.to_pango_font_weight());
+                font.update_font_description(&mut font_desc);
 
                 let attr_list = pango::AttrList::new();
                 for decoration in decorations {
@@ -414,6 +657,17 @@ fn render_item(
             // use gtk::LayoutExt;
             // layout.put(btn, rect.x.ceil_to_px(), rect.y.ceil_to_px());
         }
+        &DisplayCommand::PushClip(rect) => {
+            ctx.save();
+            ctx.rectangle(
+                rect.x.to_f64_px(),
+                rect.y.to_f64_px(),
+                rect.width.to_f64_px(),
+                rect.height.to_f64_px(),
+            );
+            ctx.clip();
+        }
+        &DisplayCommand::PopClip(_) => ctx.restore(),
     }
 }
 